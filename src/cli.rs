@@ -2,8 +2,252 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
-use clap::Parser;
+use chrono::FixedOffset;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::config::Config;
+use crate::paths::{
+    DEFAULT_MAX_FILENAME_LENGTH, is_git_compatible_ascii_filename_stem, sanitize_relative_path,
+};
+use crate::web::is_valid_csrf_secret;
+use crate::web::timefmt::parse_utc_offset;
+
+/// An extra directory served read-only under `/mounts/<name>/`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mount {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+impl FromStr for Mount {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, path) = value
+            .split_once('=')
+            .ok_or_else(|| format!("Mount '{value}' must be in the form name=path"))?;
+        let name = name.trim();
+        if !is_git_compatible_ascii_filename_stem(name) {
+            return Err(format!(
+                "Mount name '{name}' must use only ASCII letters, numbers, '-', '_', or '.'"
+            ));
+        }
+        if path.trim().is_empty() {
+            return Err(format!("Mount '{name}' must have a path"));
+        }
+        Ok(Mount {
+            name: name.to_string(),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// An external command that renders previews for one file extension.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreviewHandler {
+    /// Lowercase extension without the leading dot.
+    pub extension: String,
+    /// Program and arguments, split on whitespace.
+    pub command: Vec<String>,
+}
+
+impl FromStr for PreviewHandler {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (extension, command) = value
+            .split_once('=')
+            .ok_or_else(|| format!("Preview handler '{value}' must be in the form ext=command"))?;
+        let extension = extension
+            .trim()
+            .trim_start_matches('.')
+            .to_ascii_lowercase();
+        if extension.is_empty() || !extension.chars().all(|ch| ch.is_ascii_alphanumeric()) {
+            return Err(format!(
+                "Preview handler extension '{extension}' must use only ASCII letters and numbers"
+            ));
+        }
+        let command: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+        if command.is_empty() {
+            return Err(format!("Preview handler '{extension}' must have a command"));
+        }
+        Ok(PreviewHandler { extension, command })
+    }
+}
+
+/// CommonMark extensions enabled when rendering markdown on the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MarkdownExtensions {
+    pub tables: bool,
+    pub strikethrough: bool,
+    pub tasklists: bool,
+    pub footnotes: bool,
+}
+
+impl MarkdownExtensions {
+    /// Names accepted by `--markdown-extensions`.
+    pub const NAMES: &[&str] = &["tables", "strikethrough", "tasklists", "footnotes"];
+
+    pub const NONE: MarkdownExtensions = MarkdownExtensions {
+        tables: false,
+        strikethrough: false,
+        tasklists: false,
+        footnotes: false,
+    };
+}
+
+impl Default for MarkdownExtensions {
+    /// Everything GitHub-flavored markdown has that pulldown-cmark supports.
+    fn default() -> Self {
+        MarkdownExtensions {
+            tables: true,
+            strikethrough: true,
+            tasklists: true,
+            footnotes: true,
+        }
+    }
+}
+
+impl FromStr for MarkdownExtensions {
+    type Err = String;
+
+    /// A comma-separated list of [`MarkdownExtensions::NAMES`], or `none`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut extensions = MarkdownExtensions::NONE;
+        for name in value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            match name.to_ascii_lowercase().as_str() {
+                "none" => {}
+                "tables" => extensions.tables = true,
+                "strikethrough" => extensions.strikethrough = true,
+                "tasklists" => extensions.tasklists = true,
+                "footnotes" => extensions.footnotes = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown markdown extension '{name}'; expected none or any of {}",
+                        MarkdownExtensions::NAMES.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(extensions)
+    }
+}
+
+/// Rewrites applied to editor content before it is saved.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SaveNormalizations {
+    /// Convert CRLF and lone CR line endings to LF.
+    pub line_endings: bool,
+    /// End non-empty files with a newline.
+    pub trailing_newline: bool,
+}
+
+impl SaveNormalizations {
+    /// Names accepted by `--save-normalize`.
+    pub const NAMES: &[&str] = &["line-endings", "trailing-newline"];
+
+    pub fn is_empty(self) -> bool {
+        self == SaveNormalizations::default()
+    }
+}
+
+impl FromStr for SaveNormalizations {
+    type Err = String;
+
+    /// A comma-separated list of [`SaveNormalizations::NAMES`], or `none`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut normalizations = SaveNormalizations::default();
+        for name in value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            match name.to_ascii_lowercase().as_str() {
+                "none" => {}
+                "line-endings" => normalizations.line_endings = true,
+                "trailing-newline" => normalizations.trailing_newline = true,
+                _ => {
+                    return Err(format!(
+                        "Unknown save normalization '{name}'; expected none or any of {}",
+                        SaveNormalizations::NAMES.join(", ")
+                    ));
+                }
+            }
+        }
+        Ok(normalizations)
+    }
+}
+
+/// How a save decides whether the submitted content differs from the file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SaveComparison {
+    /// Byte-for-byte against the submitted content.
+    #[default]
+    Exact,
+    /// After applying `--save-normalize` to both sides.
+    Normalized,
+}
+
+impl FromStr for SaveComparison {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "exact" => Ok(SaveComparison::Exact),
+            "normalized" => Ok(SaveComparison::Normalized),
+            _ => Err(format!(
+                "Unknown save comparison '{value}'; expected exact or normalized"
+            )),
+        }
+    }
+}
+
+/// How far the atomic write path goes to make a write survive a crash.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Durability {
+    /// Flush the temporary file to disk before renaming it into place.
+    #[default]
+    Fsync,
+    /// Leave flushing to the operating system.
+    None,
+    /// Flush the temporary file, then the parent directory after the rename.
+    DirSync,
+}
+
+impl Durability {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Durability::Fsync => "fsync",
+            Durability::None => "none",
+            Durability::DirSync => "dir-sync",
+        }
+    }
+}
+
+impl FromStr for Durability {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "fsync" => Ok(Durability::Fsync),
+            "none" => Ok(Durability::None),
+            "dir-sync" => Ok(Durability::DirSync),
+            _ => Err(format!(
+                "Unknown durability '{value}'; expected fsync, none or dir-sync"
+            )),
+        }
+    }
+}
 
 #[derive(Parser)]
 #[command(name = "markdown-wrangler")]
@@ -28,6 +272,236 @@ pub struct Cli {
         default_value_t = 1_048_576usize
     )]
     pub max_upload_size_bytes: usize,
+
+    #[arg(
+        long,
+        help = "Maximum number of bytes scanned when detecting frontmatter",
+        default_value_t = 65_536usize
+    )]
+    pub max_frontmatter_bytes: usize,
+
+    #[arg(
+        long,
+        help = "Maximum number of items read from a frontmatter tags or categories list; the rest are ignored",
+        default_value_t = 256usize
+    )]
+    pub max_frontmatter_list_items: usize,
+
+    #[arg(
+        long,
+        value_name = "KEYS",
+        value_delimiter = ',',
+        help = "Frontmatter keys holding image paths that the problems dashboard and editor check exist, comma-separated",
+        default_value = "cover,image,thumbnail,images"
+    )]
+    pub frontmatter_asset_keys: Vec<String>,
+
+    #[arg(
+        long,
+        help = "Maximum length in bytes of a new markdown filename, including the .md extension",
+        default_value_t = DEFAULT_MAX_FILENAME_LENGTH
+    )]
+    pub max_filename_length: usize,
+
+    #[arg(
+        long,
+        env = "MARKDOWN_WRANGLER_API_TOKEN",
+        hide_env_values = true,
+        help = "Bearer token required for API writes such as PUT /raw (disabled when unset)"
+    )]
+    pub api_token: Option<String>,
+
+    #[arg(
+        long,
+        help = "Allow /import-url to fetch from loopback and private network addresses"
+    )]
+    pub allow_private_import_urls: bool,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "IP address to listen on; anything other than loopback exposes the server to the network",
+        default_value = "127.0.0.1"
+    )]
+    pub bind: String,
+
+    #[arg(long, help = "Port to listen on", default_value_t = 5420)]
+    pub port: u16,
+
+    #[arg(
+        long = "mount",
+        value_name = "NAME=PATH",
+        help = "Serve an extra directory read-only under /mounts/NAME/ (repeatable)"
+    )]
+    pub mounts: Vec<Mount>,
+
+    #[arg(
+        long,
+        help = "Never embed file content in an iframe on the file preview page"
+    )]
+    pub disable_iframe_preview: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Append an audit journal of content writes to FILE (enables edit tracking)"
+    )]
+    pub audit_journal: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Keep the CSRF secret in FILE (created with mode 0600 if missing) so tokens survive restarts"
+    )]
+    pub csrf_secret_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        env = "MARKDOWN_WRANGLER_CSRF_SECRET",
+        hide_env_values = true,
+        conflicts_with = "csrf_secret_file",
+        help = "CSRF secret as 64 hex characters, so tokens survive restarts without a --csrf-secret-file"
+    )]
+    pub csrf_secret: Option<String>,
+
+    #[arg(
+        long,
+        help = "Remember the last visited directory in a session cookie and return to it from /"
+    )]
+    pub remember_path: bool,
+
+    #[arg(
+        long,
+        help = "Show the start of each markdown file's body under its name in directory listings"
+    )]
+    pub show_snippets: bool,
+
+    #[arg(
+        long,
+        help = "Maximum number of files read for serving at once; further requests wait their turn",
+        default_value_t = 64usize,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub max_concurrent_file_serves: usize,
+
+    #[arg(
+        long = "preview-handler",
+        value_name = "EXT=COMMAND",
+        help = "Render previews of EXT files with COMMAND (stdin, or {input} for a file path; SVG/HTML on stdout). Repeatable; needs --enable-preview-commands"
+    )]
+    pub preview_handlers: Vec<PreviewHandler>,
+
+    #[arg(long, help = "Allow the configured --preview-handler commands to run")]
+    pub enable_preview_commands: bool,
+
+    #[arg(
+        long,
+        value_name = "URL",
+        help = "Public base URL of the built site, used for absolute URLs in /sitemap.xml (sitemap disabled when unset)"
+    )]
+    pub site_base_url: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Store images pasted into the editor in this directory (relative to the target directory) instead of next to the post"
+    )]
+    pub paste_image_dir: Option<String>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Serve this file as /robots.txt instead of one disallowing all crawlers"
+    )]
+    pub robots_txt: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "OFFSET",
+        help = "UTC offset dates are displayed in on pages, e.g. +10:00 (API timestamps stay UTC)",
+        default_value = "+00:00",
+        value_parser = parse_utc_offset
+    )]
+    pub utc_offset: FixedOffset,
+
+    #[arg(
+        long,
+        help = "Recompress uploaded and imported JPEG/PNG images and strip their metadata, keeping the result when smaller"
+    )]
+    pub optimize_uploads: bool,
+
+    #[arg(
+        long,
+        help = "With --optimize-uploads, store PNG images as lossless WebP (renaming them to .webp) when that is smaller"
+    )]
+    pub convert_png_uploads_to_webp: bool,
+
+    #[arg(
+        long,
+        value_name = "LIST",
+        help = "CommonMark extensions for server-rendered markdown: comma-separated tables, strikethrough, tasklists, footnotes, or none",
+        default_value = "tables,strikethrough,tasklists,footnotes"
+    )]
+    pub markdown_extensions: MarkdownExtensions,
+
+    #[arg(
+        long,
+        help = "Render :shortcode: emoji (e.g. :+1:) in server-rendered markdown, outside code"
+    )]
+    pub enable_emoji_shortcodes: bool,
+
+    #[arg(
+        long,
+        value_name = "LIST",
+        help = "Rewrite editor content before saving: comma-separated line-endings (CRLF to LF), trailing-newline, or none",
+        default_value = "none"
+    )]
+    pub save_normalize: SaveNormalizations,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        help = "How saves detect unchanged content: exact (as submitted) or normalized (after --save-normalize)",
+        default_value = "exact"
+    )]
+    pub save_comparison: SaveComparison,
+
+    #[arg(
+        long,
+        value_name = "MODE",
+        help = "How hard writes try to survive a crash: fsync (flush each file), none (leave it to the OS) or dir-sync (also flush the directory after renaming)",
+        default_value = "fsync"
+    )]
+    pub durability: Durability,
+
+    #[arg(
+        long,
+        help = "Delete files permanently instead of moving them into .wrangler-trash/"
+    )]
+    pub hard_delete: bool,
+
+    #[arg(
+        long,
+        help = "Require deletes to send confirm_name matching the file's name"
+    )]
+    pub confirm_delete_name: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Read settings from a TOML file; keys are the long option names, and flags given here win"
+    )]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Run the content checks once, print any problems and exit non-zero if
+    /// there were any.
+    Lint,
 }
 
 impl Cli {
@@ -35,6 +509,97 @@ impl Cli {
         Parser::parse()
     }
 
+    /// Parses the process arguments and merges in the `--config` file.
+    pub async fn load() -> Result<Self, String> {
+        Self::load_from(std::env::args_os()).await
+    }
+
+    /// Like [`Cli::load`] with explicit arguments. Parse errors and `--help`
+    /// exit the process as [`Cli::parse`] does.
+    pub async fn load_from<I, T>(args: I) -> Result<Self, String>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let matches = Self::command().get_matches_from(args);
+        let mut cli = Self::from_arg_matches(&matches).map_err(|err| err.to_string())?;
+        if let Some(path) = &cli.config {
+            let config = Config::load(path).await?;
+            cli.merge(config, &matches);
+        }
+        Ok(cli)
+    }
+
+    /// Takes each setting from `config` unless it was given on the command
+    /// line or through the environment.
+    fn merge(&mut self, config: Config, matches: &ArgMatches) {
+        let explicit = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = config.$field
+                        && !explicit(stringify!($field))
+                    {
+                        self.$field = value;
+                    }
+                )*
+            };
+        }
+        macro_rules! merge_optional {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if config.$field.is_some() && !explicit(stringify!($field)) {
+                        self.$field = config.$field;
+                    }
+                )*
+            };
+        }
+        merge!(
+            debug,
+            target_dir,
+            enable_otel_logs,
+            max_upload_size_bytes,
+            max_frontmatter_bytes,
+            max_frontmatter_list_items,
+            frontmatter_asset_keys,
+            max_filename_length,
+            allow_private_import_urls,
+            bind,
+            port,
+            mounts,
+            disable_iframe_preview,
+            remember_path,
+            show_snippets,
+            max_concurrent_file_serves,
+            preview_handlers,
+            enable_preview_commands,
+            utc_offset,
+            optimize_uploads,
+            convert_png_uploads_to_webp,
+            markdown_extensions,
+            enable_emoji_shortcodes,
+            save_normalize,
+            save_comparison,
+            durability,
+            hard_delete,
+            confirm_delete_name,
+        );
+        merge_optional!(
+            api_token,
+            audit_journal,
+            csrf_secret_file,
+            csrf_secret,
+            site_base_url,
+            paste_image_dir,
+            robots_txt
+        );
+    }
+
     pub fn validate(&self) -> Result<(), String> {
         if !self.target_dir.exists() {
             return Err(format!(
@@ -54,13 +619,108 @@ impl Cli {
             return Err("Maximum upload size must be greater than 0 bytes".to_string());
         }
 
+        if self.max_frontmatter_bytes == 0 {
+            return Err("Maximum frontmatter size must be greater than 0 bytes".to_string());
+        }
+
+        if self.max_frontmatter_list_items == 0 {
+            return Err("Maximum frontmatter list items must be greater than 0".to_string());
+        }
+
+        // Anything shorter cannot fit even a one-character name plus ".md".
+        if self.max_filename_length < "x.md".len() {
+            return Err("Maximum filename length must be at least 4 bytes".to_string());
+        }
+
+        if self
+            .api_token
+            .as_deref()
+            .is_some_and(|token| token.trim().is_empty())
+        {
+            return Err("API token must not be empty".to_string());
+        }
+
+        if self
+            .csrf_secret
+            .as_deref()
+            .is_some_and(|secret| !is_valid_csrf_secret(secret.trim()))
+        {
+            return Err("CSRF secret must be 64 hex characters (32 random bytes)".to_string());
+        }
+
+        if let Some(url) = &self.site_base_url
+            && !(url.starts_with("https://") || url.starts_with("http://"))
+        {
+            return Err(format!(
+                "Site base URL '{url}' must start with http:// or https://"
+            ));
+        }
+
+        if let Some(dir) = &self.paste_image_dir
+            && !sanitize_relative_path(dir).is_ok_and(|dir| !dir.is_empty())
+        {
+            return Err(format!(
+                "Paste image directory '{dir}' must be a relative path inside the target directory"
+            ));
+        }
+
+        if self.max_concurrent_file_serves == 0 {
+            return Err("Maximum concurrent file serves must be at least 1".to_string());
+        }
+
+        if self.port == 0 {
+            return Err("Port must be between 1 and 65535".to_string());
+        }
+        self.bind_addr()?;
+
+        let mut preview_extensions = HashSet::new();
+        for handler in &self.preview_handlers {
+            if !preview_extensions.insert(handler.extension.as_str()) {
+                return Err(format!(
+                    "Preview handler for '{}' is configured more than once",
+                    handler.extension
+                ));
+            }
+        }
+
+        let mut mount_names = HashSet::new();
+        for mount in &self.mounts {
+            if !mount.path.is_dir() {
+                return Err(format!(
+                    "Mount '{}' path '{}' is not a directory",
+                    mount.name,
+                    mount.path.display()
+                ));
+            }
+            if !mount_names.insert(mount.name.as_str()) {
+                return Err(format!(
+                    "Mount name '{}' is used more than once",
+                    mount.name
+                ));
+            }
+        }
+
         Ok(())
     }
+
+    /// The `--bind` address parsed together with `--port`.
+    pub fn bind_addr(&self) -> Result<SocketAddr, String> {
+        let ip: IpAddr = self.bind.trim().parse().map_err(|_| {
+            format!(
+                "Bind address '{}' must be an IP address such as 127.0.0.1 or ::1",
+                self.bind
+            )
+        })?;
+        Ok(SocketAddr::new(ip, self.port))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Cli;
+    use super::{
+        Cli, Command, Durability, FixedOffset, MarkdownExtensions, Mount, PreviewHandler,
+        SaveComparison, SaveNormalizations, SocketAddr,
+    };
     use clap::Parser;
     use std::{
         fs::File,
@@ -74,6 +734,42 @@ mod tests {
         assert!(!cli.debug);
         assert!(!cli.enable_otel_logs);
         assert_eq!(cli.max_upload_size_bytes, 1_048_576);
+        assert_eq!(cli.max_frontmatter_bytes, 65_536);
+        assert_eq!(cli.max_frontmatter_list_items, 256);
+        assert_eq!(
+            cli.frontmatter_asset_keys,
+            ["cover", "image", "thumbnail", "images"]
+        );
+        assert_eq!(cli.max_filename_length, 200);
+        assert_eq!(cli.api_token, None);
+        assert!(!cli.allow_private_import_urls);
+        assert!(cli.mounts.is_empty());
+        assert_eq!(cli.bind, "127.0.0.1");
+        assert_eq!(cli.port, 5420);
+        assert_eq!(cli.audit_journal, None);
+        assert_eq!(cli.csrf_secret_file, None);
+        assert_eq!(cli.csrf_secret, None);
+        assert!(!cli.disable_iframe_preview);
+        assert!(!cli.remember_path);
+        assert!(!cli.show_snippets);
+        assert_eq!(cli.max_concurrent_file_serves, 64);
+        assert!(cli.preview_handlers.is_empty());
+        assert!(!cli.enable_preview_commands);
+        assert_eq!(cli.site_base_url, None);
+        assert_eq!(cli.paste_image_dir, None);
+        assert_eq!(cli.robots_txt, None);
+        assert_eq!(cli.utc_offset.local_minus_utc(), 0);
+        assert!(!cli.optimize_uploads);
+        assert!(!cli.convert_png_uploads_to_webp);
+        assert_eq!(cli.markdown_extensions, MarkdownExtensions::default());
+        assert!(!cli.enable_emoji_shortcodes);
+        assert_eq!(cli.save_normalize, SaveNormalizations::default());
+        assert_eq!(cli.save_comparison, SaveComparison::Exact);
+        assert_eq!(cli.durability, Durability::Fsync);
+        assert!(!cli.hard_delete);
+        assert!(!cli.confirm_delete_name);
+        assert_eq!(cli.config, None);
+        assert_eq!(cli.command, None);
         assert_eq!(cli.target_dir, PathBuf::from("."));
     }
 
@@ -85,12 +781,92 @@ mod tests {
             "--enable-otel-logs",
             "--max-upload-size-bytes",
             "2048",
+            "--max-frontmatter-bytes",
+            "4096",
+            "--max-filename-length",
+            "120",
+            "--api-token",
+            "secret",
+            "--port",
+            "8080",
+            "--max-concurrent-file-serves",
+            "8",
+            "--csrf-secret-file",
+            "/var/lib/wrangler/csrf",
+            "--utc-offset",
+            "+10:00",
+            "--markdown-extensions",
+            "tables, Footnotes",
+            "--enable-emoji-shortcodes",
             "content",
         ]);
         assert!(cli.debug);
         assert!(cli.enable_otel_logs);
         assert_eq!(cli.max_upload_size_bytes, 2048);
+        assert_eq!(cli.max_frontmatter_bytes, 4096);
+        assert_eq!(cli.max_filename_length, 120);
+        assert_eq!(cli.api_token.as_deref(), Some("secret"));
+        assert_eq!(cli.port, 8080);
+        assert!(cli.enable_emoji_shortcodes);
+        assert_eq!(cli.max_concurrent_file_serves, 8);
+        assert_eq!(
+            cli.csrf_secret_file,
+            Some(PathBuf::from("/var/lib/wrangler/csrf"))
+        );
+        assert_eq!(cli.utc_offset.local_minus_utc(), 10 * 3600);
+        assert_eq!(
+            cli.markdown_extensions,
+            MarkdownExtensions {
+                tables: true,
+                footnotes: true,
+                ..MarkdownExtensions::NONE
+            }
+        );
+        assert_eq!(cli.target_dir, PathBuf::from("content"));
+        assert!(
+            Cli::try_parse_from(["markdown-wrangler", "--max-concurrent-file-serves", "0"])
+                .is_err()
+        );
+        assert!(Cli::try_parse_from(["markdown-wrangler", "--utc-offset", "AEST"]).is_err());
+        assert!(
+            Cli::try_parse_from(["markdown-wrangler", "--markdown-extensions", "tables,math"])
+                .is_err()
+        );
+        let none = Cli::parse_from(["markdown-wrangler", "--markdown-extensions", "none"]);
+        assert_eq!(none.markdown_extensions, MarkdownExtensions::NONE);
+
+        let cli = Cli::parse_from([
+            "markdown-wrangler",
+            "--save-normalize",
+            "line-endings, Trailing-Newline",
+            "--save-comparison",
+            "normalized",
+            "--durability",
+            "dir-sync",
+        ]);
+        assert_eq!(
+            cli.save_normalize,
+            SaveNormalizations {
+                line_endings: true,
+                trailing_newline: true,
+            }
+        );
+        assert_eq!(cli.save_comparison, SaveComparison::Normalized);
+        assert_eq!(cli.durability, Durability::DirSync);
+        assert!(Cli::try_parse_from(["markdown-wrangler", "--save-normalize", "tabs"]).is_err());
+        assert!(Cli::try_parse_from(["markdown-wrangler", "--save-comparison", "fuzzy"]).is_err());
+        assert!(Cli::try_parse_from(["markdown-wrangler", "--durability", "maybe"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_lint_subcommand() {
+        let cli = Cli::parse_from(["markdown-wrangler", "content", "lint"]);
+        assert_eq!(cli.command, Some(Command::Lint));
         assert_eq!(cli.target_dir, PathBuf::from("content"));
+
+        let cli = Cli::parse_from(["markdown-wrangler", "lint"]);
+        assert_eq!(cli.command, Some(Command::Lint));
+        assert_eq!(cli.target_dir, PathBuf::from("."));
     }
 
     #[test]
@@ -101,6 +877,39 @@ mod tests {
             target_dir: temp_dir.path().to_path_buf(),
             enable_otel_logs: false,
             max_upload_size_bytes: 1_048_576,
+            max_frontmatter_bytes: 65_536,
+            max_frontmatter_list_items: 256,
+            frontmatter_asset_keys: crate::web::checks::default_asset_keys(),
+            max_filename_length: 200,
+            api_token: None,
+            allow_private_import_urls: false,
+            mounts: Vec::new(),
+            bind: "127.0.0.1".to_string(),
+            port: 5420,
+            audit_journal: None,
+            csrf_secret_file: None,
+            csrf_secret: None,
+            disable_iframe_preview: false,
+            remember_path: false,
+            show_snippets: false,
+            max_concurrent_file_serves: 64,
+            preview_handlers: Vec::new(),
+            enable_preview_commands: false,
+            site_base_url: None,
+            paste_image_dir: None,
+            robots_txt: None,
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset is valid"),
+            optimize_uploads: false,
+            convert_png_uploads_to_webp: false,
+            markdown_extensions: MarkdownExtensions::default(),
+            enable_emoji_shortcodes: false,
+            save_normalize: SaveNormalizations::default(),
+            save_comparison: SaveComparison::Exact,
+            durability: Durability::Fsync,
+            hard_delete: false,
+            confirm_delete_name: false,
+            config: None,
+            command: None,
         };
         assert!(cli.validate().is_ok());
     }
@@ -114,6 +923,39 @@ mod tests {
             target_dir: missing.clone(),
             enable_otel_logs: false,
             max_upload_size_bytes: 1_048_576,
+            max_frontmatter_bytes: 65_536,
+            max_frontmatter_list_items: 256,
+            frontmatter_asset_keys: crate::web::checks::default_asset_keys(),
+            max_filename_length: 200,
+            api_token: None,
+            allow_private_import_urls: false,
+            mounts: Vec::new(),
+            bind: "127.0.0.1".to_string(),
+            port: 5420,
+            audit_journal: None,
+            csrf_secret_file: None,
+            csrf_secret: None,
+            disable_iframe_preview: false,
+            remember_path: false,
+            show_snippets: false,
+            max_concurrent_file_serves: 64,
+            preview_handlers: Vec::new(),
+            enable_preview_commands: false,
+            site_base_url: None,
+            paste_image_dir: None,
+            robots_txt: None,
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset is valid"),
+            optimize_uploads: false,
+            convert_png_uploads_to_webp: false,
+            markdown_extensions: MarkdownExtensions::default(),
+            enable_emoji_shortcodes: false,
+            save_normalize: SaveNormalizations::default(),
+            save_comparison: SaveComparison::Exact,
+            durability: Durability::Fsync,
+            hard_delete: false,
+            confirm_delete_name: false,
+            config: None,
+            command: None,
         };
 
         let result = cli.validate();
@@ -135,6 +977,39 @@ mod tests {
             target_dir: file_path.clone(),
             enable_otel_logs: false,
             max_upload_size_bytes: 1_048_576,
+            max_frontmatter_bytes: 65_536,
+            max_frontmatter_list_items: 256,
+            frontmatter_asset_keys: crate::web::checks::default_asset_keys(),
+            max_filename_length: 200,
+            api_token: None,
+            allow_private_import_urls: false,
+            mounts: Vec::new(),
+            bind: "127.0.0.1".to_string(),
+            port: 5420,
+            audit_journal: None,
+            csrf_secret_file: None,
+            csrf_secret: None,
+            disable_iframe_preview: false,
+            remember_path: false,
+            show_snippets: false,
+            max_concurrent_file_serves: 64,
+            preview_handlers: Vec::new(),
+            enable_preview_commands: false,
+            site_base_url: None,
+            paste_image_dir: None,
+            robots_txt: None,
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset is valid"),
+            optimize_uploads: false,
+            convert_png_uploads_to_webp: false,
+            markdown_extensions: MarkdownExtensions::default(),
+            enable_emoji_shortcodes: false,
+            save_normalize: SaveNormalizations::default(),
+            save_comparison: SaveComparison::Exact,
+            durability: Durability::Fsync,
+            hard_delete: false,
+            confirm_delete_name: false,
+            config: None,
+            command: None,
         };
 
         let result = cli.validate();
@@ -153,6 +1028,39 @@ mod tests {
             target_dir: temp_dir.path().to_path_buf(),
             enable_otel_logs: false,
             max_upload_size_bytes: 0,
+            max_frontmatter_bytes: 65_536,
+            max_frontmatter_list_items: 256,
+            frontmatter_asset_keys: crate::web::checks::default_asset_keys(),
+            max_filename_length: 200,
+            api_token: None,
+            allow_private_import_urls: false,
+            mounts: Vec::new(),
+            bind: "127.0.0.1".to_string(),
+            port: 5420,
+            audit_journal: None,
+            csrf_secret_file: None,
+            csrf_secret: None,
+            disable_iframe_preview: false,
+            remember_path: false,
+            show_snippets: false,
+            max_concurrent_file_serves: 64,
+            preview_handlers: Vec::new(),
+            enable_preview_commands: false,
+            site_base_url: None,
+            paste_image_dir: None,
+            robots_txt: None,
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset is valid"),
+            optimize_uploads: false,
+            convert_png_uploads_to_webp: false,
+            markdown_extensions: MarkdownExtensions::default(),
+            enable_emoji_shortcodes: false,
+            save_normalize: SaveNormalizations::default(),
+            save_comparison: SaveComparison::Exact,
+            durability: Durability::Fsync,
+            hard_delete: false,
+            confirm_delete_name: false,
+            config: None,
+            command: None,
         };
 
         let result = cli.validate();
@@ -161,7 +1069,295 @@ mod tests {
         assert!(err.contains("greater than 0"));
     }
 
+    #[test]
+    fn test_validate_fails_for_zero_max_frontmatter_size() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let cli = Cli {
+            debug: false,
+            target_dir: temp_dir.path().to_path_buf(),
+            enable_otel_logs: false,
+            max_upload_size_bytes: 1_048_576,
+            max_frontmatter_bytes: 0,
+            max_frontmatter_list_items: 256,
+            frontmatter_asset_keys: crate::web::checks::default_asset_keys(),
+            max_filename_length: 200,
+            api_token: None,
+            allow_private_import_urls: false,
+            mounts: Vec::new(),
+            bind: "127.0.0.1".to_string(),
+            port: 5420,
+            audit_journal: None,
+            csrf_secret_file: None,
+            csrf_secret: None,
+            disable_iframe_preview: false,
+            remember_path: false,
+            show_snippets: false,
+            max_concurrent_file_serves: 64,
+            preview_handlers: Vec::new(),
+            enable_preview_commands: false,
+            site_base_url: None,
+            paste_image_dir: None,
+            robots_txt: None,
+            utc_offset: FixedOffset::east_opt(0).expect("zero offset is valid"),
+            optimize_uploads: false,
+            convert_png_uploads_to_webp: false,
+            markdown_extensions: MarkdownExtensions::default(),
+            enable_emoji_shortcodes: false,
+            save_normalize: SaveNormalizations::default(),
+            save_comparison: SaveComparison::Exact,
+            durability: Durability::Fsync,
+            hard_delete: false,
+            confirm_delete_name: false,
+            config: None,
+            command: None,
+        };
+
+        let err = cli
+            .validate()
+            .expect_err("validation should return an error");
+        assert!(err.contains("frontmatter"));
+    }
+
+    #[test]
+    fn test_parse_and_validate_mounts() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let assets = temp_dir.path().join("assets");
+        std::fs::create_dir(&assets).expect("failed to create assets directory");
+        let mount_arg = format!("assets={}", assets.display());
+
+        let cli = Cli::parse_from([
+            "markdown-wrangler",
+            "--mount",
+            mount_arg.as_str(),
+            temp_dir.path().to_str().expect("temp path should be UTF-8"),
+        ]);
+        assert_eq!(
+            cli.mounts,
+            vec![Mount {
+                name: "assets".to_string(),
+                path: assets.clone(),
+            }]
+        );
+        assert!(cli.validate().is_ok());
+
+        let duplicate = Cli::parse_from([
+            "markdown-wrangler",
+            "--mount",
+            mount_arg.as_str(),
+            "--mount",
+            mount_arg.as_str(),
+            temp_dir.path().to_str().expect("temp path should be UTF-8"),
+        ]);
+        let err = duplicate
+            .validate()
+            .expect_err("duplicate mount names should be rejected");
+        assert!(err.contains("more than once"));
+
+        assert!("no-equals-sign".parse::<Mount>().is_err());
+        assert!("../up=/tmp".parse::<Mount>().is_err());
+    }
+
+    #[test]
+    fn test_parse_preview_handlers() {
+        let cli = Cli::parse_from([
+            "markdown-wrangler",
+            "--enable-preview-commands",
+            "--preview-handler",
+            ".DOT=dot -Tsvg",
+            "--preview-handler",
+            "puml=plantuml -tsvg -pipe",
+        ]);
+        assert!(cli.enable_preview_commands);
+        assert_eq!(
+            cli.preview_handlers.first(),
+            Some(&PreviewHandler {
+                extension: "dot".to_string(),
+                command: vec!["dot".to_string(), "-Tsvg".to_string()],
+            })
+        );
+        assert_eq!(cli.preview_handlers.len(), 2);
+
+        let duplicate = Cli::parse_from([
+            "markdown-wrangler",
+            "--preview-handler",
+            "dot=dot -Tsvg",
+            "--preview-handler",
+            "dot=other",
+        ]);
+        let err = duplicate
+            .validate()
+            .expect_err("duplicate preview extensions should be rejected");
+        assert!(err.contains("more than once"));
+
+        assert!("dot".parse::<PreviewHandler>().is_err());
+        assert!("dot=  ".parse::<PreviewHandler>().is_err());
+        assert!("d/t=cat".parse::<PreviewHandler>().is_err());
+    }
+
+    #[test]
+    fn test_validate_site_base_url() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let target = temp_dir.path().to_str().expect("temp path should be UTF-8");
+        let cli = Cli::parse_from([
+            "markdown-wrangler",
+            "--site-base-url",
+            "https://example.com",
+            target,
+        ]);
+        assert_eq!(cli.site_base_url.as_deref(), Some("https://example.com"));
+        assert!(cli.validate().is_ok());
+
+        let cli = Cli::parse_from([
+            "markdown-wrangler",
+            "--site-base-url",
+            "example.com",
+            target,
+        ]);
+        let err = cli
+            .validate()
+            .expect_err("a base URL without a scheme should be rejected");
+        assert!(err.contains("http"));
+    }
+
+    #[test]
+    fn test_validate_paste_image_dir() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let target = temp_dir.path().to_str().expect("temp path should be UTF-8");
+        for (dir, valid) in [("assets/pasted", true), ("../outside", false), ("/", false)] {
+            let cli = Cli::parse_from(["markdown-wrangler", "--paste-image-dir", dir, target]);
+            assert_eq!(cli.validate().is_ok(), valid, "{dir}");
+        }
+    }
+
+    #[test]
+    fn test_validate_csrf_secret() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let target = temp_dir.path().to_str().expect("temp path should be UTF-8");
+        let secret = "0f".repeat(32);
+        let cli = Cli::parse_from(["markdown-wrangler", "--csrf-secret", &secret, target]);
+        assert_eq!(cli.csrf_secret.as_deref(), Some(secret.as_str()));
+        assert!(cli.validate().is_ok());
+
+        let cli = Cli::parse_from(["markdown-wrangler", "--csrf-secret", "short", target]);
+        let err = cli
+            .validate()
+            .expect_err("a short secret should be rejected");
+        assert!(err.contains("64 hex"));
+
+        assert!(
+            Cli::try_parse_from([
+                "markdown-wrangler",
+                "--csrf-secret",
+                &secret,
+                "--csrf-secret-file",
+                "/tmp/csrf",
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_and_validate_bind_address() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let target = temp_dir.path().to_str().expect("temp path is UTF-8");
+        let cli = Cli::parse_from([
+            "markdown-wrangler",
+            "--bind",
+            "0.0.0.0",
+            "--port",
+            "8081",
+            target,
+        ]);
+        assert!(cli.validate().is_ok());
+        assert_eq!(
+            cli.bind_addr().expect("bind address should parse"),
+            "0.0.0.0:8081"
+                .parse::<SocketAddr>()
+                .expect("socket address literal parses")
+        );
+
+        let cli = Cli::parse_from(["markdown-wrangler", "--bind", "::1", target]);
+        assert_eq!(
+            cli.bind_addr().expect("IPv6 bind address should parse"),
+            "[::1]:5420"
+                .parse::<SocketAddr>()
+                .expect("socket address literal parses")
+        );
+
+        let cli = Cli::parse_from(["markdown-wrangler", "--bind", "not-an-ip", target]);
+        let err = cli
+            .validate()
+            .expect_err("a bind address that is not an IP should be rejected");
+        assert!(err.contains("not-an-ip"));
+
+        let cli = Cli::parse_from(["markdown-wrangler", "--port", "0", target]);
+        let err = cli.validate().expect_err("port 0 should be rejected");
+        assert!(err.contains("Port"));
+    }
+
     fn display_path(path: &Path) -> String {
         path.display().to_string()
     }
+
+    #[tokio::test]
+    async fn test_load_merges_config_file_below_explicit_flags() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let content = temp_dir.path().join("content");
+        std::fs::create_dir(&content).expect("failed to create content directory");
+        let config_path = temp_dir.path().join("wrangler.toml");
+        tokio::fs::write(
+            &config_path,
+            "target-dir = \"content\"\nport = 8080\nbind = \"0.0.0.0\"\ndebug = true\n",
+        )
+        .await
+        .expect("Failed to write config");
+        let config_arg = config_path.to_str().expect("temp path should be UTF-8");
+
+        // File values beat the defaults.
+        let cli = Cli::load_from(["markdown-wrangler", "--config", config_arg])
+            .await
+            .expect("config should load");
+        assert_eq!(cli.target_dir, content);
+        assert_eq!(cli.port, 8080);
+        assert_eq!(cli.bind, "0.0.0.0");
+        assert!(cli.debug);
+        assert_eq!(cli.max_upload_size_bytes, 1_048_576);
+        assert!(cli.validate().is_ok());
+
+        // Explicit flags beat the file, even when they repeat a default.
+        let cli = Cli::load_from([
+            "markdown-wrangler",
+            "--config",
+            config_arg,
+            "--port",
+            "5420",
+            "--bind",
+            "::1",
+            ".",
+        ])
+        .await
+        .expect("config should load");
+        assert_eq!(cli.port, 5420);
+        assert_eq!(cli.bind, "::1");
+        assert_eq!(cli.target_dir, PathBuf::from("."));
+        assert!(cli.debug);
+
+        tokio::fs::write(&config_path, "port = 0\n")
+            .await
+            .expect("Failed to write config");
+        let cli = Cli::load_from(["markdown-wrangler", "--config", config_arg])
+            .await
+            .expect("config should load");
+        assert!(cli.validate().is_err(), "merged result should be validated");
+
+        tokio::fs::write(&config_path, "max-upload-size = 10\n")
+            .await
+            .expect("Failed to write config");
+        let err = Cli::load_from(["markdown-wrangler", "--config", config_arg])
+            .await
+            .err()
+            .expect("unknown key should fail");
+        assert!(err.contains("Invalid config file"), "{err}");
+        assert!(err.contains("max-upload-size"), "{err}");
+    }
 }
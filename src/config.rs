@@ -0,0 +1,224 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Settings read from a `--config` TOML file.
+//!
+//! Keys are the long option names without the leading dashes, e.g.
+//! `max-upload-size-bytes = 2048`. Repeatable options (`mount`,
+//! `preview-handler`) take arrays of the same `name=value` strings the
+//! command line does. Every key is optional; explicit command line flags and
+//! environment variables win over the file, which wins over the defaults.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use chrono::FixedOffset;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use tokio::fs;
+
+use crate::cli::{
+    Durability, MarkdownExtensions, Mount, PreviewHandler, SaveComparison, SaveNormalizations,
+};
+use crate::web::timefmt::parse_utc_offset;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    pub debug: Option<bool>,
+    pub target_dir: Option<PathBuf>,
+    pub enable_otel_logs: Option<bool>,
+    pub max_upload_size_bytes: Option<usize>,
+    pub max_frontmatter_bytes: Option<usize>,
+    pub max_frontmatter_list_items: Option<usize>,
+    pub frontmatter_asset_keys: Option<Vec<String>>,
+    pub max_filename_length: Option<usize>,
+    pub api_token: Option<String>,
+    pub allow_private_import_urls: Option<bool>,
+    pub bind: Option<String>,
+    pub port: Option<u16>,
+    #[serde(default, rename = "mount", deserialize_with = "parse_list")]
+    pub mounts: Option<Vec<Mount>>,
+    pub disable_iframe_preview: Option<bool>,
+    pub audit_journal: Option<PathBuf>,
+    pub csrf_secret_file: Option<PathBuf>,
+    pub csrf_secret: Option<String>,
+    pub remember_path: Option<bool>,
+    pub show_snippets: Option<bool>,
+    pub max_concurrent_file_serves: Option<usize>,
+    #[serde(default, rename = "preview-handler", deserialize_with = "parse_list")]
+    pub preview_handlers: Option<Vec<PreviewHandler>>,
+    pub enable_preview_commands: Option<bool>,
+    pub site_base_url: Option<String>,
+    pub paste_image_dir: Option<String>,
+    pub robots_txt: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parse_offset")]
+    pub utc_offset: Option<FixedOffset>,
+    pub optimize_uploads: Option<bool>,
+    pub convert_png_uploads_to_webp: Option<bool>,
+    #[serde(default, deserialize_with = "parse_value")]
+    pub markdown_extensions: Option<MarkdownExtensions>,
+    pub enable_emoji_shortcodes: Option<bool>,
+    #[serde(default, deserialize_with = "parse_value")]
+    pub save_normalize: Option<SaveNormalizations>,
+    #[serde(default, deserialize_with = "parse_value")]
+    pub save_comparison: Option<SaveComparison>,
+    #[serde(default, deserialize_with = "parse_value")]
+    pub durability: Option<Durability>,
+    pub hard_delete: Option<bool>,
+    pub confirm_delete_name: Option<bool>,
+}
+
+impl Config {
+    /// Parses the contents of a config file. Errors name the offending key.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|err| err.to_string())
+    }
+
+    /// Reads and parses the config file at `path`, resolving relative paths
+    /// in it against the file's directory.
+    pub async fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .await
+            .map_err(|err| format!("Failed to read config file '{}': {err}", path.display()))?;
+        let mut config = Self::parse(&text)
+            .map_err(|err| format!("Invalid config file '{}': {err}", path.display()))?;
+        if let Some(base) = path.parent() {
+            config.resolve_paths(base);
+        }
+        Ok(config)
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        for path in [
+            self.target_dir.as_mut(),
+            self.audit_journal.as_mut(),
+            self.csrf_secret_file.as_mut(),
+        ]
+        .into_iter()
+        .flatten()
+        .chain(
+            self.mounts
+                .iter_mut()
+                .flatten()
+                .map(|mount| &mut mount.path),
+        ) {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+    }
+}
+
+/// A single `FromStr` value given as a string.
+fn parse_value<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map(Some).map_err(D::Error::custom)
+}
+
+/// An array of `FromStr` values given as strings.
+fn parse_list<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|value| value.parse().map_err(D::Error::custom))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+fn parse_offset<'de, D>(deserializer: D) -> Result<Option<FixedOffset>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    parse_utc_offset(&value).map(Some).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_reads_kebab_case_keys() {
+        let config = Config::parse(
+            r#"
+debug = true
+port = 8080
+mount = ["docs=/srv/docs"]
+preview-handler = ["dot=dot -Tsvg"]
+utc-offset = "+10:00"
+markdown-extensions = "tables"
+"#,
+        )
+        .expect("config should parse");
+        assert_eq!(config.debug, Some(true));
+        assert_eq!(config.port, Some(8080));
+        assert_eq!(
+            config.mounts,
+            Some(vec![Mount {
+                name: "docs".to_string(),
+                path: PathBuf::from("/srv/docs"),
+            }])
+        );
+        assert_eq!(
+            config.preview_handlers.map(|handlers| handlers.len()),
+            Some(1)
+        );
+        assert_eq!(
+            config.utc_offset.map(|offset| offset.local_minus_utc()),
+            Some(10 * 3600)
+        );
+        assert_eq!(
+            config.markdown_extensions,
+            Some(MarkdownExtensions {
+                tables: true,
+                ..MarkdownExtensions::NONE
+            })
+        );
+        assert_eq!(config.bind, None);
+    }
+
+    #[test]
+    fn test_parse_errors_name_the_key() {
+        let err = Config::parse("prot = 8080").expect_err("unknown key should fail");
+        assert!(err.contains("prot"), "{err}");
+
+        let err = Config::parse("port = \"eighty\"").expect_err("bad type should fail");
+        assert!(err.contains("port"), "{err}");
+
+        let err = Config::parse("utc-offset = \"AEST\"").expect_err("bad offset should fail");
+        assert!(err.contains("utc-offset"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_load_resolves_relative_paths_and_reports_missing_files() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let path = temp_dir.path().join("wrangler.toml");
+        fs::write(
+            &path,
+            "target-dir = \"content\"\naudit-journal = \"/var/log/audit\"\n",
+        )
+        .await
+        .expect("Failed to write config");
+
+        let config = Config::load(&path).await.expect("config should load");
+        assert_eq!(config.target_dir, Some(temp_dir.path().join("content")));
+        assert_eq!(config.audit_journal, Some(PathBuf::from("/var/log/audit")));
+
+        let missing = temp_dir.path().join("missing.toml");
+        let err = Config::load(&missing)
+            .await
+            .expect_err("missing file should fail");
+        assert!(err.starts_with("Failed to read config file"), "{err}");
+        assert!(err.contains("missing.toml"), "{err}");
+    }
+}
@@ -20,5 +20,7 @@
 #![deny(clippy::unreachable)]
 
 pub mod cli;
+pub mod config;
 pub mod logging;
+pub mod paths;
 pub mod web;
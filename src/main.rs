@@ -23,21 +23,53 @@
 #![deny(clippy::indexing_slicing)]
 #![deny(clippy::unreachable)]
 
-use markdown_wrangler::cli::Cli;
+use markdown_wrangler::cli::{Cli, Command};
 use markdown_wrangler::logging::{init_tracing, log_startup};
-use markdown_wrangler::web::start_server;
+use markdown_wrangler::web::error::ServerError;
+use markdown_wrangler::web::{
+    ServerOptions, WranglerConfig, checks, resolve_csrf_secret, start_server,
+};
 use tokio::signal::unix::{SignalKind, signal};
 use tracing::info;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+    let cli = match Cli::load().await {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        }
+    };
 
-    // Validate CLI arguments
+    // Validate the merged CLI arguments and config file
     if let Err(err) = cli.validate() {
         eprintln!("Error: {err}");
         std::process::exit(1);
     }
+    // Already checked by validate().
+    let bind_addr = cli.bind_addr()?;
+
+    if cli.command == Some(Command::Lint) {
+        let report = checks::lint(
+            &cli.target_dir,
+            cli.max_frontmatter_bytes,
+            &cli.frontmatter_asset_keys,
+        )
+        .await?;
+        for result in &report.results {
+            for finding in &result.findings {
+                println!("{}: [{}] {}", finding.path, result.name, finding.message);
+            }
+        }
+        let total = report.total();
+        if total > 0 {
+            eprintln!("{total} problem(s) found");
+            std::process::exit(1);
+        }
+        println!("0 problems found");
+        return Ok(());
+    }
 
     let mut hangup_waiter = signal(SignalKind::hangup())?;
     let tracing_provider = init_tracing(cli.enable_otel_logs, cli.debug)?;
@@ -49,10 +81,73 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         cli.max_upload_size_bytes
     );
 
+    let csrf_secret = match resolve_csrf_secret(cli.csrf_secret, cli.csrf_secret_file).await {
+        Ok(secret) => secret,
+        Err(err) => {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        }
+    };
+
+    let robots_txt = match &cli.robots_txt {
+        Some(path) => match tokio::fs::read_to_string(path).await {
+            Ok(contents) => Some(contents),
+            Err(err) => {
+                eprintln!("Error: failed to read {}: {err}", path.display());
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut exit_code = 0;
     tokio::select! {
-        err = start_server(cli.target_dir, cli.max_upload_size_bytes) => {
-            if let Err(err) = err {
-                eprintln!("Server error, shutting down. Error: {err}");
+        err = start_server(ServerOptions {
+            config: WranglerConfig {
+                target_dir: cli.target_dir,
+                csrf_secret,
+                max_upload_size_bytes: cli.max_upload_size_bytes,
+                max_frontmatter_bytes: cli.max_frontmatter_bytes,
+                max_frontmatter_list_items: cli.max_frontmatter_list_items,
+                frontmatter_asset_keys: cli.frontmatter_asset_keys,
+                max_filename_length: cli.max_filename_length,
+                api_token: cli.api_token,
+                allow_private_import_urls: cli.allow_private_import_urls,
+                mounts: cli.mounts,
+                audit_journal: cli.audit_journal,
+                disable_iframe_preview: cli.disable_iframe_preview,
+                remember_path: cli.remember_path,
+                show_snippets: cli.show_snippets,
+                max_concurrent_file_serves: cli.max_concurrent_file_serves,
+                preview_handlers: cli.preview_handlers,
+                enable_preview_commands: cli.enable_preview_commands,
+                site_base_url: cli.site_base_url,
+                paste_image_dir: cli.paste_image_dir,
+                robots_txt,
+                display_offset: cli.utc_offset,
+                optimize_uploads: cli.optimize_uploads,
+                convert_png_uploads_to_webp: cli.convert_png_uploads_to_webp,
+                markdown_extensions: cli.markdown_extensions,
+                emoji_shortcodes: cli.enable_emoji_shortcodes,
+                save_normalize: cli.save_normalize,
+                save_comparison: cli.save_comparison,
+                durability: cli.durability,
+                hard_delete: cli.hard_delete,
+                confirm_delete_name: cli.confirm_delete_name,
+                base_path: String::new(),
+            },
+            bind_addr,
+        }) => {
+            match err {
+                Ok(()) => {}
+                Err(err @ ServerError::AddrInUse { .. }) => {
+                    eprintln!("Error: {err}");
+                    exit_code = 1;
+                }
+                Err(err) => {
+                    eprintln!("Server error, shutting down. Error: {err}");
+                    exit_code = 1;
+                }
             }
         },
         _ = hangup_waiter.recv() => {
@@ -66,5 +161,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         eprintln!("Error shutting down tracing provider: {err}");
     }
 
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
     Ok(())
 }
@@ -0,0 +1,571 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Filename and relative-path helpers shared by the web handlers.
+//!
+//! Relative paths handled here are always *decoded* values (as produced by
+//! the query and form extractors) using `/` as the separator, relative to the
+//! content root.
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// Errors returned when a filename or relative path is not acceptable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PathError {
+    /// The filename was empty after trimming whitespace.
+    EmptyFilename,
+    /// The filename contains characters outside the allowed ASCII set.
+    InvalidFilename,
+    /// The relative path contains a `..` component.
+    ParentTraversal,
+    /// The relative path contains a NUL byte or a backslash.
+    InvalidCharacter,
+    /// The filename is longer than the configured maximum, in bytes.
+    FilenameTooLong { max_length: usize },
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathError::EmptyFilename => write!(f, "Filename is required"),
+            PathError::InvalidFilename => write!(
+                f,
+                "Filename must use only ASCII letters, numbers, '-', '_', or '.'"
+            ),
+            PathError::ParentTraversal => {
+                write!(f, "Path must not contain '..' components")
+            }
+            PathError::InvalidCharacter => {
+                write!(f, "Path must not contain NUL bytes or backslashes")
+            }
+            PathError::FilenameTooLong { max_length } => write!(
+                f,
+                "Filename must be at most {max_length} characters including the extension"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Returns `true` when `stem` is a filename stem that is safe to commit to git
+/// on every platform: non-empty ASCII made of letters, digits, `-`, `_` and
+/// `.`, without leading/trailing dots or `..`.
+///
+/// ```
+/// use markdown_wrangler::paths::is_git_compatible_ascii_filename_stem;
+///
+/// assert!(is_git_compatible_ascii_filename_stem("my-post_2.v1"));
+/// assert!(!is_git_compatible_ascii_filename_stem(".hidden"));
+/// assert!(!is_git_compatible_ascii_filename_stem("two words"));
+/// ```
+pub fn is_git_compatible_ascii_filename_stem(stem: &str) -> bool {
+    !stem.is_empty()
+        && stem.is_ascii()
+        && !stem.starts_with('.')
+        && !stem.ends_with('.')
+        && !stem.contains("..")
+        && stem
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Default for `--max-filename-length`, comfortably under the 255-byte name
+/// limit of common filesystems.
+pub const DEFAULT_MAX_FILENAME_LENGTH: usize = 200;
+
+/// Normalizes user input into a markdown filename ending in `.md`.
+///
+/// Surrounding whitespace is trimmed and an existing `.md` or `.markdown`
+/// extension (any case) is replaced with `.md`. The remaining stem must pass
+/// [`is_git_compatible_ascii_filename_stem`], and the resulting name must be
+/// at most `max_length` bytes.
+///
+/// ```
+/// use markdown_wrangler::paths::{PathError, normalize_markdown_filename};
+///
+/// assert_eq!(normalize_markdown_filename(" post ", 200).as_deref(), Ok("post.md"));
+/// assert_eq!(normalize_markdown_filename("post.markdown", 200).as_deref(), Ok("post.md"));
+/// assert_eq!(normalize_markdown_filename("", 200), Err(PathError::EmptyFilename));
+/// assert_eq!(normalize_markdown_filename("a/b", 200), Err(PathError::InvalidFilename));
+/// assert_eq!(
+///     normalize_markdown_filename("long-post", 10),
+///     Err(PathError::FilenameTooLong { max_length: 10 })
+/// );
+/// ```
+pub fn normalize_markdown_filename(filename: &str, max_length: usize) -> Result<String, PathError> {
+    let trimmed = filename.trim();
+    if trimmed.is_empty() {
+        return Err(PathError::EmptyFilename);
+    }
+
+    let mut stem = trimmed.to_string();
+    let lower = stem.to_ascii_lowercase();
+    if lower.ends_with(".markdown") {
+        stem.truncate(stem.len().saturating_sub(".markdown".len()));
+    } else if lower.ends_with(".md") {
+        stem.truncate(stem.len().saturating_sub(".md".len()));
+    }
+    let stem = stem.trim();
+
+    if !is_git_compatible_ascii_filename_stem(stem) {
+        return Err(PathError::InvalidFilename);
+    }
+
+    let filename = format!("{stem}.md");
+    if filename.len() > max_length {
+        return Err(PathError::FilenameTooLong { max_length });
+    }
+    Ok(filename)
+}
+
+/// Normalizes user input into a new name for a file that keeps the
+/// extension of `original`, as when renaming an image.
+///
+/// The extension may be omitted or given in any case. The stem must pass
+/// [`is_git_compatible_ascii_filename_stem`], and the resulting name must be
+/// at most `max_length` bytes.
+///
+/// ```
+/// use markdown_wrangler::paths::{PathError, normalize_renamed_filename};
+///
+/// assert_eq!(normalize_renamed_filename("cat.png", "dog", 200).as_deref(), Ok("dog.png"));
+/// assert_eq!(normalize_renamed_filename("cat.png", "dog.PNG", 200).as_deref(), Ok("dog.png"));
+/// assert_eq!(normalize_renamed_filename("cat.png", "dog.jpg", 200).as_deref(), Ok("dog.jpg.png"));
+/// assert_eq!(normalize_renamed_filename("cat.png", "..", 200), Err(PathError::InvalidFilename));
+/// ```
+pub fn normalize_renamed_filename(
+    original: &str,
+    filename: &str,
+    max_length: usize,
+) -> Result<String, PathError> {
+    let trimmed = filename.trim();
+    if trimmed.is_empty() {
+        return Err(PathError::EmptyFilename);
+    }
+
+    let extension = original
+        .rsplit('/')
+        .next()
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension)
+        .filter(|extension| !extension.is_empty());
+    let stem = match extension {
+        Some(extension) => trimmed
+            .rsplit_once('.')
+            .filter(|(_, given)| given.eq_ignore_ascii_case(extension))
+            .map_or(trimmed, |(stem, _)| stem),
+        None => trimmed,
+    };
+
+    if !is_git_compatible_ascii_filename_stem(stem) {
+        return Err(PathError::InvalidFilename);
+    }
+
+    let filename = match extension {
+        Some(extension) => format!("{stem}.{extension}"),
+        None => stem.to_string(),
+    };
+    if filename.len() > max_length {
+        return Err(PathError::FilenameTooLong { max_length });
+    }
+    Ok(filename)
+}
+
+/// Turns the name a browser sent for an uploaded file into a safe filename.
+///
+/// Any directory part (with `/` or `\` separators) is dropped, runs of
+/// characters outside ASCII letters, digits, `-` and `_` in the stem become a
+/// single `-` (leading ones are dropped), and the extension is lowercased.
+/// The result passes [`is_git_compatible_ascii_filename_stem`] and is at most
+/// `max_length` bytes.
+///
+/// ```
+/// use markdown_wrangler::paths::{PathError, sanitize_upload_filename};
+///
+/// assert_eq!(
+///     sanitize_upload_filename("C:\\Users\\me\\My Photo (1).JPG", 200).as_deref(),
+///     Ok("My-Photo-1.jpg")
+/// );
+/// assert_eq!(sanitize_upload_filename("../../notes.pdf", 200).as_deref(), Ok("notes.pdf"));
+/// assert_eq!(sanitize_upload_filename(".env", 200).as_deref(), Ok("env"));
+/// assert_eq!(sanitize_upload_filename("???.png", 200), Err(PathError::InvalidFilename));
+/// assert_eq!(sanitize_upload_filename("  ", 200), Err(PathError::EmptyFilename));
+/// ```
+pub fn sanitize_upload_filename(filename: &str, max_length: usize) -> Result<String, PathError> {
+    let name = filename
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    if name.is_empty() {
+        return Err(PathError::EmptyFilename);
+    }
+
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (name, None),
+    };
+    let mut sanitized = String::with_capacity(name.len());
+    for c in stem.chars() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+            sanitized.push(c);
+        } else if !sanitized.is_empty() && !sanitized.ends_with('-') {
+            sanitized.push('-');
+        }
+    }
+    let stem = sanitized.trim_end_matches('-');
+    let extension = extension
+        .map(|extension| {
+            extension
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_ascii_lowercase()
+        })
+        .filter(|extension| !extension.is_empty());
+
+    if !is_git_compatible_ascii_filename_stem(stem) {
+        return Err(PathError::InvalidFilename);
+    }
+
+    let filename = match extension {
+        Some(extension) => format!("{stem}.{extension}"),
+        None => stem.to_string(),
+    };
+    if filename.len() > max_length {
+        return Err(PathError::FilenameTooLong { max_length });
+    }
+    Ok(filename)
+}
+
+/// Turns free text (such as a post title) into a lowercase ASCII slug made of
+/// letters, digits and single `-` separators.
+///
+/// Returns an empty string when the input has no ASCII letters or digits.
+///
+/// ```
+/// use markdown_wrangler::paths::slugify;
+///
+/// assert_eq!(slugify("Hello, World!"), "hello-world");
+/// assert_eq!(slugify("  C++ notes -- 2026  "), "c-notes-2026");
+/// assert_eq!(slugify("ümlaut"), "mlaut");
+/// ```
+pub fn slugify(input: &str) -> String {
+    let mut slug = String::with_capacity(input.len());
+    for c in input.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+    slug
+}
+
+/// Canonicalizes a decoded relative path.
+///
+/// Surrounding slashes, empty components and `.` components are dropped.
+/// Paths containing `..`, NUL bytes or backslashes are rejected. The input is
+/// borrowed unchanged when it is already canonical.
+///
+/// This is a lexical check only; callers touching the filesystem must still
+/// resolve the result against the content root.
+///
+/// ```
+/// use std::borrow::Cow;
+/// use markdown_wrangler::paths::{PathError, sanitize_relative_path};
+///
+/// assert_eq!(sanitize_relative_path("/posts/2026/").as_deref(), Ok("posts/2026"));
+/// assert_eq!(sanitize_relative_path("posts//./a.md").as_deref(), Ok("posts/a.md"));
+/// assert!(matches!(sanitize_relative_path("posts"), Ok(Cow::Borrowed("posts"))));
+/// assert_eq!(sanitize_relative_path("../etc"), Err(PathError::ParentTraversal));
+/// ```
+pub fn sanitize_relative_path(path: &str) -> Result<Cow<'_, str>, PathError> {
+    if path.contains(['\0', '\\']) {
+        return Err(PathError::InvalidCharacter);
+    }
+
+    let trimmed = path.trim_matches('/');
+    let mut components = Vec::new();
+    let mut changed = false;
+    for component in trimmed.split('/').filter(|_| !trimmed.is_empty()) {
+        match component {
+            ".." => return Err(PathError::ParentTraversal),
+            "" | "." => changed = true,
+            other => components.push(other),
+        }
+    }
+
+    if changed {
+        Ok(Cow::Owned(components.join("/")))
+    } else {
+        Ok(Cow::Borrowed(trimmed))
+    }
+}
+
+/// Joins a child name onto a relative directory path.
+///
+/// ```
+/// use markdown_wrangler::paths::join_relative_path;
+///
+/// assert_eq!(join_relative_path("", "post.md"), "post.md");
+/// assert_eq!(join_relative_path("posts/", "post.md"), "posts/post.md");
+/// ```
+pub fn join_relative_path(directory: &str, name: &str) -> String {
+    let directory = directory.trim_matches('/');
+    if directory.is_empty() {
+        name.to_string()
+    } else {
+        format!("{directory}/{name}")
+    }
+}
+
+/// Spells `target` relative to `directory` (both relative to the content
+/// root), as a link written in a page inside `directory` would.
+///
+/// ```
+/// use markdown_wrangler::paths::relative_link;
+///
+/// assert_eq!(relative_link("posts", "posts/shot.png"), "shot.png");
+/// assert_eq!(relative_link("posts/2024", "assets/shot.png"), "../../assets/shot.png");
+/// assert_eq!(relative_link("", "assets/shot.png"), "assets/shot.png");
+/// ```
+pub fn relative_link(directory: &str, target: &str) -> String {
+    let from: Vec<&str> = directory
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect();
+    let to: Vec<&str> = target.split('/').filter(|part| !part.is_empty()).collect();
+    let common = from
+        .iter()
+        .zip(&to)
+        .take_while(|(from, to)| from == to)
+        .count();
+    let mut parts = vec![".."; from.len().saturating_sub(common)];
+    parts.extend(to.iter().skip(common));
+    parts.join("/")
+}
+
+/// Builds a link to `route` for a relative path, percent-encoding the whole
+/// path (including `/`, `#`, `?`, `+`, `%` and spaces) as a single `path`
+/// query value. An empty path links to `route` itself.
+///
+/// ```
+/// use markdown_wrangler::paths::path_url;
+///
+/// assert_eq!(path_url("/", ""), "/");
+/// assert_eq!(path_url("/edit", "c++/faq?.md"), "/edit?path=c%2B%2B%2Ffaq%3F.md");
+/// ```
+pub fn path_url(route: &str, relative_path: &str) -> String {
+    if relative_path.is_empty() {
+        route.to_string()
+    } else {
+        format!("{route}?path={}", urlencoding::encode(relative_path))
+    }
+}
+
+/// Returns the directory-listing URL for the parent of a relative path.
+///
+/// ```
+/// use markdown_wrangler::paths::get_parent_directory_path;
+///
+/// assert_eq!(get_parent_directory_path("post.md"), "/");
+/// assert_eq!(get_parent_directory_path("posts/2026/post.md"), "/?path=posts%2F2026");
+/// ```
+pub fn get_parent_directory_path(file_path: &str) -> String {
+    match file_path.trim_matches('/').rsplit_once('/') {
+        Some((parent, _)) => path_url("/", parent),
+        None => "/".to_string(),
+    }
+}
+
+/// Matches `text` against a shell-style glob where `*` matches any run of
+/// characters (including `/`) and `?` matches a single character.
+///
+/// ```
+/// use markdown_wrangler::paths::glob_matches;
+///
+/// assert!(glob_matches("*.tmp", "notes.tmp"));
+/// assert!(glob_matches("drafts/*", "drafts/a/b.md"));
+/// assert!(glob_matches("post-?.md", "post-1.md"));
+/// assert!(!glob_matches("*.tmp", "notes.md"));
+/// ```
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it is currently matched up to.
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || Some(&c) == text.get(t) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern
+        .get(p..)
+        .is_some_and(|rest| rest.iter().all(|&c| c == '*'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_compatible_ascii_filename_stem_rules() {
+        assert!(is_git_compatible_ascii_filename_stem("post"));
+        assert!(is_git_compatible_ascii_filename_stem("post-1_2.test"));
+        assert!(!is_git_compatible_ascii_filename_stem(""));
+        assert!(!is_git_compatible_ascii_filename_stem(".post"));
+        assert!(!is_git_compatible_ascii_filename_stem("post."));
+        assert!(!is_git_compatible_ascii_filename_stem("post..v1"));
+        assert!(!is_git_compatible_ascii_filename_stem("bad/name"));
+        assert!(!is_git_compatible_ascii_filename_stem("two words"));
+        assert!(!is_git_compatible_ascii_filename_stem("ümlaut"));
+    }
+
+    #[test]
+    fn test_normalize_markdown_filename_handles_common_inputs() {
+        assert_eq!(
+            normalize_markdown_filename("post", DEFAULT_MAX_FILENAME_LENGTH).ok(),
+            Some("post.md".to_string())
+        );
+        assert_eq!(
+            normalize_markdown_filename("post.md", DEFAULT_MAX_FILENAME_LENGTH).ok(),
+            Some("post.md".to_string())
+        );
+        assert_eq!(
+            normalize_markdown_filename("post.markdown", DEFAULT_MAX_FILENAME_LENGTH).ok(),
+            Some("post.md".to_string())
+        );
+        assert_eq!(
+            normalize_markdown_filename("  post-with-space-trim  ", DEFAULT_MAX_FILENAME_LENGTH)
+                .ok(),
+            Some("post-with-space-trim.md".to_string())
+        );
+        assert!(normalize_markdown_filename("", DEFAULT_MAX_FILENAME_LENGTH).is_err());
+        assert!(normalize_markdown_filename("bad/name", DEFAULT_MAX_FILENAME_LENGTH).is_err());
+        assert!(normalize_markdown_filename(".hidden", DEFAULT_MAX_FILENAME_LENGTH).is_err());
+        assert!(normalize_markdown_filename("bad..name", DEFAULT_MAX_FILENAME_LENGTH).is_err());
+    }
+
+    #[test]
+    fn test_normalize_markdown_filename_enforces_max_length() {
+        // 197-byte stem plus ".md" is exactly the default limit.
+        let longest = "a".repeat(DEFAULT_MAX_FILENAME_LENGTH - 3);
+        assert_eq!(
+            normalize_markdown_filename(&longest, DEFAULT_MAX_FILENAME_LENGTH),
+            Ok(format!("{longest}.md"))
+        );
+        // The limit applies after `.markdown` is shortened to `.md`.
+        assert!(
+            normalize_markdown_filename(
+                &format!("{longest}.markdown"),
+                DEFAULT_MAX_FILENAME_LENGTH
+            )
+            .is_ok()
+        );
+
+        let too_long = "a".repeat(DEFAULT_MAX_FILENAME_LENGTH - 2);
+        let err = normalize_markdown_filename(&too_long, DEFAULT_MAX_FILENAME_LENGTH)
+            .expect_err("an over-length filename should be rejected");
+        assert_eq!(
+            err,
+            PathError::FilenameTooLong {
+                max_length: DEFAULT_MAX_FILENAME_LENGTH
+            }
+        );
+        assert!(err.to_string().contains("200"));
+    }
+
+    #[test]
+    fn test_slugify_collapses_separators() {
+        assert_eq!(slugify("My First Post"), "my-first-post");
+        assert_eq!(slugify("--Already--slugged--"), "already-slugged");
+        assert_eq!(slugify("2026/01/02"), "2026-01-02");
+        assert_eq!(slugify("!!!"), "");
+    }
+
+    #[test]
+    fn test_sanitize_relative_path_rules() {
+        assert_eq!(sanitize_relative_path("").as_deref(), Ok(""));
+        assert_eq!(sanitize_relative_path("/").as_deref(), Ok(""));
+        assert_eq!(
+            sanitize_relative_path("/posts/2026/").as_deref(),
+            Ok("posts/2026")
+        );
+        assert_eq!(
+            sanitize_relative_path("posts//./2026").as_deref(),
+            Ok("posts/2026")
+        );
+        assert_eq!(
+            sanitize_relative_path("c++ #notes?/100%.md").as_deref(),
+            Ok("c++ #notes?/100%.md")
+        );
+        assert!(matches!(
+            sanitize_relative_path("posts/2026"),
+            Ok(Cow::Borrowed(_))
+        ));
+        assert_eq!(
+            sanitize_relative_path("posts/../secret"),
+            Err(PathError::ParentTraversal)
+        );
+        assert_eq!(
+            sanitize_relative_path("posts\\secret"),
+            Err(PathError::InvalidCharacter)
+        );
+        assert_eq!(
+            sanitize_relative_path("posts\0"),
+            Err(PathError::InvalidCharacter)
+        );
+    }
+
+    #[test]
+    fn test_get_parent_directory_path_formats_navigation_urls() {
+        assert_eq!(get_parent_directory_path("post.md"), "/");
+        assert_eq!(get_parent_directory_path("posts/post.md"), "/?path=posts");
+        assert_eq!(
+            get_parent_directory_path("posts/2026/post.md"),
+            "/?path=posts%2F2026"
+        );
+    }
+
+    #[test]
+    fn test_path_url_encodes_reserved_characters_once() {
+        assert_eq!(path_url("/", ""), "/");
+        assert_eq!(
+            path_url("/edit", "posts/post.md"),
+            "/edit?path=posts%2Fpost.md"
+        );
+        assert_eq!(
+            path_url("/edit", "c++ notes/faq?#1 100%.md"),
+            "/edit?path=c%2B%2B%20notes%2Ffaq%3F%231%20100%25.md"
+        );
+        assert_eq!(join_relative_path("", "post.md"), "post.md");
+        assert_eq!(join_relative_path("a#b/", "c+d.md"), "a#b/c+d.md");
+        assert_eq!(get_parent_directory_path("a%2Fb/c.md"), "/?path=a%252Fb");
+    }
+}
@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Content hashes for the files under `static/`, computed once at startup.
+//!
+//! Templates link assets through [`AssetManifest::url`], which appends
+//! `?v=<hash>` (and the mount prefix, see [`super::base_path`]). A request carrying the current hash can be cached forever;
+//! after an upgrade the hash changes, so browsers fetch the new file without
+//! a hard refresh.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tracing::warn;
+
+use super::base_path::app_url;
+
+/// Directory the `/static` route serves, relative to the working directory.
+pub(crate) const STATIC_DIR: &str = "static";
+
+/// Hex digits of the SHA-256 digest kept in asset URLs.
+const HASH_LENGTH: usize = 16;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AssetManifest {
+    root: PathBuf,
+    /// Relative path (`/`-separated) to content hash.
+    hashes: Arc<HashMap<String, String>>,
+}
+
+impl AssetManifest {
+    /// Hashes every file under `root`. A missing or unreadable directory
+    /// gives an empty manifest, so asset URLs simply go unversioned.
+    pub(crate) async fn load(root: &Path) -> Self {
+        let mut hashes = HashMap::new();
+        let mut pending = vec![PathBuf::new()];
+        while let Some(relative_dir) = pending.pop() {
+            let mut entries = match fs::read_dir(root.join(&relative_dir)).await {
+                Ok(entries) => entries,
+                Err(err) => {
+                    warn!(
+                        "Failed to read static assets in {}: {}",
+                        root.join(&relative_dir).display(),
+                        err
+                    );
+                    continue;
+                }
+            };
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                let relative_path = relative_dir.join(entry.file_name());
+                match entry.file_type().await {
+                    Ok(file_type) if file_type.is_dir() => pending.push(relative_path),
+                    Ok(file_type) if file_type.is_file() => match fs::read(entry.path()).await {
+                        Ok(bytes) => {
+                            let digest = hex::encode(Sha256::digest(&bytes));
+                            hashes.insert(
+                                relative_path.to_string_lossy().replace('\\', "/"),
+                                digest.chars().take(HASH_LENGTH).collect(),
+                            );
+                        }
+                        Err(err) => warn!(
+                            "Failed to hash static asset {}: {}",
+                            entry.path().display(),
+                            err
+                        ),
+                    },
+                    _ => {}
+                }
+            }
+        }
+        AssetManifest {
+            root: root.to_path_buf(),
+            hashes: Arc::new(hashes),
+        }
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Link to a static asset, versioned by content hash when known.
+    pub(crate) fn url(&self, name: &str) -> String {
+        match self.hashes.get(name) {
+            Some(hash) => app_url(&format!("/static/{name}?v={hash}")),
+            None => app_url(&format!("/static/{name}")),
+        }
+    }
+
+    /// Whether `version` is the current hash of `name`.
+    pub(crate) fn is_current(&self, name: &str, version: &str) -> bool {
+        self.hashes.get(name).is_some_and(|hash| hash == version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_manifest_versions_known_assets() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::create_dir(temp_dir.path().join("vendor"))
+            .await
+            .expect("failed to create vendor directory");
+        fs::write(temp_dir.path().join("vendor/lib.js"), "lib")
+            .await
+            .expect("failed to write asset");
+
+        let manifest = AssetManifest::load(temp_dir.path()).await;
+        let url = manifest.url("vendor/lib.js");
+        let (path, version) = url.split_once("?v=").expect("known asset is versioned");
+        assert_eq!(path, "/static/vendor/lib.js");
+        assert_eq!(version.len(), HASH_LENGTH);
+        assert!(manifest.is_current("vendor/lib.js", version));
+        assert!(!manifest.is_current("vendor/lib.js", "stale"));
+        assert_eq!(manifest.url("missing.css"), "/static/missing.css");
+    }
+}
@@ -0,0 +1,246 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Append-only audit journal of content writes.
+//!
+//! Each successful write is appended as one JSON object per line. The journal
+//! is the source of truth for per-file edit statistics, which are aggregated
+//! incrementally: only bytes appended since the last lookup are parsed.
+
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use super::unix_seconds;
+
+/// Operations that change a file's content and count as an edit.
+const EDIT_OPERATIONS: &[&str] = &[
+    "create", "save", "write", "upload", "import", "copy", "restore",
+];
+
+/// One line of the journal.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct AuditEntry {
+    /// Unix seconds.
+    pub timestamp: u64,
+    pub operation: String,
+    pub path: String,
+    /// Where the change came from, e.g. `web` or `api`.
+    pub via: String,
+    /// Previous path for moves.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+}
+
+/// Aggregated history of one path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileStats {
+    pub edits: usize,
+    /// Timestamp of the first journal entry for this path.
+    pub first_seen: u64,
+    pub last: AuditEntry,
+}
+
+#[derive(Default)]
+struct StatsIndex {
+    /// Journal length already folded into `files`.
+    offset: u64,
+    files: HashMap<String, FileStats>,
+}
+
+impl StatsIndex {
+    fn apply(&mut self, entry: AuditEntry) {
+        match entry.operation.as_str() {
+            "delete" => {
+                self.files.remove(&entry.path);
+                return;
+            }
+            "move" => {
+                let previous = entry.from.as_ref().and_then(|from| self.files.remove(from));
+                let stats = previous.unwrap_or(FileStats {
+                    edits: 0,
+                    first_seen: entry.timestamp,
+                    last: entry.clone(),
+                });
+                self.files.insert(
+                    entry.path.clone(),
+                    FileStats {
+                        last: entry,
+                        ..stats
+                    },
+                );
+                return;
+            }
+            _ => {}
+        }
+
+        let is_edit = EDIT_OPERATIONS.contains(&entry.operation.as_str());
+        match self.files.get_mut(&entry.path) {
+            Some(stats) => {
+                stats.edits += usize::from(is_edit);
+                stats.last = entry;
+            }
+            None => {
+                self.files.insert(
+                    entry.path.clone(),
+                    FileStats {
+                        edits: usize::from(is_edit),
+                        first_seen: entry.timestamp,
+                        last: entry,
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuditJournal {
+    path: PathBuf,
+    write_lock: Arc<Mutex<()>>,
+    stats: Arc<Mutex<StatsIndex>>,
+}
+
+impl AuditJournal {
+    pub fn new(path: PathBuf) -> Self {
+        AuditJournal {
+            path,
+            write_lock: Arc::new(Mutex::new(())),
+            stats: Arc::new(Mutex::new(StatsIndex::default())),
+        }
+    }
+
+    /// Appends an entry. Failures are logged rather than returned because the
+    /// write being audited has already happened.
+    pub async fn record(&self, operation: &str, path: &str, via: &str, from: Option<&str>) {
+        let entry = AuditEntry {
+            timestamp: unix_seconds(SystemTime::now()),
+            operation: operation.to_string(),
+            path: path.to_string(),
+            via: via.to_string(),
+            from: from.map(ToString::to_string),
+        };
+        if let Err(err) = self.append(&entry).await {
+            warn!(
+                "Failed to append to audit journal {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+
+    async fn append(&self, entry: &AuditEntry) -> Result<(), std::io::Error> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let _guard = self.write_lock.lock().await;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await
+    }
+
+    /// Returns the aggregated history of `path`, reading only the part of the
+    /// journal appended since the previous call.
+    pub async fn file_stats(&self, path: &str) -> Result<Option<FileStats>, std::io::Error> {
+        let mut index = self.stats.lock().await;
+
+        let length = match fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err),
+        };
+        if length < index.offset {
+            // The journal was truncated or rotated; start over.
+            *index = StatsIndex::default();
+        }
+
+        if length > index.offset {
+            let mut file = fs::File::open(&self.path).await?;
+            file.seek(SeekFrom::Start(index.offset)).await?;
+            let mut appended = Vec::new();
+            file.read_to_end(&mut appended).await?;
+
+            // Leave a partially written trailing line for the next lookup.
+            let complete = appended
+                .iter()
+                .rposition(|byte| *byte == b'\n')
+                .map_or(0, |position| position + 1);
+            for line in appended
+                .get(..complete)
+                .unwrap_or_default()
+                .split(|b| *b == b'\n')
+            {
+                if line.is_empty() {
+                    continue;
+                }
+                match serde_json::from_slice::<AuditEntry>(line) {
+                    Ok(entry) => index.apply(entry),
+                    Err(err) => warn!("Skipping malformed audit journal line: {}", err),
+                }
+            }
+            index.offset += complete as u64;
+        }
+
+        Ok(index.files.get(path).cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_file_stats_follow_moves_and_deletes() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let journal = AuditJournal::new(temp_dir.path().join("audit.jsonl"));
+
+        assert_eq!(
+            journal.file_stats("a.md").await.expect("stats should load"),
+            None
+        );
+
+        journal.record("create", "a.md", "web", None).await;
+        journal.record("save", "a.md", "web", None).await;
+        let stats = journal
+            .file_stats("a.md")
+            .await
+            .expect("stats should load")
+            .expect("a.md should have stats");
+        assert_eq!(stats.edits, 2);
+        assert_eq!(stats.last.operation, "save");
+
+        journal.record("move", "b.md", "api", Some("a.md")).await;
+        journal.record("write", "b.md", "api", None).await;
+        assert_eq!(
+            journal.file_stats("a.md").await.expect("stats should load"),
+            None
+        );
+        let stats = journal
+            .file_stats("b.md")
+            .await
+            .expect("stats should load")
+            .expect("b.md should inherit a.md's history");
+        assert_eq!(stats.edits, 3);
+        assert_eq!(stats.last.via, "api");
+
+        journal.record("delete", "b.md", "web", None).await;
+        assert_eq!(
+            journal.file_stats("b.md").await.expect("stats should load"),
+            None
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The path prefix the router is mounted under.
+//!
+//! Routes are always matched without the prefix (`Router::nest` strips it),
+//! but every URL handed to the browser needs it. [`scope`] runs each request
+//! with the configured prefix in a task-local, so links built deep inside
+//! helpers through [`app_url`] and [`route_url`] pick it up without
+//! threading the state through. Outside a request (tests, background tasks)
+//! the prefix is empty and URLs come out unchanged.
+
+use std::sync::Arc;
+
+use axum::{extract::Request, extract::State, middleware::Next, response::Response};
+
+use super::AppState;
+
+tokio::task_local! {
+    static BASE_PATH: Arc<str>;
+}
+
+/// `/editor/` or `editor` becomes `/editor`; `/` and empty become empty.
+pub(crate) fn normalize(base_path: &str) -> String {
+    let trimmed = base_path.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{trimmed}")
+    }
+}
+
+/// Middleware that makes the configured prefix visible to [`app_url`].
+pub(crate) async fn scope(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    BASE_PATH.scope(state.base_path, next.run(request)).await
+}
+
+/// The prefix of the request being handled, e.g. `/editor`, or empty.
+pub(crate) fn current() -> Arc<str> {
+    BASE_PATH.try_with(Arc::clone).unwrap_or_default()
+}
+
+/// Prefixes an app-absolute URL such as `/edit?path=a.md`. The root `/`
+/// becomes the bare prefix, since a nested router does not match a trailing
+/// slash.
+pub(crate) fn app_url(url: &str) -> String {
+    let base_path = current();
+    if base_path.is_empty() || !url.starts_with('/') || url.starts_with("//") {
+        return url.to_string();
+    }
+    match url.strip_prefix('/') {
+        Some(rest) if rest.is_empty() || rest.starts_with('?') || rest.starts_with('#') => {
+            format!("{base_path}{rest}")
+        }
+        _ => format!("{base_path}{url}"),
+    }
+}
+
+/// [`crate::paths::path_url`] with the prefix applied.
+pub(crate) fn route_url(route: &str, relative_path: &str) -> String {
+    app_url(&crate::paths::path_url(route, relative_path))
+}
+
+/// [`crate::paths::get_parent_directory_path`] with the prefix applied.
+pub(crate) fn parent_url(file_path: &str) -> String {
+    app_url(&crate::paths::get_parent_directory_path(file_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(""), "");
+        assert_eq!(normalize("/"), "");
+        assert_eq!(normalize("editor"), "/editor");
+        assert_eq!(normalize("/tools/editor/"), "/tools/editor");
+    }
+
+    #[tokio::test]
+    async fn test_app_url_applies_prefix_only_inside_scope() {
+        assert_eq!(app_url("/edit?path=a.md"), "/edit?path=a.md");
+        BASE_PATH
+            .scope(Arc::from("/editor"), async {
+                assert_eq!(app_url("/edit?path=a.md"), "/editor/edit?path=a.md");
+                assert_eq!(app_url("/"), "/editor");
+                assert_eq!(app_url("/?path=posts"), "/editor?path=posts");
+                assert_eq!(app_url("https://example.com/"), "https://example.com/");
+                assert_eq!(app_url("//example.com/"), "//example.com/");
+                assert_eq!(route_url("/", "posts"), "/editor?path=posts");
+            })
+            .await;
+    }
+}
@@ -0,0 +1,937 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Content checks shared by the `/problems` dashboard and the `lint`
+//! subcommand.
+//!
+//! Each check implements [`Check`] and inspects a [`ContentIndex`] snapshot of
+//! the content tree. Register new checks in [`default_checks`] so the web UI
+//! and the CLI always run the same set.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use serde::Serialize;
+use tokio::fs;
+
+use super::deadline::Deadline;
+use super::ignore_marker;
+use super::{
+    FrontmatterFormat, SCALARS_ONLY, extract_frontmatter, frontmatter_len, is_markdown_file,
+    parse_frontmatter, parse_frontmatter_prefix,
+};
+use crate::paths::join_relative_path;
+
+/// A markdown document loaded into the index.
+pub struct IndexedDocument {
+    /// Path relative to the content root, using `/` separators.
+    pub path: String,
+    pub content: String,
+    /// Frontmatter `title`, parsed once when the index is built.
+    pub title: Option<String>,
+}
+
+/// Snapshot of the content tree that checks run against.
+pub struct ContentIndex {
+    pub documents: Vec<IndexedDocument>,
+    /// Every non-hidden file (markdown or not), relative to the content root.
+    pub files: Vec<String>,
+    pub max_frontmatter_bytes: usize,
+    /// The walk hit its [`Deadline`] and only covers part of the tree.
+    pub truncated: bool,
+}
+
+impl ContentIndex {
+    /// Walks `target_dir`, skipping hidden entries and never following
+    /// symlinks. `deadline` is checked before every entry; once it expires
+    /// the walk stops and the partial index is returned with `truncated` set.
+    pub async fn build(
+        target_dir: &Path,
+        max_frontmatter_bytes: usize,
+        deadline: &Deadline,
+    ) -> Result<Self, std::io::Error> {
+        let mut index = ContentIndex {
+            documents: Vec::new(),
+            files: Vec::new(),
+            max_frontmatter_bytes,
+            truncated: false,
+        };
+        let mut pending = vec![String::new()];
+
+        'walk: while let Some(relative_dir) = pending.pop() {
+            let mut entries = fs::read_dir(target_dir.join(&relative_dir)).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if deadline.expired() {
+                    index.truncated = true;
+                    break 'walk;
+                }
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if file_name.starts_with('.') {
+                    continue;
+                }
+
+                let relative_path = join_relative_path(&relative_dir, &file_name);
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() {
+                    if !ignore_marker::is_marked(&entry.path()).await {
+                        pending.push(relative_path);
+                    }
+                } else if file_type.is_file() {
+                    if is_markdown_file(&file_name)
+                        && let Ok(content) = fs::read_to_string(entry.path()).await
+                    {
+                        // Titles only need the bounded prefix scan; the full
+                        // content is kept for the link checks.
+                        let title = parse_frontmatter_prefix(
+                            &content,
+                            max_frontmatter_bytes,
+                            SCALARS_ONLY,
+                            false,
+                        )
+                        .found()
+                        .and_then(|(_, title, ..)| title);
+                        index.documents.push(IndexedDocument {
+                            path: relative_path.clone(),
+                            content,
+                            title,
+                        });
+                    }
+                    index.files.push(relative_path);
+                }
+            }
+        }
+
+        index.documents.sort_by(|a, b| a.path.cmp(&b.path));
+        index.files.sort();
+        Ok(index)
+    }
+}
+
+impl ContentIndex {
+    /// Whether `path` (relative, `/`-separated) is a file in the index.
+    pub fn contains_file(&self, path: &str) -> bool {
+        self.files
+            .binary_search_by(|file| file.as_str().cmp(path))
+            .is_ok()
+    }
+}
+
+/// How a link destination was classified.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    /// A relative or root-relative link to a file in the index.
+    Internal,
+    /// A relative or root-relative link whose target does not exist.
+    Broken,
+    /// Any URL with a scheme other than `mailto:`.
+    External,
+    /// A same-page `#fragment`.
+    Anchor,
+    Mailto,
+}
+
+/// A link found in a markdown document.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OutgoingLink {
+    pub kind: LinkKind,
+    pub destination: String,
+    pub text: String,
+    /// 1-based line in the source file (frontmatter included).
+    pub line: usize,
+    /// Index path the link resolves to, for internal links.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+fn has_url_scheme(destination: &str) -> bool {
+    destination.starts_with("//")
+        || destination.split_once(':').is_some_and(|(scheme, _)| {
+            !scheme.is_empty()
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+}
+
+/// Joins `path` onto the directory of `document_path`, or onto the content
+/// root when it starts with `/`. `None` when `..` would leave the root.
+fn join_from_document(document_path: &str, path: &str) -> Option<String> {
+    let mut components: Vec<&str> = match document_path.rsplit_once('/') {
+        Some((directory, _)) if !path.starts_with('/') => directory.split('/').collect(),
+        _ => Vec::new(),
+    };
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                // Escaping the content root can never resolve.
+                components.pop()?;
+            }
+            other => components.push(other),
+        }
+    }
+    Some(components.join("/"))
+}
+
+/// Resolves an internal link destination from `document_path` against the
+/// index. Root-relative links start at the content root; others are relative
+/// to the document's directory. Extensionless and trailing-slash links also
+/// match `<path>.md`, `<path>/index.md` and `<path>/_index.md`.
+pub fn resolve_internal_link(
+    index: &ContentIndex,
+    document_path: &str,
+    destination: &str,
+) -> Option<String> {
+    let path = destination.split(['#', '?']).next().unwrap_or_default();
+    let path = urlencoding::decode(path).ok()?;
+    let joined = join_from_document(document_path, &path)?;
+
+    let candidates = [
+        joined.clone(),
+        format!("{joined}.md"),
+        join_relative_path(&joined, "index.md"),
+        join_relative_path(&joined, "_index.md"),
+    ];
+    candidates
+        .into_iter()
+        .filter(|candidate| !candidate.is_empty())
+        .find(|candidate| index.contains_file(candidate))
+}
+
+/// Extracts every markdown link from `content`, classifying internal links
+/// against `index`. Shared by `/api/links` and [`BrokenLinksCheck`].
+pub fn extract_links(
+    index: &ContentIndex,
+    document_path: &str,
+    content: &str,
+) -> Vec<OutgoingLink> {
+    let body_start = frontmatter_len(content, index.max_frontmatter_bytes).unwrap_or(0);
+    let Some(body) = content.get(body_start..) else {
+        return Vec::new();
+    };
+    let line_offset = content
+        .get(..body_start)
+        .unwrap_or_default()
+        .matches('\n')
+        .count();
+
+    let mut links = Vec::new();
+    let mut open: Option<(String, usize, String)> = None;
+    let options = Options::ENABLE_TABLES | Options::ENABLE_FOOTNOTES | Options::ENABLE_TASKLISTS;
+    for (event, range) in Parser::new_ext(body, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                let line = line_offset
+                    + body
+                        .get(..range.start)
+                        .unwrap_or_default()
+                        .matches('\n')
+                        .count()
+                    + 1;
+                open = Some((dest_url.into_string(), line, String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, link_text)) = open.as_mut() {
+                    link_text.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Link) => {
+                let Some((destination, line, text)) = open.take() else {
+                    continue;
+                };
+                let (kind, target) = if destination.starts_with('#') {
+                    (LinkKind::Anchor, None)
+                } else if destination
+                    .get(..7)
+                    .is_some_and(|scheme| scheme.eq_ignore_ascii_case("mailto:"))
+                {
+                    (LinkKind::Mailto, None)
+                } else if has_url_scheme(&destination) {
+                    (LinkKind::External, None)
+                } else {
+                    match resolve_internal_link(index, document_path, &destination) {
+                        Some(target) => (LinkKind::Internal, Some(target)),
+                        None => (LinkKind::Broken, None),
+                    }
+                };
+                links.push(OutgoingLink {
+                    kind,
+                    destination,
+                    text,
+                    line,
+                    target,
+                });
+            }
+            _ => {}
+        }
+    }
+    links
+}
+
+/// Frontmatter keys read for image paths unless `--frontmatter-asset-keys`
+/// says otherwise.
+pub const DEFAULT_ASSET_KEYS: &[&str] = &["cover", "image", "thumbnail", "images"];
+
+/// The asset paths named by the frontmatter `keys` of `content`, as
+/// `(key, path)` pairs. A key may hold a path, a list of paths, or a mapping
+/// with an `image` or `src` path (as PaperMod's `cover`). URLs are skipped.
+pub fn frontmatter_asset_references(
+    content: &str,
+    max_frontmatter_bytes: usize,
+    keys: &[String],
+) -> Vec<(String, String)> {
+    let Some((.., extra)) = parse_frontmatter(content, max_frontmatter_bytes, SCALARS_ONLY) else {
+        return Vec::new();
+    };
+    let mut references = Vec::new();
+    for key in keys {
+        let values = match extra.get(key) {
+            Some(serde_json::Value::Array(items)) => items.iter().collect(),
+            Some(value) => vec![value],
+            None => Vec::new(),
+        };
+        for value in values {
+            let path = match value {
+                serde_json::Value::Object(mapping) => mapping
+                    .get("image")
+                    .or_else(|| mapping.get("src"))
+                    .and_then(serde_json::Value::as_str),
+                value => value.as_str(),
+            };
+            if let Some(path) = path.map(str::trim)
+                && !path.is_empty()
+                && !has_url_scheme(path)
+            {
+                references.push((key.clone(), path.to_string()));
+            }
+        }
+    }
+    references
+}
+
+/// Where an asset path from the frontmatter of `document_path` may be, in
+/// lookup order: relative to the document (so inside its page bundle for an
+/// `index.md`), then from the content root.
+pub fn asset_candidates(document_path: &str, reference: &str) -> Vec<String> {
+    let path = reference.split(['#', '?']).next().unwrap_or_default();
+    let mut candidates = Vec::new();
+    for candidate in [
+        join_from_document(document_path, path),
+        join_from_document("", path),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if !candidate.is_empty() && !candidates.contains(&candidate) {
+            candidates.push(candidate);
+        }
+    }
+    candidates
+}
+
+/// A single problem reported by a check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub path: String,
+    pub message: String,
+}
+
+/// A content check that can be run from the web dashboard or the CLI.
+pub trait Check: Send + Sync {
+    /// Stable identifier used in URLs and CLI output.
+    fn name(&self) -> &'static str;
+
+    /// Human-readable category shown on the dashboard.
+    fn title(&self) -> &'static str;
+
+    fn run(&self, index: &ContentIndex) -> Vec<Finding>;
+}
+
+/// Flags markdown files that start a frontmatter block which cannot be parsed.
+pub struct InvalidFrontmatterCheck;
+
+impl Check for InvalidFrontmatterCheck {
+    fn name(&self) -> &'static str {
+        "invalid-frontmatter"
+    }
+
+    fn title(&self) -> &'static str {
+        "Invalid frontmatter"
+    }
+
+    fn run(&self, index: &ContentIndex) -> Vec<Finding> {
+        index
+            .documents
+            .iter()
+            .filter_map(|document| {
+                let content = document.content.as_str();
+                let looks_like_frontmatter = content.starts_with("---\n")
+                    || content.starts_with("---\r\n")
+                    || content.starts_with("+++\n")
+                    || content.starts_with("+++\r\n")
+                    || content.starts_with('{');
+                if !looks_like_frontmatter
+                    || parse_frontmatter(content, index.max_frontmatter_bytes, SCALARS_ONLY)
+                        .is_some()
+                {
+                    return None;
+                }
+
+                let message = match extract_frontmatter(content, index.max_frontmatter_bytes) {
+                    Some((FrontmatterFormat::Yaml, block)) if block.trim().is_empty() => {
+                        return None;
+                    }
+                    Some((FrontmatterFormat::Yaml, _)) => "YAML frontmatter could not be parsed",
+                    Some((FrontmatterFormat::Json, _)) => "JSON frontmatter could not be parsed",
+                    Some((FrontmatterFormat::Toml, _)) => "TOML frontmatter could not be parsed",
+                    None if content.starts_with('{') => {
+                        // A leading `{` is just as likely to be body text.
+                        return None;
+                    }
+                    None => "Frontmatter block is not terminated (or exceeds the size limit)",
+                };
+                Some(Finding {
+                    path: document.path.clone(),
+                    message: message.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Flags relative links whose target is not in the content tree.
+pub struct BrokenLinksCheck;
+
+impl Check for BrokenLinksCheck {
+    fn name(&self) -> &'static str {
+        "broken-links"
+    }
+
+    fn title(&self) -> &'static str {
+        "Broken internal links"
+    }
+
+    fn run(&self, index: &ContentIndex) -> Vec<Finding> {
+        index
+            .documents
+            .iter()
+            .flat_map(|document| {
+                extract_links(index, &document.path, &document.content)
+                    .into_iter()
+                    .filter(|link| link.kind == LinkKind::Broken)
+                    .map(|link| Finding {
+                        path: document.path.clone(),
+                        message: format!(
+                            "Line {}: link to '{}' does not resolve",
+                            link.line, link.destination
+                        ),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// Flags frontmatter image paths (`cover`, `thumbnail`, ...) that resolve to
+/// no file; themes drop such images without an error.
+pub struct MissingAssetsCheck {
+    keys: Vec<String>,
+}
+
+impl MissingAssetsCheck {
+    /// Checks the frontmatter `keys`; empty keys are ignored.
+    pub fn new(keys: &[String]) -> Self {
+        MissingAssetsCheck {
+            keys: keys.iter().filter(|key| !key.is_empty()).cloned().collect(),
+        }
+    }
+}
+
+impl Check for MissingAssetsCheck {
+    fn name(&self) -> &'static str {
+        "missing-assets"
+    }
+
+    fn title(&self) -> &'static str {
+        "Missing frontmatter images"
+    }
+
+    fn run(&self, index: &ContentIndex) -> Vec<Finding> {
+        index
+            .documents
+            .iter()
+            .flat_map(|document| {
+                frontmatter_asset_references(
+                    &document.content,
+                    index.max_frontmatter_bytes,
+                    &self.keys,
+                )
+                .into_iter()
+                .filter(|(_, reference)| {
+                    !asset_candidates(&document.path, reference)
+                        .iter()
+                        .any(|candidate| index.contains_file(candidate))
+                })
+                .map(|(key, reference)| Finding {
+                    path: document.path.clone(),
+                    message: format!("'{key}' points at '{reference}', which does not exist"),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The checks run by both `/problems` and `lint`; `asset_keys` are the
+/// frontmatter keys [`MissingAssetsCheck`] reads.
+pub fn default_checks(asset_keys: &[String]) -> Vec<Box<dyn Check>> {
+    vec![
+        Box::new(InvalidFrontmatterCheck),
+        Box::new(BrokenLinksCheck),
+        Box::new(MissingAssetsCheck::new(asset_keys)),
+    ]
+}
+
+/// [`DEFAULT_ASSET_KEYS`] as owned strings.
+pub fn default_asset_keys() -> Vec<String> {
+    DEFAULT_ASSET_KEYS.iter().map(ToString::to_string).collect()
+}
+
+/// Findings of one check.
+pub struct CheckResult {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub findings: Vec<Finding>,
+}
+
+/// Results of running every registered check over one index snapshot.
+pub struct ProblemsReport {
+    pub results: Vec<CheckResult>,
+    pub generated_at: SystemTime,
+}
+
+impl ProblemsReport {
+    pub fn total(&self) -> usize {
+        self.results
+            .iter()
+            .map(|result| result.findings.len())
+            .sum()
+    }
+}
+
+pub fn run_checks(checks: &[Box<dyn Check>], index: &ContentIndex) -> ProblemsReport {
+    ProblemsReport {
+        results: checks
+            .iter()
+            .map(|check| CheckResult {
+                name: check.name(),
+                title: check.title(),
+                findings: check.run(index),
+            })
+            .collect(),
+        generated_at: SystemTime::now(),
+    }
+}
+
+/// Builds an index of `target_dir` and runs the default checks over it.
+pub async fn lint(
+    target_dir: &Path,
+    max_frontmatter_bytes: usize,
+    asset_keys: &[String],
+) -> Result<ProblemsReport, std::io::Error> {
+    let index =
+        ContentIndex::build(target_dir, max_frontmatter_bytes, &Deadline::default()).await?;
+    Ok(run_checks(&default_checks(asset_keys), &index))
+}
+
+/// Caches the latest [`ContentIndex`] and [`ProblemsReport`] until the
+/// content changes.
+#[derive(Clone)]
+pub struct ChecksCache {
+    checks: Arc<Vec<Box<dyn Check>>>,
+    cached: Arc<RwLock<Option<Arc<ProblemsReport>>>>,
+    cached_index: Arc<RwLock<Option<Arc<ContentIndex>>>>,
+    /// Bumped on every invalidation so a report computed from an older
+    /// snapshot is never stored over a newer invalidation.
+    generation: Arc<AtomicU64>,
+}
+
+impl ChecksCache {
+    pub fn new(checks: Vec<Box<dyn Check>>) -> Self {
+        ChecksCache {
+            checks: Arc::new(checks),
+            cached: Arc::new(RwLock::new(None)),
+            cached_index: Arc::new(RwLock::new(None)),
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut cached) = self.cached.write() {
+            *cached = None;
+        }
+        if let Ok(mut cached_index) = self.cached_index.write() {
+            *cached_index = None;
+        }
+    }
+
+    fn cached_report(&self) -> Option<Arc<ProblemsReport>> {
+        self.cached.read().ok().and_then(|cached| cached.clone())
+    }
+
+    fn cached_index(&self) -> Option<Arc<ContentIndex>> {
+        self.cached_index
+            .read()
+            .ok()
+            .and_then(|cached| cached.clone())
+    }
+
+    /// Returns the cached content index, rebuilding it when missing or when
+    /// `refresh` is set. A rebuild cut short by `deadline` is returned
+    /// (`truncated` set) but never cached.
+    pub async fn index(
+        &self,
+        target_dir: &Path,
+        max_frontmatter_bytes: usize,
+        refresh: bool,
+        deadline: &Deadline,
+    ) -> Result<Arc<ContentIndex>, std::io::Error> {
+        if !refresh && let Some(index) = self.cached_index() {
+            return Ok(index);
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let index =
+            Arc::new(ContentIndex::build(target_dir, max_frontmatter_bytes, deadline).await?);
+        if !index.truncated
+            && self.generation.load(Ordering::SeqCst) == generation
+            && let Ok(mut cached_index) = self.cached_index.write()
+        {
+            *cached_index = Some(index.clone());
+        }
+        Ok(index)
+    }
+
+    /// Returns the cached report, recomputing it when missing or when
+    /// `refresh` is set. Checks over part of the tree would under-report, so
+    /// an index cut short by `deadline` is a `TimedOut` error instead.
+    pub async fn report(
+        &self,
+        target_dir: &Path,
+        max_frontmatter_bytes: usize,
+        refresh: bool,
+        deadline: &Deadline,
+    ) -> Result<Arc<ProblemsReport>, std::io::Error> {
+        if !refresh && let Some(report) = self.cached_report() {
+            return Ok(report);
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let index = self
+            .index(target_dir, max_frontmatter_bytes, refresh, deadline)
+            .await?;
+        if index.truncated {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "content walk stopped before it finished",
+            ));
+        }
+        let report = Arc::new(run_checks(&self.checks, &index));
+
+        if self.generation.load(Ordering::SeqCst) == generation
+            && let Ok(mut cached) = self.cached.write()
+        {
+            *cached = Some(report.clone());
+        }
+        Ok(report)
+    }
+}
+
+impl Default for ChecksCache {
+    fn default() -> Self {
+        ChecksCache::new(default_checks(&default_asset_keys()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use tempfile::TempDir;
+
+    struct CountingCheck {
+        runs: Arc<AtomicUsize>,
+    }
+
+    impl Check for CountingCheck {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn title(&self) -> &'static str {
+            "Fake check"
+        }
+
+        fn run(&self, index: &ContentIndex) -> Vec<Finding> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            index
+                .documents
+                .iter()
+                .filter(|document| document.content.contains("TODO"))
+                .map(|document| Finding {
+                    path: document.path.clone(),
+                    message: "Contains TODO".to_string(),
+                })
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_aggregates_findings_across_checks() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("failed to create posts directory");
+        fs::write(
+            temp_dir.path().join("posts/a.md"),
+            "---\ntitle: [\n---\nTODO",
+        )
+        .await
+        .expect("failed to write a.md");
+        fs::write(temp_dir.path().join("b.md"), "TODO")
+            .await
+            .expect("failed to write b.md");
+        fs::write(temp_dir.path().join(".hidden.md"), "TODO")
+            .await
+            .expect("failed to write hidden file");
+
+        let mut checks = default_checks(&default_asset_keys());
+        checks.push(Box::new(CountingCheck {
+            runs: Arc::new(AtomicUsize::new(0)),
+        }));
+        let index = ContentIndex::build(temp_dir.path(), 65_536, &Deadline::default())
+            .await
+            .expect("index should build");
+        let report = run_checks(&checks, &index);
+
+        assert_eq!(report.total(), 3);
+        let counts: Vec<(&str, usize)> = report
+            .results
+            .iter()
+            .map(|result| (result.name, result.findings.len()))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                ("invalid-frontmatter", 1),
+                ("broken-links", 0),
+                ("missing-assets", 0),
+                ("fake", 2)
+            ]
+        );
+        assert_eq!(
+            report.results[0].findings[0].path, "posts/a.md",
+            "findings use relative paths"
+        );
+    }
+
+    #[test]
+    fn test_extract_links_classifies_every_category() {
+        let index = ContentIndex {
+            documents: Vec::new(),
+            files: vec![
+                "about.md".to_string(),
+                "images/logo.png".to_string(),
+                "posts/hello.md".to_string(),
+                "posts/series/_index.md".to_string(),
+            ],
+            max_frontmatter_bytes: 65_536,
+            truncated: false,
+        };
+        let content = "---\ntitle: Fixture\n---\n\
+            See [hello](hello.md) and [the series](series/).\n\
+            [About](/about) and ![logo](../images/logo.png).\n\
+            \n\
+            [Missing](../nope.md), [escape](../../../etc/passwd)\n\
+            [Docs](https://example.com/docs) [Jump](#top)\n\
+            [Mail `me`](mailto:me@example.com)\n";
+
+        let links = extract_links(&index, "posts/fixture.md", content);
+        let summary: Vec<(LinkKind, &str, usize, Option<&str>)> = links
+            .iter()
+            .map(|link| {
+                (
+                    link.kind,
+                    link.text.as_str(),
+                    link.line,
+                    link.target.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (LinkKind::Internal, "hello", 4, Some("posts/hello.md")),
+                (
+                    LinkKind::Internal,
+                    "the series",
+                    4,
+                    Some("posts/series/_index.md")
+                ),
+                (LinkKind::Internal, "About", 5, Some("about.md")),
+                (LinkKind::Broken, "Missing", 7, None),
+                (LinkKind::Broken, "escape", 7, None),
+                (LinkKind::External, "Docs", 8, None),
+                (LinkKind::Anchor, "Jump", 8, None),
+                (LinkKind::Mailto, "Mail me", 9, None),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_assets_resolve_from_bundle_then_root() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("posts/trip"))
+            .await
+            .expect("failed to create bundle directory");
+        fs::create_dir(root.join("images"))
+            .await
+            .expect("failed to create images directory");
+        for (path, content) in [
+            ("posts/trip/index.md", "---\ncover: hero.jpg\n---\nTrip"),
+            ("posts/trip/hero.jpg", ""),
+            ("images/site.png", ""),
+            (
+                "posts/plain.md",
+                "---\nthumbnail: images/site.png\nimage: /images/site.png\n---\nPlain",
+            ),
+            (
+                "posts/broken.md",
+                "---\nimages: [gone.jpg, \"https://cdn.example.com/x.jpg\"]\n\
+                 cover:\n  image: hero.jpg\n---\nBroken",
+            ),
+        ] {
+            fs::write(root.join(path), content)
+                .await
+                .expect("failed to write fixture");
+        }
+        let index = ContentIndex::build(root, 65_536, &Deadline::default())
+            .await
+            .expect("index should build");
+
+        let messages: Vec<(String, String)> = MissingAssetsCheck::new(&default_asset_keys())
+            .run(&index)
+            .into_iter()
+            .map(|finding| (finding.path, finding.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (
+                    "posts/broken.md".to_string(),
+                    "'cover' points at 'hero.jpg', which does not exist".to_string()
+                ),
+                (
+                    "posts/broken.md".to_string(),
+                    "'images' points at 'gone.jpg', which does not exist".to_string()
+                ),
+            ]
+        );
+
+        assert!(
+            MissingAssetsCheck::new(&["banner".to_string()])
+                .run(&index)
+                .is_empty()
+        );
+        assert_eq!(
+            asset_candidates("posts/trip/index.md", "img/a.png?v=1"),
+            vec!["posts/trip/img/a.png".to_string(), "img/a.png".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_reuses_report_until_invalidated_or_refreshed() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(temp_dir.path().join("a.md"), "TODO")
+            .await
+            .expect("failed to write a.md");
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let cache = ChecksCache::new(vec![Box::new(CountingCheck { runs: runs.clone() })]);
+
+        let first = cache
+            .report(temp_dir.path(), 65_536, false, &Deadline::default())
+            .await
+            .expect("report should build");
+        assert_eq!(first.total(), 1);
+        cache
+            .report(temp_dir.path(), 65_536, false, &Deadline::default())
+            .await
+            .expect("cached report should load");
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        fs::write(temp_dir.path().join("a.md"), "done")
+            .await
+            .expect("failed to rewrite a.md");
+        cache.invalidate();
+        let after_invalidate = cache
+            .report(temp_dir.path(), 65_536, false, &Deadline::default())
+            .await
+            .expect("report should rebuild");
+        assert_eq!(after_invalidate.total(), 0);
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        cache
+            .report(temp_dir.path(), 65_536, true, &Deadline::default())
+            .await
+            .expect("refresh should rebuild");
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_expired_deadline_stops_the_walk_early() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        for directory in 0..20 {
+            let dir = temp_dir.path().join(format!("section-{directory}"));
+            fs::create_dir(&dir)
+                .await
+                .expect("failed to create directory");
+            for file in 0..100 {
+                fs::write(dir.join(format!("post-{file}.md")), "# Post\n")
+                    .await
+                    .expect("failed to write post");
+            }
+        }
+        let token = tokio_util::sync::CancellationToken::new();
+        token.cancel();
+        let cancelled = Deadline::new(token);
+
+        let started = std::time::Instant::now();
+        let index = ContentIndex::build(temp_dir.path(), 65_536, &cancelled)
+            .await
+            .expect("a cancelled walk still returns");
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+        assert!(index.truncated);
+        assert!(index.documents.is_empty());
+
+        // A cut-short index is never cached and a report refuses it.
+        let cache = ChecksCache::default();
+        let err = cache
+            .report(temp_dir.path(), 65_536, false, &cancelled)
+            .await
+            .err()
+            .expect("a report over part of the tree should fail");
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        let index = cache
+            .index(temp_dir.path(), 65_536, false, &Deadline::default())
+            .await
+            .expect("index should build");
+        assert!(!index.truncated);
+        assert_eq!(index.documents.len(), 2000);
+    }
+}
@@ -0,0 +1,66 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The subset of the server configuration front-end code may rely on.
+//!
+//! Served as JSON from `GET /api/config` and embedded in the editor and
+//! directory pages as a `data-client-config` attribute. [`ClientConfig`] is
+//! built field by field from [`AppState`] and has no field that could hold
+//! a secret (CSRF secret, API token) or a filesystem path, so neither can
+//! leak through it.
+
+use serde::Serialize;
+
+use super::{AppState, CSRF_TOKEN_LIFETIME_SECONDS, routes};
+
+/// How often the editor compares its local draft against the server copy.
+pub(crate) const DRAFT_CHECK_INTERVAL_MS: u64 = 5000;
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct ClientConfig {
+    api_version: u32,
+    csrf_field: &'static str,
+    api_auth_header: &'static str,
+    csrf_token_lifetime_seconds: u64,
+    draft_check_interval_ms: u64,
+    max_upload_size_bytes: usize,
+    max_frontmatter_bytes: usize,
+    /// Whether an API token is configured; the token itself is never exposed.
+    api_writes_enabled: bool,
+    preview_commands_enabled: bool,
+    iframe_preview_disabled: bool,
+    edit_tracking_enabled: bool,
+    remember_path: bool,
+    /// Names under `/mounts/`; their directories are not exposed.
+    mounts: Vec<String>,
+}
+
+impl ClientConfig {
+    pub(crate) fn new(state: &AppState) -> Self {
+        ClientConfig {
+            api_version: routes::ROUTES_MANIFEST_VERSION,
+            csrf_field: routes::CSRF_FIELD,
+            api_auth_header: routes::API_AUTH_HEADER,
+            csrf_token_lifetime_seconds: CSRF_TOKEN_LIFETIME_SECONDS,
+            draft_check_interval_ms: DRAFT_CHECK_INTERVAL_MS,
+            max_upload_size_bytes: state.max_upload_size_bytes,
+            max_frontmatter_bytes: state.max_frontmatter_bytes,
+            api_writes_enabled: state.api_token.is_some(),
+            preview_commands_enabled: state.preview_commands.is_some(),
+            iframe_preview_disabled: state.disable_iframe_preview,
+            edit_tracking_enabled: state.audit.is_some(),
+            remember_path: state.remember_path,
+            mounts: state
+                .mounts
+                .iter()
+                .map(|mount| mount.name.clone())
+                .collect(),
+        }
+    }
+
+    /// JSON for the `data-client-config` attribute (escaped by the template).
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
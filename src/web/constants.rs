@@ -26,3 +26,28 @@ pub(crate) fn is_safe_for_iframe(path: &str) -> bool {
     // Allow text files, web files, and documents that browsers can display safely
     IFRAME_SAFE_EXTENSIONS.contains(&lower_path.split('.').next_back().unwrap_or(""))
 }
+
+/// Text formats that can be fetched verbatim from `/raw` for editing in
+/// external tools, in addition to markdown.
+pub(crate) const RAW_TEXT_EXTENSIONS: &[&str] = &[
+    "md", "markdown", "txt", "html", "htm", "css", "js", "json", "xml", "csv", "log", "yml",
+    "yaml", "toml", "ini", "conf", "cfg", "org", "adoc", "asciidoc",
+];
+
+pub(crate) fn is_raw_text_file(path: &str) -> bool {
+    let lower_path = path.to_lowercase();
+    RAW_TEXT_EXTENSIONS.contains(&lower_path.split('.').next_back().unwrap_or(""))
+}
+
+/// Non-markdown document formats that open in the editor as plain text
+/// (no live preview).
+pub(crate) const TEXT_DOCUMENT_EXTENSIONS: &[&str] = &["org", "adoc", "asciidoc"];
+
+pub(crate) fn is_text_document_file(path: &str) -> bool {
+    let lower_path = path.to_lowercase();
+    TEXT_DOCUMENT_EXTENSIONS.contains(&lower_path.split('.').next_back().unwrap_or(""))
+}
+
+/// How many `<name>-N.md` alternatives the new-file form tries before giving
+/// up on suggesting a free name.
+pub(crate) const MAX_FILENAME_SUGGESTIONS: usize = 100;
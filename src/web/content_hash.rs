@@ -0,0 +1,166 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! SHA-256 content hashes for change detection.
+//!
+//! `/file-info` and `/file-content` report a `content_hash` so clients can
+//! tell edits apart that an mtime cannot (same-second writes, `touch`). The
+//! same hex digest, quoted, is the strong `ETag` of the file routes. Hashes
+//! are cached by path, mtime and size; the cache is cleared by any
+//! successful write request, since an in-app write within the mtime
+//! granularity could otherwise keep a stale entry.
+
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+/// Larger files get no hash; clients fall back to the mtime.
+pub(crate) const MAX_HASHED_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Hashes kept in memory; the cache is emptied when full.
+const MAX_CACHED_HASHES: usize = 1024;
+
+/// Lowercase hex SHA-256 of `bytes`.
+pub(crate) fn hash_bytes(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+/// Modification time and size the hash was computed for, and the hash.
+type CachedHash = (SystemTime, u64, String);
+
+#[derive(Clone, Default)]
+pub(crate) struct ContentHashCache {
+    entries: Arc<Mutex<HashMap<PathBuf, CachedHash>>>,
+}
+
+impl ContentHashCache {
+    /// Hash of the file at `path`, or `None` when it is over
+    /// [`MAX_HASHED_BYTES`]. Unchanged files are not read again.
+    pub(crate) async fn hash_file(&self, path: &Path) -> std::io::Result<Option<String>> {
+        // Metadata first: if the file changes before it is read, the entry is
+        // keyed by the older mtime and the next poll hashes again.
+        let metadata = fs::metadata(path).await?;
+        if metadata.len() > MAX_HASHED_BYTES {
+            return Ok(None);
+        }
+        let modified = metadata.modified()?;
+        if let Some(hash) = self.get(path, modified, metadata.len()) {
+            return Ok(Some(hash));
+        }
+        let bytes = fs::read(path).await?;
+        Ok(Some(self.insert(path, modified, metadata.len(), &bytes)))
+    }
+
+    /// Like [`ContentHashCache::hash_file`] for a file whose `bytes` were
+    /// already read, after `metadata` was taken.
+    pub(crate) fn hash_loaded(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+        bytes: &[u8],
+    ) -> std::io::Result<Option<String>> {
+        if metadata.len() > MAX_HASHED_BYTES {
+            return Ok(None);
+        }
+        let modified = metadata.modified()?;
+        Ok(Some(match self.get(path, modified, metadata.len()) {
+            Some(hash) => hash,
+            None => self.insert(path, modified, metadata.len(), bytes),
+        }))
+    }
+
+    pub(crate) fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+
+    fn get(&self, path: &Path, modified: SystemTime, size: u64) -> Option<String> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(path)
+            .filter(|(cached_modified, cached_size, _)| {
+                *cached_modified == modified && *cached_size == size
+            })
+            .map(|(_, _, hash)| hash.clone())
+    }
+
+    fn insert(&self, path: &Path, modified: SystemTime, size: u64, bytes: &[u8]) -> String {
+        let hash = hash_bytes(bytes);
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= MAX_CACHED_HASHES {
+                entries.clear();
+            }
+            entries.insert(path.to_path_buf(), (modified, size, hash.clone()));
+        }
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    async fn set_mtime(path: &Path, time: SystemTime) {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .await
+            .expect("Failed to open file")
+            .into_std()
+            .await
+            .set_modified(time)
+            .expect("Failed to set mtime");
+    }
+
+    #[tokio::test]
+    async fn test_hash_is_cached_until_mtime_size_or_clear() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let path = temp_dir.path().join("post.md");
+        let fixed = SystemTime::now() - Duration::from_secs(60);
+        fs::write(&path, "# One").await.expect("Failed to write");
+        set_mtime(&path, fixed).await;
+
+        let cache = ContentHashCache::default();
+        let first = cache.hash_file(&path).await.expect("Failed to hash");
+        assert_eq!(first, Some(hash_bytes(b"# One")));
+
+        // Same size and mtime: served from the cache without reading.
+        fs::write(&path, "# Two").await.expect("Failed to write");
+        set_mtime(&path, fixed).await;
+        assert_eq!(cache.hash_file(&path).await.expect("hash"), first);
+
+        cache.clear();
+        assert_eq!(
+            cache.hash_file(&path).await.expect("hash"),
+            Some(hash_bytes(b"# Two"))
+        );
+
+        fs::write(&path, "# Three").await.expect("Failed to write");
+        set_mtime(&path, fixed).await;
+        assert_eq!(
+            cache.hash_file(&path).await.expect("hash"),
+            Some(hash_bytes(b"# Three"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_files_are_not_hashed() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let path = temp_dir.path().join("big.txt");
+        let file = fs::File::create(&path).await.expect("Failed to create");
+        file.set_len(MAX_HASHED_BYTES + 1)
+            .await
+            .expect("Failed to extend file");
+        let cache = ContentHashCache::default();
+        assert_eq!(cache.hash_file(&path).await.expect("hash"), None);
+    }
+}
@@ -0,0 +1,107 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Request-scoped deadlines for walks over the content tree.
+//!
+//! [`scope`] gives every request a [`Deadline`]: a cancellation token that
+//! fires once the request is finished or dropped (the client went away),
+//! plus a [`WALK_TIME_LIMIT`] budget. Handlers take it with the
+//! [`RequestDeadline`] extractor and pass it down to
+//! [`ContentIndex::build`](super::checks::ContentIndex::build), which checks
+//! it between files and stops early, marking its result truncated. Work not
+//! tied to a request (the `lint` command) uses [`Deadline::default`], which
+//! never expires.
+
+use std::convert::Infallible;
+use std::time::{Duration, Instant};
+
+use axum::extract::{FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::middleware::Next;
+use axum::response::Response;
+use tokio_util::sync::CancellationToken;
+
+/// Longest a single request may spend walking the content tree.
+pub const WALK_TIME_LIMIT: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Debug, Default)]
+pub struct Deadline {
+    token: CancellationToken,
+    expires_at: Option<Instant>,
+}
+
+impl Deadline {
+    /// Expires when `token` is cancelled.
+    pub fn new(token: CancellationToken) -> Self {
+        Deadline {
+            token,
+            expires_at: None,
+        }
+    }
+
+    /// Also expires once `limit` has passed.
+    pub fn with_limit(mut self, limit: Duration) -> Self {
+        self.expires_at = Instant::now().checked_add(limit);
+        self
+    }
+
+    /// Whether the work should stop now.
+    pub fn expired(&self) -> bool {
+        self.token.is_cancelled()
+            || self
+                .expires_at
+                .is_some_and(|expires_at| Instant::now() >= expires_at)
+    }
+}
+
+/// Middleware attaching a [`Deadline`] to the request. The token is
+/// cancelled when this future completes or is dropped, so anything still
+/// holding a clone learns that nobody is waiting for its result.
+pub(crate) async fn scope(mut request: Request, next: Next) -> Response {
+    let token = CancellationToken::new();
+    let _cancel_on_drop = token.clone().drop_guard();
+    request
+        .extensions_mut()
+        .insert(Deadline::new(token).with_limit(WALK_TIME_LIMIT));
+    next.run(request).await
+}
+
+/// The request's [`Deadline`]; one that never expires when [`scope`] is not
+/// installed.
+pub(crate) struct RequestDeadline(pub(crate) Deadline);
+
+impl<S> FromRequestParts<S> for RequestDeadline
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(RequestDeadline(
+            parts
+                .extensions
+                .get::<Deadline>()
+                .cloned()
+                .unwrap_or_default(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deadline_expires_on_cancel_or_limit() {
+        assert!(!Deadline::default().expired());
+
+        let token = CancellationToken::new();
+        let deadline = Deadline::new(token.clone()).with_limit(WALK_TIME_LIMIT);
+        assert!(!deadline.expired());
+        token.cancel();
+        assert!(deadline.expired());
+
+        assert!(Deadline::default().with_limit(Duration::ZERO).expired());
+    }
+}
@@ -0,0 +1,206 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! File writes that honour `--durability`.
+//!
+//! Every handler that writes content goes through these helpers so the
+//! policy applies uniformly: `fsync` flushes each file before it becomes
+//! visible, `none` leaves flushing to the operating system, and `dir-sync`
+//! additionally flushes the parent directory so the rename or creation
+//! itself survives a crash. Save latency is recorded so the cost of the
+//! chosen policy shows up on `/api/status`.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rand::RngExt;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+use crate::cli::Durability;
+
+/// Flushes the directory holding `path`, so a rename or newly created entry
+/// in it is on disk.
+async fn sync_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) => fs::File::open(parent).await?.sync_all().await,
+        None => Ok(()),
+    }
+}
+
+/// Flushes `file` when the policy asks for it.
+async fn sync_file(file: &fs::File, durability: Durability) -> std::io::Result<()> {
+    match durability {
+        Durability::None => Ok(()),
+        Durability::Fsync | Durability::DirSync => file.sync_all().await,
+    }
+}
+
+/// Flushes the parent directory of `path` under `dir-sync`.
+async fn sync_dir(path: &Path, durability: Durability) -> std::io::Result<()> {
+    match durability {
+        Durability::DirSync => sync_parent(path).await,
+        Durability::Fsync | Durability::None => Ok(()),
+    }
+}
+
+/// Writes `contents` to a hidden temporary file next to `path` and renames
+/// it into place, so a crash mid-write leaves the old file or the new one,
+/// never a truncated one. The temporary file is removed on failure; an
+/// existing file keeps its permissions.
+pub(crate) async fn write_atomic(
+    path: &Path,
+    contents: &[u8],
+    durability: Durability,
+) -> std::io::Result<()> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temp_path = path.with_file_name(format!(
+        ".{name}.{}.tmp",
+        hex::encode(rand::rng().random::<[u8; 4]>())
+    ));
+    let result = async {
+        let mut file = fs::File::create_new(&temp_path).await?;
+        file.write_all(contents).await?;
+        sync_file(&file, durability).await?;
+        drop(file);
+        if let Ok(metadata) = fs::metadata(path).await {
+            fs::set_permissions(&temp_path, metadata.permissions()).await?;
+        }
+        fs::rename(&temp_path, path).await
+    }
+    .await;
+    if result.is_err()
+        && let Err(err) = fs::remove_file(&temp_path).await
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        warn!(
+            "Failed to remove temporary file {}: {}",
+            temp_path.display(),
+            err
+        );
+    }
+    result?;
+    sync_dir(path, durability).await
+}
+
+/// Creates `path`, failing with `AlreadyExists` if anything is there, and
+/// writes `contents` to it.
+pub(crate) async fn write_new(
+    path: &Path,
+    contents: &[u8],
+    durability: Durability,
+) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .await?;
+    file.write_all(contents).await?;
+    sync_file(&file, durability).await?;
+    drop(file);
+    sync_dir(path, durability).await
+}
+
+/// Copies `from` to `to`, returning the number of bytes copied.
+pub(crate) async fn copy_file(
+    from: &Path,
+    to: &Path,
+    durability: Durability,
+) -> std::io::Result<u64> {
+    let bytes = fs::copy(from, to).await?;
+    if durability != Durability::None {
+        sync_file(&fs::File::open(to).await?, durability).await?;
+    }
+    sync_dir(to, durability).await?;
+    Ok(bytes)
+}
+
+/// Renames `from` to `to`; under `dir-sync` both directories are flushed.
+pub(crate) async fn rename(from: &Path, to: &Path, durability: Durability) -> std::io::Result<()> {
+    fs::rename(from, to).await?;
+    sync_dir(to, durability).await?;
+    sync_dir(from, durability).await
+}
+
+/// Running total of `/save` write times, shared across clones of the state.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SaveLatency {
+    saves: Arc<AtomicU64>,
+    total_micros: Arc<AtomicU64>,
+}
+
+impl SaveLatency {
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        self.total_micros.fetch_add(micros, Ordering::Relaxed);
+        self.saves.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Number of saves recorded so far.
+    pub(crate) fn saves(&self) -> u64 {
+        self.saves.load(Ordering::Relaxed)
+    }
+
+    /// Mean write time in milliseconds, or `None` before the first save.
+    pub(crate) fn average_ms(&self) -> Option<f64> {
+        let saves = self.saves();
+        (saves > 0)
+            .then(|| self.total_micros.load(Ordering::Relaxed) as f64 / saves as f64 / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_every_policy_writes_and_copies() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        for (index, durability) in [Durability::Fsync, Durability::None, Durability::DirSync]
+            .into_iter()
+            .enumerate()
+        {
+            let path = temp_dir.path().join(format!("post-{index}.md"));
+            write_new(&path, b"# One\n", durability)
+                .await
+                .expect("write_new failed");
+            let err = write_new(&path, b"# Two\n", durability)
+                .await
+                .expect_err("write_new must not overwrite");
+            assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+
+            write_atomic(&path, b"# Two\n", durability)
+                .await
+                .expect("write_atomic failed");
+            let copy = temp_dir.path().join(format!("copy-{index}.md"));
+            assert_eq!(
+                copy_file(&path, &copy, durability)
+                    .await
+                    .expect("copy_file failed"),
+                6
+            );
+            assert_eq!(
+                fs::read_to_string(&copy).await.expect("Failed to read"),
+                "# Two\n"
+            );
+        }
+    }
+
+    #[test]
+    fn test_save_latency_average() {
+        let latency = SaveLatency::default();
+        assert_eq!(latency.average_ms(), None);
+        latency.record(Duration::from_millis(2));
+        latency.clone().record(Duration::from_millis(4));
+        assert_eq!(latency.saves(), 2);
+        assert_eq!(latency.average_ms(), Some(3.0));
+    }
+}
@@ -0,0 +1,203 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! `:shortcode:` to emoji replacement for `--enable-emoji-shortcodes`.
+//!
+//! Names follow GitHub's; only a common subset is known and anything else is
+//! left as written.
+
+use std::borrow::Cow;
+
+/// Shortcode names without colons, sorted for binary search.
+const SHORTCODES: &[(&str, &str)] = &[
+    ("+1", "👍"),
+    ("-1", "👎"),
+    ("100", "💯"),
+    ("angry", "😠"),
+    ("arrow_down", "⬇️"),
+    ("arrow_left", "⬅️"),
+    ("arrow_right", "➡️"),
+    ("arrow_up", "⬆️"),
+    ("art", "🎨"),
+    ("beer", "🍺"),
+    ("blush", "😊"),
+    ("book", "📖"),
+    ("bookmark", "🔖"),
+    ("books", "📚"),
+    ("boom", "💥"),
+    ("broken_heart", "💔"),
+    ("bug", "🐛"),
+    ("bulb", "💡"),
+    ("cake", "🍰"),
+    ("calendar", "📆"),
+    ("camera", "📷"),
+    ("cat", "🐱"),
+    ("chart_with_downwards_trend", "📉"),
+    ("chart_with_upwards_trend", "📈"),
+    ("clap", "👏"),
+    ("clock3", "🕒"),
+    ("cloud", "☁️"),
+    ("coffee", "☕"),
+    ("computer", "💻"),
+    ("confused", "😕"),
+    ("construction", "🚧"),
+    ("crab", "🦀"),
+    ("cry", "😢"),
+    ("date", "📅"),
+    ("dog", "🐶"),
+    ("earth_africa", "🌍"),
+    ("email", "📧"),
+    ("envelope", "✉️"),
+    ("evergreen_tree", "🌲"),
+    ("exclamation", "❗"),
+    ("eyes", "👀"),
+    ("fire", "🔥"),
+    ("free", "🆓"),
+    ("gear", "⚙️"),
+    ("gift", "🎁"),
+    ("globe_with_meridians", "🌐"),
+    ("grin", "😁"),
+    ("hammer", "🔨"),
+    ("headphones", "🎧"),
+    ("heart", "❤️"),
+    ("heart_eyes", "😍"),
+    ("heavy_check_mark", "✔️"),
+    ("hourglass", "⌛"),
+    ("house", "🏠"),
+    ("information_source", "ℹ️"),
+    ("iphone", "📱"),
+    ("joy", "😂"),
+    ("key", "🔑"),
+    ("laughing", "😆"),
+    ("link", "🔗"),
+    ("lipstick", "💄"),
+    ("lock", "🔒"),
+    ("mag", "🔍"),
+    ("medal_sports", "🏅"),
+    ("memo", "📝"),
+    ("movie_camera", "🎥"),
+    ("muscle", "💪"),
+    ("musical_note", "🎵"),
+    ("neutral_face", "😐"),
+    ("new", "🆕"),
+    ("no_entry", "⛔"),
+    ("ok_hand", "👌"),
+    ("package", "📦"),
+    ("pencil2", "✏️"),
+    ("penguin", "🐧"),
+    ("phone", "☎️"),
+    ("pizza", "🍕"),
+    ("point_down", "👇"),
+    ("point_left", "👈"),
+    ("point_right", "👉"),
+    ("point_up", "☝️"),
+    ("pray", "🙏"),
+    ("question", "❓"),
+    ("rage", "😡"),
+    ("rainbow", "🌈"),
+    ("raised_hands", "🙌"),
+    ("recycle", "♻️"),
+    ("rocket", "🚀"),
+    ("scream", "😱"),
+    ("seedling", "🌱"),
+    ("slightly_smiling_face", "🙂"),
+    ("smile", "😄"),
+    ("smiley", "😃"),
+    ("snake", "🐍"),
+    ("snowflake", "❄️"),
+    ("sob", "😭"),
+    ("sparkles", "✨"),
+    ("speech_balloon", "💬"),
+    ("star", "⭐"),
+    ("sun_with_face", "🌞"),
+    ("sunglasses", "😎"),
+    ("sunny", "☀️"),
+    ("sweat_smile", "😅"),
+    ("tada", "🎉"),
+    ("thinking", "🤔"),
+    ("thought_balloon", "💭"),
+    ("thumbsdown", "👎"),
+    ("thumbsup", "👍"),
+    ("trophy", "🏆"),
+    ("umbrella", "☔"),
+    ("unicorn", "🦄"),
+    ("unlock", "🔓"),
+    ("upside_down_face", "🙃"),
+    ("warning", "⚠️"),
+    ("wave", "👋"),
+    ("whale", "🐳"),
+    ("white_check_mark", "✅"),
+    ("wink", "😉"),
+    ("wrench", "🔧"),
+    ("x", "❌"),
+    ("zap", "⚡"),
+];
+
+fn lookup(name: &str) -> Option<&'static str> {
+    SHORTCODES
+        .binary_search_by(|(candidate, _)| (*candidate).cmp(name))
+        .ok()
+        .and_then(|index| SHORTCODES.get(index))
+        .map(|(_, emoji)| *emoji)
+}
+
+fn is_shortcode_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '+' | '-')
+}
+
+/// Replaces every known `:name:` in `text` with its emoji.
+pub(crate) fn replace_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        let (before, from_colon) = rest.split_at(start);
+        output.push_str(before);
+        let after_colon = from_colon.get(1..).unwrap_or_default();
+        let name_len = after_colon
+            .find(|c: char| !is_shortcode_char(c))
+            .unwrap_or(after_colon.len());
+        let (name, tail) = after_colon.split_at(name_len);
+        match (tail.strip_prefix(':'), lookup(name)) {
+            (Some(remainder), Some(emoji)) if !name.is_empty() => {
+                output.push_str(emoji);
+                rest = remainder;
+            }
+            _ => {
+                // Not a shortcode; the closing colon may start the next one.
+                output.push(':');
+                rest = after_colon;
+            }
+        }
+    }
+    output.push_str(rest);
+    Cow::Owned(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shortcodes_are_sorted_and_unique() {
+        assert!(
+            SHORTCODES
+                .windows(2)
+                .all(|pair| matches!(pair, [(a, _), (b, _)] if a < b))
+        );
+    }
+
+    #[test]
+    fn test_replace_shortcodes() {
+        assert_eq!(replace_shortcodes("Ship it :+1: :rocket:"), "Ship it 👍 🚀");
+        assert_eq!(replace_shortcodes("at 10:30:45"), "at 10:30:45");
+        assert_eq!(replace_shortcodes(":not_a_code: :tada:"), ":not_a_code: 🎉");
+        assert_eq!(replace_shortcodes("::+1:"), ":👍");
+        assert_eq!(replace_shortcodes("Rust :crab:"), "Rust 🦀");
+        assert!(matches!(replace_shortcodes("no colons"), Cow::Borrowed(_)));
+    }
+}
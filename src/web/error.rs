@@ -1,30 +1,198 @@
-use axum::http::StatusCode;
+use axum::Json;
+use axum::extract::Request;
+use axum::http::{StatusCode, header};
+use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 
+tokio::task_local! {
+    /// Whether the request being handled asked for JSON, for error bodies.
+    static PREFERS_JSON: bool;
+}
+
+#[derive(Debug)]
 pub(crate) enum WebError {
     BadRequest(String),
     NotFound(String),
     Internal(String),
     Unauthorized,
     Forbidden(String),
+    Conflict(String),
+    PreconditionFailed(String),
+    PayloadTooLarge(String),
+    /// The path is part of a bulk operation still in progress.
+    Locked(String),
+    /// Maintenance mode is refusing writes.
+    ServiceUnavailable(String),
+}
+
+impl std::fmt::Display for WebError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WebError::BadRequest(msg)
+            | WebError::NotFound(msg)
+            | WebError::Internal(msg)
+            | WebError::Forbidden(msg)
+            | WebError::Conflict(msg)
+            | WebError::PreconditionFailed(msg)
+            | WebError::PayloadTooLarge(msg)
+            | WebError::Locked(msg)
+            | WebError::ServiceUnavailable(msg) => f.write_str(msg),
+            WebError::Unauthorized => f.write_str("Unauthorized access"),
+        }
+    }
+}
+
+impl WebError {
+    fn status(&self) -> StatusCode {
+        match self {
+            WebError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            WebError::NotFound(_) => StatusCode::NOT_FOUND,
+            WebError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            WebError::Unauthorized => StatusCode::UNAUTHORIZED,
+            WebError::Forbidden(_) => StatusCode::FORBIDDEN,
+            WebError::Conflict(_) => StatusCode::CONFLICT,
+            WebError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            WebError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            WebError::Locked(_) => StatusCode::LOCKED,
+            WebError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
 }
 
+/// Plain text, or `{"error": "...", "status": 404}` when the request's
+/// `Accept` header prefers JSON (see [`negotiate`]).
 impl IntoResponse for WebError {
     fn into_response(self) -> Response {
-        match self {
-            WebError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg).into_response(),
-            WebError::NotFound(msg) => (StatusCode::NOT_FOUND, msg).into_response(),
-            WebError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg).into_response(),
-            WebError::Unauthorized => {
-                (StatusCode::UNAUTHORIZED, "Unauthorized access".to_string()).into_response()
-            }
-            WebError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg).into_response(),
+        let status = self.status();
+        let message = self.to_string();
+        if PREFERS_JSON.try_with(|prefers_json| *prefers_json) == Ok(true) {
+            let body = serde_json::json!({ "error": message, "status": status.as_u16() });
+            (status, Json(body)).into_response()
+        } else {
+            (status, message).into_response()
         }
     }
 }
 
+/// Middleware recording whether the client prefers JSON, so errors raised
+/// anywhere while handling the request come back in the form it can parse.
+pub(crate) async fn negotiate(request: Request, next: Next) -> Response {
+    let prefers_json = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(accepts_json_first);
+    PREFERS_JSON.scope(prefers_json, next.run(request)).await
+}
+
+/// Whether JSON ranks at least as high as any text type in an `Accept`
+/// header. Wildcards favour neither, so browsers keep getting text.
+fn accepts_json_first(accept: &str) -> bool {
+    let mut json = 0.0f32;
+    let mut text = 0.0f32;
+    for item in accept.split(',') {
+        let mut params = item.split(';');
+        let media_type = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|quality| quality.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if media_type == "application/json" || media_type.ends_with("+json") {
+            json = json.max(quality);
+        } else if media_type.starts_with("text/") {
+            text = text.max(quality);
+        }
+    }
+    json > 0.0 && json >= text
+}
+
+impl From<crate::paths::PathError> for WebError {
+    fn from(err: crate::paths::PathError) -> Self {
+        WebError::BadRequest(err.to_string())
+    }
+}
+
 impl From<std::io::Error> for WebError {
     fn from(err: std::io::Error) -> Self {
         WebError::Internal(format!("IO error: {err}"))
     }
 }
+
+impl From<async_zip::error::ZipError> for WebError {
+    fn from(err: async_zip::error::ZipError) -> Self {
+        WebError::Internal(format!("Snapshot archive error: {err}"))
+    }
+}
+
+/// Errors that stop the web server from starting or running.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ServerError {
+    /// Another process is already listening on the requested address.
+    AddrInUse { address: std::net::SocketAddr },
+    /// Binding the listener failed for any other reason.
+    Bind {
+        address: String,
+        source: std::io::Error,
+    },
+    /// `--csrf-secret-file` could not be read, parsed or created.
+    CsrfSecret {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    /// The server stopped with an I/O error after starting.
+    Serve(std::io::Error),
+}
+
+impl std::fmt::Display for ServerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ServerError::AddrInUse { address } => write!(
+                f,
+                "{address} is already in use; choose another port with --port"
+            ),
+            ServerError::Bind { address, source } => {
+                write!(f, "failed to listen on {address}: {source}")
+            }
+            ServerError::CsrfSecret { path, source } => {
+                write!(f, "failed to load CSRF secret {}: {source}", path.display())
+            }
+            ServerError::Serve(err) => write!(f, "server error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ServerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ServerError::AddrInUse { .. } => None,
+            ServerError::Bind { source, .. } | ServerError::CsrfSecret { source, .. } => {
+                Some(source)
+            }
+            ServerError::Serve(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepts_json_first() {
+        assert!(accepts_json_first("application/json"));
+        assert!(accepts_json_first("application/problem+json"));
+        assert!(accepts_json_first("application/json, text/plain"));
+        assert!(accepts_json_first("text/html;q=0.9, application/json"));
+        assert!(!accepts_json_first("text/html, application/json;q=0.9"));
+        assert!(!accepts_json_first("*/*"));
+        assert!(!accepts_json_first(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        ));
+        assert!(!accepts_json_first("application/json;q=0"));
+    }
+}
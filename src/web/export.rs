@@ -0,0 +1,100 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! `/export/frontmatter`: every markdown file's frontmatter as one table,
+//! for auditing content in a spreadsheet.
+//!
+//! CSV flattens `tags` and `categories` into one `, `-joined cell each and
+//! leaves out other keys; JSON keeps them under `extra`.
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+/// Written before CSV output with `bom=true` so Excel reads it as UTF-8.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+const CSV_HEADER: [&str; 8] = [
+    "path",
+    "title",
+    "date",
+    "draft",
+    "tags",
+    "categories",
+    "word_count",
+    "modified",
+];
+
+/// One markdown file in the export.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct FrontmatterRow {
+    pub(crate) path: String,
+    pub(crate) title: Option<String>,
+    pub(crate) date: Option<String>,
+    pub(crate) draft: Option<bool>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) categories: Vec<String>,
+    /// Words in the body, frontmatter excluded.
+    pub(crate) word_count: usize,
+    /// RFC 3339, UTC.
+    pub(crate) modified: Option<String>,
+    /// Frontmatter keys without a column of their own.
+    pub(crate) extra: HashMap<String, serde_json::Value>,
+}
+
+/// Whitespace-separated words, the way a word processor would count them.
+pub(crate) fn word_count(body: &str) -> usize {
+    body.split_whitespace().count()
+}
+
+/// `rows` as CSV with a header row, optionally preceded by a UTF-8 BOM.
+pub(crate) fn to_csv(rows: &[FrontmatterRow], bom: bool) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(if bom { UTF8_BOM.to_vec() } else { Vec::new() });
+    writer.write_record(CSV_HEADER)?;
+    for row in rows {
+        writer.write_record([
+            row.path.as_str(),
+            row.title.as_deref().unwrap_or_default(),
+            row.date.as_deref().unwrap_or_default(),
+            row.draft
+                .map_or("", |draft| if draft { "true" } else { "false" }),
+            &row.tags.join(", "),
+            &row.categories.join(", "),
+            &row.word_count.to_string(),
+            row.modified.as_deref().unwrap_or_default(),
+        ])?;
+    }
+    writer
+        .into_inner()
+        .map_err(|err| csv::Error::from(err.into_error()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv_quotes_joined_lists_and_adds_bom_on_request() {
+        let rows = [FrontmatterRow {
+            path: "posts/a.md".to_string(),
+            title: Some("Say \"hi\"".to_string()),
+            draft: Some(false),
+            tags: vec!["rust".to_string(), "web".to_string()],
+            word_count: 3,
+            ..Default::default()
+        }];
+
+        let csv = String::from_utf8(to_csv(&rows, false).expect("CSV should be written"))
+            .expect("CSV should be UTF-8");
+        assert_eq!(
+            csv,
+            "path,title,date,draft,tags,categories,word_count,modified\n\
+             posts/a.md,\"Say \"\"hi\"\"\",,false,\"rust, web\",,3,\n"
+        );
+
+        let with_bom = to_csv(&rows, true).expect("CSV should be written");
+        assert!(with_bom.starts_with(UTF8_BOM));
+        assert_eq!(with_bom.get(UTF8_BOM.len()..), Some(csv.as_bytes()));
+    }
+}
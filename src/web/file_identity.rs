@@ -0,0 +1,134 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Which file the editor was opened on, for `/save` to notice when it has
+//! been replaced underneath it.
+//!
+//! Comparing mtimes alone misses a file swapped for a different one with an
+//! older timestamp (checking out an old branch, `rsync --times`), so the
+//! identity also carries the device and inode on unix, and the size. The
+//! editor gets it as an opaque token signed with the CSRF secret, so a
+//! client cannot claim to have seen a file it has not.
+
+use std::fs::Metadata;
+use std::time::UNIX_EPOCH;
+
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+
+use super::error::WebError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct FileIdentity {
+    /// Device and inode; both zero where the platform has none.
+    dev: u64,
+    ino: u64,
+    size: u64,
+    /// Nanoseconds since the epoch, zero when unknown.
+    modified: u128,
+}
+
+impl FileIdentity {
+    pub(crate) fn of(metadata: &Metadata) -> Self {
+        #[cfg(unix)]
+        let (dev, ino) = {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.dev(), metadata.ino())
+        };
+        #[cfg(not(unix))]
+        let (dev, ino) = (0, 0);
+        FileIdentity {
+            dev,
+            ino,
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_nanos())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn payload(&self) -> String {
+        format!("{}.{}.{}.{}", self.dev, self.ino, self.size, self.modified)
+    }
+
+    /// The token the editor form carries, `payload:signature`.
+    pub(crate) fn sign(&self, secret: &str) -> String {
+        let payload = self.payload();
+        format!("{payload}:{}", hex::encode(signature(secret, &payload)))
+    }
+
+    /// Reads back a token from [`FileIdentity::sign`], refusing any that
+    /// was not signed with `secret`.
+    pub(crate) fn verify(token: &str, secret: &str) -> Result<Self, WebError> {
+        let invalid = || WebError::Forbidden("Invalid file identity".to_string());
+        let (payload, provided) = token.split_once(':').ok_or_else(invalid)?;
+        let provided = hex::decode(provided).map_err(|_| invalid())?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|_| invalid())?;
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&provided).map_err(|_| invalid())?;
+
+        let mut fields = payload.split('.');
+        let mut next = || fields.next().ok_or_else(invalid);
+        let identity = FileIdentity {
+            dev: next()?.parse().map_err(|_| invalid())?,
+            ino: next()?.parse().map_err(|_| invalid())?,
+            size: next()?.parse().map_err(|_| invalid())?,
+            modified: next()?.parse().map_err(|_| invalid())?,
+        };
+        if fields.next().is_some() {
+            return Err(invalid());
+        }
+        Ok(identity)
+    }
+}
+
+fn signature(secret: &str, payload: &str) -> Vec<u8> {
+    match Hmac::<Sha256>::new_from_slice(secret.as_bytes()) {
+        Ok(mut mac) => {
+            mac.update(payload.as_bytes());
+            mac.finalize().into_bytes().to_vec()
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[test]
+    fn test_sign_and_verify_round_trip_and_reject_tampering() {
+        let identity = FileIdentity {
+            dev: 2049,
+            ino: 131_072,
+            size: 42,
+            modified: 1_700_000_000_123_456_789,
+        };
+        let token = identity.sign(SECRET);
+        assert_eq!(
+            FileIdentity::verify(&token, SECRET).expect("token should verify"),
+            identity
+        );
+
+        let (payload, signature) = token.split_once(':').expect("token has a signature");
+        let forged = format!("{}:{signature}", payload.replace("131072", "131073"));
+        for token in [
+            forged.as_str(),
+            payload,
+            "",
+            "not-hex:zz",
+            &identity.sign(&SECRET.replace('0', "1")),
+        ] {
+            assert!(
+                FileIdentity::verify(token, SECRET).is_err(),
+                "{token} should be refused"
+            );
+        }
+    }
+}
@@ -0,0 +1,101 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Markdown normalization for `/api/format`.
+//!
+//! The body is parsed with pulldown-cmark and serialized back with
+//! pulldown-cmark-to-cmark, which settles heading spacing, list markers and
+//! emphasis tokens. Frontmatter is split off first and kept byte for byte.
+
+use pulldown_cmark::{Options as ParseOptions, Parser};
+use pulldown_cmark_to_cmark::{Options as WriteOptions, calculate_code_block_token_count};
+
+use super::frontmatter_len;
+
+fn parse_options() -> ParseOptions {
+    ParseOptions::ENABLE_TABLES
+        | ParseOptions::ENABLE_FOOTNOTES
+        | ParseOptions::ENABLE_STRIKETHROUGH
+        | ParseOptions::ENABLE_TASKLISTS
+}
+
+/// Returns `content` with its markdown body normalized and any frontmatter
+/// block untouched.
+pub(crate) fn format_markdown(
+    content: &str,
+    max_frontmatter_bytes: usize,
+) -> Result<String, pulldown_cmark_to_cmark::Error> {
+    let split = frontmatter_len(content, max_frontmatter_bytes).unwrap_or(0);
+    let (frontmatter, body) = content.split_at_checked(split).unwrap_or(("", content));
+
+    let events: Vec<_> = Parser::new_ext(body, parse_options()).collect();
+    let options = WriteOptions {
+        list_token: '-',
+        emphasis_token: '_',
+        code_block_token_count: calculate_code_block_token_count(events.iter())
+            .unwrap_or(3)
+            .max(3),
+        ..WriteOptions::default()
+    };
+    let mut formatted = String::with_capacity(body.len());
+    pulldown_cmark_to_cmark::cmark_with_options(events.iter(), &mut formatted, options)?;
+
+    // Blank lines inside lists and quotes can pick up indentation padding.
+    let mut output = String::with_capacity(content.len());
+    output.push_str(frontmatter);
+    if !frontmatter.is_empty() && !formatted.is_empty() {
+        output.push('\n');
+    }
+    for line in formatted.lines() {
+        if line.trim().is_empty() {
+            output.push('\n');
+        } else {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_FRONTMATTER_BYTES: usize = 65_536;
+
+    #[test]
+    fn test_format_normalizes_list_markers_and_headings() {
+        let input = "#   Title   \nSome *text* here.\n* one\n+ two\n* three\n\n\n\n## Next\n";
+        let formatted =
+            format_markdown(input, MAX_FRONTMATTER_BYTES).expect("formatting should succeed");
+        assert_eq!(
+            formatted,
+            "# Title\n\nSome _text_ here.\n\n- one\n\n- two\n\n- three\n\n## Next\n"
+        );
+    }
+
+    #[test]
+    fn test_format_preserves_frontmatter_verbatim() {
+        let frontmatter = "---\ntitle:   \"Spacing  kept\"   \ntags: [a,b]\n---\n";
+        let input = format!("{frontmatter}* item\n");
+        let formatted =
+            format_markdown(&input, MAX_FRONTMATTER_BYTES).expect("formatting should succeed");
+        assert!(formatted.starts_with(frontmatter));
+        assert_eq!(&formatted[frontmatter.len()..], "\n- item\n");
+
+        let json = "{\n  \"title\":   \"JSON\"\n}\n";
+        let formatted = format_markdown(&format!("{json}text"), MAX_FRONTMATTER_BYTES)
+            .expect("formatting should succeed");
+        assert!(formatted.starts_with(json));
+    }
+
+    #[test]
+    fn test_format_is_idempotent_and_keeps_code_blocks() {
+        let input = "Intro\n\n```rust\nfn main() {}  \n```\n\n1. first\n1. second\n";
+        let once = format_markdown(input, MAX_FRONTMATTER_BYTES).expect("first pass");
+        let twice = format_markdown(&once, MAX_FRONTMATTER_BYTES).expect("second pass");
+        assert_eq!(once, twice);
+        assert!(once.contains("fn main() {}  \n"));
+    }
+}
@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! `.wrangler-ignore` markers: a directory holding one is treated as if it
+//! did not exist.
+//!
+//! Walks (listings, the content index behind search and checks, directory
+//! copies) skip a marked directory with [`is_marked`], and
+//! `validate_file_path`/`validate_directory_path` refuse anything under one
+//! with [`is_hidden`], so its files cannot be reached by guessing a URL
+//! either. A marker in the content root itself is ignored.
+
+use std::path::Path;
+
+use tokio::fs;
+
+/// Name of the marker file.
+pub(crate) const IGNORE_MARKER: &str = ".wrangler-ignore";
+
+/// Whether `dir` holds a marker.
+pub(crate) async fn is_marked(dir: &Path) -> bool {
+    fs::try_exists(dir.join(IGNORE_MARKER))
+        .await
+        .unwrap_or(false)
+}
+
+/// Whether canonical `path` is `base` or below it and inside a marked
+/// directory other than `base`.
+pub(crate) fn is_hidden(base: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .take_while(|dir| *dir != base)
+        .any(|dir| dir.join(IGNORE_MARKER).exists())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_marker_hides_the_subtree_below_the_root() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("private/notes"))
+            .await
+            .expect("Failed to create directory");
+        fs::write(root.join("private").join(IGNORE_MARKER), "")
+            .await
+            .expect("Failed to write marker");
+        fs::write(root.join(IGNORE_MARKER), "")
+            .await
+            .expect("Failed to write marker");
+
+        assert!(is_marked(&root.join("private")).await);
+        assert!(!is_marked(&root.join("private/notes")).await);
+        assert!(is_hidden(root, &root.join("private")));
+        assert!(is_hidden(root, &root.join("private/notes/a.md")));
+        assert!(!is_hidden(root, &root.join("public/a.md")));
+        assert!(!is_hidden(root, root));
+    }
+}
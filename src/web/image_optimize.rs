@@ -0,0 +1,173 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Optional re-encoding of uploaded images (`--optimize-uploads`).
+//!
+//! Only JPEG and PNG are touched. Re-encoding drops EXIF and other metadata,
+//! so any EXIF orientation is applied to the pixels first. JPEGs are
+//! recompressed at [`JPEG_QUALITY`]; PNGs are recompressed losslessly, or
+//! converted to lossless WebP with `--convert-png-uploads-to-webp`. The
+//! result is kept only when it is smaller than the upload.
+
+use std::io::Cursor;
+
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader,
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+        webp::WebPEncoder,
+    },
+    metadata::Orientation,
+};
+
+pub(crate) const JPEG_QUALITY: u8 = 82;
+
+/// A smaller encoding of an upload.
+#[derive(Debug)]
+pub(crate) struct OptimizedImage {
+    pub(crate) bytes: Vec<u8>,
+    /// Set when the image was converted and the file needs this extension.
+    pub(crate) extension: Option<&'static str>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UploadOptimizer {
+    convert_png_to_webp: bool,
+}
+
+impl UploadOptimizer {
+    pub(crate) fn new(convert_png_to_webp: bool) -> Self {
+        UploadOptimizer {
+            convert_png_to_webp,
+        }
+    }
+
+    /// Re-encodes `bytes`, or returns `None` to store the upload unchanged
+    /// (unsupported format, undecodable, or no smaller).
+    pub(crate) fn optimize(self, bytes: &[u8]) -> Option<OptimizedImage> {
+        let format = image::guess_format(bytes).ok()?;
+        if !matches!(format, ImageFormat::Jpeg | ImageFormat::Png) {
+            return None;
+        }
+        let image = decode_oriented(bytes, format)?;
+
+        let mut output = Vec::new();
+        let extension = match format {
+            ImageFormat::Jpeg => {
+                let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+                rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY))
+                    .ok()?;
+                None
+            }
+            _ if self.convert_png_to_webp => {
+                // The WebP encoder takes 8-bit RGB(A) only.
+                let image = if image.color().has_alpha() {
+                    DynamicImage::ImageRgba8(image.to_rgba8())
+                } else {
+                    DynamicImage::ImageRgb8(image.to_rgb8())
+                };
+                image
+                    .write_with_encoder(WebPEncoder::new_lossless(&mut output))
+                    .ok()?;
+                Some("webp")
+            }
+            _ => {
+                image
+                    .write_with_encoder(PngEncoder::new_with_quality(
+                        &mut output,
+                        CompressionType::Best,
+                        FilterType::Adaptive,
+                    ))
+                    .ok()?;
+                None
+            }
+        };
+
+        (output.len() < bytes.len()).then_some(OptimizedImage {
+            bytes: output,
+            extension,
+        })
+    }
+}
+
+fn decode_oriented(bytes: &[u8], format: ImageFormat) -> Option<DynamicImage> {
+    let mut decoder = ImageReader::with_format(Cursor::new(bytes), format)
+        .into_decoder()
+        .ok()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).ok()?;
+    image.apply_orientation(orientation);
+    Some(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A noisy gradient: compresses poorly at maximum JPEG quality.
+    fn sample_image() -> DynamicImage {
+        DynamicImage::ImageRgb8(image::RgbImage::from_fn(256, 256, |x, y| {
+            let noise = (x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)) % 32;
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, (noise * 8) as u8])
+        }))
+    }
+
+    #[test]
+    fn test_jpeg_is_recompressed_and_stays_jpeg() {
+        let mut original = Vec::new();
+        sample_image()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut original, 100))
+            .expect("Failed to encode JPEG");
+
+        let optimized = UploadOptimizer::new(false)
+            .optimize(&original)
+            .expect("Quality 100 JPEG should shrink");
+        assert!(optimized.bytes.len() < original.len());
+        assert_eq!(optimized.extension, None);
+        assert_eq!(
+            image::guess_format(&optimized.bytes).ok(),
+            Some(ImageFormat::Jpeg)
+        );
+    }
+
+    #[test]
+    fn test_png_converts_to_webp_only_when_asked() {
+        let mut original = Vec::new();
+        sample_image()
+            .write_with_encoder(PngEncoder::new_with_quality(
+                &mut original,
+                CompressionType::Fast,
+                FilterType::NoFilter,
+            ))
+            .expect("Failed to encode PNG");
+
+        let converted = UploadOptimizer::new(true)
+            .optimize(&original)
+            .expect("Lossless WebP should beat an unfiltered PNG");
+        assert_eq!(converted.extension, Some("webp"));
+        assert_eq!(
+            image::guess_format(&converted.bytes).ok(),
+            Some(ImageFormat::WebP)
+        );
+
+        if let Some(recompressed) = UploadOptimizer::new(false).optimize(&original) {
+            assert_eq!(recompressed.extension, None);
+            assert_eq!(
+                image::guess_format(&recompressed.bytes).ok(),
+                Some(ImageFormat::Png)
+            );
+        }
+    }
+
+    #[test]
+    fn test_other_formats_are_left_alone() {
+        let mut gif = Cursor::new(Vec::new());
+        sample_image()
+            .write_to(&mut gif, ImageFormat::Gif)
+            .expect("Failed to encode GIF");
+        assert!(UploadOptimizer::new(true).optimize(gif.get_ref()).is_none());
+        assert!(UploadOptimizer::new(true).optimize(b"<svg/>").is_none());
+    }
+}
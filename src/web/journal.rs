@@ -0,0 +1,306 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Intent journals for bulk operations, so a crash halfway through leaves a
+//! record of what was and was not written.
+//!
+//! Before a bulk operation writes anything it creates one JSON-lines file
+//! under [`JOURNAL_DIR`]: the first line lists every target with the SHA-256
+//! (and, for small text files, the content) of what was there before, and a
+//! `{"done": index}` line is appended as each target is written. The file is
+//! removed when the operation finishes, so any journal found at startup
+//! belongs to an operation that never completed.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+
+use super::unix_seconds;
+use crate::paths::sanitize_relative_path;
+
+/// Journal location, relative to the content root.
+pub(crate) const JOURNAL_DIR: &str = ".wrangler/journal";
+
+/// Files up to this size keep their previous content in the journal.
+pub(crate) const MAX_PREIMAGE_BYTES: u64 = 64 * 1024;
+
+/// One file a bulk operation is about to write.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub(crate) struct JournalTarget {
+    pub(crate) path: String,
+    /// Source path for moves and copies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) from: Option<String>,
+    /// SHA-256 of what was at `path` before; `None` when nothing was.
+    #[serde(default)]
+    pub(crate) pre_hash: Option<String>,
+    /// Previous content of `path` when it was small UTF-8 text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) preimage: Option<String>,
+}
+
+impl JournalTarget {
+    pub(crate) fn new(path: String, from: Option<String>) -> Self {
+        JournalTarget {
+            path,
+            from,
+            pre_hash: None,
+            preimage: None,
+        }
+    }
+
+    /// `from → path` for moves and copies, otherwise just the path.
+    pub(crate) fn describe(&self) -> String {
+        match &self.from {
+            Some(from) => format!("{from} → {}", self.path),
+            None => self.path.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Intent {
+    id: String,
+    operation: String,
+    /// Unix seconds.
+    started: u64,
+    targets: Vec<JournalTarget>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Done {
+    done: usize,
+}
+
+/// An open journal; removed by [`OperationJournal::finish`].
+pub(crate) struct OperationJournal {
+    path: PathBuf,
+    file: fs::File,
+}
+
+impl OperationJournal {
+    /// Records the intent to write `targets` (paths relative to `root`)
+    /// before any of them is touched.
+    pub(crate) async fn begin(
+        root: &Path,
+        operation: &str,
+        mut targets: Vec<JournalTarget>,
+    ) -> std::io::Result<Self> {
+        for target in &mut targets {
+            // Invalid paths are rejected by the operation itself; just skip them.
+            if let Ok(relative) = sanitize_relative_path(&target.path)
+                && !relative.is_empty()
+            {
+                let full_path = root.join(relative.as_ref());
+                capture_preimage(&full_path, target).await?;
+            }
+        }
+        let intent = Intent {
+            id: format!(
+                "{}-{:016x}",
+                unix_seconds(SystemTime::now()),
+                rand::rng().random::<u64>()
+            ),
+            operation: operation.to_string(),
+            started: unix_seconds(SystemTime::now()),
+            targets,
+        };
+
+        let dir = root.join(JOURNAL_DIR);
+        fs::create_dir_all(&dir).await?;
+        let path = dir.join(format!("{}.jsonl", intent.id));
+        let mut line = serde_json::to_string(&intent)?;
+        line.push('\n');
+        let mut file = fs::OpenOptions::new()
+            .create_new(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.sync_data().await?;
+        Ok(OperationJournal { path, file })
+    }
+
+    /// Marks the target at `index` (in the order given to `begin`) written.
+    /// A failure is logged; the operation itself carries on.
+    pub(crate) async fn mark_done(&mut self, index: usize) {
+        let result = async {
+            let mut line = serde_json::to_string(&Done { done: index })?;
+            line.push('\n');
+            self.file.write_all(line.as_bytes()).await?;
+            self.file.flush().await
+        }
+        .await;
+        if let Err(err) = result {
+            warn!("Failed to update journal {}: {}", self.path.display(), err);
+        }
+    }
+
+    /// Removes the journal once the operation is over, successful or not.
+    pub(crate) async fn finish(self) {
+        drop(self.file);
+        if let Err(err) = fs::remove_file(&self.path).await {
+            warn!("Failed to remove journal {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+async fn capture_preimage(path: &Path, target: &mut JournalTarget) -> std::io::Result<()> {
+    // Symlinks are not followed out of the content tree.
+    let metadata = match fs::symlink_metadata(path).await {
+        Ok(metadata) if metadata.is_file() => metadata,
+        Ok(_) => return Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let bytes = fs::read(path).await?;
+    target.pre_hash = Some(hex::encode(Sha256::digest(&bytes)));
+    if metadata.len() <= MAX_PREIMAGE_BYTES {
+        target.preimage = String::from_utf8(bytes).ok();
+    }
+    Ok(())
+}
+
+/// A journal left behind by an operation that did not finish.
+#[derive(Clone, Debug)]
+pub(crate) struct InterruptedOperation {
+    pub(crate) operation: String,
+    /// Unix seconds.
+    pub(crate) started: u64,
+    /// Journal file, relative to the content root.
+    pub(crate) journal: String,
+    pub(crate) completed: Vec<JournalTarget>,
+    pub(crate) pending: Vec<JournalTarget>,
+}
+
+/// Reads every journal under `root`. Call before serving requests: journals
+/// of operations in progress look exactly like interrupted ones.
+pub(crate) async fn find_interrupted(root: &Path) -> Vec<InterruptedOperation> {
+    let mut entries = match fs::read_dir(root.join(JOURNAL_DIR)).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(err) => {
+            warn!("Failed to read the operation journal directory: {}", err);
+            return Vec::new();
+        }
+    };
+
+    let mut interrupted = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if !file_name.ends_with(".jsonl") {
+            continue;
+        }
+        let journal = format!("{JOURNAL_DIR}/{file_name}");
+        match fs::read_to_string(entry.path()).await {
+            Ok(content) => match parse_journal(&content, journal.clone()) {
+                Some(operation) => interrupted.push(operation),
+                None => warn!("Ignoring unreadable journal {}", journal),
+            },
+            Err(err) => warn!("Failed to read journal {}: {}", journal, err),
+        }
+    }
+    interrupted.sort_by_key(|operation| operation.started);
+    interrupted
+}
+
+fn parse_journal(content: &str, journal: String) -> Option<InterruptedOperation> {
+    let mut lines = content.lines();
+    let intent: Intent = serde_json::from_str(lines.next()?).ok()?;
+    let mut done = vec![false; intent.targets.len()];
+    // A torn last line from the crash is skipped.
+    for line in lines {
+        if let Ok(Done { done: index }) = serde_json::from_str(line)
+            && let Some(flag) = done.get_mut(index)
+        {
+            *flag = true;
+        }
+    }
+
+    let (completed, pending): (Vec<_>, Vec<_>) = intent
+        .targets
+        .into_iter()
+        .zip(done)
+        .partition(|(_, done)| *done);
+    Some(InterruptedOperation {
+        operation: intent.operation,
+        started: intent.started,
+        journal,
+        completed: completed.into_iter().map(|(target, _)| target).collect(),
+        pending: pending.into_iter().map(|(target, _)| target).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_unfinished_journal_is_found_with_progress() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(temp_dir.path().join("a.md"), "# Before")
+            .await
+            .expect("Failed to write file");
+
+        let targets = ["a.md", "b.md", "c.md"]
+            .into_iter()
+            .map(|path| JournalTarget::new(path.to_string(), None))
+            .collect();
+        let mut journal = OperationJournal::begin(temp_dir.path(), "test", targets)
+            .await
+            .expect("Failed to begin journal");
+        journal.mark_done(0).await;
+        // Crash: the journal is never finished.
+        drop(journal);
+
+        let interrupted = find_interrupted(temp_dir.path()).await;
+        assert_eq!(interrupted.len(), 1);
+        let operation = interrupted.first().expect("one interrupted operation");
+        assert_eq!(operation.operation, "test");
+        assert!(operation.journal.starts_with(JOURNAL_DIR));
+        let completed: Vec<&str> = operation
+            .completed
+            .iter()
+            .map(|t| t.path.as_str())
+            .collect();
+        let pending: Vec<&str> = operation.pending.iter().map(|t| t.path.as_str()).collect();
+        assert_eq!(completed, ["a.md"]);
+        assert_eq!(pending, ["b.md", "c.md"]);
+
+        let before = operation.completed.first().expect("completed target");
+        assert_eq!(before.preimage.as_deref(), Some("# Before"));
+        assert_eq!(
+            before.pre_hash.as_deref(),
+            Some(hex::encode(Sha256::digest(b"# Before")).as_str())
+        );
+        assert!(operation.pending.iter().all(|t| t.pre_hash.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_finished_journal_is_removed_and_torn_lines_ignored() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let journal = OperationJournal::begin(
+            temp_dir.path(),
+            "test",
+            vec![JournalTarget::new("a.md".to_string(), None)],
+        )
+        .await
+        .expect("Failed to begin journal");
+        journal.finish().await;
+        assert!(find_interrupted(temp_dir.path()).await.is_empty());
+
+        let torn = "{\"id\":\"x\",\"operation\":\"test\",\"started\":1,\"targets\":[{\"path\":\"a.md\"}]}\n{\"done\":0}\n{\"do";
+        let operation = parse_journal(torn, "x".to_string()).expect("intent line parses");
+        assert_eq!(operation.completed.len(), 1);
+        assert!(operation.pending.is_empty());
+        assert!(parse_journal("not json", "y".to_string()).is_none());
+    }
+}
@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Maintenance mode: while on, mutating requests are refused with 503 and
+//! every page shows a banner, but reads keep working.
+//!
+//! The notice is persisted under the hidden `.wrangler` directory so a
+//! restart does not silently end it. An optional expiry ends it by itself;
+//! an expired notice is simply ignored, on disk and in memory.
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use axum::extract::{Request, State};
+use axum::http::{HeaderValue, Method, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::warn;
+
+use super::error::WebError;
+use super::timefmt::epoch_seconds;
+use super::{AppState, durable, routes};
+use crate::cli::Durability;
+
+/// Notice location, relative to the content root. The file routes refuse
+/// dot-prefixed paths, so only the admin route can change it.
+pub(crate) const MAINTENANCE_FILE: &str = ".wrangler/maintenance.json";
+
+tokio::task_local! {
+    static ACTIVE_MESSAGE: Option<String>;
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub(crate) struct MaintenanceNotice {
+    pub(crate) message: String,
+    /// Epoch seconds after which the notice no longer applies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) expires_at: Option<u64>,
+}
+
+impl MaintenanceNotice {
+    fn is_expired(&self, now: SystemTime) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| epoch_seconds(now) >= expires_at)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Maintenance {
+    path: PathBuf,
+    durability: Durability,
+    notice: Arc<Mutex<Option<MaintenanceNotice>>>,
+}
+
+impl Maintenance {
+    /// Persists to `path` with `durability`, picking up a notice left there
+    /// by an earlier run.
+    pub(crate) async fn load(path: PathBuf, durability: Durability) -> Self {
+        let notice = match fs::read(&path).await {
+            Ok(bytes) => serde_json::from_slice::<MaintenanceNotice>(&bytes)
+                .inspect_err(|err| {
+                    warn!(
+                        "Ignoring unreadable maintenance notice {}: {}",
+                        path.display(),
+                        err
+                    );
+                })
+                .ok()
+                .filter(|notice| !notice.is_expired(SystemTime::now())),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!(
+                        "Failed to read maintenance notice {}: {}",
+                        path.display(),
+                        err
+                    );
+                }
+                None
+            }
+        };
+        if let Some(notice) = &notice {
+            warn!("Starting in maintenance mode: {}", notice.message);
+        }
+        Maintenance {
+            path,
+            durability,
+            notice: Arc::new(Mutex::new(notice)),
+        }
+    }
+
+    /// The notice in force, if any.
+    pub(crate) fn active(&self) -> Option<MaintenanceNotice> {
+        let notice = self.notice.lock().ok()?.clone()?;
+        (!notice.is_expired(SystemTime::now())).then_some(notice)
+    }
+
+    /// Turns maintenance on with `notice`, or off with `None`, and persists
+    /// the change.
+    pub(crate) async fn set(&self, notice: Option<MaintenanceNotice>) -> std::io::Result<()> {
+        match &notice {
+            Some(notice) => {
+                if let Some(parent) = self.path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                durable::write_atomic(
+                    &self.path,
+                    &serde_json::to_vec_pretty(notice)?,
+                    self.durability,
+                )
+                .await?;
+            }
+            None => match fs::remove_file(&self.path).await {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            },
+        }
+        if let Ok(mut current) = self.notice.lock() {
+            *current = notice;
+        }
+        Ok(())
+    }
+}
+
+/// The message of the notice in force for the request being rendered, for
+/// the banner in `base.html`.
+pub(crate) fn banner() -> Option<String> {
+    ACTIVE_MESSAGE.try_with(Clone::clone).ok().flatten()
+}
+
+/// Middleware refusing mutating requests with 503 while maintenance is on,
+/// and making the notice visible to [`banner`].
+pub(crate) async fn enforce(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let notice = state.maintenance.active();
+    if let Some(notice) = &notice
+        && is_mutating(request.method())
+        && !routes::is_read_only(request.uri().path())
+    {
+        let mut response =
+            WebError::ServiceUnavailable(format!("Maintenance in progress: {}", notice.message))
+                .into_response();
+        if let Some(expires_at) = notice.expires_at {
+            let retry_after = expires_at.saturating_sub(epoch_seconds(SystemTime::now()));
+            if let Ok(value) = HeaderValue::from_str(&retry_after.to_string()) {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+        }
+        return response;
+    }
+    ACTIVE_MESSAGE
+        .scope(notice.map(|notice| notice.message), next.run(request))
+        .await
+}
+
+fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_notice_survives_restart_until_cleared_or_expired() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let path = temp_dir.path().join(MAINTENANCE_FILE);
+        let notice = MaintenanceNotice {
+            message: "Restoring a snapshot".to_string(),
+            expires_at: None,
+        };
+
+        let maintenance = Maintenance::load(path.clone(), Durability::DirSync).await;
+        assert_eq!(maintenance.active(), None);
+        maintenance
+            .set(Some(notice.clone()))
+            .await
+            .expect("Failed to enable maintenance");
+        assert_eq!(
+            Maintenance::load(path.clone(), Durability::Fsync)
+                .await
+                .active(),
+            Some(notice)
+        );
+
+        maintenance
+            .set(Some(MaintenanceNotice {
+                message: "Done already".to_string(),
+                expires_at: Some(epoch_seconds(SystemTime::now()) - 1),
+            }))
+            .await
+            .expect("Failed to update maintenance");
+        assert_eq!(maintenance.active(), None);
+        assert_eq!(
+            Maintenance::load(path.clone(), Durability::Fsync)
+                .await
+                .active(),
+            None
+        );
+
+        maintenance
+            .set(None)
+            .await
+            .expect("Failed to disable maintenance");
+        assert!(!path.exists());
+        maintenance
+            .set(None)
+            .await
+            .expect("Disabling twice should succeed");
+    }
+}
@@ -0,0 +1,323 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Server-side markdown rendering for content shown inside app pages.
+//!
+//! The output is safe to embed unescaped: raw HTML in the source is shown
+//! as text, and link and image destinations with a scheme other than
+//! [`SAFE_SCHEMES`] become `#`. Relative destinations are resolved against
+//! the document's directory and pointed at the app's own routes, since the
+//! page they appear on is not at the document's path.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd, html};
+
+use super::base_path::route_url;
+use super::{emoji, file_view_url};
+use crate::cli::MarkdownExtensions;
+
+/// URL schemes passed through unchanged.
+pub(crate) const SAFE_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Rendered documents kept in memory; the cache is emptied when full.
+const MAX_CACHED_RENDERS: usize = 256;
+
+/// Parser options for the enabled `--markdown-extensions`.
+pub(crate) fn parser_options(extensions: MarkdownExtensions) -> Options {
+    let mut options = Options::empty();
+    options.set(Options::ENABLE_TABLES, extensions.tables);
+    options.set(Options::ENABLE_STRIKETHROUGH, extensions.strikethrough);
+    options.set(Options::ENABLE_TASKLISTS, extensions.tasklists);
+    options.set(Options::ENABLE_FOOTNOTES, extensions.footnotes);
+    options
+}
+
+/// Renders `markdown` (frontmatter already removed) from the document at
+/// `document_path`, relative to the content root. With `emoji_shortcodes`,
+/// `:name:` in text outside code becomes the emoji.
+pub(crate) fn render_html(
+    markdown: &str,
+    document_path: &str,
+    extensions: MarkdownExtensions,
+    emoji_shortcodes: bool,
+) -> String {
+    let mut in_code_block = false;
+    let events = Parser::new_ext(markdown, parser_options(extensions)).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        Event::Start(Tag::CodeBlock(kind)) => {
+            in_code_block = true;
+            Event::Start(Tag::CodeBlock(kind))
+        }
+        Event::End(TagEnd::CodeBlock) => {
+            in_code_block = false;
+            Event::End(TagEnd::CodeBlock)
+        }
+        Event::Text(text) if emoji_shortcodes && !in_code_block => {
+            match emoji::replace_shortcodes(&text) {
+                Cow::Borrowed(_) => Event::Text(text),
+                Cow::Owned(replaced) => Event::Text(replaced.into()),
+            }
+        }
+        Event::Start(Tag::Link {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Link {
+            link_type,
+            dest_url: rewrite_destination(&dest_url, document_path, false).into(),
+            title,
+            id,
+        }),
+        Event::Start(Tag::Image {
+            link_type,
+            dest_url,
+            title,
+            id,
+        }) => Event::Start(Tag::Image {
+            link_type,
+            dest_url: rewrite_destination(&dest_url, document_path, true).into(),
+            title,
+            id,
+        }),
+        other => other,
+    });
+
+    let mut output = String::new();
+    html::push_html(&mut output, events);
+    output
+}
+
+/// The readable text of `markdown`, formatting and raw HTML dropped and
+/// whitespace collapsed, cut to `max_chars` characters ending in `…` when
+/// longer.
+pub(crate) fn plain_text_snippet(markdown: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(fragment) | Event::Code(fragment) => text.push_str(&fragment),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+        // Whitespace collapses below, so this is enough to fill the snippet.
+        if text.len() > max_chars.saturating_mul(4) {
+            break;
+        }
+    }
+
+    let words = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if words.chars().count() <= max_chars {
+        return words;
+    }
+    let mut snippet: String = words.chars().take(max_chars.saturating_sub(1)).collect();
+    snippet.truncate(snippet.trim_end().len());
+    snippet.push('…');
+    snippet
+}
+
+fn scheme(destination: &str) -> Option<&str> {
+    let (scheme, _) = destination.split_once(':')?;
+    let mut chars = scheme.chars();
+    let starts_alphabetic = chars.next().is_some_and(|c| c.is_ascii_alphabetic());
+    (starts_alphabetic && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)))
+        .then_some(scheme)
+}
+
+/// Maps a link or image destination to something safe to emit on an app page.
+fn rewrite_destination(destination: &str, document_path: &str, image: bool) -> String {
+    let destination = destination.trim();
+    if destination.is_empty() || destination.starts_with('#') {
+        return destination.to_string();
+    }
+    if let Some(scheme) = scheme(destination) {
+        return if SAFE_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str()) {
+            destination.to_string()
+        } else {
+            "#".to_string()
+        };
+    }
+    if destination.starts_with("//") {
+        // Protocol-relative: another host, so not a content path.
+        return destination.to_string();
+    }
+
+    let (path, fragment) = match destination.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (destination, None),
+    };
+    let path = path.split('?').next().unwrap_or_default();
+    let Ok(path) = urlencoding::decode(path) else {
+        return "#".to_string();
+    };
+
+    let mut components: Vec<&str> = match document_path.rsplit_once('/') {
+        Some((directory, _)) if !path.starts_with('/') => directory.split('/').collect(),
+        _ => Vec::new(),
+    };
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    // Escapes the content root.
+                    return "#".to_string();
+                }
+            }
+            other => components.push(other),
+        }
+    }
+    let resolved = components.join("/");
+
+    let mut url = if image {
+        route_url("/image", &resolved)
+    } else if path.ends_with('/') || resolved.is_empty() {
+        route_url("/", &resolved)
+    } else {
+        file_view_url(&resolved).unwrap_or_else(|| route_url("/", &resolved))
+    };
+    if let Some(fragment) = fragment {
+        url.push('#');
+        url.push_str(fragment);
+    }
+    url
+}
+
+/// Rendered HTML keyed by document path and invalidated by mtime.
+#[derive(Clone, Default)]
+pub(crate) struct RenderCache {
+    entries: Arc<Mutex<HashMap<String, (SystemTime, String)>>>,
+}
+
+impl RenderCache {
+    pub(crate) fn get(&self, path: &str, modified: SystemTime) -> Option<String> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(path)
+            .filter(|(cached_modified, _)| *cached_modified == modified)
+            .map(|(_, html)| html.clone())
+    }
+
+    pub(crate) fn insert(&self, path: &str, modified: SystemTime, html: String) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= MAX_CACHED_RENDERS {
+                entries.clear();
+            }
+            entries.insert(path.to_string(), (modified, html));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_snippet_drops_markup_and_caps_length() {
+        assert_eq!(
+            plain_text_snippet(
+                "# Title\n\nSome **bold** and `code`<br>\ntext.\n\n- item",
+                100
+            ),
+            "Title Some bold and code text. item"
+        );
+        let snippet = plain_text_snippet(&"word ".repeat(100), 20);
+        assert_eq!(snippet, "word word word word…");
+        assert_eq!(snippet.chars().count(), 20);
+        assert_eq!(plain_text_snippet("", 20), "");
+    }
+
+    #[test]
+    fn test_raw_html_is_shown_as_text() {
+        let html = render_html(
+            "# Notes\n\n<script>alert(1)</script>\n\nSome <b>bold</b> text",
+            "posts/README.md",
+            MarkdownExtensions::default(),
+            false,
+        );
+        assert!(html.contains("<h1>Notes</h1>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains("&lt;b&gt;bold&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn test_destinations_are_made_safe_and_resolved() {
+        let document = "posts/README.md";
+        assert_eq!(
+            rewrite_destination("javascript:alert(1)", document, false),
+            "#"
+        );
+        assert_eq!(
+            rewrite_destination("JaVaScRiPt:alert(1)", document, false),
+            "#"
+        );
+        assert_eq!(rewrite_destination("data:text/html,x", document, true), "#");
+        assert_eq!(
+            rewrite_destination("https://example.com/a", document, false),
+            "https://example.com/a"
+        );
+        assert_eq!(rewrite_destination("#usage", document, false), "#usage");
+        assert_eq!(
+            rewrite_destination("first-post.md#intro", document, false),
+            "/edit?path=posts%2Ffirst-post.md#intro"
+        );
+        assert_eq!(
+            rewrite_destination("../images/cat%20one.png", document, true),
+            "/image?path=images%2Fcat%20one.png"
+        );
+        assert_eq!(
+            rewrite_destination("2025/", document, false),
+            "/?path=posts%2F2025"
+        );
+        assert_eq!(
+            rewrite_destination("../../etc/passwd", document, false),
+            "#"
+        );
+
+        let html = render_html(
+            "[x](javascript:alert(1)) ![y](cat.png)",
+            document,
+            MarkdownExtensions::default(),
+            false,
+        );
+        assert!(html.contains(r##"<a href="#">x</a>"##));
+        assert!(html.contains(r#"src="/image?path=posts%2Fcat.png""#));
+    }
+
+    #[test]
+    fn test_table_renders_only_with_tables_extension() {
+        let source = "| a | b |\n|---|---|\n| 1 | 2 |\n\n~~gone~~";
+        let html = render_html(source, "README.md", MarkdownExtensions::default(), false);
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<del>gone</del>"));
+
+        let only_strikethrough: MarkdownExtensions =
+            "strikethrough".parse().expect("extension list parses");
+        let html = render_html(source, "README.md", only_strikethrough, false);
+        assert!(!html.contains("<table>"));
+        assert!(html.contains("| a | b |"));
+        assert!(html.contains("<del>gone</del>"));
+
+        let html = render_html(source, "README.md", MarkdownExtensions::NONE, false);
+        assert!(!html.contains("<table>"));
+        assert!(!html.contains("<del>"));
+    }
+
+    #[test]
+    fn test_emoji_shortcodes_render_outside_code_only() {
+        let source = "Looks good :+1:\n\n```\nreact with :+1:\n```\n\nInline `:+1:` too";
+        let html = render_html(source, "README.md", MarkdownExtensions::default(), true);
+        assert!(html.contains("<p>Looks good 👍</p>"));
+        assert!(html.contains("<pre><code>react with :+1:\n</code></pre>"));
+        assert!(html.contains("<code>:+1:</code>"));
+
+        let html = render_html(source, "README.md", MarkdownExtensions::default(), false);
+        assert!(html.contains("<p>Looks good :+1:</p>"));
+        assert!(!html.contains('👍'));
+    }
+}
@@ -2,35 +2,81 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+pub(crate) mod assets;
+pub mod audit;
+pub(crate) mod base_path;
+pub mod checks;
+pub(crate) mod client_config;
 pub(crate) mod constants;
+pub(crate) mod content_hash;
+pub mod deadline;
+pub(crate) mod durable;
+pub(crate) mod emoji;
 pub mod error;
+pub(crate) mod export;
+pub(crate) mod file_identity;
+pub(crate) mod format;
+pub(crate) mod ignore_marker;
+pub(crate) mod image_optimize;
+pub(crate) mod journal;
+pub(crate) mod maintenance;
+pub(crate) mod markdown;
+pub(crate) mod operations;
+pub(crate) mod pdf;
+pub(crate) mod preview_commands;
+pub(crate) mod quickswitch;
+pub mod redirects;
+pub(crate) mod remote;
+pub(crate) mod routes;
+pub(crate) mod save_normalize;
+pub(crate) mod sitemap;
+pub(crate) mod snapshot;
+pub(crate) mod sort;
+pub(crate) mod timefmt;
+pub(crate) mod trash;
 
 use askama::Template;
 use askama_web::WebTemplate;
 use axum::{
     Router,
-    extract::{Form, Multipart, Query, State},
-    http::HeaderValue,
-    response::{Json, Redirect, Response},
-    routing::{get, post},
+    extract::{Form, FromRequestParts, Multipart, Path as UrlPath, Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header, request::Parts},
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Json, Redirect, Response},
+    routing::{MethodRouter, get, post},
 };
 use constants::*;
 use hmac::{Hmac, KeyInit, Mac};
 
 use rand::RngExt;
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
 use std::{
     collections::HashMap,
+    net::SocketAddr,
     path::{Path, PathBuf},
-    time::{SystemTime, UNIX_EPOCH},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpListener;
-use tower_http::services::ServeDir;
-use tracing::{debug, info, warn};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tower_http::decompression::RequestDecompressionLayer;
+use tower_http::services::ServeFile;
+use tracing::{Instrument, debug, info, info_span, warn};
 
-use crate::web::error::WebError;
+use crate::cli::{
+    Durability, MarkdownExtensions, Mount, PreviewHandler, SaveComparison, SaveNormalizations,
+};
+use crate::paths::{
+    get_parent_directory_path, glob_matches, is_git_compatible_ascii_filename_stem,
+    join_relative_path, normalize_markdown_filename, normalize_renamed_filename, relative_link,
+    sanitize_relative_path, sanitize_upload_filename, slugify,
+};
+use crate::web::base_path::{app_url, parent_url, route_url};
+use crate::web::deadline::{Deadline, RequestDeadline};
+use crate::web::error::{ServerError, WebError};
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -39,6 +85,252 @@ pub struct AppState {
     pub target_dir: PathBuf,
     pub csrf_secret: String,
     pub max_upload_size_bytes: usize,
+    pub max_frontmatter_bytes: usize,
+    /// Most items kept from a frontmatter `tags` or `categories` list.
+    pub max_frontmatter_list_items: usize,
+    /// Frontmatter keys holding image paths, checked for missing files.
+    pub frontmatter_asset_keys: Vec<String>,
+    /// Longest accepted new markdown filename, in bytes.
+    pub max_filename_length: usize,
+    /// Bearer token for the HTTP API; API writes are disabled when unset.
+    pub api_token: Option<String>,
+    /// Lets `/import-url` fetch from loopback/private networks (SSRF guard off).
+    pub allow_private_import_urls: bool,
+    /// Cached `/problems` results, invalidated by any successful write request.
+    pub checks: checks::ChecksCache,
+    /// Extra directories served read-only under `/mounts/<name>/`.
+    pub mounts: Vec<Mount>,
+    /// Journal of content writes; edit tracking is disabled when unset.
+    pub audit: Option<audit::AuditJournal>,
+    /// Never embed file content in an iframe on the preview page.
+    pub disable_iframe_preview: bool,
+    /// Whether `target_dir` treats names differing only by case as the same
+    /// file, as probed at startup.
+    pub case_insensitive_fs: bool,
+    /// Send `/` back to the last visited directory.
+    pub remember_path: bool,
+    /// Show the start of each markdown body in directory listings.
+    pub show_snippets: bool,
+    /// Where moved and deleted files went, for stale links.
+    pub redirects: redirects::PathRedirects,
+    /// Caps how many files are open for serving at once, so bursts of
+    /// downloads cannot exhaust file descriptors.
+    pub file_serves: Arc<Semaphore>,
+    /// Bulk operations in progress and the paths they lock.
+    pub(crate) operations: operations::OperationRegistry,
+    /// Content hashes of the static assets, for cache-busting URLs.
+    pub(crate) assets: assets::AssetManifest,
+    /// External preview renderers; `None` unless `--enable-preview-commands`.
+    pub(crate) preview_commands: Option<preview_commands::PreviewCommands>,
+    /// Public URL of the built site; `/sitemap.xml` is disabled when unset.
+    pub site_base_url: Option<String>,
+    /// `--paste-image-dir`: where `/api/upload-image` stores images instead
+    /// of the post's directory.
+    pub paste_image_dir: Option<String>,
+    /// Served as `/robots.txt`.
+    pub robots_txt: String,
+    /// Zone dates are displayed in on HTML pages; API timestamps stay UTC.
+    pub display_offset: chrono::FixedOffset,
+    /// Re-encodes uploaded images; `None` unless `--optimize-uploads`.
+    pub(crate) upload_optimizer: Option<image_optimize::UploadOptimizer>,
+    /// `--markdown-extensions` for server-rendered markdown.
+    pub markdown_extensions: MarkdownExtensions,
+    /// Render `:shortcode:` as emoji in server-side markdown.
+    pub emoji_shortcodes: bool,
+    /// `--save-normalize` rewrites applied to saved editor content.
+    pub save_normalize: SaveNormalizations,
+    /// `--save-comparison`: how `/save` detects unchanged content.
+    pub save_comparison: SaveComparison,
+    /// `--durability`: how far content writes go to survive a crash.
+    pub durability: Durability,
+    /// `--hard-delete`: remove deleted files instead of moving them into
+    /// the trash directory.
+    pub hard_delete: bool,
+    /// `--confirm-delete-name`: `/delete` requires `confirm_name` to match
+    /// the file's name.
+    pub confirm_delete_name: bool,
+    /// Write times of `/save`, reported by `/api/status`.
+    pub(crate) save_latency: durable::SaveLatency,
+    /// When the router was built, for the uptime in `/api/ping`.
+    pub(crate) started_at: std::time::Instant,
+    /// Rendered `README.md`/`_index.md` blocks for directory listings.
+    pub(crate) readme_cache: markdown::RenderCache,
+    /// File hashes for `/file-info`, cleared by any successful write request.
+    pub(crate) content_hashes: content_hash::ContentHashCache,
+    /// Bulk operations whose journals were left behind by an earlier run.
+    pub(crate) interrupted_operations: Arc<Vec<journal::InterruptedOperation>>,
+    /// Path the router is nested under (e.g. `/editor`), empty at the root.
+    pub(crate) base_path: Arc<str>,
+    /// Maintenance notice; while set, mutating routes answer 503.
+    pub(crate) maintenance: maintenance::Maintenance,
+}
+
+/// Appends to the audit journal when one is configured. Paths are recorded
+/// in canonical relative form so lookups match regardless of how the client
+/// spelled them.
+async fn record_audit(state: &AppState, operation: &str, path: &str, via: &str) {
+    if let Some(audit) = &state.audit {
+        let path = sanitize_relative_path(path).unwrap_or(std::borrow::Cow::Borrowed(path));
+        audit.record(operation, &path, via, None).await;
+    }
+}
+
+/// Refuses single-file writes to a path a bulk operation is working on.
+fn ensure_not_locked(state: &AppState, path: &str) -> Result<(), WebError> {
+    let path = sanitize_relative_path(path)?;
+    match state.operations.conflict(&path) {
+        Some(operation) => Err(WebError::Locked(format!(
+            "{path} is locked while a {operation} is in progress; try again shortly"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// How long a CSRF token stays valid after it is issued.
+const CSRF_TOKEN_LIFETIME_SECONDS: u64 = 3600;
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Guards against runaway `/copy-directory` requests.
+const MAX_COPY_DEPTH: usize = 16;
+const MAX_COPY_FILES: usize = 5_000;
+const MAX_COPY_BYTES: u64 = 256 * 1024 * 1024;
+
+const THEME_COOKIE_NAME: &str = "theme";
+const THEME_COOKIE_MAX_AGE_SECONDS: u64 = 60 * 60 * 24 * 365;
+/// Session cookie holding the last visited directory for `--remember-path`.
+const LAST_PATH_COOKIE_NAME: &str = "last_path";
+
+/// Served as `/robots.txt` unless `--robots-txt` names a file, keeping
+/// crawlers and link preview bots on the network away from drafts.
+pub const DEFAULT_ROBOTS_TXT: &str = "User-agent: *\nDisallow: /\n";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Theme {
+    Light,
+    Dark,
+    #[default]
+    Auto,
+}
+
+impl Theme {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            "auto" => Some(Theme::Auto),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+            Theme::Auto => "auto",
+        }
+    }
+
+    fn css_class(self) -> &'static str {
+        match self {
+            Theme::Light => "theme-light",
+            Theme::Dark => "theme-dark",
+            Theme::Auto => "theme-auto",
+        }
+    }
+}
+
+fn cookie_value<'a>(headers: &'a HeaderMap, cookie_name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == cookie_name)
+        .map(|(_, value)| value)
+}
+
+fn theme_from_headers(headers: &HeaderMap) -> Theme {
+    cookie_value(headers, THEME_COOKIE_NAME)
+        .and_then(Theme::parse)
+        .unwrap_or_default()
+}
+
+/// Builds the `Set-Cookie` value recording `path` as the last visited
+/// directory. Visiting the root clears it.
+fn last_path_cookie(path: &str) -> Result<HeaderValue, WebError> {
+    let cookie = if path.is_empty() {
+        format!("{LAST_PATH_COOKIE_NAME}=; Path=/; Max-Age=0; SameSite=Lax; HttpOnly")
+    } else {
+        format!(
+            "{LAST_PATH_COOKIE_NAME}={}; Path=/; SameSite=Lax; HttpOnly",
+            urlencoding::encode(path)
+        )
+    };
+    HeaderValue::from_str(&cookie)
+        .map_err(|err| WebError::Internal(format!("Failed to build last path cookie: {err}")))
+}
+
+/// The remembered directory, if the cookie holds a safe path that still
+/// exists.
+fn remembered_directory(headers: &HeaderMap, base_dir: &Path) -> Option<String> {
+    let value = cookie_value(headers, LAST_PATH_COOKIE_NAME)?;
+    let decoded = urlencoding::decode(value).ok()?;
+    let path = sanitize_relative_path(&decoded).ok()?;
+    if path.is_empty() {
+        return None;
+    }
+    validate_directory_path(base_dir, &path).ok()?;
+    Some(path.to_string())
+}
+
+/// Extracts the theme preference from the request cookies, falling back to
+/// [`Theme::Auto`] when the cookie is missing or invalid.
+struct ThemePreference(Theme);
+
+impl<S> FromRequestParts<S> for ThemePreference
+where
+    S: Send + Sync,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(ThemePreference(theme_from_headers(&parts.headers)))
+    }
+}
+
+/// Authenticates API clients via `Authorization: Bearer <token>`. Cookies are
+/// never consulted, so routes guarded by this extractor do not need CSRF tokens.
+struct ApiToken;
+
+impl FromRequestParts<AppState> for ApiToken {
+    type Rejection = WebError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let Some(expected) = state.api_token.as_deref() else {
+            return Err(WebError::Unauthorized);
+        };
+
+        let provided = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(WebError::Unauthorized)?;
+
+        // Compare digests so the comparison time does not depend on how much
+        // of the token matched.
+        if Sha256::digest(provided.trim().as_bytes()) != Sha256::digest(expected.as_bytes()) {
+            return Err(WebError::Unauthorized);
+        }
+
+        Ok(ApiToken)
+    }
 }
 
 #[derive(Debug)]
@@ -46,9 +338,10 @@ struct DirectoryEntry {
     name: String,
     is_directory: bool,
     path: String,
+    modified: Option<SystemTime>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 struct Breadcrumb {
     name: String,
     url: String,
@@ -62,54 +355,247 @@ struct DirectoryEntryView {
     url: String,
     has_url: bool,
     executable: bool,
+    modified: Option<timefmt::HumanTime>,
+    /// Start of a markdown file's body, with `--show-snippets`.
+    snippet: Option<String>,
+    /// Directories get a delete control posting this path.
+    delete_path: Option<String>,
+}
+
+/// Template filters; `{{ "/save"|app_url }}` adds the mount prefix to a
+/// literal app URL.
+mod filters {
+    #[askama::filter_fn]
+    pub(crate) fn app_url(
+        url: impl std::fmt::Display,
+        _: &dyn askama::Values,
+    ) -> askama::Result<String> {
+        Ok(super::base_path::app_url(&url.to_string()))
+    }
 }
 
 #[derive(Template, WebTemplate)]
 #[template(path = "directory.html")]
 struct DirectoryTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    client_config: client_config::ClientConfig,
     at_root: bool,
+    root_url: String,
     breadcrumbs: Vec<Breadcrumb>,
     has_parent: bool,
     parent_url: String,
     new_file_url: String,
     upload_image_url: String,
+    delete_dir_url: String,
+    /// The listed directory, for the upload form.
+    path_value: String,
+    readme: Option<ReadmeView>,
     entries: Vec<DirectoryEntryView>,
+    draft_count: usize,
+    csrf_token: String,
+    current_url: String,
+}
+
+/// A custom frontmatter key and its value as text, for the editor.
+struct FrontmatterFieldView {
+    key: String,
+    value: String,
+}
+
+/// A directory's `README.md` or `_index.md`, rendered above the listing.
+struct ReadmeView {
+    path: String,
+    edit_url: String,
+    /// Sanitized by [`markdown::render_html`].
+    html: String,
+    /// Long notes start folded behind a `<details>` element.
+    collapsed: bool,
 }
 
 #[derive(Template, WebTemplate)]
 #[template(path = "editor.html")]
 struct EditorTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    client_config: client_config::ClientConfig,
     file_path: String,
+    /// Prefills the rename form.
+    file_name: String,
     content: String,
     csrf_token: String,
     is_draft: bool,
+    /// `key: path` for frontmatter images that resolve to no file.
+    missing_assets: Vec<String>,
+    /// Frontmatter keys without a field of their own, shown read-only.
+    extra_frontmatter: Vec<FrontmatterFieldView>,
+    /// Text documents (org, AsciiDoc) are edited without the markdown preview.
+    is_markdown: bool,
     cancel_url: String,
+    /// Epoch seconds, sent back as `expected_mtime` on delete and
+    /// `base_modified_time` on save.
+    modified_time: String,
+    /// Signed [`file_identity::FileIdentity`], sent back on save.
+    file_identity: String,
+    modified: Option<timefmt::HumanTime>,
+    csrf_expires_in: u64,
+    edit_summary: Option<String>,
+    /// Delete confirmation for the whole folder when the file is a leaf
+    /// bundle's `index.md`; the delete button targets it instead.
+    bundle_delete_url: Option<String>,
+    /// `--confirm-delete-name`: the delete form asks for the file name.
+    confirm_delete_name: bool,
+}
+
+struct FindingView {
+    path: String,
+    message: String,
+    url: String,
+}
+
+struct ProblemCategoryView {
+    name: &'static str,
+    title: &'static str,
+    count: usize,
+    url: String,
+    findings: Vec<FindingView>,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "problems.html")]
+struct ProblemsTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    total: usize,
+    categories: Vec<ProblemCategoryView>,
+    selected: Option<ProblemCategoryView>,
+    generated_at: String,
+    interrupted: Vec<InterruptedOperationView>,
+}
+
+/// A journal from a bulk operation that did not finish.
+struct InterruptedOperationView {
+    operation: String,
+    started: timefmt::HumanTime,
+    journal: String,
+    completed: Vec<String>,
+    pending: Vec<String>,
+}
+
+struct HighlightSegment {
+    text: String,
+    matched: bool,
+}
+
+struct QuickSwitchView {
+    url: String,
+    path: Vec<HighlightSegment>,
+    title: Vec<HighlightSegment>,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "quickswitch.html")]
+struct QuickSwitchTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    query: String,
+    results: Vec<QuickSwitchView>,
+    truncated: bool,
+}
+
+struct SnapshotView {
+    name: String,
+    size: String,
+    created: String,
+    restore_url: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "snapshots.html")]
+struct SnapshotsTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    csrf_token: String,
+    snapshots: Vec<SnapshotView>,
+    created: Option<String>,
+    max_snapshots: usize,
+}
+
+struct TrashView {
+    id: String,
+    /// Where it was deleted from, or its file name when that is unknown.
+    original_path: String,
+    has_original: bool,
+    is_dir: bool,
+    deleted: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "trash.html")]
+struct TrashTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    csrf_token: String,
+    items: Vec<TrashView>,
+    /// Files removed by `/trash/empty`, shown after its redirect.
+    emptied: Option<usize>,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "snapshot_restore.html")]
+struct SnapshotRestoreTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    name: String,
+    size: String,
+    csrf_token: String,
+    report: Option<snapshot::RestoreReport>,
 }
 
 #[derive(Template, WebTemplate)]
 #[template(path = "image_preview.html")]
 struct ImagePreviewTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
     file_path: String,
+    file_name: String,
     encoded_path: String,
     file_size: String,
     parent_path: String,
     csrf_token: String,
+    modified_time: String,
+    confirm_delete_name: bool,
 }
 
 #[derive(Template, WebTemplate)]
 #[template(path = "file_preview.html")]
 struct FilePreviewTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
     file_path: String,
+    file_name: String,
     encoded_path: String,
     file_size: String,
     file_type: String,
     csrf_token: String,
     can_iframe: bool,
+    modified_time: String,
+    pdf_page_count: Option<usize>,
+    pdf_title: Option<String>,
+    /// Set when a PDF cannot be shown inline; the page offers a download instead.
+    download_only_reason: Option<&'static str>,
+    /// The file could be shown inline but `--disable-iframe-preview` is set.
+    iframe_preview_disabled: bool,
+    /// Output of a configured preview command, if one handles this type.
+    command_preview: Option<preview_commands::PreviewOutput>,
+    confirm_delete_name: bool,
 }
 
 #[derive(Template, WebTemplate)]
 #[template(path = "status_page.html")]
 struct StatusPageTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
     title: String,
     heading: String,
     heading_class: String,
@@ -118,121 +604,664 @@ struct StatusPageTemplate {
     show_edit_button: bool,
     edit_url: String,
     back_url: String,
+    /// Links to `/trash`, for deletions that can be undone there.
+    show_trash_link: bool,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "frontmatter_error.html")]
+struct FrontmatterErrorTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    file_path: String,
+    error_message: String,
+    error_location: String,
+    content: String,
+    csrf_token: String,
+    /// Carried over from the editor so the retry is still checked.
+    file_identity: String,
+    base_modified_time: String,
+    /// The button clicked in the editor, kept for the retry.
+    action: SaveAction,
+    edit_url: String,
+    back_url: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "save_conflict.html")]
+struct SaveConflictTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    file_path: String,
+    file_size: String,
+    modified_display: String,
+    content: String,
+    csrf_token: String,
+    /// Of the file as it is now, so saving from this page overwrites it.
+    file_identity: String,
+    /// The button clicked in the editor, kept for the retry.
+    action: SaveAction,
+    edit_url: String,
+    back_url: String,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "delete_conflict.html")]
+struct DeleteConflictTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    file_path: String,
+    file_size: String,
+    modified_time: String,
+    modified_display: String,
+    csrf_token: String,
+    back_url: String,
+    confirm_delete_name: bool,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "delete_directory.html")]
+struct DeleteDirectoryTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    dir_path: String,
+    summary: DirectoryTreeSummary,
+    /// The first [`MAX_LISTED_DELETE_FILES`] files, relative to the folder.
+    listed_files: Vec<String>,
+    unlisted_files: usize,
+    total_size: String,
+    csrf_token: String,
+    /// The folder itself; the parent once it is gone.
+    cancel_url: String,
+    back_url: String,
+    deleted: bool,
+    /// `--hard-delete`: the folder is removed rather than trashed.
+    hard_delete: bool,
+    /// Where a deleted folder went, relative to the content root.
+    trash_folder: Option<String>,
 }
 
 #[derive(Template, WebTemplate)]
 #[template(path = "new_file.html")]
 struct NewFileTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
     current_path_display: String,
     path_value: String,
     back_url: String,
     csrf_token: String,
+    /// Filename to pre-fill after a check or a rejected submission.
+    filename_value: String,
+    /// Why the last submission was rejected.
+    error_message: Option<String>,
+    /// Relative path the submission creates (or would have created).
+    planned_path: Option<String>,
+    /// Set when a dry run found nothing wrong.
+    confirmed: bool,
+    /// A free filename to offer when the requested one is taken.
+    suggested_filename: Option<String>,
 }
 
 #[derive(Template, WebTemplate)]
 #[template(path = "upload_image.html")]
 struct UploadImageTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
     current_path_display: String,
     path_value: String,
     back_url: String,
     csrf_token: String,
     max_upload_size: String,
+    optimize_uploads: bool,
+}
+
+#[derive(Template, WebTemplate)]
+#[template(path = "upload_result.html")]
+struct UploadResultTemplate {
+    theme: Theme,
+    assets: assets::AssetManifest,
+    current_path_display: String,
+    back_url: String,
+    files: Vec<UploadedFileView>,
+}
+
+/// A file stored by `POST /upload`.
+struct UploadedFileView {
+    path: String,
+    url: String,
 }
 
 #[derive(Deserialize)]
 struct EditForm {
     path: String,
-    content: String,
+    /// `None` when the field was not submitted at all; an empty string is a
+    /// legitimate request to save an empty file.
+    #[serde(default)]
+    content: Option<String>,
     csrf_token: String,
+    /// Save even though the new frontmatter does not parse.
+    #[serde(default)]
+    force: bool,
+    /// Signed identity of the file the editor loaded; a save over a file
+    /// that has since been replaced or changed is refused.
+    #[serde(default)]
+    file_identity: Option<String>,
+    /// Epoch seconds the file was modified at when the editor loaded it;
+    /// the save is refused when the file has been modified since.
+    #[serde(default)]
+    base_modified_time: Option<String>,
+    /// Value of the submit button that was clicked.
+    #[serde(default)]
+    action: SaveAction,
+}
+
+/// What `/save` does after writing, chosen by the editor's submit buttons.
+/// Unknown values are a plain save.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+enum SaveAction {
+    /// Show the saved status page.
+    #[default]
+    Save,
+    /// Redirect to the parent listing.
+    SaveAndClose,
+    /// Flip the frontmatter `draft` flag in the same write, then reopen the
+    /// editor.
+    ToggleDraft,
+}
+
+impl From<String> for SaveAction {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "save-and-close" => SaveAction::SaveAndClose,
+            "toggle-draft" => SaveAction::ToggleDraft,
+            _ => SaveAction::Save,
+        }
+    }
+}
+
+impl SaveAction {
+    /// The form value that parses back to `self`, for pages that resubmit a
+    /// save.
+    fn form_value(self) -> &'static str {
+        match self {
+            SaveAction::Save => "save",
+            SaveAction::SaveAndClose => "save-and-close",
+            SaveAction::ToggleDraft => "toggle-draft",
+        }
+    }
 }
 
 #[derive(Deserialize)]
-struct DeleteForm {
+struct RenameForm {
     path: String,
+    /// New filename in the same directory. Exactly one of `new_name` and
+    /// `new_path` is given.
+    #[serde(default)]
+    new_name: Option<String>,
+    /// Destination relative to the content root, for moves.
+    #[serde(default)]
+    new_path: Option<String>,
     csrf_token: String,
 }
 
 #[derive(Deserialize)]
-struct NewFileForm {
+struct DuplicateForm {
     path: String,
+    /// Name of the copy, in the source's directory; goes through
+    /// `normalize_markdown_filename`.
     filename: String,
     csrf_token: String,
 }
 
-#[derive(Serialize)]
-struct FileInfo {
-    modified_time: String,
-    size: u64,
+#[derive(Deserialize)]
+struct SaveAsForm {
+    /// File the editor content came from; the editor's own form sends it
+    /// as `path`. Its extension is kept for non-markdown files.
+    #[serde(default, alias = "path")]
+    src: Option<String>,
+    /// Destination relative to the content root.
+    new_path: String,
+    content: String,
+    csrf_token: String,
 }
 
-#[derive(Serialize)]
-struct FileContent {
-    content: String,
-    modified_time: String,
+#[derive(Deserialize)]
+struct SetDraftForm {
+    path: String,
+    draft: bool,
+    csrf_token: String,
 }
 
-pub(crate) fn generate_csrf_token(secret: &str) -> String {
-    let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
-        Ok(duration) => duration.as_secs(),
-        Err(err) => {
-            warn!(
-                "System time is before UNIX_EPOCH while generating CSRF token: {}",
-                err
-            );
-            0
-        }
-    };
-    let nonce: u64 = rand::rng().random();
+#[derive(Deserialize)]
+struct RestoreTrashForm {
+    id: String,
+    /// Where to put the file instead of its original path, which may have
+    /// been taken since.
+    #[serde(default)]
+    destination: Option<String>,
+    csrf_token: String,
+}
 
-    let payload = format!("{timestamp}:{nonce}");
-    let signature = match HmacSha256::new_from_slice(secret.as_bytes()) {
-        Ok(mut mac) => {
-            mac.update(payload.as_bytes());
-            hex::encode(mac.finalize().into_bytes())
-        }
-        Err(err) => {
-            warn!(
-                "Failed to initialize HMAC for CSRF token generation: {}",
-                err
-            );
-            String::new()
-        }
-    };
+#[derive(Deserialize)]
+struct EmptyTrashForm {
+    csrf_token: String,
+}
 
-    format!("{payload}:{signature}")
+#[derive(Deserialize)]
+struct DeleteForm {
+    path: String,
+    csrf_token: String,
+    /// Modification time the client saw when rendering the page; when present
+    /// the delete is refused if the file has changed since.
+    #[serde(default)]
+    expected_mtime: Option<String>,
+    /// Explicit override to delete even though the file changed.
+    #[serde(default)]
+    force: bool,
+    /// The file's name, typed by the user; required with
+    /// `--confirm-delete-name`.
+    #[serde(default)]
+    confirm_name: Option<String>,
 }
 
-pub(crate) fn validate_csrf_token(token: &str, secret: &str) -> Result<(), WebError> {
-    let parts: Vec<&str> = token.split(':').collect();
+#[derive(Deserialize)]
+struct DeleteDirectoryForm {
+    path: String,
+    csrf_token: String,
+}
 
-    let [timestamp_str, nonce, provided_signature] = parts.as_slice() else {
-        return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
-    };
+#[derive(Deserialize)]
+struct DeleteEmptyDirectoryForm {
+    path: String,
+    csrf_token: String,
+    /// Go to the `/delete-dir` confirmation page instead of refusing a
+    /// folder that is not empty.
+    #[serde(default)]
+    recursive: bool,
+}
 
-    // Check if token is not too old (1 hour)
-    if let Ok(timestamp) = timestamp_str.parse::<u64>() {
-        let current_time = match SystemTime::now().duration_since(UNIX_EPOCH) {
-            Ok(duration) => duration.as_secs(),
-            Err(err) => {
-                warn!(
-                    "System time is before UNIX_EPOCH while validating CSRF token: {}",
-                    err
-                );
-                return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
-            }
-        };
+#[derive(Deserialize)]
+struct PreferencesForm {
+    theme: String,
+    csrf_token: String,
+    #[serde(default)]
+    return_to: String,
+}
 
-        if current_time.saturating_sub(timestamp) > 3600 {
-            debug!(?timestamp, ?current_time, "CSRF token expired");
-            return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
-        }
-    } else {
-        return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
-    }
+#[derive(Deserialize)]
+struct NewFileForm {
+    path: String,
+    filename: String,
+    csrf_token: String,
+}
 
-    let Ok(signature_bytes) = hex::decode(provided_signature) else {
-        return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
-    };
+#[derive(Deserialize)]
+struct CopyDirectoryForm {
+    source: String,
+    destination: String,
+    csrf_token: String,
+    /// Comma- or newline-separated globs matched against each entry's name
+    /// and its path relative to the source directory.
+    #[serde(default)]
+    exclude: String,
+    /// Mark copied markdown as drafts and drop their dates.
+    #[serde(default)]
+    reset_drafts: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct CopyDirectoryResult {
+    source: String,
+    destination: String,
+    directories: usize,
+    files: usize,
+    bytes: u64,
+    skipped: usize,
+}
+
+#[derive(Deserialize)]
+struct SnapshotForm {
+    csrf_token: String,
+}
+
+#[derive(Deserialize)]
+struct RestoreSnapshotForm {
+    name: String,
+    csrf_token: String,
+}
+
+#[derive(Deserialize)]
+struct ImportUrlForm {
+    url: String,
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    filename: Option<String>,
+    csrf_token: String,
+}
+
+/// JSON body of `POST /api/upload-image`.
+#[derive(Deserialize)]
+struct PasteImageRequest {
+    /// The markdown file the image is pasted into.
+    path: String,
+    csrf_token: String,
+    /// Base64 image bytes, optionally as a `data:` URL.
+    data: String,
+}
+
+#[derive(Serialize)]
+struct ImportUrlResult {
+    path: String,
+    markdown: String,
+}
+
+#[derive(Serialize)]
+struct FileInfo {
+    #[serde(flatten)]
+    modified: timefmt::ModifiedTimes,
+    size: u64,
+    /// Hex SHA-256 of the file; `null` over `content_hash::MAX_HASHED_BYTES`.
+    content_hash: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ListResponse {
+    path: String,
+    entries: Vec<ListEntry>,
+}
+
+/// Markdown files either side of `path` in its directory's listing order.
+#[derive(Serialize)]
+struct NeighborsResponse {
+    path: String,
+    previous: Option<Neighbor>,
+    next: Option<Neighbor>,
+}
+
+#[derive(Serialize)]
+struct Neighbor {
+    name: String,
+    path: String,
+    edit_url: String,
+}
+
+#[derive(Serialize)]
+struct ListEntry {
+    name: String,
+    path: String,
+    is_directory: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    /// Pixel dimensions for images the `image` crate can read; omitted when
+    /// the header cannot be decoded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<u32>,
+}
+
+#[derive(Default, Serialize)]
+struct LinksResponse {
+    path: String,
+    internal: Vec<checks::OutgoingLink>,
+    broken: Vec<checks::OutgoingLink>,
+    external: Vec<checks::OutgoingLink>,
+    anchors: Vec<checks::OutgoingLink>,
+    mailto: Vec<checks::OutgoingLink>,
+}
+
+/// Markdown files whose content is byte-for-byte identical.
+#[derive(Serialize)]
+struct DuplicateGroup {
+    /// Hex SHA-256 of the shared content.
+    hash: String,
+    size: usize,
+    paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct DuplicatesResponse {
+    groups: Vec<DuplicateGroup>,
+}
+
+#[derive(Deserialize)]
+struct FormatRequest {
+    content: String,
+}
+
+#[derive(Serialize)]
+struct FormatResponse {
+    content: String,
+    changed: bool,
+}
+
+#[derive(Deserialize)]
+struct RenderRequest {
+    content: String,
+    /// Document the content belongs to, for resolving relative links.
+    #[serde(default)]
+    path: String,
+}
+
+#[derive(Serialize)]
+struct QuickSwitchResult {
+    path: String,
+    title: Option<String>,
+    url: String,
+    score: i64,
+    /// Character offsets into `path` / `title` for highlighting.
+    path_positions: Vec<usize>,
+    title_positions: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct QuickSwitchResponse {
+    query: String,
+    results: Vec<QuickSwitchResult>,
+    /// The search stopped at the request deadline and missed part of the tree.
+    truncated: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "tracking", rename_all = "snake_case")]
+enum FileStatsResponse {
+    Disabled {
+        message: &'static str,
+    },
+    Enabled {
+        path: String,
+        edits: usize,
+        /// Unix seconds of the first journal entry, or the filesystem
+        /// creation time when the journal has no history for the file.
+        created: u64,
+        created_source: &'static str,
+        last_operation: Option<audit::AuditEntry>,
+    },
+}
+
+#[derive(Deserialize)]
+struct MoveManyRequest {
+    csrf_token: String,
+    moves: Vec<MovePair>,
+}
+
+#[derive(Deserialize)]
+struct MovePair {
+    src: String,
+    dest: String,
+}
+
+#[derive(Serialize)]
+struct MoveResult {
+    src: String,
+    dest: String,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct MoveManyResponse {
+    moved: usize,
+    failed: usize,
+    results: Vec<MoveResult>,
+}
+
+#[derive(Deserialize)]
+struct MaintenanceRequest {
+    csrf_token: String,
+    enabled: bool,
+    /// Shown in the banner and the 503 responses.
+    #[serde(default)]
+    message: Option<String>,
+    /// Ends maintenance by itself after this long.
+    #[serde(default)]
+    expires_in_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Ping {
+    version: &'static str,
+    uptime_secs: u64,
+}
+
+#[derive(Serialize)]
+struct ServerStatus {
+    durability: &'static str,
+    saves: u64,
+    /// Mean time `/save` spent writing, in milliseconds; `null` before the
+    /// first save.
+    average_save_ms: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct MaintenanceStatus {
+    enabled: bool,
+    message: Option<String>,
+    /// RFC 3339, when the notice expires by itself.
+    expires_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PublishByTagRequest {
+    csrf_token: String,
+    tag: String,
+}
+
+#[derive(Serialize)]
+struct PublishResult {
+    path: String,
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct PublishByTagResponse {
+    tag: String,
+    published: usize,
+    failed: usize,
+    results: Vec<PublishResult>,
+}
+
+#[derive(Deserialize)]
+struct RawWriteParams {
+    path: String,
+    #[serde(default)]
+    create: Option<String>,
+}
+
+#[derive(Serialize)]
+struct FrontmatterPatchResult {
+    path: String,
+    etag: String,
+    #[serde(flatten)]
+    modified: timefmt::ModifiedTimes,
+    /// The frontmatter after the patch was applied.
+    frontmatter: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct RawWriteResult {
+    path: String,
+    etag: String,
+    #[serde(flatten)]
+    modified: timefmt::ModifiedTimes,
+}
+
+#[derive(Serialize)]
+struct FileContent {
+    content: String,
+    #[serde(flatten)]
+    modified: timefmt::ModifiedTimes,
+    /// As in [`FileInfo`].
+    content_hash: Option<String>,
+}
+
+pub(crate) fn generate_csrf_token(secret: &str) -> String {
+    let timestamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(err) => {
+            warn!(
+                "System time is before UNIX_EPOCH while generating CSRF token: {}",
+                err
+            );
+            0
+        }
+    };
+    let nonce: u64 = rand::rng().random();
+
+    let payload = format!("{timestamp}:{nonce}");
+    let signature = match HmacSha256::new_from_slice(secret.as_bytes()) {
+        Ok(mut mac) => {
+            mac.update(payload.as_bytes());
+            hex::encode(mac.finalize().into_bytes())
+        }
+        Err(err) => {
+            warn!(
+                "Failed to initialize HMAC for CSRF token generation: {}",
+                err
+            );
+            String::new()
+        }
+    };
+
+    format!("{payload}:{signature}")
+}
+
+pub(crate) fn validate_csrf_token(token: &str, secret: &str) -> Result<(), WebError> {
+    let parts: Vec<&str> = token.split(':').collect();
+
+    let [timestamp_str, nonce, provided_signature] = parts.as_slice() else {
+        return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
+    };
+
+    // Check if token is not too old
+    if let Ok(timestamp) = timestamp_str.parse::<u64>() {
+        let current_time = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs(),
+            Err(err) => {
+                warn!(
+                    "System time is before UNIX_EPOCH while validating CSRF token: {}",
+                    err
+                );
+                return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
+            }
+        };
+
+        if current_time.saturating_sub(timestamp) > CSRF_TOKEN_LIFETIME_SECONDS {
+            debug!(?timestamp, ?current_time, "CSRF token expired");
+            return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
+        }
+    } else {
+        return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
+    }
+
+    let Ok(signature_bytes) = hex::decode(provided_signature) else {
+        return Err(WebError::Forbidden("Invalid CSRF Token".to_string()));
+    };
 
     let payload = format!("{timestamp_str}:{nonce}");
     let mut mac = match HmacSha256::new_from_slice(secret.as_bytes()) {
@@ -254,6 +1283,164 @@ pub(crate) fn validate_csrf_token(token: &str, secret: &str) -> Result<(), WebEr
     }
 }
 
+fn random_csrf_secret() -> String {
+    hex::encode(rand::rng().random::<[u8; 32]>())
+}
+
+/// Whether `secret` is a 32-byte CSRF secret written as 64 hex characters.
+pub(crate) fn is_valid_csrf_secret(secret: &str) -> bool {
+    secret.len() == 64 && hex::decode(secret).is_ok()
+}
+
+/// The CSRF secret [`start_server`] should use: `secret` when given (from
+/// `MARKDOWN_WRANGLER_CSRF_SECRET`), else the one kept in `secret_file`,
+/// else a random one. A random per-boot secret invalidates open pages'
+/// tokens on restart; the other two keep them valid.
+pub async fn resolve_csrf_secret(
+    secret: Option<String>,
+    secret_file: Option<PathBuf>,
+) -> Result<String, ServerError> {
+    if let Some(secret) = secret {
+        return Ok(secret.trim().to_string());
+    }
+    match secret_file {
+        Some(path) => load_or_create_csrf_secret(&path)
+            .await
+            .map_err(|source| ServerError::CsrfSecret { path, source }),
+        None => Ok(random_csrf_secret()),
+    }
+}
+
+/// Reads the hex CSRF secret kept in `path` for `--csrf-secret-file`, or
+/// creates the file with a new secret, readable only by its owner, when it
+/// does not exist yet.
+async fn load_or_create_csrf_secret(path: &Path) -> std::io::Result<String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    match fs::read_to_string(path).await {
+        Ok(content) => {
+            let secret = content.trim();
+            if !is_valid_csrf_secret(secret) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "expected a 32-byte secret written as 64 hex characters",
+                ));
+            }
+            if let Ok(metadata) = fs::metadata(path).await
+                && metadata.permissions().mode() & 0o077 != 0
+            {
+                warn!(
+                    "CSRF secret file {} is readable by other users; consider chmod 600",
+                    path.display()
+                );
+            }
+            Ok(secret.to_string())
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            let secret = random_csrf_secret();
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)
+                .await?;
+            file.write_all(format!("{secret}\n").as_bytes()).await?;
+            file.sync_all().await?;
+            info!("Wrote a new CSRF secret to {}", path.display());
+            Ok(secret)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Seconds until a CSRF token expires, based on its embedded timestamp. This
+/// does not check the signature; it only tells the client when to refresh.
+fn csrf_token_remaining_seconds(token: &str) -> u64 {
+    let issued_at = token
+        .split(':')
+        .next()
+        .and_then(|timestamp| timestamp.parse::<u64>().ok())
+        .unwrap_or(0);
+    let now = unix_seconds(SystemTime::now());
+    CSRF_TOKEN_LIFETIME_SECONDS.saturating_sub(now.saturating_sub(issued_at))
+}
+
+#[derive(Serialize)]
+struct CsrfRefresh {
+    csrf_token: String,
+    expires_in: u64,
+}
+
+async fn refresh_csrf_token(State(state): State<AppState>) -> Json<CsrfRefresh> {
+    let csrf_token = generate_csrf_token(&state.csrf_secret);
+    Json(CsrfRefresh {
+        expires_in: csrf_token_remaining_seconds(&csrf_token),
+        csrf_token,
+    })
+}
+
+async fn routes_manifest() -> Json<routes::RouteManifest> {
+    Json(routes::manifest())
+}
+
+async fn get_client_config(State(state): State<AppState>) -> Json<client_config::ClientConfig> {
+    Json(client_config::ClientConfig::new(&state))
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<ServerStatus> {
+    Json(ServerStatus {
+        durability: state.durability.as_str(),
+        saves: state.save_latency.saves(),
+        average_save_ms: state.save_latency.average_ms(),
+    })
+}
+
+async fn ping(State(state): State<AppState>) -> Json<Ping> {
+    Json(Ping {
+        version: env!("CARGO_PKG_VERSION"),
+        uptime_secs: state.started_at.elapsed().as_secs(),
+    })
+}
+
+/// Turns maintenance mode on or off. Needs both the bearer token and a CSRF
+/// token, since it locks every user out of writing.
+async fn set_maintenance(
+    _auth: ApiToken,
+    State(state): State<AppState>,
+    Json(request): Json<MaintenanceRequest>,
+) -> Result<Json<MaintenanceStatus>, WebError> {
+    validate_csrf_token(&request.csrf_token, &state.csrf_secret)?;
+
+    let notice = request.enabled.then(|| maintenance::MaintenanceNotice {
+        message: request
+            .message
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty())
+            .unwrap_or_else(|| "Writes are paused for maintenance".to_string()),
+        expires_at: request
+            .expires_in_secs
+            .map(|seconds| timefmt::epoch_seconds(SystemTime::now()).saturating_add(seconds)),
+    });
+    state
+        .maintenance
+        .set(notice.clone())
+        .await
+        .map_err(|err| WebError::Internal(format!("Failed to save maintenance state: {err}")))?;
+    match &notice {
+        Some(notice) => warn!("Maintenance mode on: {}", notice.message),
+        None => info!("Maintenance mode off"),
+    }
+
+    Ok(Json(MaintenanceStatus {
+        enabled: notice.is_some(),
+        expires_at: notice
+            .as_ref()
+            .and_then(|notice| notice.expires_at)
+            .map(|seconds| timefmt::rfc3339(UNIX_EPOCH + Duration::from_secs(seconds))),
+        message: notice.map(|notice| notice.message),
+    }))
+}
+
 async fn list_directory(
     base_dir: &Path,
     relative_path: &str,
@@ -279,42 +1466,92 @@ async fn list_directory(
         );
         return Err(WebError::Unauthorized);
     }
+    if is_dot_path(&canonical_base, &canonical_full)
+        || ignore_marker::is_hidden(&canonical_base, &canonical_full)
+    {
+        return Err(WebError::NotFound("Not found".to_string()));
+    }
 
+    let preference = read_sort_preference(&canonical_full).await;
     let mut entries = fs::read_dir(&full_path).await?;
 
-    let mut directory_entries = Vec::new();
+    let mut keyed_entries = Vec::new();
 
     while let Some(entry) = entries.next_entry().await? {
         let file_name = entry.file_name().to_string_lossy().to_string();
 
-        // Skip hidden files
+        // Skip hidden files, which include the trash directory
         if file_name.starts_with('.') {
             continue;
         }
 
         let is_directory = entry.file_type().await?.is_dir();
+        if is_directory && ignore_marker::is_marked(&entry.path()).await {
+            continue;
+        }
+        // Always read: the listing shows each entry's modification time.
+        let metadata = entry.metadata().await?;
+        let (modified, size) = (metadata.modified().ok(), metadata.len());
+
+        let entry_path = join_relative_path(relative_path, &file_name);
+
+        keyed_entries.push((
+            sort::SortKey {
+                is_directory,
+                name: file_name.clone(),
+                modified,
+                size,
+            },
+            DirectoryEntry {
+                name: file_name,
+                is_directory,
+                path: entry_path,
+                modified,
+            },
+        ));
+    }
 
-        let entry_path = if relative_path.is_empty() {
-            file_name.clone()
-        } else {
-            format!("{relative_path}/{file_name}")
-        };
+    // Directories first, then files, each ordered by the preference
+    keyed_entries.sort_by(|(a, _), (b, _)| preference.compare(a, b));
 
-        directory_entries.push(DirectoryEntry {
-            name: file_name,
-            is_directory,
-            path: entry_path,
-        });
-    }
+    Ok(keyed_entries.into_iter().map(|(_, entry)| entry).collect())
+}
 
-    // Sort directories first, then files
-    directory_entries.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-        (true, false) => std::cmp::Ordering::Less,
-        (false, true) => std::cmp::Ordering::Greater,
-        _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-    });
+/// Loads `dir`'s `.wranglersort`, falling back to the default order when it
+/// is missing, too large or unreadable.
+async fn read_sort_preference(dir: &Path) -> sort::SortPreference {
+    let path = dir.join(sort::SORT_PREFERENCE_FILE);
+    let Ok(metadata) = fs::metadata(&path).await else {
+        return sort::SortPreference::default();
+    };
+    if !metadata.is_file() || metadata.len() > sort::MAX_SORT_PREFERENCE_BYTES {
+        warn!("Ignoring oversized or non-file {}", path.display());
+        return sort::SortPreference::default();
+    }
+    match fs::read_to_string(&path).await {
+        Ok(content) => {
+            let (preference, problems) = sort::SortPreference::parse(&content);
+            for problem in problems {
+                warn!("{}: {}", path.display(), problem);
+            }
+            preference
+        }
+        Err(err) => {
+            warn!("Failed to read {}: {}", path.display(), err);
+            sort::SortPreference::default()
+        }
+    }
+}
 
-    Ok(directory_entries)
+/// Whether canonical `path` has a dot-prefixed component below `base`. The
+/// trash, `.wrangler/` (redirects, snapshots, the maintenance flag) and other
+/// dotfiles are never listed, so they are not reachable by URL either.
+fn is_dot_path(base: &Path, path: &Path) -> bool {
+    path.strip_prefix(base).is_ok_and(|relative| {
+        relative
+            .components()
+            .any(|part| part.as_os_str().to_string_lossy().starts_with('.'))
+    })
 }
 
 fn validate_file_path(base_dir: &Path, relative_path: &str) -> Result<PathBuf, WebError> {
@@ -333,6 +1570,11 @@ fn validate_file_path(base_dir: &Path, relative_path: &str) -> Result<PathBuf, W
             "Path outside base directory".to_string(),
         ));
     }
+    if is_dot_path(&canonical_base, &canonical_full)
+        || ignore_marker::is_hidden(&canonical_base, &canonical_full)
+    {
+        return Err(WebError::NotFound("Not found".to_string()));
+    }
 
     if !canonical_full.is_file() {
         return Err(WebError::BadRequest("Path is not a file".to_string()));
@@ -360,6 +1602,11 @@ fn validate_directory_path(base_dir: &Path, relative_path: &str) -> Result<PathB
             "Path outside base directory".to_string(),
         ));
     }
+    if is_dot_path(&canonical_base, &canonical_full)
+        || ignore_marker::is_hidden(&canonical_base, &canonical_full)
+    {
+        return Err(WebError::NotFound("Not found".to_string()));
+    }
 
     if !canonical_full.is_dir() {
         return Err(WebError::BadRequest("Path is not a directory".to_string()));
@@ -368,41 +1615,6 @@ fn validate_directory_path(base_dir: &Path, relative_path: &str) -> Result<PathB
     Ok(canonical_full)
 }
 
-fn is_git_compatible_ascii_filename_stem(stem: &str) -> bool {
-    !stem.is_empty()
-        && stem.is_ascii()
-        && !stem.starts_with('.')
-        && !stem.ends_with('.')
-        && !stem.contains("..")
-        && stem
-            .chars()
-            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
-}
-
-fn normalize_markdown_filename(filename: &str) -> Result<String, WebError> {
-    let trimmed = filename.trim();
-    if trimmed.is_empty() {
-        return Err(WebError::BadRequest("Filename is required".to_string()));
-    }
-
-    let mut stem = trimmed.to_string();
-    let lower = stem.to_ascii_lowercase();
-    if lower.ends_with(".markdown") {
-        stem.truncate(stem.len().saturating_sub(".markdown".len()));
-    } else if lower.ends_with(".md") {
-        stem.truncate(stem.len().saturating_sub(".md".len()));
-    }
-    let stem = stem.trim();
-
-    if !is_git_compatible_ascii_filename_stem(stem) {
-        return Err(WebError::BadRequest(
-            "Filename must use only ASCII letters, numbers, '-', '_', or '.'".to_string(),
-        ));
-    }
-
-    Ok(format!("{stem}.md"))
-}
-
 fn normalize_image_filename(filename: &str) -> Result<String, WebError> {
     let trimmed = filename.trim();
     if trimmed.is_empty() {
@@ -452,6 +1664,11 @@ fn is_markdown_file(path: &str) -> bool {
     path.to_lowercase().ends_with(".md") || path.to_lowercase().ends_with(".markdown")
 }
 
+/// Files the editor can open: markdown, plus plain-text document formats.
+fn is_editable_file(path: &str) -> bool {
+    is_markdown_file(path) || is_text_document_file(path)
+}
+
 fn validate_image_bytes(file_name: &str, bytes: &[u8]) -> Result<(), WebError> {
     if bytes.is_empty() {
         return Err(WebError::BadRequest(
@@ -478,10 +1695,12 @@ fn validate_image_bytes(file_name: &str, bytes: &[u8]) -> Result<(), WebError> {
         .map_err(|_| WebError::BadRequest("Uploaded file is not a valid image".to_string()))
 }
 
-#[derive(Clone, Copy)]
-enum FrontmatterFormat {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrontmatterFormat {
     Yaml,
     Json,
+    /// Hugo's `+++`-fenced TOML.
+    Toml,
 }
 
 type ParsedFrontmatter = (
@@ -493,31 +1712,108 @@ type ParsedFrontmatter = (
     HashMap<String, serde_json::Value>,
 );
 
-fn extract_yaml_frontmatter(content: &str) -> Option<&str> {
-    let start = if content.starts_with("---\n") {
-        4
-    } else if content.starts_with("---\r\n") {
-        5
+/// Extra bytes [`read_frontmatter_prefix`] reads past the frontmatter limit,
+/// so a closing fence that starts just inside the limit is read whole.
+const FRONTMATTER_PREFIX_SLACK: usize = 1024;
+
+/// Prefix scans give up on a YAML block after this many lines.
+const MAX_FRONTMATTER_LINES: usize = 2000;
+
+/// Deepest nesting a frontmatter block may have before it is treated as
+/// unusable instead of being handed to the recursive YAML/JSON parsers.
+const MAX_FRONTMATTER_DEPTH: usize = 32;
+
+/// Outcome of looking for frontmatter in what may be only a file prefix.
+#[derive(Debug, PartialEq)]
+enum FrontmatterProbe<T> {
+    Found(T),
+    /// No frontmatter, or a block over the byte/line limits.
+    Absent,
+    /// The prefix ended before the block could be closed or ruled out.
+    Truncated,
+}
+
+impl<T> FrontmatterProbe<T> {
+    fn found(self) -> Option<T> {
+        match self {
+            FrontmatterProbe::Found(value) => Some(value),
+            FrontmatterProbe::Absent | FrontmatterProbe::Truncated => None,
+        }
+    }
+}
+
+const YAML_FENCE: &str = "---";
+const TOML_FENCE: &str = "+++";
+
+/// Length of the opening fence line of a YAML or TOML block, newline
+/// included.
+fn opening_fence_len(content: &str) -> usize {
+    if content
+        .get(YAML_FENCE.len()..)
+        .is_some_and(|rest| rest.starts_with("\r\n"))
+    {
+        YAML_FENCE.len() + 2
     } else {
-        return None;
+        YAML_FENCE.len() + 1
+    }
+}
+
+/// Scans for a block between two `fence` lines. With `truncated` set,
+/// `content` is a prefix and its unfinished last line can neither close nor
+/// rule out the block.
+fn scan_fenced_frontmatter<'a>(
+    content: &'a str,
+    fence: &str,
+    max_bytes: usize,
+    max_lines: usize,
+    truncated: bool,
+) -> FrontmatterProbe<&'a str> {
+    let Some(after_fence) = content.strip_prefix(fence) else {
+        return FrontmatterProbe::Absent;
     };
+    if !after_fence.starts_with('\n') && !after_fence.starts_with("\r\n") {
+        return FrontmatterProbe::Absent;
+    }
+    let start = opening_fence_len(content);
 
     let rest = &content[start..];
     let mut offset = start;
-    for line in rest.split('\n') {
-        if line.trim_end_matches('\r').trim() == "---" {
-            return Some(&content[start..offset]);
+    let mut lines = rest.split('\n').enumerate().peekable();
+    while let Some((line_number, line)) = lines.next() {
+        if offset > max_bytes || line_number >= max_lines {
+            return FrontmatterProbe::Absent;
+        }
+
+        if truncated && lines.peek().is_none() {
+            return FrontmatterProbe::Truncated;
+        }
+
+        if line.trim_end_matches('\r').trim() == fence {
+            return FrontmatterProbe::Found(&content[start..offset]);
         }
 
         offset = std::cmp::min(content.len(), offset + line.len() + 1);
     }
 
-    None
+    FrontmatterProbe::Absent
+}
+
+fn extract_yaml_frontmatter(content: &str, max_bytes: usize) -> Option<&str> {
+    scan_fenced_frontmatter(content, YAML_FENCE, max_bytes, usize::MAX, false).found()
+}
+
+fn extract_toml_frontmatter(content: &str, max_bytes: usize) -> Option<&str> {
+    scan_fenced_frontmatter(content, TOML_FENCE, max_bytes, usize::MAX, false).found()
 }
 
-fn extract_json_frontmatter(content: &str) -> Option<&str> {
+/// Scans for a JSON block; see [`scan_fenced_frontmatter`] for `truncated`.
+fn scan_json_frontmatter(
+    content: &str,
+    max_bytes: usize,
+    truncated: bool,
+) -> FrontmatterProbe<&str> {
     if !content.starts_with('{') {
-        return None;
+        return FrontmatterProbe::Absent;
     }
 
     let mut depth = 0usize;
@@ -525,6 +1821,10 @@ fn extract_json_frontmatter(content: &str) -> Option<&str> {
     let mut escape_next = false;
 
     for (idx, ch) in content.char_indices() {
+        if idx >= max_bytes {
+            return FrontmatterProbe::Absent;
+        }
+
         if in_string {
             if escape_next {
                 escape_next = false;
@@ -541,45 +1841,152 @@ fn extract_json_frontmatter(content: &str) -> Option<&str> {
             '{' => depth = depth.saturating_add(1),
             '}' => {
                 if depth == 0 {
-                    return None;
+                    return FrontmatterProbe::Absent;
                 }
 
                 depth -= 1;
                 if depth == 0 {
                     let object_end = idx + ch.len_utf8();
                     let remainder = &content[object_end..];
+                    let after_ws = remainder.trim_start_matches([' ', '\t', '\r']);
 
-                    if remainder.is_empty() {
-                        return Some(&content[..object_end]);
+                    if truncated && after_ws.is_empty() {
+                        return FrontmatterProbe::Truncated;
                     }
 
-                    let after_ws = remainder.trim_start_matches([' ', '\t', '\r']);
-                    if after_ws.starts_with('\n') {
-                        return Some(&content[..object_end]);
+                    if remainder.is_empty() || after_ws.starts_with('\n') {
+                        return FrontmatterProbe::Found(&content[..object_end]);
                     }
 
-                    return None;
+                    return FrontmatterProbe::Absent;
                 }
             }
             _ => {}
         }
     }
 
-    None
+    if truncated {
+        FrontmatterProbe::Truncated
+    } else {
+        FrontmatterProbe::Absent
+    }
+}
+
+fn extract_json_frontmatter(content: &str, max_bytes: usize) -> Option<&str> {
+    scan_json_frontmatter(content, max_bytes, false).found()
 }
 
-fn extract_frontmatter(content: &str) -> Option<(FrontmatterFormat, &str)> {
-    if let Some(frontmatter) = extract_yaml_frontmatter(content) {
+/// Finds a leading YAML, TOML or JSON frontmatter block, scanning at most
+/// `max_bytes` of `content` so oversized blocks are treated as absent.
+fn extract_frontmatter(content: &str, max_bytes: usize) -> Option<(FrontmatterFormat, &str)> {
+    if let Some(frontmatter) = extract_yaml_frontmatter(content, max_bytes) {
         return Some((FrontmatterFormat::Yaml, frontmatter));
     }
 
-    if let Some(frontmatter) = extract_json_frontmatter(content) {
+    if let Some(frontmatter) = extract_toml_frontmatter(content, max_bytes) {
+        return Some((FrontmatterFormat::Toml, frontmatter));
+    }
+
+    if let Some(frontmatter) = extract_json_frontmatter(content, max_bytes) {
         return Some((FrontmatterFormat::Json, frontmatter));
     }
 
     None
 }
 
+/// Which frontmatter format `content` uses, if any. Code that rewrites
+/// frontmatter should write back in this format rather than converting.
+/// Unlike [`extract_frontmatter`] there is no size limit, since the whole
+/// document is already in memory.
+pub fn detect_frontmatter_format(content: &str) -> Option<FrontmatterFormat> {
+    extract_frontmatter(content, content.len()).map(|(format, _)| format)
+}
+
+/// [`extract_frontmatter`] for a prefix read by [`read_frontmatter_prefix`],
+/// also giving up after [`MAX_FRONTMATTER_LINES`] lines.
+fn probe_frontmatter(
+    prefix: &str,
+    max_bytes: usize,
+    truncated: bool,
+) -> FrontmatterProbe<(FrontmatterFormat, &str)> {
+    let scans = [
+        (
+            FrontmatterFormat::Yaml,
+            scan_fenced_frontmatter(
+                prefix,
+                YAML_FENCE,
+                max_bytes,
+                MAX_FRONTMATTER_LINES,
+                truncated,
+            ),
+        ),
+        (
+            FrontmatterFormat::Toml,
+            scan_fenced_frontmatter(
+                prefix,
+                TOML_FENCE,
+                max_bytes,
+                MAX_FRONTMATTER_LINES,
+                truncated,
+            ),
+        ),
+        (
+            FrontmatterFormat::Json,
+            scan_json_frontmatter(prefix, max_bytes, truncated),
+        ),
+    ];
+    for (format, probe) in scans {
+        match probe {
+            FrontmatterProbe::Found(block) => return FrontmatterProbe::Found((format, block)),
+            FrontmatterProbe::Truncated => return FrontmatterProbe::Truncated,
+            FrontmatterProbe::Absent => {}
+        }
+    }
+    FrontmatterProbe::Absent
+}
+
+/// Reads just enough of `path` to find frontmatter of up to `max_bytes`.
+/// The flag is true when the file continues past the returned text; an
+/// incomplete UTF-8 sequence at the cut is dropped.
+async fn read_frontmatter_prefix(
+    path: &Path,
+    max_bytes: usize,
+) -> Result<(String, bool), std::io::Error> {
+    use tokio::io::AsyncReadExt;
+
+    let limit = max_bytes.saturating_add(FRONTMATTER_PREFIX_SLACK);
+    let mut bytes = Vec::new();
+    fs::File::open(path)
+        .await?
+        .take(u64::try_from(limit).unwrap_or(u64::MAX).saturating_add(1))
+        .read_to_end(&mut bytes)
+        .await?;
+    let truncated = bytes.len() > limit;
+    bytes.truncate(limit);
+    if truncated
+        && let Err(err) = std::str::from_utf8(&bytes)
+        && err.error_len().is_none()
+    {
+        bytes.truncate(err.valid_up_to());
+    }
+    let text = String::from_utf8(bytes)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    Ok((text, truncated))
+}
+
+/// Byte length of the leading frontmatter block, including the line that
+/// closes it.
+fn frontmatter_len(content: &str, max_bytes: usize) -> Option<usize> {
+    let block_end = match extract_frontmatter(content, max_bytes)? {
+        (FrontmatterFormat::Yaml | FrontmatterFormat::Toml, block) => {
+            opening_fence_len(content) + block.len()
+        }
+        (FrontmatterFormat::Json, block) => block.len(),
+    };
+    let rest = content.get(block_end..)?;
+    Some(block_end + rest.find('\n').map_or(rest.len(), |newline| newline + 1))
+}
+
 fn parse_bool_value(value: &serde_json::Value) -> Option<bool> {
     if let Some(boolean) = value.as_bool() {
         return Some(boolean);
@@ -629,38 +2036,127 @@ fn parse_string_value(value: &serde_json::Value) -> Option<String> {
     maybe_string.filter(|text| !text.is_empty())
 }
 
-fn parse_string_list_value(value: &serde_json::Value) -> Vec<String> {
-    if let Some(items) = value.as_array() {
-        return items.iter().filter_map(parse_string_value).collect();
-    }
+/// A `tags`/`categories` value as a list: an array, or a comma-separated
+/// string. Items past `max_items` are dropped without being copied, so a
+/// pathological list costs no more than a short one.
+fn parse_string_list_value(value: &serde_json::Value, max_items: usize) -> Vec<String> {
+    let mut items: Vec<String> = if let Some(items) = value.as_array() {
+        items
+            .iter()
+            .filter_map(parse_string_value)
+            .take(max_items)
+            .collect()
+    } else if let Some(text) = value.as_str() {
+        text.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .take(max_items)
+            .map(ToString::to_string)
+            .collect()
+    } else {
+        parse_string_value(value).into_iter().collect()
+    };
+    items.truncate(max_items);
+    items
+}
 
-    if let Some(text) = value.as_str() {
-        if text.contains(',') {
-            return text
-                .split(',')
-                .map(str::trim)
-                .filter(|item| !item.is_empty())
-                .map(ToString::to_string)
-                .collect();
+/// `max_list_items` for callers that only want the scalar fields; `tags`
+/// and `categories` come back empty.
+const SCALARS_ONLY: usize = 0;
+
+fn parse_frontmatter(
+    content: &str,
+    max_bytes: usize,
+    max_list_items: usize,
+) -> Option<ParsedFrontmatter> {
+    let (format, frontmatter) = extract_frontmatter(content, max_bytes)?;
+    parse_frontmatter_block(format, frontmatter, max_list_items)
+}
+
+/// [`parse_frontmatter`] over a bounded prefix (see [`probe_frontmatter`]).
+/// A block that is found but does not parse counts as absent.
+fn parse_frontmatter_prefix(
+    prefix: &str,
+    max_bytes: usize,
+    max_list_items: usize,
+    truncated: bool,
+) -> FrontmatterProbe<ParsedFrontmatter> {
+    match probe_frontmatter(prefix, max_bytes, truncated) {
+        FrontmatterProbe::Found((format, frontmatter)) => {
+            parse_frontmatter_block(format, frontmatter, max_list_items)
+                .map_or(FrontmatterProbe::Absent, FrontmatterProbe::Found)
         }
+        FrontmatterProbe::Absent => FrontmatterProbe::Absent,
+        FrontmatterProbe::Truncated => FrontmatterProbe::Truncated,
+    }
+}
 
-        let trimmed = text.trim();
-        if !trimmed.is_empty() {
-            return vec![trimmed.to_string()];
+/// Cheap upper bound on how deeply `block` nests, counting indentation
+/// levels, `- ` sequence markers and unclosed `[`/`{` outside quotes. Stops
+/// counting once the depth passes [`MAX_FRONTMATTER_DEPTH`].
+fn frontmatter_too_deep(block: &str) -> bool {
+    let mut indents: Vec<usize> = Vec::new();
+    let mut flow_depth = 0usize;
+    for line in block.lines() {
+        let trimmed = line.trim_start_matches(' ');
+        if trimmed.trim().is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if flow_depth == 0 {
+            let indent = line.len() - trimmed.len();
+            while indents.last().is_some_and(|&last| last >= indent) {
+                indents.pop();
+            }
+            indents.push(indent);
+        }
+        let mut rest = trimmed;
+        let mut sequence_depth = 0;
+        while let Some(item) = rest.strip_prefix("- ") {
+            sequence_depth += 1;
+            rest = item.trim_start_matches(' ');
+        }
+        if indents.len() + sequence_depth + flow_depth > MAX_FRONTMATTER_DEPTH {
+            return true;
         }
 
-        return Vec::new();
+        let mut quote = None;
+        let mut chars = rest.chars();
+        while let Some(ch) = chars.next() {
+            match quote {
+                Some('"') if ch == '\\' => {
+                    chars.next();
+                }
+                Some(open) if ch == open => quote = None,
+                Some(_) => {}
+                None => match ch {
+                    '"' | '\'' => quote = Some(ch),
+                    '[' | '{' => {
+                        flow_depth += 1;
+                        if indents.len() + sequence_depth + flow_depth > MAX_FRONTMATTER_DEPTH {
+                            return true;
+                        }
+                    }
+                    ']' | '}' => flow_depth = flow_depth.saturating_sub(1),
+                    _ => {}
+                },
+            }
+        }
     }
-
-    parse_string_value(value).map_or_else(Vec::new, |item| vec![item])
+    false
 }
 
-fn parse_frontmatter(content: &str) -> Option<ParsedFrontmatter> {
-    let (format, frontmatter) = extract_frontmatter(content)?;
-
+fn parse_frontmatter_block(
+    format: FrontmatterFormat,
+    frontmatter: &str,
+    max_list_items: usize,
+) -> Option<ParsedFrontmatter> {
+    if frontmatter_too_deep(frontmatter) {
+        return None;
+    }
     let parsed_value = match format {
         FrontmatterFormat::Yaml => serde_yaml::from_str::<serde_json::Value>(frontmatter).ok()?,
         FrontmatterFormat::Json => serde_json::from_str::<serde_json::Value>(frontmatter).ok()?,
+        FrontmatterFormat::Toml => toml_to_json(toml::from_str(frontmatter).ok()?),
     };
 
     let serde_json::Value::Object(mut object) = parsed_value else {
@@ -673,11 +2169,15 @@ fn parse_frontmatter(content: &str) -> Option<ParsedFrontmatter> {
     let tags = object
         .remove("tags")
         .as_ref()
-        .map_or_else(Vec::new, parse_string_list_value);
+        .map_or_else(Vec::new, |value| {
+            parse_string_list_value(value, max_list_items)
+        });
     let categories = object
         .remove("categories")
         .as_ref()
-        .map_or_else(Vec::new, parse_string_list_value);
+        .map_or_else(Vec::new, |value| {
+            parse_string_list_value(value, max_list_items)
+        });
     let extra = object
         .into_iter()
         .collect::<HashMap<String, serde_json::Value>>();
@@ -685,8 +2185,92 @@ fn parse_frontmatter(content: &str) -> Option<ParsedFrontmatter> {
     Some((draft, title, date, tags, categories, extra))
 }
 
-fn has_draft_frontmatter(content: &str) -> bool {
-    if let Some((draft, _, _, _, _, _)) = parse_frontmatter(content) {
+/// A TOML value as JSON. Dates and times become their TOML text, as they
+/// would be quoted strings in YAML.
+fn toml_to_json(value: toml::Value) -> serde_json::Value {
+    match value {
+        toml::Value::String(text) => serde_json::Value::String(text),
+        toml::Value::Integer(number) => serde_json::Value::from(number),
+        toml::Value::Float(number) => serde_json::Value::from(number),
+        toml::Value::Boolean(boolean) => serde_json::Value::Bool(boolean),
+        toml::Value::Datetime(datetime) => serde_json::Value::String(datetime.to_string()),
+        toml::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(toml_to_json).collect())
+        }
+        toml::Value::Table(table) => serde_json::Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Why a frontmatter block failed to parse, with a position in the file.
+#[derive(Debug, PartialEq)]
+struct FrontmatterSyntaxError {
+    message: String,
+    /// One-based line and column in the whole file, when known.
+    location: Option<(usize, usize)>,
+}
+
+impl FrontmatterSyntaxError {
+    fn location_text(&self) -> String {
+        self.location
+            .map(|(line, column)| format!("line {line}, column {column}"))
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the parse error when `content` has a frontmatter block that is
+/// not valid YAML or JSON; `None` when it parses or there is no block.
+fn frontmatter_syntax_error(content: &str, max_bytes: usize) -> Option<FrontmatterSyntaxError> {
+    let (format, frontmatter) = extract_frontmatter(content, max_bytes)?;
+    if frontmatter_too_deep(frontmatter) {
+        return Some(FrontmatterSyntaxError {
+            message: format!("Frontmatter is nested more than {MAX_FRONTMATTER_DEPTH} levels deep"),
+            location: None,
+        });
+    }
+    match (format, frontmatter) {
+        (FrontmatterFormat::Yaml, frontmatter) => {
+            let err = serde_yaml::from_str::<serde_yaml::Value>(frontmatter).err()?;
+            Some(FrontmatterSyntaxError {
+                message: err.to_string(),
+                // The block starts after the opening `---` line.
+                location: err
+                    .location()
+                    .map(|location| (location.line() + 1, location.column())),
+            })
+        }
+        (FrontmatterFormat::Json, frontmatter) => {
+            let err = serde_json::from_str::<serde_json::Value>(frontmatter).err()?;
+            Some(FrontmatterSyntaxError {
+                message: err.to_string(),
+                location: Some((err.line(), err.column())),
+            })
+        }
+        (FrontmatterFormat::Toml, frontmatter) => {
+            let err = toml::from_str::<toml::Table>(frontmatter).err()?;
+            let location = err.span().and_then(|span| {
+                let before = frontmatter.get(..span.start)?;
+                let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+                // The block starts after the opening `+++` line.
+                Some((
+                    before.matches('\n').count() + 2,
+                    before.get(line_start..)?.chars().count() + 1,
+                ))
+            });
+            Some(FrontmatterSyntaxError {
+                message: err.message().to_string(),
+                location,
+            })
+        }
+    }
+}
+
+fn has_draft_frontmatter(content: &str, max_bytes: usize) -> bool {
+    if let Some((draft, _, _, _, _, _)) = parse_frontmatter(content, max_bytes, SCALARS_ONLY) {
         return draft.unwrap_or(false);
     }
 
@@ -716,31 +2300,20 @@ fn format_file_size(size_bytes: u64) -> String {
     }
 }
 
-fn get_parent_directory_path(file_path: &str) -> String {
-    if let Some(parent) = std::path::Path::new(file_path).parent() {
-        let parent_str = parent.to_string_lossy();
-        if parent_str.is_empty() || parent_str == "." {
-            "/".to_string()
-        } else {
-            format!("/?path={}", urlencoding::encode(&parent_str))
-        }
-    } else {
-        "/".to_string()
-    }
-}
-
-async fn get_file_modification_time(file_path: &Path) -> Result<String, WebError> {
+async fn get_file_modified(file_path: &Path) -> Result<SystemTime, WebError> {
     fs::metadata(file_path)
         .await
         .and_then(|metadata| metadata.modified())
-        .map(|time| {
-            time.duration_since(SystemTime::UNIX_EPOCH)
-                .map(|duration| duration.as_secs().to_string())
-                .unwrap_or_else(|_| "0".to_string())
-        })
         .map_err(|e| WebError::Internal(format!("Failed to get file modification time: {e}")))
 }
 
+/// Epoch seconds as a string, the form `expected_mtime` round-trips in.
+async fn get_file_modification_time(file_path: &Path) -> Result<String, WebError> {
+    get_file_modified(file_path)
+        .await
+        .map(|time| timefmt::epoch_seconds(time).to_string())
+}
+
 fn get_file_type_description(file_path: &str) -> &'static str {
     let lower_path = file_path.to_lowercase();
 
@@ -789,43 +2362,59 @@ fn build_breadcrumbs(current_path: &str) -> Vec<Breadcrumb> {
         path_so_far.push_str(part);
         breadcrumbs.push(Breadcrumb {
             name: part.to_string(),
-            url: format!("/?path={}", urlencoding::encode(&path_so_far)),
+            url: route_url("/", &path_so_far),
         });
     }
 
     breadcrumbs
 }
 
-fn build_directory_entry_views(entries: &[DirectoryEntry]) -> Vec<DirectoryEntryView> {
+fn build_directory_entry_views(
+    entries: &[DirectoryEntry],
+    snippets: &HashMap<String, String>,
+    offset: chrono::FixedOffset,
+) -> Vec<DirectoryEntryView> {
     entries
         .iter()
         .map(|entry| {
+            let modified = entry
+                .modified
+                .map(|time| timefmt::HumanTime::new(time, offset));
             if entry.is_directory {
                 DirectoryEntryView {
                     icon: "📁",
                     class_name: "directory",
                     name: entry.name.clone(),
-                    url: format!("/?path={}", urlencoding::encode(&entry.path)),
+                    url: route_url("/", &entry.path),
                     has_url: true,
                     executable: false,
+                    modified,
+                    snippet: None,
+                    delete_path: Some(entry.path.clone()),
                 }
-            } else if is_markdown_file(&entry.name) {
+            } else if is_editable_file(&entry.name) {
                 DirectoryEntryView {
                     icon: "📄",
                     class_name: "file",
                     name: entry.name.clone(),
-                    url: format!("/edit?path={}", urlencoding::encode(&entry.path)),
+                    url: route_url("/edit", &entry.path),
                     has_url: true,
                     executable: false,
+                    modified,
+                    snippet: snippets.get(&entry.path).cloned(),
+                    delete_path: None,
                 }
             } else if is_image_file(&entry.name) {
                 DirectoryEntryView {
                     icon: "🖼️",
                     class_name: "file",
                     name: entry.name.clone(),
-                    url: format!("/preview?path={}", urlencoding::encode(&entry.path)),
+                    url: route_url("/preview", &entry.path),
                     has_url: true,
                     executable: false,
+                    modified,
+                    snippet: None,
+                    delete_path: None,
                 }
             } else if is_executable_file(&entry.name) {
                 DirectoryEntryView {
@@ -835,2365 +2424,13036 @@ fn build_directory_entry_views(entries: &[DirectoryEntry]) -> Vec<DirectoryEntry
                     url: String::new(),
                     has_url: false,
                     executable: true,
+                    modified,
+                    snippet: None,
+                    delete_path: None,
                 }
             } else {
                 DirectoryEntryView {
                     icon: "📄",
                     class_name: "file",
                     name: entry.name.clone(),
-                    url: format!("/file-preview?path={}", urlencoding::encode(&entry.path)),
+                    url: route_url("/file-preview", &entry.path),
                     has_url: true,
                     executable: false,
+                    modified,
+                    snippet: None,
+                    delete_path: None,
                 }
             }
         })
         .collect()
 }
 
+/// The page a file is opened with from the directory listing, or `None` for
+/// files that are never linked (executables).
+fn file_view_url(path: &str) -> Option<String> {
+    if is_editable_file(path) {
+        Some(route_url("/edit", path))
+    } else if is_image_file(path) {
+        Some(route_url("/preview", path))
+    } else if is_executable_file(path) {
+        None
+    } else {
+        Some(route_url("/file-preview", path))
+    }
+}
+
+/// Directory notes shown above a listing, in order of preference.
+const README_NAMES: &[&str] = &["readme.md", "_index.md"];
+
+/// Notes longer than this many lines start collapsed.
+const README_COLLAPSE_LINES: usize = 30;
+
+/// Larger notes are not rendered into the listing.
+const MAX_README_BYTES: u64 = 256 * 1024;
+
+/// Renders the directory's README or `_index.md`, if it has one. Any failure
+/// only drops the block; the listing itself is never affected.
+async fn load_directory_readme(state: &AppState, entries: &[DirectoryEntry]) -> Option<ReadmeView> {
+    let entry = README_NAMES.iter().find_map(|name| {
+        entries
+            .iter()
+            .find(|entry| !entry.is_directory && entry.name.eq_ignore_ascii_case(name))
+    })?;
+
+    let full_path = state.target_dir.join(&entry.path);
+    let result: std::io::Result<(String, bool)> = async {
+        let metadata = fs::metadata(&full_path).await?;
+        if metadata.len() > MAX_README_BYTES {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("larger than {}", format_file_size(MAX_README_BYTES)),
+            ));
+        }
+        let modified = metadata.modified()?;
+        let content = fs::read_to_string(&full_path).await?;
+        let body = content
+            .get(frontmatter_len(&content, state.max_frontmatter_bytes).unwrap_or(0)..)
+            .unwrap_or_default();
+        let collapsed = body.lines().count() > README_COLLAPSE_LINES;
+        let html = match state.readme_cache.get(&entry.path, modified) {
+            Some(html) => html,
+            None => {
+                let html = markdown::render_html(
+                    body,
+                    &entry.path,
+                    state.markdown_extensions,
+                    state.emoji_shortcodes,
+                );
+                state
+                    .readme_cache
+                    .insert(&entry.path, modified, html.clone());
+                html
+            }
+        };
+        Ok((html, collapsed))
+    }
+    .await;
+
+    match result {
+        Ok((html, collapsed)) => Some(ReadmeView {
+            path: entry.path.clone(),
+            edit_url: route_url("/edit", &entry.path),
+            html,
+            collapsed,
+        }),
+        Err(err) => {
+            warn!("Not rendering {} above the listing: {}", entry.path, err);
+            None
+        }
+    }
+}
+
+/// Characters of body text shown under a markdown file in the listing.
+const SNIPPET_CHARS: usize = 160;
+
+/// The listing snippet for a markdown file from the start of its content,
+/// after any frontmatter. `None` when the prefix ends inside the
+/// frontmatter or there is no text.
+fn markdown_snippet(prefix: &str, max_frontmatter_bytes: usize) -> Option<String> {
+    let body = match frontmatter_len(prefix, max_frontmatter_bytes) {
+        Some(len) => prefix.get(len..)?,
+        None if prefix.starts_with(YAML_FENCE)
+            || prefix.starts_with(TOML_FENCE)
+            || prefix.starts_with('{') =>
+        {
+            return None;
+        }
+        None => prefix,
+    };
+    let snippet = markdown::plain_text_snippet(body, SNIPPET_CHARS);
+    (!snippet.is_empty()).then_some(snippet)
+}
+
+/// Snippets for the markdown files in a listing, keyed by path. Only a
+/// bounded prefix of each file is read.
+async fn load_entry_snippets(
+    base_dir: &Path,
+    entries: &[DirectoryEntry],
+    max_frontmatter_bytes: usize,
+) -> HashMap<String, String> {
+    let mut snippets = HashMap::new();
+    for entry in entries
+        .iter()
+        .filter(|entry| !entry.is_directory && is_markdown_file(&entry.name))
+    {
+        match read_frontmatter_prefix(&base_dir.join(&entry.path), max_frontmatter_bytes).await {
+            Ok((prefix, _)) => {
+                if let Some(snippet) = markdown_snippet(&prefix, max_frontmatter_bytes) {
+                    snippets.insert(entry.path.clone(), snippet);
+                }
+            }
+            Err(err) => warn!("Failed to read {} for its snippet: {}", entry.path, err),
+        }
+    }
+    snippets
+}
+
+/// Counts the immediate markdown children of a directory listing whose
+/// frontmatter marks them as drafts.
+async fn count_draft_entries(
+    base_dir: &Path,
+    entries: &[DirectoryEntry],
+    max_frontmatter_bytes: usize,
+) -> usize {
+    let mut draft_count = 0;
+    for entry in entries
+        .iter()
+        .filter(|entry| !entry.is_directory && is_markdown_file(&entry.name))
+    {
+        match read_frontmatter_prefix(&base_dir.join(&entry.path), max_frontmatter_bytes).await {
+            Ok((prefix, truncated)) => {
+                match parse_frontmatter_prefix(
+                    &prefix,
+                    max_frontmatter_bytes,
+                    SCALARS_ONLY,
+                    truncated,
+                ) {
+                    FrontmatterProbe::Found((Some(true), ..)) => draft_count += 1,
+                    FrontmatterProbe::Found(_) | FrontmatterProbe::Absent => {}
+                    FrontmatterProbe::Truncated => debug!(
+                        "Frontmatter of {} runs past the scan limit; draft state unknown",
+                        entry.path
+                    ),
+                }
+            }
+            Err(err) => warn!("Failed to read {} for draft count: {}", entry.path, err),
+        }
+    }
+    draft_count
+}
+
 async fn index(
+    ThemePreference(theme): ThemePreference,
     Query(params): Query<HashMap<String, String>>,
     State(state): State<AppState>,
-) -> Result<DirectoryTemplate, WebError> {
-    let path = params.get("path").map(|s| s.as_str()).unwrap_or("");
+    headers: HeaderMap,
+) -> Result<Response, WebError> {
+    if state.remember_path
+        && !params.contains_key("path")
+        && let Some(remembered) = remembered_directory(&headers, &state.target_dir)
+    {
+        return Ok(Redirect::to(&route_url("/", &remembered)).into_response());
+    }
+
+    let path = sanitize_relative_path(params.get("path").map(|s| s.as_str()).unwrap_or(""))?;
+    let path = path.as_ref();
+
+    // A file path (e.g. a hand-edited URL) goes to the page that opens it.
+    if !path.is_empty() && validate_file_path(&state.target_dir, path).is_ok() {
+        return match file_view_url(path) {
+            Some(url) => Ok(Redirect::to(&url).into_response()),
+            None => Err(WebError::BadRequest(format!(
+                "{path} is a file, not a directory"
+            ))),
+        };
+    }
 
     let entries = list_directory(&state.target_dir, path).await?;
-    let parent_url = if let Some(pos) = path.rfind('/') {
-        let parent_path = &path[..pos];
-        if parent_path.is_empty() {
-            "/".to_string()
-        } else {
-            format!("/?path={}", urlencoding::encode(parent_path))
-        }
+    // With a remembered path, a bare `/` would bounce straight back, so root
+    // links say `?path=` explicitly.
+    let root_url = app_url(if state.remember_path { "/?path=" } else { "/" });
+    let parent_url = match get_parent_directory_path(path) {
+        parent if parent == "/" => root_url.clone(),
+        parent => app_url(&parent),
+    };
+    let draft_count =
+        count_draft_entries(&state.target_dir, &entries, state.max_frontmatter_bytes).await;
+    let snippets = if state.show_snippets {
+        load_entry_snippets(&state.target_dir, &entries, state.max_frontmatter_bytes).await
     } else {
-        "/".to_string()
+        HashMap::new()
     };
 
-    Ok(DirectoryTemplate {
+    let template = DirectoryTemplate {
+        theme,
+        assets: state.assets.clone(),
+        client_config: client_config::ClientConfig::new(&state),
         at_root: path.is_empty(),
+        root_url: root_url.clone(),
         breadcrumbs: build_breadcrumbs(path),
         has_parent: !path.is_empty(),
         parent_url,
-        new_file_url: if path.is_empty() {
-            "/new-file".to_string()
-        } else {
-            format!("/new-file?path={}", urlencoding::encode(path))
-        },
-        upload_image_url: if path.is_empty() {
-            "/upload-image".to_string()
+        new_file_url: route_url("/new-file", path),
+        upload_image_url: route_url("/upload-image", path),
+        delete_dir_url: route_url("/delete-dir", path),
+        path_value: path.to_string(),
+        readme: load_directory_readme(&state, &entries).await,
+        entries: build_directory_entry_views(&entries, &snippets, state.display_offset),
+        draft_count,
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        current_url: if path.is_empty() {
+            root_url
         } else {
-            format!("/upload-image?path={}", urlencoding::encode(path))
+            route_url("/", path)
         },
-        entries: build_directory_entry_views(&entries),
-    })
+    };
+    if state.remember_path {
+        Ok(([(header::SET_COOKIE, last_path_cookie(path)?)], template).into_response())
+    } else {
+        Ok(template.into_response())
+    }
 }
 
-async fn new_file_form(
-    Query(params): Query<HashMap<String, String>>,
-    State(state): State<AppState>,
-) -> Result<NewFileTemplate, WebError> {
-    let path = params.get("path").map(|s| s.as_str()).unwrap_or("");
-    validate_directory_path(&state.target_dir, path)?;
-
-    Ok(NewFileTemplate {
-        current_path_display: if path.is_empty() {
-            "/".to_string()
-        } else {
-            format!("/{}", path)
-        },
-        path_value: path.to_string(),
-        back_url: if path.is_empty() {
-            "/".to_string()
-        } else {
-            format!("/?path={}", urlencoding::encode(path))
-        },
-        csrf_token: generate_csrf_token(&state.csrf_secret),
-    })
+/// Only allow redirects back to local, absolute paths on this server.
+fn is_local_redirect_target(target: &str) -> bool {
+    target.starts_with('/') && !target.starts_with("//") && !target.contains('\\')
 }
 
-async fn create_new_file(
+async fn update_preferences(
     State(state): State<AppState>,
-    Form(form): Form<NewFileForm>,
-) -> Result<Redirect, WebError> {
+    Form(form): Form<PreferencesForm>,
+) -> Result<Response, WebError> {
     validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
 
-    let canonical_dir = validate_directory_path(&state.target_dir, &form.path)?;
-    let markdown_filename = normalize_markdown_filename(&form.filename)?;
-    let full_path = canonical_dir.join(&markdown_filename);
-
-    if fs::try_exists(&full_path).await? {
-        return Err(WebError::BadRequest("File already exists".to_string()));
-    }
-
-    fs::write(&full_path, "").await?;
+    let theme = Theme::parse(&form.theme).ok_or(WebError::BadRequest(
+        "Theme must be one of light, dark, or auto".to_string(),
+    ))?;
+    let cookie = format!(
+        "{THEME_COOKIE_NAME}={}; Path=/; Max-Age={THEME_COOKIE_MAX_AGE_SECONDS}; SameSite=Lax; HttpOnly",
+        theme.as_str()
+    );
+    let cookie = HeaderValue::from_str(&cookie)
+        .map_err(|err| WebError::Internal(format!("Failed to build theme cookie: {err}")))?;
 
-    let new_relative_path = if form.path.is_empty() {
-        markdown_filename
+    let return_to = if is_local_redirect_target(&form.return_to) {
+        form.return_to.clone()
     } else {
-        format!("{}/{}", form.path, markdown_filename)
+        app_url("/")
     };
-    Ok(Redirect::to(&format!(
-        "/edit?path={}",
-        urlencoding::encode(&new_relative_path)
-    )))
+
+    info!("Theme preference updated: {}", theme.as_str());
+    Ok(([(header::SET_COOKIE, cookie)], Redirect::to(&return_to)).into_response())
 }
 
-async fn upload_image_form(
+async fn get_breadcrumbs(
     Query(params): Query<HashMap<String, String>>,
     State(state): State<AppState>,
-) -> Result<UploadImageTemplate, WebError> {
-    let path = params.get("path").map(|s| s.as_str()).unwrap_or("");
-    validate_directory_path(&state.target_dir, path)?;
+) -> Result<Json<Vec<Breadcrumb>>, WebError> {
+    let path = sanitize_relative_path(params.get("path").map(|s| s.as_str()).unwrap_or(""))?;
+    validate_directory_path(&state.target_dir, &path)?;
 
-    Ok(UploadImageTemplate {
-        current_path_display: if path.is_empty() {
-            "/".to_string()
-        } else {
-            format!("/{}", path)
-        },
-        path_value: path.to_string(),
-        back_url: if path.is_empty() {
-            "/".to_string()
-        } else {
-            format!("/?path={}", urlencoding::encode(path))
-        },
-        csrf_token: generate_csrf_token(&state.csrf_secret),
-        max_upload_size: format_file_size(state.max_upload_size_bytes as u64),
-    })
+    if path.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+    Ok(Json(build_breadcrumbs(&path)))
 }
 
-async fn upload_image(
-    State(state): State<AppState>,
-    mut multipart: Multipart,
-) -> Result<Redirect, WebError> {
-    let mut path = String::new();
-    let mut csrf_token = String::new();
-    let mut image_file_name: Option<String> = None;
-    let mut image_bytes: Option<Vec<u8>> = None;
+/// Detects whether `dir` is on a case-insensitive filesystem by creating a
+/// lowercase probe file and looking it up in upper case.
+async fn probe_case_insensitive(dir: &Path) -> Result<bool, std::io::Error> {
+    let probe_name = format!(
+        ".markdown-wrangler-case-probe-{}",
+        hex::encode(rand::rng().random::<[u8; 4]>())
+    );
+    let probe_path = dir.join(&probe_name);
+    fs::write(&probe_path, b"").await?;
+    let result = fs::try_exists(dir.join(probe_name.to_uppercase())).await;
+    if let Err(err) = fs::remove_file(&probe_path).await {
+        warn!(
+            "Failed to remove case probe {}: {}",
+            probe_path.display(),
+            err
+        );
+    }
+    result
+}
 
-    while let Some(field) = multipart
-        .next_field()
+/// On case-insensitive filesystems, returns the entry in `dir` whose name
+/// matches `name` apart from case (exact matches are left to the caller's
+/// normal existence checks).
+async fn find_case_collision(
+    case_insensitive: bool,
+    dir: &Path,
+    name: &str,
+) -> Result<Option<String>, WebError> {
+    if !case_insensitive {
+        return Ok(None);
+    }
+    let lower_name = name.to_lowercase();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let entry_name = entry.file_name().to_string_lossy().to_string();
+        if entry_name != name && entry_name.to_lowercase() == lower_name {
+            return Ok(Some(entry_name));
+        }
+    }
+    Ok(None)
+}
+
+fn case_collision_error(existing: &str) -> WebError {
+    WebError::Conflict(format!(
+        "Conflicts with existing '{existing}' (names differ only by case)"
+    ))
+}
+
+/// Moves a single file within the target directory, refusing to overwrite an
+/// existing destination.
+async fn move_file(
+    base_dir: &Path,
+    src: &str,
+    dest: &str,
+    case_insensitive: bool,
+    durability: Durability,
+) -> Result<(), WebError> {
+    let src = sanitize_relative_path(src)?;
+    let dest = sanitize_relative_path(dest)?;
+
+    let source_path = validate_file_path(base_dir, &src)?;
+
+    let (dest_parent, dest_name) = dest.rsplit_once('/').unwrap_or(("", dest.as_ref()));
+    if dest_name.is_empty() {
+        return Err(WebError::BadRequest(
+            "Destination filename is required".to_string(),
+        ));
+    }
+    if is_executable_file(dest_name) {
+        return Err(WebError::Forbidden(
+            "Destination file type not allowed".to_string(),
+        ));
+    }
+
+    let dest_dir = validate_directory_path(base_dir, dest_parent)?;
+    let dest_path = dest_dir.join(dest_name);
+    let source_name = source_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let is_case_only_rename = source_path.parent() == Some(dest_dir.as_path())
+        && source_name != dest_name
+        && source_name.to_lowercase() == dest_name.to_lowercase();
+
+    if is_case_only_rename && case_insensitive {
+        // Renaming straight onto a case variant of itself is a no-op (or
+        // worse) on case-insensitive filesystems, so go via a temporary name.
+        let temp_path = dest_dir.join(format!(
+            ".{source_name}.{}.renaming",
+            hex::encode(rand::rng().random::<[u8; 4]>())
+        ));
+        durable::rename(&source_path, &temp_path, durability).await?;
+        if let Err(err) = durable::rename(&temp_path, &dest_path, durability).await {
+            // Put the file back where it was before reporting the failure.
+            durable::rename(&temp_path, &source_path, durability).await?;
+            return Err(err.into());
+        }
+        return Ok(());
+    }
+
+    if let Some(existing) = find_case_collision(case_insensitive, &dest_dir, dest_name).await? {
+        return Err(case_collision_error(&existing));
+    }
+    if fs::try_exists(&dest_path).await? {
+        return Err(WebError::Conflict("Destination already exists".to_string()));
+    }
+
+    durable::rename(&source_path, &dest_path, durability).await?;
+    Ok(())
+}
+
+/// Moves each `{ src, dest }` pair independently so one bad pair does not
+/// abort the rest of the batch.
+async fn move_many(
+    State(state): State<AppState>,
+    Json(request): Json<MoveManyRequest>,
+) -> Result<Json<MoveManyResponse>, WebError> {
+    validate_csrf_token(&request.csrf_token, &state.csrf_secret)?;
+
+    let _operation = state.operations.begin(
+        "batch move",
+        request
+            .moves
+            .iter()
+            .flat_map(|pair| [&pair.src, &pair.dest])
+            .filter_map(|path| sanitize_relative_path(path).ok())
+            .map(|path| path.to_string())
+            .collect(),
+    );
+    let targets = request
+        .moves
+        .iter()
+        .map(|pair| journal::JournalTarget::new(pair.dest.clone(), Some(pair.src.clone())))
+        .collect();
+    let mut journal =
+        journal::OperationJournal::begin(&state.target_dir, "batch move", targets).await?;
+    let mut results = Vec::with_capacity(request.moves.len());
+    for (index, pair) in request.moves.into_iter().enumerate() {
+        let error = match move_file(
+            &state.target_dir,
+            &pair.src,
+            &pair.dest,
+            state.case_insensitive_fs,
+            state.durability,
+        )
         .await
-        .map_err(|err| WebError::BadRequest(format!("Invalid multipart payload: {err}")))?
-    {
-        let field_name = field.name().unwrap_or("").to_string();
-        match field_name.as_str() {
-            "path" => {
-                path = field
-                    .text()
-                    .await
-                    .map_err(|err| WebError::BadRequest(format!("Invalid path field: {err}")))?;
+        {
+            Ok(()) => {
+                journal.mark_done(index).await;
+                info!("File moved: {} -> {}", pair.src, pair.dest);
+                if let (Ok(src), Ok(dest)) = (
+                    sanitize_relative_path(&pair.src),
+                    sanitize_relative_path(&pair.dest),
+                ) {
+                    state
+                        .redirects
+                        .record(
+                            &src,
+                            redirects::PathFate::Moved {
+                                to: dest.to_string(),
+                            },
+                        )
+                        .await;
+                    if let Some(audit) = &state.audit {
+                        audit.record("move", &dest, "api", Some(&src)).await;
+                    }
+                }
+                None
             }
-            "csrf_token" => {
-                csrf_token = field.text().await.map_err(|err| {
-                    WebError::BadRequest(format!("Invalid CSRF token field: {err}"))
-                })?;
+            Err(err) => {
+                warn!("Failed to move {} -> {}: {}", pair.src, pair.dest, err);
+                Some(err.to_string())
             }
-            "image" => {
-                image_file_name = field.file_name().map(ToString::to_string);
-                let bytes = field.bytes().await.map_err(|err| {
-                    WebError::BadRequest(format!("Invalid image upload field: {err}"))
-                })?;
-                image_bytes = Some(bytes.to_vec());
+        };
+        results.push(MoveResult {
+            src: pair.src,
+            dest: pair.dest,
+            ok: error.is_none(),
+            error,
+        });
+    }
+    journal.finish().await;
+
+    let moved = results.iter().filter(|result| result.ok).count();
+    Ok(Json(MoveManyResponse {
+        moved,
+        failed: results.len() - moved,
+        results,
+    }))
+}
+
+/// Rewrites markdown frontmatter so a copied post starts over as a draft:
+/// `draft` is set to true and `date` removed. Files without frontmatter get a
+/// minimal YAML block.
+fn reset_draft_frontmatter(content: &str, max_frontmatter_bytes: usize) -> String {
+    match extract_frontmatter(content, max_frontmatter_bytes) {
+        Some((FrontmatterFormat::Yaml, block)) => {
+            let start = opening_fence_len(content);
+            let rest = content.get(start + block.len()..).unwrap_or_default();
+            let mut lines: Vec<&str> = block
+                .lines()
+                .filter(|line| !line.starts_with("date:"))
+                .map(|line| {
+                    if line.starts_with("draft:") {
+                        "draft: true"
+                    } else {
+                        line
+                    }
+                })
+                .collect();
+            if !lines.contains(&"draft: true") {
+                lines.push("draft: true");
+            }
+            format!("---\n{}\n{rest}", lines.join("\n"))
+        }
+        Some((FrontmatterFormat::Json, block)) => {
+            let rest = content.get(block.len()..).unwrap_or_default();
+            match serde_json::from_str::<serde_json::Value>(block) {
+                Ok(serde_json::Value::Object(mut object)) => {
+                    object.remove("date");
+                    object.insert("draft".to_string(), serde_json::Value::Bool(true));
+                    let rendered =
+                        serde_json::to_string_pretty(&object).unwrap_or_else(|_| block.to_string());
+                    format!("{rendered}{rest}")
+                }
+                _ => content.to_string(),
+            }
+        }
+        Some((FrontmatterFormat::Toml, block)) => {
+            let rest = content
+                .get(opening_fence_len(content) + block.len()..)
+                .unwrap_or_default();
+            match toml::from_str::<toml::Table>(block) {
+                Ok(mut table) => {
+                    table.remove("date");
+                    table.insert("draft".to_string(), toml::Value::Boolean(true));
+                    match toml::to_string(&table) {
+                        Ok(rendered) => format!("+++\n{rendered}{rest}"),
+                        Err(_) => content.to_string(),
+                    }
+                }
+                Err(_) => content.to_string(),
             }
-            _ => {}
         }
+        None => format!("---\ndraft: true\n---\n{content}"),
     }
+}
 
-    if csrf_token.is_empty() {
-        return Err(WebError::BadRequest("Missing CSRF token".to_string()));
+/// Applies a JSON merge patch to a YAML mapping: `null` removes a key, nested
+/// mappings are merged key by key, anything else replaces the value. Key
+/// order is kept, with new keys appended.
+fn merge_yaml_patch(target: &mut serde_yaml::Mapping, patch: serde_yaml::Mapping) {
+    for (key, value) in patch {
+        match value {
+            serde_yaml::Value::Null => {
+                target.shift_remove(&key);
+            }
+            serde_yaml::Value::Mapping(nested) => {
+                if let Some(serde_yaml::Value::Mapping(existing)) = target.get_mut(&key) {
+                    merge_yaml_patch(existing, nested);
+                } else {
+                    let mut fresh = serde_yaml::Mapping::new();
+                    merge_yaml_patch(&mut fresh, nested);
+                    target.insert(key, serde_yaml::Value::Mapping(fresh));
+                }
+            }
+            value => {
+                target.insert(key, value);
+            }
+        }
     }
-    validate_csrf_token(&csrf_token, &state.csrf_secret)?;
+}
 
-    let canonical_dir = validate_directory_path(&state.target_dir, &path)?;
-    let raw_image_file_name =
-        image_file_name.ok_or(WebError::BadRequest("Image file is required".to_string()))?;
-    let normalized_image_file_name = normalize_image_filename(&raw_image_file_name)?;
-    let image_bytes = image_bytes.ok_or(WebError::BadRequest(
-        "Image file payload is required".to_string(),
-    ))?;
+/// The JSON counterpart of [`merge_yaml_patch`].
+fn merge_json_patch(
+    target: &mut serde_json::Map<String, serde_json::Value>,
+    patch: serde_json::Map<String, serde_json::Value>,
+) {
+    for (key, value) in patch {
+        match value {
+            serde_json::Value::Null => {
+                target.remove(&key);
+            }
+            serde_json::Value::Object(nested) => {
+                let entry = target
+                    .entry(key)
+                    .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+                if !entry.is_object() {
+                    *entry = serde_json::Value::Object(serde_json::Map::new());
+                }
+                if let serde_json::Value::Object(existing) = entry {
+                    merge_json_patch(existing, nested);
+                }
+            }
+            value => {
+                target.insert(key, value);
+            }
+        }
+    }
+}
 
-    if image_bytes.len() > state.max_upload_size_bytes {
-        return Err(WebError::BadRequest(format!(
-            "Uploaded image exceeds maximum size of {}",
-            format_file_size(state.max_upload_size_bytes as u64)
-        )));
+/// The TOML counterpart of [`merge_yaml_patch`]. TOML has no null, so patch
+/// values that cannot be represented (`null` inside an array) are refused.
+fn merge_toml_patch(
+    target: &mut toml::Table,
+    patch: serde_json::Map<String, serde_json::Value>,
+) -> Result<(), toml::ser::Error> {
+    for (key, value) in patch {
+        match value {
+            serde_json::Value::Null => {
+                target.remove(&key);
+            }
+            serde_json::Value::Object(nested) => {
+                let entry = target
+                    .entry(key)
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+                if !entry.is_table() {
+                    *entry = toml::Value::Table(toml::Table::new());
+                }
+                if let toml::Value::Table(existing) = entry {
+                    merge_toml_patch(existing, nested)?;
+                }
+            }
+            value => {
+                target.insert(key, toml::Value::try_from(value)?);
+            }
+        }
     }
-    validate_image_bytes(&normalized_image_file_name, &image_bytes)?;
+    Ok(())
+}
 
-    let full_path = canonical_dir.join(&normalized_image_file_name);
-    if fs::try_exists(&full_path).await? {
-        return Err(WebError::BadRequest("File already exists".to_string()));
+/// Merges `patch` into the frontmatter of `content`, keeping its format
+/// (YAML, TOML or JSON) and leaving the body byte for byte. Files without
+/// frontmatter get a new YAML block. Returns the new content and the merged
+/// frontmatter.
+fn patch_frontmatter(
+    content: &str,
+    patch: serde_json::Map<String, serde_json::Value>,
+    max_frontmatter_bytes: usize,
+) -> Result<(String, serde_json::Value), WebError> {
+    let invalid =
+        |err: &dyn std::fmt::Display| WebError::BadRequest(format!("Invalid frontmatter: {err}"));
+    let not_a_mapping =
+        || WebError::BadRequest("Frontmatter is not a key/value mapping".to_string());
+    let body = frontmatter_len(content, max_frontmatter_bytes)
+        .and_then(|len| content.get(len..))
+        .unwrap_or(content);
+
+    match extract_frontmatter(content, max_frontmatter_bytes) {
+        Some((FrontmatterFormat::Json, block)) => {
+            let serde_json::Value::Object(mut object) =
+                serde_json::from_str(block).map_err(|err| invalid(&err))?
+            else {
+                return Err(not_a_mapping());
+            };
+            merge_json_patch(&mut object, patch);
+            let rendered = serde_json::to_string_pretty(&object).map_err(|err| invalid(&err))?;
+            Ok((
+                format!("{rendered}\n{body}"),
+                serde_json::Value::Object(object),
+            ))
+        }
+        Some((FrontmatterFormat::Toml, block)) => {
+            let mut table: toml::Table = toml::from_str(block).map_err(|err| invalid(&err))?;
+            merge_toml_patch(&mut table, patch).map_err(|err| invalid(&err))?;
+            let rendered = toml::to_string(&table).map_err(|err| invalid(&err))?;
+            Ok((
+                format!("+++\n{rendered}+++\n{body}"),
+                toml_to_json(toml::Value::Table(table)),
+            ))
+        }
+        yaml => {
+            let mut mapping = match yaml {
+                Some((_, block)) if !block.trim().is_empty() => {
+                    match serde_yaml::from_str(block).map_err(|err| invalid(&err))? {
+                        serde_yaml::Value::Mapping(mapping) => mapping,
+                        _ => return Err(not_a_mapping()),
+                    }
+                }
+                _ => serde_yaml::Mapping::new(),
+            };
+            let serde_yaml::Value::Mapping(patch) =
+                serde_yaml::to_value(patch).map_err(|err| invalid(&err))?
+            else {
+                return Err(not_a_mapping());
+            };
+            merge_yaml_patch(&mut mapping, patch);
+            let merged = serde_json::to_value(&mapping).map_err(|err| invalid(&err))?;
+            if mapping.is_empty() && yaml.is_none() {
+                return Ok((content.to_string(), merged));
+            }
+            let rendered = if mapping.is_empty() {
+                String::new()
+            } else {
+                serde_yaml::to_string(&mapping).map_err(|err| invalid(&err))?
+            };
+            Ok((format!("---\n{rendered}---\n{body}"), merged))
+        }
     }
+}
+
+/// Sets `draft: false` in the frontmatter of one markdown file.
+async fn publish_draft(state: &AppState, path: &str) -> Result<(), WebError> {
+    let full_path = validate_file_path(&state.target_dir, path)?;
+    let content = fs::read_to_string(&full_path).await?;
+    let mut patch = serde_json::Map::new();
+    patch.insert("draft".to_string(), serde_json::Value::Bool(false));
+    let (published, _) = patch_frontmatter(&content, patch, state.max_frontmatter_bytes)?;
+    durable::write_atomic(&full_path, published.as_bytes(), state.durability).await?;
+    Ok(())
+}
 
-    fs::write(&full_path, image_bytes).await?;
+/// Whether `line` sets the top-level `key` with `separator` (`:` in YAML,
+/// `=` in TOML). Indented lines belong to nested values.
+fn is_key_line(line: &str, key: &str, separator: char) -> bool {
+    line.strip_prefix(key)
+        .is_some_and(|rest| rest.trim_start_matches([' ', '\t']).starts_with(separator))
+}
 
-    let relative_path = if path.is_empty() {
-        normalized_image_file_name
+/// `block` (the lines between the fences) with its `draft` line replaced by
+/// `replacement`, or `replacement` inserted when there is none. TOML keys
+/// after the first `[table]` header belong to that table, so the search
+/// stops there and a new line goes first; YAML gets it last.
+fn set_fenced_draft(block: &str, replacement: &str, format: FrontmatterFormat) -> String {
+    let separator = if format == FrontmatterFormat::Toml {
+        '='
     } else {
-        format!("{}/{}", path, normalized_image_file_name)
+        ':'
     };
-    Ok(Redirect::to(&format!(
-        "/preview?path={}",
-        urlencoding::encode(&relative_path)
-    )))
+    let newline = if block.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut rewritten = String::with_capacity(block.len() + replacement.len() + 2);
+    let mut searching = true;
+    let mut found = false;
+    for line in block.split_inclusive('\n') {
+        if format == FrontmatterFormat::Toml && line.trim_start().starts_with('[') {
+            searching = false;
+        }
+        if searching && is_key_line(line, "draft", separator) {
+            let ending = line
+                .get(line.trim_end_matches(['\r', '\n']).len()..)
+                .unwrap_or_default();
+            rewritten.push_str(replacement);
+            rewritten.push_str(ending);
+            searching = false;
+            found = true;
+        } else {
+            rewritten.push_str(line);
+        }
+    }
+    if !found {
+        if format == FrontmatterFormat::Toml {
+            rewritten.insert_str(0, &format!("{replacement}{newline}"));
+        } else {
+            rewritten.push_str(replacement);
+            rewritten.push_str(newline);
+        }
+    }
+    rewritten
 }
 
-async fn edit_file(
-    Query(params): Query<HashMap<String, String>>,
-    State(state): State<AppState>,
-) -> Result<EditorTemplate, WebError> {
-    let file_path = params
-        .get("path")
-        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+/// Index just past the closing quote of the JSON string opening at `start`.
+fn json_string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut index = start + 1;
+    loop {
+        match bytes.get(index)? {
+            b'\\' => index += 2,
+            b'"' => return Some(index + 1),
+            _ => index += 1,
+        }
+    }
+}
 
-    if !is_markdown_file(file_path) {
-        return Err(WebError::BadRequest(
-            "File is not a markdown file".to_string(),
-        ));
+/// Byte range of the value of the top-level `"draft"` key of a JSON object,
+/// when it has one and the value is a scalar.
+fn json_draft_value(block: &str) -> Option<std::ops::Range<usize>> {
+    let bytes = block.as_bytes();
+    let skip_whitespace = |mut index: usize| {
+        while bytes.get(index).is_some_and(u8::is_ascii_whitespace) {
+            index += 1;
+        }
+        index
+    };
+    let mut depth = 0usize;
+    let mut index = 0;
+    while let Some(&byte) = bytes.get(index) {
+        match byte {
+            b'"' => {
+                let end = json_string_end(bytes, index)?;
+                let colon = skip_whitespace(end);
+                if depth == 1
+                    && block.get(index..end) == Some("\"draft\"")
+                    && bytes.get(colon) == Some(&b':')
+                {
+                    let start = skip_whitespace(colon + 1);
+                    let end = match bytes.get(start)? {
+                        b'"' => json_string_end(bytes, start)?,
+                        b'{' | b'[' => return None,
+                        _ => {
+                            let mut end = start;
+                            while bytes.get(end).is_some_and(|byte| {
+                                !b",}".contains(byte) && !byte.is_ascii_whitespace()
+                            }) {
+                                end += 1;
+                            }
+                            end
+                        }
+                    };
+                    return Some(start..end);
+                }
+                index = end;
+                continue;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        index += 1;
     }
+    None
+}
 
-    let full_path = validate_file_path(&state.target_dir, file_path)?;
+/// A JSON frontmatter object with its top-level `draft` value replaced, or
+/// a `"draft"` member added as the first key in the object's own layout.
+fn set_json_draft(block: &str, draft: bool) -> Option<String> {
+    if let Some(range) = json_draft_value(block) {
+        return Some(format!(
+            "{}{draft}{}",
+            block.get(..range.start)?,
+            block.get(range.end..)?
+        ));
+    }
+    let rest = block.strip_prefix('{')?;
+    let members = rest.trim_start();
+    if members.starts_with('}') {
+        return Some(format!("{{\"draft\": {draft}{rest}"));
+    }
+    // One key per line gets the new key on its own line, indented like the
+    // first; an inline object gets it inline.
+    let layout = rest.get(..rest.len() - members.len())?;
+    if layout.contains('\n') {
+        Some(format!("{{{layout}\"draft\": {draft},{rest}"))
+    } else {
+        Some(format!("{{{layout}\"draft\": {draft}, {members}"))
+    }
+}
 
-    let content = fs::read_to_string(&full_path).await?;
-    let is_draft = has_draft_frontmatter(&content);
-    let csrf_token = generate_csrf_token(&state.csrf_secret);
-    let cancel_url = get_parent_directory_path(file_path);
-    Ok(EditorTemplate {
-        file_path: file_path.to_string(),
-        content,
-        csrf_token,
-        is_draft,
-        cancel_url,
-    })
+/// Sets `draft` in the frontmatter of `content` by rewriting only the
+/// `draft` line, or inserting one, so the rest of the document stays byte
+/// for byte. Files without frontmatter get a minimal YAML block. The result
+/// is parsed back, and anything the line rewrite cannot handle (a value
+/// spread over several lines, say) is refused rather than written.
+fn set_draft_flag(
+    content: &str,
+    draft: bool,
+    max_frontmatter_bytes: usize,
+) -> Result<String, WebError> {
+    let Some((format, block)) = extract_frontmatter(content, max_frontmatter_bytes) else {
+        return Ok(format!("---\ndraft: {draft}\n---\n{content}"));
+    };
+    let start = match format {
+        FrontmatterFormat::Json => 0,
+        FrontmatterFormat::Yaml | FrontmatterFormat::Toml => opening_fence_len(content),
+    };
+    let unsupported = || {
+        WebError::BadRequest(
+            "The draft flag could not be set in this frontmatter; edit it by hand".to_string(),
+        )
+    };
+    let rewritten = match format {
+        FrontmatterFormat::Yaml => set_fenced_draft(block, &format!("draft: {draft}"), format),
+        FrontmatterFormat::Toml => set_fenced_draft(block, &format!("draft = {draft}"), format),
+        FrontmatterFormat::Json => set_json_draft(block, draft).ok_or_else(unsupported)?,
+    };
+    let updated = format!(
+        "{}{rewritten}{}",
+        content.get(..start).unwrap_or_default(),
+        content.get(start + block.len()..).unwrap_or_default()
+    );
+    let (parsed, ..) =
+        parse_frontmatter(&updated, updated.len(), usize::MAX).ok_or_else(unsupported)?;
+    if parsed != Some(draft) {
+        return Err(unsupported());
+    }
+    Ok(updated)
 }
 
-async fn save_file(
+/// Marks a markdown file as a draft or published without sending its whole
+/// content, then reopens it in the editor.
+async fn set_draft(
     State(state): State<AppState>,
-    Form(form): Form<EditForm>,
-) -> Result<StatusPageTemplate, WebError> {
-    // Validate CSRF token
+    Form(form): Form<SetDraftForm>,
+) -> Result<Redirect, WebError> {
     validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
-
     if !is_markdown_file(&form.path) {
         return Err(WebError::BadRequest(
             "File is not a markdown file".to_string(),
         ));
     }
-
     let full_path = validate_file_path(&state.target_dir, &form.path)?;
-    // Read existing content to check if it has changed
-    let existing_content = fs::read_to_string(&full_path).await?;
-    if existing_content == form.content {
-        // Content hasn't changed, don't write to disk
-        info!("File content unchanged, skipping write: {}", form.path);
-        let back_url = get_parent_directory_path(&form.path);
-        let edit_url = format!("/edit?path={}", urlencoding::encode(&form.path));
-        Ok(StatusPageTemplate {
-            title: "File Unchanged - Markdown Wrangler".to_string(),
-            heading: "ℹ️ No Changes to Save".to_string(),
-            heading_class: "success".to_string(),
-            file_path: form.path,
-            detail_text: "content is unchanged.".to_string(),
-            show_edit_button: true,
-            edit_url,
-            back_url,
-        })
-    } else {
-        // Content has changed, write to disk
-        fs::write(&full_path, &form.content).await?;
+    let path = sanitize_relative_path(&form.path)?;
+    ensure_not_locked(&state, &path)?;
 
-        info!("File saved successfully: {}", form.path);
-        let back_url = get_parent_directory_path(&form.path);
-        let edit_url = format!("/edit?path={}", urlencoding::encode(&form.path));
-
-        Ok(StatusPageTemplate {
-            title: "File Saved - Markdown Wrangler".to_string(),
-            heading: "✅ File Saved Successfully!".to_string(),
-            heading_class: "success".to_string(),
-            file_path: form.path.to_string(),
-            detail_text: "has been saved.".to_string(),
-            show_edit_button: true,
-            edit_url,
-            back_url,
-        })
+    let content = fs::read_to_string(&full_path).await?;
+    let updated = set_draft_flag(&content, form.draft, state.max_frontmatter_bytes)?;
+    if updated != content {
+        durable::write_atomic(&full_path, updated.as_bytes(), state.durability).await?;
+        info!("Draft set to {} for {}", form.draft, path);
+        record_audit(&state, "write", &path, "web").await;
     }
+    Ok(Redirect::to(&route_url("/edit", &path)))
 }
 
-async fn preview_image(
-    Query(params): Query<HashMap<String, String>>,
+/// Publishes every draft whose `tags` include the requested tag (compared
+/// case-insensitively). Each file succeeds or fails on its own.
+async fn publish_by_tag(
+    RequestDeadline(deadline): RequestDeadline,
     State(state): State<AppState>,
-) -> Result<ImagePreviewTemplate, WebError> {
-    let file_path = params
-        .get("path")
-        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?
-        .to_owned();
-
-    if !is_image_file(&file_path) {
-        return Err(WebError::BadRequest(
-            "File is not an image file".to_string(),
-        ));
+    Json(request): Json<PublishByTagRequest>,
+) -> Result<Json<PublishByTagResponse>, WebError> {
+    validate_csrf_token(&request.csrf_token, &state.csrf_secret)?;
+    let tag = request.tag.trim().to_lowercase();
+    if tag.is_empty() {
+        return Err(WebError::BadRequest("Tag is required".to_string()));
     }
 
-    let full_path = validate_file_path(&state.target_dir, &file_path)?;
+    // Rebuilt rather than cached: this writes based on what it finds.
+    let index = complete_index(&state, &deadline, true).await?;
+    let paths: Vec<String> = index
+        .documents
+        .iter()
+        .filter(|document| {
+            parse_frontmatter(
+                &document.content,
+                state.max_frontmatter_bytes,
+                state.max_frontmatter_list_items,
+            )
+            .is_some_and(|(draft, _, _, tags, ..)| {
+                draft == Some(true) && tags.iter().any(|item| item.to_lowercase() == tag)
+            })
+        })
+        .map(|document| document.path.clone())
+        .collect();
 
-    let csrf_token = generate_csrf_token(&state.csrf_secret);
-    let parent_path = get_parent_directory_path(&file_path);
-    let encoded_path = urlencoding::encode(&file_path).into_owned();
-    let file_size = get_file_size(&full_path).await.map(format_file_size)?;
-    Ok(ImagePreviewTemplate {
-        encoded_path,
-        parent_path,
-        file_path,
-        file_size,
-        csrf_token,
-    })
+    let _operation = state.operations.begin("publish by tag", paths.clone());
+    let targets = paths
+        .iter()
+        .map(|path| journal::JournalTarget::new(path.clone(), None))
+        .collect();
+    let mut journal =
+        journal::OperationJournal::begin(&state.target_dir, "publish by tag", targets).await?;
+    let mut results = Vec::with_capacity(paths.len());
+    for (index, path) in paths.into_iter().enumerate() {
+        let error = match publish_draft(&state, &path).await {
+            Ok(()) => {
+                journal.mark_done(index).await;
+                info!("Draft published by tag '{}': {}", request.tag.trim(), path);
+                record_audit(&state, "write", &path, "api").await;
+                None
+            }
+            Err(err) => {
+                warn!("Failed to publish {}: {}", path, err);
+                Some(err.to_string())
+            }
+        };
+        results.push(PublishResult {
+            path,
+            ok: error.is_none(),
+            error,
+        });
+    }
+    journal.finish().await;
+
+    let published = results.iter().filter(|result| result.ok).count();
+    Ok(Json(PublishByTagResponse {
+        tag: request.tag.trim().to_string(),
+        published,
+        failed: results.len() - published,
+        results,
+    }))
 }
 
-async fn serve_image(
-    Query(params): Query<HashMap<String, String>>,
-    State(state): State<AppState>,
-) -> Result<axum::response::Response, WebError> {
-    let file_path = params
-        .get("path")
-        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+/// A file or directory scheduled for copying, relative to the source root.
+struct CopyPlanEntry {
+    relative_path: PathBuf,
+    is_directory: bool,
+}
 
-    if !is_image_file(file_path) {
+/// Walks `source` and lists what would be copied, enforcing the depth, file
+/// count and size limits before anything is written.
+async fn plan_directory_copy(
+    source: &Path,
+    excludes: &[&str],
+    result: &mut CopyDirectoryResult,
+) -> Result<Vec<CopyPlanEntry>, WebError> {
+    let mut plan = Vec::new();
+    let mut pending = vec![(PathBuf::new(), 0usize)];
+
+    while let Some((relative_dir, depth)) = pending.pop() {
+        let mut entries = fs::read_dir(source.join(&relative_dir)).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let relative_path = relative_dir.join(&file_name);
+            let relative_display = relative_path.to_string_lossy().replace('\\', "/");
+
+            if file_name.starts_with('.')
+                || excludes.iter().any(|pattern| {
+                    glob_matches(pattern, &file_name) || glob_matches(pattern, &relative_display)
+                })
+            {
+                result.skipped += 1;
+                continue;
+            }
+
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() && ignore_marker::is_marked(&entry.path()).await {
+                result.skipped += 1;
+                continue;
+            }
+            if file_type.is_dir() {
+                if depth + 1 > MAX_COPY_DEPTH {
+                    return Err(WebError::BadRequest(format!(
+                        "Directory is nested deeper than {MAX_COPY_DEPTH} levels"
+                    )));
+                }
+                pending.push((relative_path.clone(), depth + 1));
+                plan.push(CopyPlanEntry {
+                    relative_path,
+                    is_directory: true,
+                });
+            } else if file_type.is_file() {
+                result.files += 1;
+                result.bytes += entry.metadata().await?.len();
+                if result.files > MAX_COPY_FILES {
+                    return Err(WebError::BadRequest(format!(
+                        "Directory has more than {MAX_COPY_FILES} files"
+                    )));
+                }
+                if result.bytes > MAX_COPY_BYTES {
+                    return Err(WebError::BadRequest(format!(
+                        "Directory is larger than {}",
+                        format_file_size(MAX_COPY_BYTES)
+                    )));
+                }
+                plan.push(CopyPlanEntry {
+                    relative_path,
+                    is_directory: false,
+                });
+            } else {
+                // Symlinks and special files are never followed or copied.
+                result.skipped += 1;
+            }
+        }
+    }
+
+    // Parents must exist before their children are created.
+    plan.sort_by_key(|entry| entry.relative_path.components().count());
+    Ok(plan)
+}
+
+async fn copy_directory(
+    State(state): State<AppState>,
+    Form(form): Form<CopyDirectoryForm>,
+) -> Result<Json<CopyDirectoryResult>, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+
+    let source = sanitize_relative_path(&form.source)?;
+    if source.is_empty() {
         return Err(WebError::BadRequest(
-            "File is not an image file".to_string(),
+            "Cannot copy the root directory".to_string(),
         ));
     }
+    let source_dir = validate_directory_path(&state.target_dir, &source)?;
 
-    let full_path = validate_file_path(&state.target_dir, file_path)?;
-    let file_contents = fs::read(&full_path).await?;
-    // Determine content type based on file extension
-    let content_type = match full_path.extension().and_then(|s| s.to_str()) {
-        Some("jpg") | Some("jpeg") => "image/jpeg",
-        Some("png") => "image/png",
-        Some("gif") => "image/gif",
-        Some("webp") => "image/webp",
-        Some("svg") => "image/svg+xml",
-        Some("bmp") => "image/bmp",
-        Some("tiff") | Some("tif") => "image/tiff",
-        _ => "application/octet-stream",
+    let destination_name = form.destination.trim();
+    if destination_name.is_empty()
+        || destination_name.starts_with('.')
+        || destination_name.contains(['/', '\\', '\0'])
+    {
+        return Err(WebError::BadRequest(
+            "Destination must be a single visible directory name".to_string(),
+        ));
+    }
+    let parent = source.rsplit_once('/').map_or("", |(parent, _)| parent);
+    let destination = join_relative_path(parent, destination_name);
+    let parent_dir = validate_directory_path(&state.target_dir, parent)?;
+    if let Some(existing) =
+        find_case_collision(state.case_insensitive_fs, &parent_dir, destination_name).await?
+    {
+        return Err(case_collision_error(&existing));
+    }
+    let destination_dir = parent_dir.join(destination_name);
+
+    let excludes: Vec<&str> = form
+        .exclude
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .collect();
+
+    let mut result = CopyDirectoryResult {
+        source: source.to_string(),
+        destination: destination.clone(),
+        ..Default::default()
     };
+    let plan = plan_directory_copy(&source_dir, &excludes, &mut result).await?;
 
-    let mut response = Response::new(axum::body::Body::from(file_contents));
+    let _operation = state
+        .operations
+        .begin("directory copy", vec![destination.clone()]);
+    // Journal targets follow the plan's file order, which the copy loop keeps.
+    let targets = plan
+        .iter()
+        .filter(|entry| !entry.is_directory)
+        .map(|entry| {
+            let path = entry.relative_path.to_string_lossy().replace('\\', "/");
+            journal::JournalTarget::new(
+                join_relative_path(&destination, &path),
+                Some(join_relative_path(&source, &path)),
+            )
+        })
+        .collect();
+    let mut journal =
+        journal::OperationJournal::begin(&state.target_dir, "directory copy", targets).await?;
+    if let Err(err) = fs::create_dir(&destination_dir).await {
+        journal.finish().await;
+        return Err(match err.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                WebError::Conflict("Destination already exists".to_string())
+            }
+            _ => WebError::from(err),
+        });
+    }
+    info!(
+        "Copying directory {} to {} ({} files, {} bytes)",
+        source, destination, result.files, result.bytes
+    );
 
-    let header_value = match HeaderValue::from_str(content_type) {
-        Ok(value) => value,
-        Err(err) => {
-            warn!(
-                "Invalid content type '{}' for image response, using fallback: {}",
-                content_type, err
-            );
-            HeaderValue::from_static("application/octet-stream")
+    let mut copied_files = 0usize;
+    for entry in plan {
+        let from = source_dir.join(&entry.relative_path);
+        let to = destination_dir.join(&entry.relative_path);
+        if entry.is_directory {
+            fs::create_dir(&to).await?;
+            result.directories += 1;
+            continue;
+        }
+
+        let relative_display = entry.relative_path.to_string_lossy();
+        if form.reset_drafts && is_markdown_file(&relative_display) {
+            let content = fs::read_to_string(&from).await?;
+            durable::write_new(
+                &to,
+                reset_draft_frontmatter(&content, state.max_frontmatter_bytes).as_bytes(),
+                state.durability,
+            )
+            .await?;
+        } else {
+            durable::copy_file(&from, &to, state.durability).await?;
+        }
+        journal.mark_done(copied_files).await;
+        copied_files += 1;
+        record_audit(
+            &state,
+            "copy",
+            &join_relative_path(&destination, &relative_display),
+            "web",
+        )
+        .await;
+        info!(
+            "Copied {}/{} into {} ({}/{})",
+            source, relative_display, destination, copied_files, result.files
+        );
+    }
+
+    journal.finish().await;
+    info!(
+        "Directory copy complete: {} -> {} ({} directories, {} files, {} skipped)",
+        source, destination, result.directories, result.files, result.skipped
+    );
+    Ok(Json(result))
+}
+
+async fn create_snapshot(
+    State(state): State<AppState>,
+    Form(form): Form<SnapshotForm>,
+) -> Result<Redirect, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+
+    let mut totals = CopyDirectoryResult::default();
+    let plan = plan_directory_copy(&state.target_dir, &[], &mut totals).await?;
+    let files: Vec<PathBuf> = plan
+        .into_iter()
+        .filter(|entry| !entry.is_directory)
+        .map(|entry| entry.relative_path)
+        .collect();
+
+    let snapshot_dir = state.target_dir.join(snapshot::SNAPSHOT_DIR);
+    fs::create_dir_all(&snapshot_dir).await?;
+    let timestamp = unix_seconds(SystemTime::now());
+    let mut attempt = 0;
+    let name = loop {
+        let name = snapshot::snapshot_name(timestamp, attempt);
+        if !fs::try_exists(snapshot_dir.join(&name)).await? {
+            break name;
         }
+        attempt += 1;
     };
-    response.headers_mut().insert("Content-Type", header_value);
 
-    Ok(response)
+    snapshot::write_snapshot(&snapshot_dir.join(&name), &state.target_dir, &files).await?;
+    info!(
+        "Created snapshot {} ({} files, {} bytes)",
+        name, totals.files, totals.bytes
+    );
+    snapshot::prune_snapshots(&snapshot_dir, snapshot::MAX_SNAPSHOTS).await?;
+
+    Ok(Redirect::to(&app_url(&format!(
+        "/snapshots?created={}",
+        urlencoding::encode(&name)
+    ))))
 }
 
-async fn preview_file(
+async fn list_snapshots(
+    ThemePreference(theme): ThemePreference,
     Query(params): Query<HashMap<String, String>>,
     State(state): State<AppState>,
-) -> Result<FilePreviewTemplate, WebError> {
-    let file_path = params
-        .get("path")
-        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+) -> Result<SnapshotsTemplate, WebError> {
+    let snapshots = snapshot::list_snapshots(&state.target_dir.join(snapshot::SNAPSHOT_DIR))
+        .await?
+        .into_iter()
+        .map(|info| SnapshotView {
+            restore_url: app_url(&format!(
+                "/snapshots/restore?name={}",
+                urlencoding::encode(&info.name)
+            )),
+            size: format_file_size(info.size),
+            created: format_http_date(UNIX_EPOCH + Duration::from_secs(info.created)),
+            name: info.name,
+        })
+        .collect();
+    Ok(SnapshotsTemplate {
+        theme,
+        assets: state.assets.clone(),
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        snapshots,
+        created: params
+            .get("created")
+            .filter(|name| snapshot::is_snapshot_name(name))
+            .cloned(),
+        max_snapshots: snapshot::MAX_SNAPSHOTS,
+    })
+}
 
-    // Don't preview markdown or image files with this handler
-    if is_markdown_file(file_path) || is_image_file(file_path) {
-        return Err(WebError::BadRequest(
-            "Use specific handlers for markdown and image files".to_string(),
-        ));
+/// Resolves a snapshot name from a request to its archive path.
+async fn snapshot_path(state: &AppState, name: &str) -> Result<PathBuf, WebError> {
+    if !snapshot::is_snapshot_name(name) {
+        return Err(WebError::BadRequest("Invalid snapshot name".to_string()));
     }
-
-    let full_path = validate_file_path(&state.target_dir, file_path)?;
-    let csrf_token = generate_csrf_token(&state.csrf_secret);
-    match get_file_size(&full_path).await {
-        Ok(size_bytes) => {
-            let file_size = format_file_size(size_bytes);
-            Ok(FilePreviewTemplate {
-                file_path: file_path.to_string(),
-                encoded_path: urlencoding::encode(file_path).into_owned(),
-                file_size: file_size.to_string(),
-                file_type: get_file_type_description(file_path).to_string(),
-                csrf_token: csrf_token.to_string(),
-                can_iframe: is_safe_for_iframe(file_path),
-            })
-        }
-        Err(err) => {
-            warn!("Failed to get file size: {}", err);
-            // Fall back to generating without size info
-            Ok(FilePreviewTemplate {
-                file_path: file_path.to_string(),
-                encoded_path: urlencoding::encode(file_path).into_owned(),
-                file_size: "Unknown".to_string(),
-                file_type: get_file_type_description(file_path).to_string(),
-                csrf_token: csrf_token.to_string(),
-                can_iframe: is_safe_for_iframe(file_path),
-            })
-        }
+    let path = state.target_dir.join(snapshot::SNAPSHOT_DIR).join(name);
+    if !fs::try_exists(&path).await? {
+        return Err(WebError::NotFound(format!("Snapshot {name} not found")));
     }
+    Ok(path)
 }
 
-async fn serve_file(
+async fn confirm_restore_snapshot(
+    ThemePreference(theme): ThemePreference,
     Query(params): Query<HashMap<String, String>>,
     State(state): State<AppState>,
-) -> Result<axum::response::Response, WebError> {
-    let file_path = params
-        .get("path")
-        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+) -> Result<SnapshotRestoreTemplate, WebError> {
+    let name = params.get("name").cloned().unwrap_or_default();
+    let path = snapshot_path(&state, &name).await?;
+    Ok(SnapshotRestoreTemplate {
+        theme,
+        assets: state.assets.clone(),
+        size: format_file_size(fs::metadata(&path).await?.len()),
+        name,
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        report: None,
+    })
+}
 
-    // Only serve safe files
-    if !is_safe_for_iframe(file_path) || is_executable_file(file_path) {
-        return Err(WebError::Forbidden(
-            "File type not allowed for security reasons".to_string(),
-        ));
+async fn restore_snapshot(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    Form(form): Form<RestoreSnapshotForm>,
+) -> Result<SnapshotRestoreTemplate, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+    let path = snapshot_path(&state, &form.name).await?;
+
+    let _operation = state
+        .operations
+        .begin("snapshot restore", vec![String::new()]);
+    let report =
+        snapshot::restore_snapshot(&path, &state.target_dir, MAX_COPY_BYTES, state.durability)
+            .await?;
+    for restored in report.restored.iter().chain(&report.added) {
+        record_audit(&state, "restore", restored, "web").await;
     }
+    info!(
+        "Restored snapshot {} ({} restored, {} added, {} unchanged)",
+        form.name,
+        report.restored.len(),
+        report.added.len(),
+        report.unchanged
+    );
 
-    let full_path = validate_file_path(&state.target_dir, file_path)?;
+    Ok(SnapshotRestoreTemplate {
+        theme,
+        assets: state.assets.clone(),
+        size: format_file_size(fs::metadata(&path).await?.len()),
+        name: form.name,
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        report: Some(report),
+    })
+}
 
-    let file_contents = fs::read(&full_path).await?;
-    // Determine content type based on file extension
-    let content_type = match full_path.extension().and_then(|s| s.to_str()) {
-        Some("txt") | Some("log") => "text/plain; charset=utf-8",
-        Some("html") | Some("htm") => "text/html; charset=utf-8",
-        Some("css") => "text/css; charset=utf-8",
-        Some("js") => "application/javascript; charset=utf-8",
-        Some("json") => "application/json; charset=utf-8",
-        Some("xml") => "application/xml; charset=utf-8",
-        Some("pdf") => "application/pdf",
-        Some("csv") => "text/csv; charset=utf-8",
-        Some("yml") | Some("yaml") => "text/yaml; charset=utf-8",
-        Some("toml") => "text/plain; charset=utf-8",
-        Some("ini") | Some("conf") | Some("cfg") => "text/plain; charset=utf-8",
-        _ => "text/plain; charset=utf-8",
+async fn problems(
+    ThemePreference(theme): ThemePreference,
+    RequestDeadline(deadline): RequestDeadline,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<ProblemsTemplate, WebError> {
+    let refresh = params.get("refresh").is_some_and(|value| value == "1");
+    let report = state
+        .checks
+        .report(
+            &state.target_dir,
+            state.max_frontmatter_bytes,
+            refresh,
+            &deadline,
+        )
+        .await
+        .map_err(|err| match err.kind() {
+            std::io::ErrorKind::TimedOut => walk_timed_out(),
+            _ => err.into(),
+        })?;
+
+    let mut categories: Vec<ProblemCategoryView> = report
+        .results
+        .iter()
+        .map(|result| ProblemCategoryView {
+            name: result.name,
+            title: result.title,
+            count: result.findings.len(),
+            url: app_url(&format!(
+                "/problems?check={}",
+                urlencoding::encode(result.name)
+            )),
+            findings: result
+                .findings
+                .iter()
+                .map(|finding| FindingView {
+                    path: finding.path.clone(),
+                    message: finding.message.clone(),
+                    url: if is_markdown_file(&finding.path) {
+                        route_url("/edit", &finding.path)
+                    } else {
+                        route_url("/file-preview", &finding.path)
+                    },
+                })
+                .collect(),
+        })
+        .collect();
+
+    let selected = match params.get("check") {
+        Some(name) => {
+            let position = categories
+                .iter()
+                .position(|category| category.name == name.as_str())
+                .ok_or_else(|| WebError::NotFound(format!("Unknown check: {name}")))?;
+            Some(categories.remove(position))
+        }
+        None => None,
     };
 
-    let mut response = axum::response::Response::new(axum::body::Body::from(file_contents));
+    // Journals the user has since deleted are dropped from the startup list.
+    let mut interrupted = Vec::new();
+    for operation in state.interrupted_operations.iter() {
+        if !fs::try_exists(state.target_dir.join(&operation.journal)).await? {
+            continue;
+        }
+        let started = UNIX_EPOCH
+            .checked_add(Duration::from_secs(operation.started))
+            .unwrap_or(UNIX_EPOCH);
+        interrupted.push(InterruptedOperationView {
+            operation: operation.operation.clone(),
+            started: timefmt::HumanTime::new(started, state.display_offset),
+            journal: operation.journal.clone(),
+            completed: operation
+                .completed
+                .iter()
+                .map(journal::JournalTarget::describe)
+                .collect(),
+            pending: operation
+                .pending
+                .iter()
+                .map(journal::JournalTarget::describe)
+                .collect(),
+        });
+    }
 
-    let headers = response.headers_mut();
-    headers.insert("Content-Type", HeaderValue::from_static(content_type));
+    Ok(ProblemsTemplate {
+        theme,
+        assets: state.assets.clone(),
+        total: report.total(),
+        categories,
+        selected,
+        generated_at: format_http_date(report.generated_at),
+        interrupted,
+    })
+}
 
-    headers.insert(
-        "X-Content-Type-Options",
-        HeaderValue::from_static("nosniff"),
-    );
-    headers.insert("X-Frame-Options", HeaderValue::from_static("SAMEORIGIN"));
+fn quickswitch_url(path: &str) -> String {
+    if is_markdown_file(path) {
+        route_url("/edit", path)
+    } else {
+        route_url("/file-preview", path)
+    }
+}
 
-    Ok(response)
+/// Runs a quick-switcher query against the cached content index. The flag
+/// is set when the index was cut short by `deadline`, so the hits only
+/// cover part of the tree.
+async fn quickswitch_hits(
+    state: &AppState,
+    query: &str,
+    deadline: &Deadline,
+) -> Result<(Vec<quickswitch::QuickSwitchHit>, bool), WebError> {
+    if query.trim().is_empty() {
+        return Ok((Vec::new(), false));
+    }
+    let index = state
+        .checks
+        .index(
+            &state.target_dir,
+            state.max_frontmatter_bytes,
+            false,
+            deadline,
+        )
+        .await?;
+    Ok((quickswitch::search(&index, query), index.truncated))
 }
 
-#[derive(Deserialize)]
-pub struct ParamsWithPath {
-    path: String,
+/// The content index for answers that would be wrong if built from part of
+/// the tree: a walk cut short by `deadline` is a 503 instead.
+async fn complete_index(
+    state: &AppState,
+    deadline: &Deadline,
+    refresh: bool,
+) -> Result<Arc<checks::ContentIndex>, WebError> {
+    let index = state
+        .checks
+        .index(
+            &state.target_dir,
+            state.max_frontmatter_bytes,
+            refresh,
+            deadline,
+        )
+        .await?;
+    if index.truncated {
+        return Err(walk_timed_out());
+    }
+    Ok(index)
 }
 
-async fn get_file_info(
-    Query(params): Query<ParamsWithPath>,
-    State(state): State<AppState>,
-) -> Result<Json<FileInfo>, WebError> {
-    let full_path = validate_file_path(&state.target_dir, &params.path)?;
-    let modified_time = get_file_modification_time(&full_path).await?;
-    let size = get_file_size(&full_path).await?;
-    Ok(Json(FileInfo {
-        modified_time,
-        size,
-    }))
+fn walk_timed_out() -> WebError {
+    WebError::ServiceUnavailable(
+        "Scanning the content tree took too long; try again shortly".to_string(),
+    )
 }
 
-async fn get_file_content(
+async fn quickswitch_api(
+    RequestDeadline(deadline): RequestDeadline,
     Query(params): Query<HashMap<String, String>>,
     State(state): State<AppState>,
-) -> Result<Json<FileContent>, WebError> {
-    let file_path = params
-        .get("path")
-        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+) -> Result<Json<QuickSwitchResponse>, WebError> {
+    let query = params.get("q").cloned().unwrap_or_default();
+    let (hits, truncated) = quickswitch_hits(&state, &query, &deadline).await?;
+    let results = hits
+        .into_iter()
+        .map(|hit| QuickSwitchResult {
+            url: quickswitch_url(&hit.path),
+            path: hit.path,
+            title: hit.title,
+            score: hit.score,
+            path_positions: hit.path_positions,
+            title_positions: hit.title_positions,
+        })
+        .collect();
+    Ok(Json(QuickSwitchResponse {
+        query,
+        results,
+        truncated,
+    }))
+}
 
-    // Only allow markdown files for this endpoint
-    if !is_markdown_file(file_path) {
-        return Err(WebError::BadRequest(
-            "Only markdown files are supported".to_string(),
-        ));
+/// Splits `text` into runs of matched and unmatched characters.
+fn highlight_segments(text: &str, positions: &[usize]) -> Vec<HighlightSegment> {
+    let mut segments: Vec<HighlightSegment> = Vec::new();
+    for (index, c) in text.chars().enumerate() {
+        let matched = positions.contains(&index);
+        match segments.last_mut() {
+            Some(segment) if segment.matched == matched => segment.text.push(c),
+            _ => segments.push(HighlightSegment {
+                text: c.to_string(),
+                matched,
+            }),
+        }
     }
-
-    let full_path = validate_file_path(&state.target_dir, file_path)?;
-    let file_content = FileContent {
-        content: fs::read_to_string(&full_path).await?,
-        modified_time: get_file_modification_time(&full_path).await?,
-    };
-    Ok(Json(file_content))
+    segments
 }
 
-async fn delete_file(
+/// Server-rendered quick switcher for browsers without JavaScript.
+async fn quickswitch_page(
+    ThemePreference(theme): ThemePreference,
+    RequestDeadline(deadline): RequestDeadline,
+    Query(params): Query<HashMap<String, String>>,
     State(state): State<AppState>,
-    Form(form): Form<DeleteForm>,
-) -> Result<StatusPageTemplate, WebError> {
-    // Validate CSRF token
-    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
-
-    // Validate the file path
-    let full_path = validate_file_path(&state.target_dir, &form.path)?;
-    fs::remove_file(&full_path).await?;
-    info!("File deleted successfully: {}", form.path);
-    let back_url = get_parent_directory_path(&form.path);
-    Ok(StatusPageTemplate {
-        title: "File Deleted - Markdown Wrangler".to_string(),
-        heading: "🗑️ File Deleted Successfully!".to_string(),
-        heading_class: "success".to_string(),
-        file_path: form.path,
-        detail_text: "has been deleted.".to_string(),
-        show_edit_button: false,
-        edit_url: "".to_string(),
-        back_url,
+) -> Result<QuickSwitchTemplate, WebError> {
+    let query = params.get("q").cloned().unwrap_or_default();
+    let (hits, truncated) = quickswitch_hits(&state, &query, &deadline).await?;
+    let results = hits
+        .into_iter()
+        .map(|hit| QuickSwitchView {
+            url: quickswitch_url(&hit.path),
+            path: highlight_segments(&hit.path, &hit.path_positions),
+            title: hit
+                .title
+                .as_deref()
+                .map(|title| highlight_segments(title, &hit.title_positions))
+                .unwrap_or_default(),
+        })
+        .collect();
+    Ok(QuickSwitchTemplate {
+        theme,
+        assets: state.assets.clone(),
+        query,
+        results,
+        truncated,
     })
 }
 
-async fn handler_404() -> WebError {
-    WebError::NotFound("Not found".to_string())
-}
+async fn new_file_form(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<NewFileTemplate, WebError> {
+    let path = sanitize_relative_path(params.get("path").map(|s| s.as_str()).unwrap_or(""))?;
+    let path = path.as_ref();
+    validate_directory_path(&state.target_dir, path)?;
 
-fn create_router(state: AppState) -> Router {
-    Router::new()
-        .route("/", get(index))
-        .route("/new-file", get(new_file_form).post(create_new_file))
-        .route("/upload-image", get(upload_image_form).post(upload_image))
-        .route("/edit", get(edit_file))
-        .route("/save", post(save_file))
-        .route("/delete", post(delete_file))
-        .route("/preview", get(preview_image))
-        .route("/image", get(serve_image))
-        .route("/file-preview", get(preview_file))
-        .route("/file", get(serve_file))
-        .route("/file-info", get(get_file_info))
-        .route("/file-content", get(get_file_content))
-        .nest_service("/static", ServeDir::new("static"))
-        .fallback(handler_404)
-        .with_state(state)
+    Ok(new_file_template(theme, path, &state))
 }
 
-pub async fn start_server(
-    target_dir: PathBuf,
-    max_upload_size_bytes: usize,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Generate a random CSRF secret
-    let csrf_secret = hex::encode(rand::rng().random::<[u8; 32]>());
-    let state = AppState {
-        target_dir,
-        csrf_secret,
-        max_upload_size_bytes,
-    };
-    let app = create_router(state);
+fn new_file_template(theme: Theme, path: &str, state: &AppState) -> NewFileTemplate {
+    NewFileTemplate {
+        theme,
+        assets: state.assets.clone(),
+        current_path_display: if path.is_empty() {
+            "/".to_string()
+        } else {
+            format!("/{}", path)
+        },
+        path_value: path.to_string(),
+        back_url: route_url("/", path),
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        filename_value: String::new(),
+        error_message: None,
+        planned_path: None,
+        confirmed: false,
+        suggested_filename: None,
+    }
+}
 
-    let address = "127.0.0.1:5420";
-    let listener = TcpListener::bind(address).await?;
-    info!(
-        "Web server listening on http://{}, press Ctrl+C to stop",
-        address
-    );
+/// Why `name` cannot be created in `dir`, if anything: an existing entry of
+/// that name, or one differing only by case on a case-insensitive filesystem.
+async fn new_file_conflict(
+    case_insensitive: bool,
+    dir: &Path,
+    name: &str,
+) -> Result<Option<WebError>, WebError> {
+    if let Some(existing) = find_case_collision(case_insensitive, dir, name).await? {
+        return Ok(Some(case_collision_error(&existing)));
+    }
+    match fs::symlink_metadata(dir.join(name)).await {
+        Ok(_) => Ok(Some(WebError::Conflict("File already exists".to_string()))),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
 
-    axum::serve(listener, app).await?;
+/// First free `<stem>-N.md` name next to a taken markdown filename.
+async fn suggest_markdown_filename(
+    case_insensitive: bool,
+    dir: &Path,
+    taken: &str,
+) -> Result<Option<String>, WebError> {
+    let stem = taken.strip_suffix(".md").unwrap_or(taken);
+    for attempt in 2..=MAX_FILENAME_SUGGESTIONS + 1 {
+        let candidate = format!("{stem}-{attempt}.md");
+        if new_file_conflict(case_insensitive, dir, &candidate)
+            .await?
+            .is_none()
+        {
+            return Ok(Some(candidate));
+        }
+    }
+    Ok(None)
+}
 
-    Ok(())
+/// Re-renders the new-file form for a taken name, offering a free one.
+async fn new_file_conflict_page(
+    state: &AppState,
+    dir: &Path,
+    taken: &str,
+    mut page: NewFileTemplate,
+    err: &WebError,
+) -> Result<Response, WebError> {
+    page.error_message = Some(err.to_string());
+    page.suggested_filename =
+        suggest_markdown_filename(state.case_insensitive_fs, dir, taken).await?;
+    Ok((StatusCode::CONFLICT, page).into_response())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::{
-        body::Body,
-        http::{Method, Request, StatusCode},
-    };
-    use http_body_util::BodyExt;
-    use sha2::Digest;
-    use tempfile::TempDir;
-    use tower::ServiceExt;
+/// Creates a markdown file and opens it in the editor. With `?dry_run=1`
+/// nothing is written: the form is re-rendered with the path that would be
+/// created. Rejected filenames also re-render the form rather than failing
+/// with a bare status.
+async fn create_new_file(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+    Form(form): Form<NewFileForm>,
+) -> Result<Response, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
 
-    const DEFAULT_MAX_UPLOAD_SIZE_BYTES: usize = 1_048_576;
-    async fn create_test_app_with_max_upload_size(
-        max_upload_size_bytes: usize,
-    ) -> (Router, TempDir, String) {
-        let temp_dir = TempDir::new().expect("failed to create temporary test directory");
-        let csrf_secret = "test_secret_key_for_csrf_testing".to_string();
-        let state = AppState {
-            target_dir: temp_dir.path().to_path_buf(),
-            csrf_secret: csrf_secret.clone(),
-            max_upload_size_bytes,
+    let dir_path = sanitize_relative_path(&form.path)?;
+    let canonical_dir = validate_directory_path(&state.target_dir, &dir_path)?;
+    let dry_run = params.get("dry_run").is_some_and(|value| value == "1");
+
+    let mut page = new_file_template(theme, &dir_path, &state);
+    page.filename_value = form.filename.trim().to_string();
+
+    let markdown_filename =
+        match normalize_markdown_filename(&form.filename, state.max_filename_length) {
+            Ok(name) => name,
+            Err(err) => {
+                page.error_message = Some(err.to_string());
+                return Ok((StatusCode::BAD_REQUEST, page).into_response());
+            }
         };
-        let app = create_router(state);
-        (app, temp_dir, csrf_secret)
+    let new_relative_path = join_relative_path(&dir_path, &markdown_filename);
+    page.planned_path = Some(new_relative_path.clone());
+
+    if let Some(err) = new_file_conflict(
+        state.case_insensitive_fs,
+        &canonical_dir,
+        &markdown_filename,
+    )
+    .await?
+    {
+        return new_file_conflict_page(&state, &canonical_dir, &markdown_filename, page, &err)
+            .await;
     }
 
-    async fn create_test_app() -> (Router, TempDir, String) {
-        create_test_app_with_max_upload_size(DEFAULT_MAX_UPLOAD_SIZE_BYTES).await
+    if dry_run {
+        page.confirmed = true;
+        return Ok(page.into_response());
     }
 
-    fn create_expired_csrf_token(secret: &str) -> String {
-        let expired_timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system clock should be after UNIX_EPOCH")
-            .as_secs()
-            - 7200; // 2 hours ago
-        let payload = format!("{expired_timestamp}:12345");
-        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
-            .expect("failed to initialize HMAC for expired CSRF token");
-        mac.update(payload.as_bytes());
-        let signature = hex::encode(mac.finalize().into_bytes());
-        format!("{payload}:{signature}")
+    // `create_new` makes the existence check and creation a single atomic
+    // step, so concurrent requests for the same name cannot clobber each other.
+    if let Err(err) = durable::write_new(
+        &canonical_dir.join(&markdown_filename),
+        b"",
+        state.durability,
+    )
+    .await
+    {
+        return match err.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                let err = WebError::Conflict("File already exists".to_string());
+                new_file_conflict_page(&state, &canonical_dir, &markdown_filename, page, &err).await
+            }
+            _ => Err(err.into()),
+        };
     }
 
-    fn create_legacy_keyed_csrf_token(secret: &str) -> String {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system clock should be after UNIX_EPOCH")
-            .as_secs();
-        let payload = format!("{timestamp}:12345");
-        let mut hasher = Sha256::new();
-        hasher.update(payload.as_bytes());
-        hasher.update(secret.as_bytes());
-        let signature = hex::encode(hasher.finalize());
-        format!("{payload}:{signature}")
-    }
+    record_audit(&state, "create", &new_relative_path, "web").await;
+    Ok(Redirect::to(&route_url("/edit", &new_relative_path)).into_response())
+}
 
-    fn extract_csrf_token_from_html(html: &str) -> Option<String> {
-        let marker = r#"name="csrf_token" value=""#;
-        let start = html.find(marker)? + marker.len();
-        let remainder = &html[start..];
-        let end = remainder.find('"')?;
-        Some(remainder[..end].to_string())
-    }
+async fn upload_image_form(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<UploadImageTemplate, WebError> {
+    let path = sanitize_relative_path(params.get("path").map(|s| s.as_str()).unwrap_or(""))?;
+    let path = path.as_ref();
+    validate_directory_path(&state.target_dir, path)?;
 
-    fn build_upload_multipart_body(
-        boundary: &str,
-        path: &str,
-        csrf_token: &str,
-        file_name: &str,
-        file_bytes: &[u8],
-    ) -> Vec<u8> {
-        let mut body = Vec::new();
-        body.extend_from_slice(
-            format!(
-                "--{boundary}\r\nContent-Disposition: form-data; name=\"path\"\r\n\r\n{path}\r\n"
-            )
-            .as_bytes(),
-        );
-        body.extend_from_slice(
-            format!(
-                "--{boundary}\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{csrf_token}\r\n"
-            )
-            .as_bytes(),
-        );
-        body.extend_from_slice(
-            format!(
-                "--{boundary}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"{file_name}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
-            )
-            .as_bytes(),
-        );
-        body.extend_from_slice(file_bytes);
-        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
-        body
-    }
+    Ok(UploadImageTemplate {
+        theme,
+        assets: state.assets.clone(),
+        current_path_display: if path.is_empty() {
+            "/".to_string()
+        } else {
+            format!("/{}", path)
+        },
+        path_value: path.to_string(),
+        back_url: route_url("/", path),
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        max_upload_size: format_file_size(state.max_upload_size_bytes as u64),
+        optimize_uploads: state.upload_optimizer.is_some(),
+    })
+}
 
-    fn create_valid_png_bytes() -> Vec<u8> {
-        let image = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255]));
-        let dynamic_image = image::DynamicImage::ImageRgba8(image);
-        let mut bytes = Vec::new();
-        let mut cursor = std::io::Cursor::new(&mut bytes);
-        dynamic_image
-            .write_to(&mut cursor, image::ImageFormat::Png)
-            .expect("failed to encode valid png bytes for test");
-        bytes
+async fn upload_image(
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Redirect, WebError> {
+    let mut path = String::new();
+    let mut csrf_token = String::new();
+    let mut image_file_name: Option<String> = None;
+    let mut image_bytes: Option<Vec<u8>> = None;
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| WebError::BadRequest(format!("Invalid multipart payload: {err}")))?
+    {
+        let field_name = field.name().unwrap_or("").to_string();
+        match field_name.as_str() {
+            "path" => {
+                path = field
+                    .text()
+                    .await
+                    .map_err(|err| WebError::BadRequest(format!("Invalid path field: {err}")))?;
+            }
+            "csrf_token" => {
+                csrf_token = field.text().await.map_err(|err| {
+                    WebError::BadRequest(format!("Invalid CSRF token field: {err}"))
+                })?;
+            }
+            "image" => {
+                image_file_name = field.file_name().map(ToString::to_string);
+                let bytes = field.bytes().await.map_err(|err| {
+                    WebError::BadRequest(format!("Invalid image upload field: {err}"))
+                })?;
+                image_bytes = Some(bytes.to_vec());
+            }
+            _ => {}
+        }
     }
 
-    #[test]
-    fn test_csrf_token_generation() {
-        let secret = "test_secret";
-        let token = generate_csrf_token(secret);
+    if csrf_token.is_empty() {
+        return Err(WebError::BadRequest("Missing CSRF token".to_string()));
+    }
+    validate_csrf_token(&csrf_token, &state.csrf_secret)?;
 
-        // Token should have 3 parts separated by colons
-        let parts: Vec<&str> = token.split(':').collect();
-        assert_eq!(parts.len(), 3);
+    let raw_image_file_name =
+        image_file_name.ok_or(WebError::BadRequest("Image file is required".to_string()))?;
+    let image_bytes = image_bytes.ok_or(WebError::BadRequest(
+        "Image file payload is required".to_string(),
+    ))?;
 
-        // All parts should be non-empty
-        assert!(!parts[0].is_empty()); // timestamp
-        assert!(!parts[1].is_empty()); // nonce
-        assert!(!parts[2].is_empty()); // signature
-    }
+    let relative_path = store_image(&state, &path, &raw_image_file_name, &image_bytes).await?;
+    record_audit(&state, "upload", &relative_path, "web").await;
+    Ok(Redirect::to(&route_url("/preview", &relative_path)))
+}
 
-    #[test]
-    fn test_csrf_token_validation_valid() {
-        let secret = "test_secret";
-        let token = generate_csrf_token(secret);
+/// `POST /upload`: stores one or more files (the repeated `files` field) in
+/// the `path` directory. Every file is checked before any is written, so a
+/// rejected file leaves the directory untouched.
+async fn upload_files(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<UploadResultTemplate, WebError> {
+    let mut path = String::new();
+    let mut csrf_token = String::new();
+    let mut uploads: Vec<(String, Vec<u8>)> = Vec::new();
 
-        // Valid token should pass validation
-        assert!(validate_csrf_token(&token, secret).is_ok());
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| WebError::BadRequest(format!("Invalid multipart payload: {err}")))?
+    {
+        let field_name = field.name().unwrap_or("").to_string();
+        match field_name.as_str() {
+            "path" => {
+                path = field
+                    .text()
+                    .await
+                    .map_err(|err| WebError::BadRequest(format!("Invalid path field: {err}")))?;
+            }
+            "csrf_token" => {
+                csrf_token = field.text().await.map_err(|err| {
+                    WebError::BadRequest(format!("Invalid CSRF token field: {err}"))
+                })?;
+            }
+            "files" => {
+                let file_name = field.file_name().unwrap_or("").to_string();
+                // Reading in chunks stops an oversized file at the limit
+                // instead of buffering all of it first.
+                let mut bytes = Vec::new();
+                while let Some(chunk) = field.chunk().await.map_err(|err| {
+                    WebError::BadRequest(format!("Invalid file upload field: {err}"))
+                })? {
+                    if bytes.len() + chunk.len() > state.max_upload_size_bytes {
+                        return Err(WebError::PayloadTooLarge(format!(
+                            "'{file_name}' exceeds maximum size of {}",
+                            format_file_size(state.max_upload_size_bytes as u64)
+                        )));
+                    }
+                    bytes.extend_from_slice(&chunk);
+                }
+                // Browsers send an empty, unnamed part when no file was picked.
+                if !(file_name.is_empty() && bytes.is_empty()) {
+                    uploads.push((file_name, bytes));
+                }
+            }
+            _ => {}
+        }
     }
 
-    #[test]
-    fn test_csrf_token_validation_wrong_secret() {
-        let secret = "test_secret";
-        let wrong_secret = "wrong_secret";
-        let token = generate_csrf_token(secret);
+    if csrf_token.is_empty() {
+        return Err(WebError::BadRequest("Missing CSRF token".to_string()));
+    }
+    validate_csrf_token(&csrf_token, &state.csrf_secret)?;
 
-        // Token with wrong secret should fail validation
-        assert!(validate_csrf_token(&token, wrong_secret).is_err());
+    let path = sanitize_relative_path(&path)?;
+    let path = path.as_ref();
+    let canonical_dir = validate_directory_path(&state.target_dir, path)?;
+    if uploads.is_empty() {
+        return Err(WebError::BadRequest(
+            "At least one file is required".to_string(),
+        ));
     }
 
-    #[test]
-    fn test_csrf_token_validation_malformed() {
-        let secret = "test_secret";
+    let mut stored: Vec<(String, Vec<u8>)> = Vec::with_capacity(uploads.len());
+    for (raw_file_name, bytes) in uploads {
+        let file_name = sanitize_upload_filename(&raw_file_name, state.max_filename_length)?;
+        if is_executable_file(&file_name) {
+            return Err(WebError::BadRequest(format!(
+                "Executable files cannot be uploaded: {file_name}"
+            )));
+        }
+        if bytes.is_empty() {
+            return Err(WebError::BadRequest(format!(
+                "Uploaded file is empty: {file_name}"
+            )));
+        }
+        if is_image_file(&file_name) {
+            validate_image_bytes(&file_name, &bytes)?;
+        }
+        if stored
+            .iter()
+            .any(|(name, _)| name.eq_ignore_ascii_case(&file_name))
+        {
+            return Err(WebError::BadRequest(format!(
+                "'{file_name}' is included more than once"
+            )));
+        }
+        if let Some(existing) =
+            find_case_collision(state.case_insensitive_fs, &canonical_dir, &file_name).await?
+        {
+            return Err(case_collision_error(&existing));
+        }
+        if fs::try_exists(canonical_dir.join(&file_name)).await? {
+            return Err(WebError::Conflict(format!(
+                "File already exists: {file_name}"
+            )));
+        }
+        stored.push((file_name, bytes));
+    }
 
-        // Various malformed tokens should fail validation
-        assert!(validate_csrf_token("", secret).is_err());
-        assert!(validate_csrf_token("invalid", secret).is_err());
-        assert!(validate_csrf_token("only:two", secret).is_err());
-        assert!(validate_csrf_token("too:many:parts:here", secret).is_err());
-        assert!(validate_csrf_token(":::", secret).is_err());
-        assert!(validate_csrf_token("1:2:not_hex_signature_@", secret).is_err());
+    let mut files = Vec::with_capacity(stored.len());
+    for (file_name, bytes) in stored {
+        durable::write_new(&canonical_dir.join(&file_name), &bytes, state.durability).await?;
+        let relative_path = join_relative_path(path, &file_name);
+        record_audit(&state, "upload", &relative_path, "web").await;
+        files.push(UploadedFileView {
+            url: route_url("/", &relative_path),
+            path: relative_path,
+        });
     }
 
-    #[test]
-    fn test_csrf_token_validation_expired() {
-        let secret = "test_secret";
+    Ok(UploadResultTemplate {
+        theme,
+        assets: state.assets.clone(),
+        current_path_display: format!("/{path}"),
+        back_url: route_url("/", path),
+        files,
+    })
+}
 
-        let expired_token = create_expired_csrf_token(secret);
+/// Validates an image and writes it into `directory`, returning its relative
+/// path. Shared by uploads and URL imports.
+async fn store_image(
+    state: &AppState,
+    directory: &str,
+    file_name: &str,
+    image_bytes: &[u8],
+) -> Result<String, WebError> {
+    let canonical_dir =
+        validate_directory_path(&state.target_dir, &sanitize_relative_path(directory)?)?;
+    let normalized_image_file_name = normalize_image_filename(file_name)?;
 
-        // Expired token should fail validation
-        assert!(validate_csrf_token(&expired_token, secret).is_err());
+    if image_bytes.len() > state.max_upload_size_bytes {
+        return Err(WebError::PayloadTooLarge(format!(
+            "Uploaded image exceeds maximum size of {}",
+            format_file_size(state.max_upload_size_bytes as u64)
+        )));
     }
+    validate_image_bytes(&normalized_image_file_name, image_bytes)?;
+    let (normalized_image_file_name, image_bytes) = match state.upload_optimizer {
+        Some(optimizer) => {
+            optimize_image(optimizer, normalized_image_file_name, image_bytes).await?
+        }
+        None => (
+            normalized_image_file_name,
+            std::borrow::Cow::Borrowed(image_bytes),
+        ),
+    };
+    let image_bytes = image_bytes.as_ref();
 
-    #[test]
-    fn test_csrf_token_validation_rejects_legacy_keyed_hash_token() {
-        let secret = "test_secret";
-        let legacy_token = create_legacy_keyed_csrf_token(secret);
-        assert!(validate_csrf_token(&legacy_token, secret).is_err());
-    }
-
-    #[test]
-    fn test_csrf_token_validation_invalid_timestamp() {
-        let secret = "test_secret";
-
-        // Token with invalid timestamp should fail validation
-        let invalid_token = "invalid_timestamp:12345:signature";
-        assert!(validate_csrf_token(invalid_token, secret).is_err());
+    let full_path = canonical_dir.join(&normalized_image_file_name);
+    if let Some(existing) = find_case_collision(
+        state.case_insensitive_fs,
+        &canonical_dir,
+        &normalized_image_file_name,
+    )
+    .await?
+    {
+        return Err(case_collision_error(&existing));
     }
-
-    #[test]
-    fn test_has_draft_frontmatter_with_yaml_true() {
-        let content = r#"---
-title: Post
-draft: true
----
-# Hello
-"#;
-        assert!(has_draft_frontmatter(content));
+    if fs::try_exists(&full_path).await? {
+        return Err(WebError::BadRequest("File already exists".to_string()));
     }
 
-    #[test]
-    fn test_has_draft_frontmatter_with_yaml_false() {
-        let content = r#"---
-title: Post
-draft: false
----
-# Hello
-"#;
-        assert!(!has_draft_frontmatter(content));
-    }
+    durable::write_new(&full_path, image_bytes, state.durability).await?;
 
-    #[test]
-    fn test_has_draft_frontmatter_with_json_true() {
-        let content = r#"{
-  "title": "Post",
-  "draft": true
+    Ok(join_relative_path(directory, &normalized_image_file_name))
 }
-# Hello
-"#;
-        assert!(has_draft_frontmatter(content));
-    }
 
-    #[test]
-    fn test_has_draft_frontmatter_with_json_false() {
-        let content = r#"{
-  "title": "Post",
-  "draft": false
+/// Runs `--optimize-uploads` on a validated image, renaming it when it was
+/// converted to another format.
+async fn optimize_image(
+    optimizer: image_optimize::UploadOptimizer,
+    file_name: String,
+    image_bytes: &[u8],
+) -> Result<(String, std::borrow::Cow<'_, [u8]>), WebError> {
+    let input = image_bytes.to_vec();
+    let optimized = tokio::task::spawn_blocking(move || optimizer.optimize(&input))
+        .await
+        .map_err(|err| WebError::Internal(format!("Image optimization failed: {err}")))?;
+    let Some(optimized) = optimized else {
+        return Ok((file_name, std::borrow::Cow::Borrowed(image_bytes)));
+    };
+
+    let file_name = match optimized.extension {
+        Some(extension) => {
+            let stem = file_name
+                .rsplit_once('.')
+                .map_or(file_name.as_str(), |(stem, _)| stem);
+            format!("{stem}.{extension}")
+        }
+        None => file_name,
+    };
+    info!(
+        "Optimized {} from {} to {}",
+        file_name,
+        format_file_size(image_bytes.len() as u64),
+        format_file_size(optimized.bytes.len() as u64)
+    );
+    Ok((file_name, std::borrow::Cow::Owned(optimized.bytes)))
 }
-# Hello
-"#;
-        assert!(!has_draft_frontmatter(content));
+
+/// Picks a filename for an imported image: the explicit one if given,
+/// otherwise the URL's last path segment, falling back to the content type's
+/// extension when that segment is not an image name.
+fn import_file_name(requested: Option<&str>, remote: &remote::RemoteImage) -> String {
+    if let Some(requested) = requested.map(str::trim).filter(|name| !name.is_empty()) {
+        return requested.to_string();
     }
 
-    #[test]
-    fn test_parse_frontmatter_yaml_collects_standard_fields_and_extra() {
-        let content = r#"---
-title: "Post Title"
-date: "2026-02-15"
-draft: true
-tags:
-  - rust
-  - web
-categories: docs
-custom_score: 42
-author:
-  name: James
----
-# Hello
-"#;
+    match remote.url_file_name.as_deref() {
+        Some(name) if is_image_file(name) => name.to_string(),
+        name => {
+            let stem = name
+                .and_then(|name| Path::new(name).file_stem())
+                .and_then(|stem| stem.to_str())
+                .map(slugify)
+                .filter(|stem| !stem.is_empty())
+                .unwrap_or_else(|| "image".to_string());
+            format!("{stem}.{}", remote.extension)
+        }
+    }
+}
 
-        let parsed = parse_frontmatter(content).expect("yaml frontmatter should parse");
-        let (draft, title, date, tags, categories, extra) = parsed;
+async fn import_url(
+    State(state): State<AppState>,
+    Form(form): Form<ImportUrlForm>,
+) -> Result<Json<ImportUrlResult>, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+    validate_directory_path(&state.target_dir, &sanitize_relative_path(&form.path)?)?;
+
+    let remote = remote::fetch_remote_image(
+        &form.url,
+        state.max_upload_size_bytes,
+        state.allow_private_import_urls,
+    )
+    .await
+    .inspect_err(|err| warn!("Failed to import {}: {}", form.url, err))?;
+
+    let file_name = import_file_name(form.filename.as_deref(), &remote);
+    let relative_path = store_image(&state, &form.path, &file_name, &remote.bytes).await?;
+    info!("Imported {} to {}", form.url, relative_path);
+    record_audit(&state, "import", &relative_path, "web").await;
+
+    let alt_text = Path::new(&relative_path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string();
+    Ok(Json(ImportUrlResult {
+        markdown: format!("![{alt_text}](/{relative_path})"),
+        path: relative_path,
+    }))
+}
 
-        assert_eq!(draft, Some(true));
-        assert_eq!(title, Some("Post Title".to_string()));
-        assert_eq!(date, Some("2026-02-15".to_string()));
-        assert_eq!(tags, vec!["rust".to_string(), "web".to_string()]);
-        assert_eq!(categories, vec!["docs".to_string()]);
-        assert_eq!(
-            extra.get("custom_score").and_then(|value| value.as_i64()),
-            Some(42)
-        );
-        assert!(extra.contains_key("author"));
+/// Reads the `path`, `csrf_token` and `image` fields of a multipart
+/// `/api/upload-image` request.
+async fn read_pasted_image_form(
+    state: &AppState,
+    mut multipart: Multipart,
+) -> Result<(String, String, Vec<u8>), WebError> {
+    let mut path = String::new();
+    let mut csrf_token = String::new();
+    let mut image_bytes = Vec::new();
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|err| WebError::BadRequest(format!("Invalid multipart payload: {err}")))?
+    {
+        match field.name().unwrap_or("") {
+            "path" => {
+                path = field
+                    .text()
+                    .await
+                    .map_err(|err| WebError::BadRequest(format!("Invalid path field: {err}")))?;
+            }
+            "csrf_token" => {
+                csrf_token = field.text().await.map_err(|err| {
+                    WebError::BadRequest(format!("Invalid CSRF token field: {err}"))
+                })?;
+            }
+            "image" => {
+                while let Some(chunk) = field.chunk().await.map_err(|err| {
+                    WebError::BadRequest(format!("Invalid image upload field: {err}"))
+                })? {
+                    if image_bytes.len() + chunk.len() > state.max_upload_size_bytes {
+                        return Err(WebError::PayloadTooLarge(format!(
+                            "Pasted image exceeds maximum size of {}",
+                            format_file_size(state.max_upload_size_bytes as u64)
+                        )));
+                    }
+                    image_bytes.extend_from_slice(&chunk);
+                }
+            }
+            _ => {}
+        }
     }
-
-    #[test]
-    fn test_parse_frontmatter_json_collects_standard_fields_and_extra() {
-        let content = r#"{
-  "title": "Post Title",
-  "date": "2026-02-15",
-  "draft": "true",
-  "tags": "rust, web",
-  "categories": ["docs", "guides"],
-  "custom_score": 42
+    Ok((path, csrf_token, image_bytes))
 }
-# Hello
-"#;
 
-        let parsed = parse_frontmatter(content).expect("json frontmatter should parse");
-        let (draft, title, date, tags, categories, extra) = parsed;
+/// Decodes the base64 `data` of a JSON `/api/upload-image` request, with or
+/// without a `data:image/...;base64,` prefix.
+fn decode_pasted_image(data: &str, max_bytes: usize) -> Result<Vec<u8>, WebError> {
+    use base64::Engine;
 
-        assert_eq!(draft, Some(true));
-        assert_eq!(title, Some("Post Title".to_string()));
-        assert_eq!(date, Some("2026-02-15".to_string()));
-        assert_eq!(tags, vec!["rust".to_string(), "web".to_string()]);
-        assert_eq!(categories, vec!["docs".to_string(), "guides".to_string()]);
-        assert_eq!(
-            extra.get("custom_score").and_then(|value| value.as_i64()),
-            Some(42)
-        );
+    let encoded = match data.trim().strip_prefix("data:") {
+        Some(data_url) => data_url.split_once(',').map_or("", |(_, encoded)| encoded),
+        None => data.trim(),
+    };
+    if encoded.len() / 4 * 3 > max_bytes.saturating_add(3) {
+        return Err(WebError::PayloadTooLarge(format!(
+            "Pasted image exceeds maximum size of {}",
+            format_file_size(max_bytes as u64)
+        )));
     }
+    let encoded: String = encoded.split_ascii_whitespace().collect();
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|_| WebError::BadRequest("Pasted image is not valid base64".to_string()))
+}
 
-    #[test]
-    fn test_parse_frontmatter_without_frontmatter_returns_none() {
-        let content = "# Just markdown\n\nNo frontmatter.";
-        assert!(parse_frontmatter(content).is_none());
-    }
+/// Name for a pasted image: the post's name, the upload time and the start
+/// of the content hash, e.g. `my-post-20261016-142501-3fa2b1c4.png`.
+fn pasted_image_file_name(
+    post_path: &str,
+    bytes: &[u8],
+    now: SystemTime,
+) -> Result<String, WebError> {
+    let extension = match image::guess_format(bytes) {
+        Ok(image::ImageFormat::Png) => "png",
+        Ok(image::ImageFormat::Jpeg) => "jpg",
+        Ok(image::ImageFormat::Gif) => "gif",
+        Ok(image::ImageFormat::WebP) => "webp",
+        Ok(image::ImageFormat::Bmp) => "bmp",
+        Ok(image::ImageFormat::Tiff) => "tiff",
+        _ => {
+            return Err(WebError::BadRequest(
+                "Pasted data is not a PNG, JPEG, GIF, WebP, BMP or TIFF image".to_string(),
+            ));
+        }
+    };
+    let post_stem = Path::new(post_path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(slugify)
+        .filter(|stem| is_git_compatible_ascii_filename_stem(stem))
+        .unwrap_or_else(|| "image".to_string());
+    let timestamp = chrono::DateTime::<chrono::Utc>::from(now).format("%Y%m%d-%H%M%S");
+    let hash = hex::encode(Sha256::digest(bytes));
+    let short_hash = hash.get(..8).unwrap_or(&hash);
+    Ok(format!("{post_stem}-{timestamp}-{short_hash}.{extension}"))
+}
 
-    #[test]
-    fn test_has_draft_frontmatter_with_unterminated_yaml() {
-        let content = r#"---
-title: Post
-draft: true
-# Hello
-"#;
-        assert!(!has_draft_frontmatter(content));
+/// `POST /api/upload-image`: stores an image pasted into the editor next to
+/// the markdown file (or in `--paste-image-dir`) under a generated name and
+/// answers with the markdown that embeds it. Takes a multipart form with an
+/// `image` file or JSON with base64 `data`.
+async fn paste_image(
+    State(state): State<AppState>,
+    request: Request,
+) -> Result<Json<ImportUrlResult>, WebError> {
+    use axum::extract::FromRequest;
+
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("multipart/form-data"));
+    let (path, csrf_token, image_bytes) = if is_multipart {
+        let multipart = Multipart::from_request(request, &state)
+            .await
+            .map_err(|err| WebError::BadRequest(format!("Invalid multipart payload: {err}")))?;
+        read_pasted_image_form(&state, multipart).await?
+    } else {
+        let Json(body) = Json::<PasteImageRequest>::from_request(request, &state)
+            .await
+            .map_err(|err| WebError::BadRequest(err.body_text()))?;
+        let image_bytes = decode_pasted_image(&body.data, state.max_upload_size_bytes)?;
+        (body.path, body.csrf_token, image_bytes)
+    };
+    validate_csrf_token(&csrf_token, &state.csrf_secret)?;
+    if image_bytes.is_empty() {
+        return Err(WebError::BadRequest("Image data is required".to_string()));
     }
 
-    #[test]
-    fn test_has_draft_frontmatter_with_unterminated_json() {
-        let content = r#"{
-  "title": "Post",
-  "draft": true
-# Hello
-"#;
-        assert!(!has_draft_frontmatter(content));
+    let post_path = sanitize_relative_path(&path)?;
+    if !is_markdown_file(&post_path) {
+        return Err(WebError::BadRequest(
+            "Images can only be pasted into markdown files".to_string(),
+        ));
     }
+    validate_file_path(&state.target_dir, &post_path)?;
+    let post_directory = post_path
+        .rsplit_once('/')
+        .map_or("", |(directory, _)| directory)
+        .to_string();
+    let directory = match &state.paste_image_dir {
+        Some(directory) => {
+            let directory = sanitize_relative_path(directory)?;
+            fs::create_dir_all(state.target_dir.join(directory.as_ref())).await?;
+            directory.into_owned()
+        }
+        None => post_directory.clone(),
+    };
+    ensure_not_locked(&state, &directory)?;
 
-    #[test]
-    fn test_has_draft_frontmatter_with_invalid_json_syntax() {
-        let content = r#"{
-  "title": "Post",
-  "draft": tru
+    let file_name = pasted_image_file_name(&post_path, &image_bytes, SystemTime::now())?;
+    let relative_path = store_image(&state, &directory, &file_name, &image_bytes).await?;
+    info!("Pasted image into {}: {}", post_path, relative_path);
+    record_audit(&state, "upload", &relative_path, "web").await;
+
+    let link = relative_link(&post_directory, &relative_path);
+    let link = if link.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+        format!("<{link}>")
+    } else {
+        link
+    };
+    Ok(Json(ImportUrlResult {
+        markdown: format!("![]({link})"),
+        path: relative_path,
+    }))
 }
-# Hello
-"#;
-        assert!(!has_draft_frontmatter(content));
+
+async fn edit_file(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<EditorTemplate, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+
+    if !is_editable_file(file_path) {
+        return Err(WebError::BadRequest(
+            "File is not a markdown or text document file".to_string(),
+        ));
     }
 
-    #[tokio::test]
-    async fn test_save_endpoint_without_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
 
-        // Create a test markdown file
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
+    let file_identity =
+        file_identity::FileIdentity::of(&fs::metadata(&full_path).await?).sign(&state.csrf_secret);
+    let content = fs::read_to_string(&full_path).await?;
+    let is_markdown = is_markdown_file(file_path);
+    let is_draft = is_markdown && has_draft_frontmatter(&content, state.max_frontmatter_bytes);
+    let missing_assets = if is_markdown {
+        missing_frontmatter_assets(&state, &sanitize_relative_path(file_path)?, &content).await
+    } else {
+        Vec::new()
+    };
+    let extra_frontmatter = if is_markdown {
+        extra_frontmatter_fields(&content, state.max_frontmatter_bytes)
+    } else {
+        Vec::new()
+    };
+    let csrf_token = generate_csrf_token(&state.csrf_secret);
+    let cancel_url = parent_url(file_path);
+    let modified = get_file_modified(&full_path)
+        .await
+        .inspect_err(|err| warn!("Failed to read mtime of {}: {}", file_path, err))
+        .ok();
+    let modified_time = modified
+        .map(|time| timefmt::epoch_seconds(time).to_string())
+        .unwrap_or_default();
+    let edit_summary = match &state.audit {
+        Some(audit) => audit
+            .file_stats(&sanitize_relative_path(file_path)?)
             .await
-            .expect("Failed to write test file");
+            .inspect_err(|err| warn!("Failed to read edit stats for {}: {}", file_path, err))
+            .ok()
+            .flatten()
+            .map(|stats| describe_file_stats(&stats, state.display_offset)),
+        None => None,
+    };
+    Ok(EditorTemplate {
+        theme,
+        assets: state.assets.clone(),
+        client_config: client_config::ClientConfig::new(&state),
+        file_path: file_path.to_string(),
+        file_name: base_name(file_path).to_string(),
+        content,
+        csrf_expires_in: csrf_token_remaining_seconds(&csrf_token),
+        csrf_token,
+        is_draft,
+        missing_assets,
+        extra_frontmatter,
+        is_markdown,
+        cancel_url,
+        modified_time,
+        file_identity,
+        modified: modified.map(|time| timefmt::HumanTime::new(time, state.display_offset)),
+        edit_summary,
+        bundle_delete_url: leaf_bundle_directory(file_path)
+            .map(|directory| route_url("/delete-dir", directory)),
+        confirm_delete_name: state.confirm_delete_name,
+    })
+}
 
-        // Try to save without CSRF token
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/save")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from("path=test.md&content=# Updated Content"))
-            .expect("failed to build save request without csrf token");
+/// Characters of a custom frontmatter value shown in the editor.
+const EXTRA_FIELD_CHARS: usize = 200;
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("failed to send save request without csrf token");
+/// The `extra` frontmatter of `content` sorted by key, strings as written
+/// and other values as compact JSON.
+fn extra_frontmatter_fields(content: &str, max_bytes: usize) -> Vec<FrontmatterFieldView> {
+    let Some((.., extra)) = parse_frontmatter(content, max_bytes, SCALARS_ONLY) else {
+        return Vec::new();
+    };
+    let mut fields: Vec<FrontmatterFieldView> = extra
+        .into_iter()
+        .map(|(key, value)| {
+            let mut value = match value {
+                serde_json::Value::String(text) => text,
+                value => value.to_string(),
+            };
+            if let Some((cut, _)) = value.char_indices().nth(EXTRA_FIELD_CHARS) {
+                value.truncate(cut);
+                value.push('…');
+            }
+            FrontmatterFieldView { key, value }
+        })
+        .collect();
+    fields.sort_by(|a, b| a.key.cmp(&b.key));
+    fields
+}
 
-        // Should return 422 Unprocessable Entity due to missing CSRF token
-        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+/// `key: path` for each frontmatter image of `content` that resolves to no
+/// file, looked up as [`checks::MissingAssetsCheck`] does.
+async fn missing_frontmatter_assets(
+    state: &AppState,
+    file_path: &str,
+    content: &str,
+) -> Vec<String> {
+    let mut missing = Vec::new();
+    for (key, reference) in checks::frontmatter_asset_references(
+        content,
+        state.max_frontmatter_bytes,
+        &state.frontmatter_asset_keys,
+    ) {
+        let mut found = false;
+        for candidate in checks::asset_candidates(file_path, &reference) {
+            if fs::metadata(state.target_dir.join(&candidate))
+                .await
+                .is_ok_and(|metadata| metadata.is_file())
+            {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            missing.push(format!("{key}: {reference}"));
+        }
     }
+    missing
+}
 
-    #[tokio::test]
-    async fn test_save_endpoint_with_invalid_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
+/// The last component of a relative path.
+fn base_name(path: &str) -> &str {
+    path.rsplit_once('/').map_or(path, |(_, name)| name)
+}
 
-        // Create a test markdown file
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
-            .await
-            .expect("Failed to write test file");
+/// The folder of a Hugo leaf bundle when `path` is its `index.md`: the page
+/// and its resources live and die together. `_index.md` (a branch bundle)
+/// and a top-level `index.md` are ordinary files.
+fn leaf_bundle_directory(path: &str) -> Option<&str> {
+    let (directory, name) = path.trim_matches('/').rsplit_once('/')?;
+    let is_index =
+        name.eq_ignore_ascii_case("index.md") || name.eq_ignore_ascii_case("index.markdown");
+    (is_index && !directory.is_empty()).then_some(directory)
+}
 
-        // Try to save with invalid CSRF token
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/save")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(
-                "path=test.md&content=# Updated Content&csrf_token=invalid",
-            ))
-            .expect("failed to build save request with invalid csrf token");
+/// Compact history line for the editor, e.g. "Edited 14 times, last via web
+/// on Feb 3".
+fn describe_file_stats(stats: &audit::FileStats, offset: chrono::FixedOffset) -> String {
+    let times = if stats.edits == 1 { "time" } else { "times" };
+    let date = UNIX_EPOCH
+        .checked_add(std::time::Duration::from_secs(stats.last.timestamp))
+        .map(|time| timefmt::short_date(time, offset))
+        .unwrap_or_else(|| "an unknown date".to_string());
+    format!(
+        "Edited {} {times}, last via {} on {date}",
+        stats.edits, stats.last.via
+    )
+}
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("failed to send save request with invalid csrf token");
+async fn save_file(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    Form(form): Form<EditForm>,
+) -> Result<Response, WebError> {
+    // Validate CSRF token
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
 
-        // Should return 403 Forbidden due to invalid CSRF token
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    if !is_editable_file(&form.path) {
+        return Err(WebError::BadRequest(
+            "File is not a markdown or text document file".to_string(),
+        ));
+    }
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("failed to collect response body for invalid csrf save")
-            .to_bytes();
-        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
-        assert!(
-            body_text
-                .to_ascii_lowercase()
-                .contains("invalid csrf token")
-        );
+    let content = form
+        .content
+        .ok_or(WebError::BadRequest("Missing content field".to_string()))?;
 
-        // File should remain unchanged
-        let content = fs::read_to_string(&test_file)
-            .await
-            .expect("failed to read file");
-        assert_eq!(content, "# Test");
+    let full_path = validate_file_path(&state.target_dir, &form.path)?;
+    ensure_not_locked(&state, &form.path)?;
+
+    let file_identity = form.file_identity.as_deref().unwrap_or_default();
+    if !file_identity.is_empty() {
+        let loaded = file_identity::FileIdentity::verify(file_identity, &state.csrf_secret)?;
+        let metadata = fs::metadata(&full_path).await?;
+        let current = file_identity::FileIdentity::of(&metadata);
+        if current != loaded {
+            info!(
+                "Refusing save of {}: file changed since the editor loaded it ({:?} now, {:?} then)",
+                form.path, current, loaded
+            );
+            let page = SaveConflictTemplate {
+                theme,
+                assets: state.assets.clone(),
+                file_size: format_file_size(metadata.len()),
+                modified_display: timefmt::absolute(
+                    get_file_modified(&full_path).await?,
+                    state.display_offset,
+                ),
+                content,
+                csrf_token: generate_csrf_token(&state.csrf_secret),
+                file_identity: current.sign(&state.csrf_secret),
+                action: form.action,
+                edit_url: route_url("/edit", &form.path),
+                back_url: parent_url(&form.path),
+                file_path: form.path,
+            };
+            return Ok((StatusCode::CONFLICT, page).into_response());
+        }
+    }
+    if let Some(base_modified_time) = form.base_modified_time.as_deref()
+        && !base_modified_time.is_empty()
+    {
+        let modified_time = get_file_modification_time(&full_path).await?;
+        if modified_time != base_modified_time {
+            info!(
+                "Refusing save of {}: modified at {} on disk, {} when loaded",
+                form.path, modified_time, base_modified_time
+            );
+            return Err(WebError::Conflict(format!(
+                "{} was changed by someone else after you opened it, so it was not saved. Reload the editor to see the current version.",
+                form.path
+            )));
+        }
     }
 
-    #[tokio::test]
-    async fn test_save_endpoint_with_expired_csrf_token() {
-        let (app, temp_dir, csrf_secret) = create_test_app().await;
-
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
-            .await
-            .expect("Failed to write test file");
+    // Read existing content to check if it has changed
+    let existing_content = fs::read_to_string(&full_path).await?;
+    let comparison_note = save_normalize::describe(state.save_normalize, state.save_comparison);
+    let mut to_write = save_normalize::content_to_write(
+        &existing_content,
+        &content,
+        state.save_normalize,
+        state.save_comparison,
+    );
+    if form.action == SaveAction::ToggleDraft {
+        if !is_markdown_file(&form.path) {
+            return Err(WebError::BadRequest(
+                "File is not a markdown file".to_string(),
+            ));
+        }
+        let base = to_write.as_deref().unwrap_or(&existing_content);
+        let draft = !has_draft_frontmatter(base, state.max_frontmatter_bytes);
+        let toggled = set_draft_flag(base, draft, state.max_frontmatter_bytes)?;
+        if toggled != existing_content {
+            to_write = Some(std::borrow::Cow::Owned(toggled));
+        }
+    }
+    let Some(new_content) = to_write else {
+        // Content hasn't changed, don't write to disk
+        info!("File content unchanged, skipping write: {}", form.path);
+        if form.action == SaveAction::SaveAndClose {
+            return Ok(Redirect::to(&parent_url(&form.path)).into_response());
+        }
+        let back_url = parent_url(&form.path);
+        let edit_url = route_url("/edit", &form.path);
+        return Ok(StatusPageTemplate {
+            theme,
+            assets: state.assets.clone(),
+            title: "File Unchanged - Markdown Wrangler".to_string(),
+            heading: "ℹ️ No Changes to Save".to_string(),
+            heading_class: "success".to_string(),
+            file_path: form.path,
+            detail_text: format!("content is unchanged ({comparison_note})."),
+            show_edit_button: true,
+            show_trash_link: false,
+            edit_url,
+            back_url,
+        }
+        .into_response());
+    };
 
-        let expired_token = create_expired_csrf_token(&csrf_secret);
-        let body = format!(
-            "path=test.md&content=# Updated Content&csrf_token={}",
-            urlencoding::encode(&expired_token)
+    // Only guard files whose frontmatter parsed before this edit, so
+    // files without frontmatter (or already broken) save as before.
+    if !form.force
+        && extract_frontmatter(&existing_content, state.max_frontmatter_bytes).is_some()
+        && frontmatter_syntax_error(&existing_content, state.max_frontmatter_bytes).is_none()
+        && let Some(err) = frontmatter_syntax_error(&content, state.max_frontmatter_bytes)
+    {
+        info!(
+            "Refusing save of {} with unparseable frontmatter: {}",
+            form.path, err.message
         );
+        let page = FrontmatterErrorTemplate {
+            theme,
+            assets: state.assets.clone(),
+            error_location: err.location_text(),
+            error_message: err.message,
+            content,
+            csrf_token: generate_csrf_token(&state.csrf_secret),
+            file_identity: form.file_identity.unwrap_or_default(),
+            base_modified_time: form.base_modified_time.unwrap_or_default(),
+            action: form.action,
+            edit_url: route_url("/edit", &form.path),
+            back_url: parent_url(&form.path),
+            file_path: form.path,
+        };
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response());
+    }
 
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/save")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body))
-            .expect("failed to build save request with expired csrf token");
+    // Content has changed, write to disk
+    let started = std::time::Instant::now();
+    durable::write_atomic(&full_path, new_content.as_bytes(), state.durability).await?;
+    state.save_latency.record(started.elapsed());
+
+    info!("File saved successfully: {}", form.path);
+    record_audit(&state, "save", &form.path, "web").await;
+    let back_url = parent_url(&form.path);
+    let edit_url = route_url("/edit", &form.path);
+    match form.action {
+        SaveAction::Save => {}
+        SaveAction::SaveAndClose => return Ok(Redirect::to(&back_url).into_response()),
+        SaveAction::ToggleDraft => return Ok(Redirect::to(&edit_url).into_response()),
+    }
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("failed to send save request with expired csrf token");
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    Ok(StatusPageTemplate {
+        theme,
+        assets: state.assets.clone(),
+        title: "File Saved - Markdown Wrangler".to_string(),
+        heading: "✅ File Saved Successfully!".to_string(),
+        heading_class: "success".to_string(),
+        file_path: form.path.to_string(),
+        detail_text: format!("has been saved ({comparison_note})."),
+        show_edit_button: true,
+        show_trash_link: false,
+        edit_url,
+        back_url,
+    }
+    .into_response())
+}
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("failed to collect response body for expired csrf save")
-            .to_bytes();
-        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
-        assert!(
-            body_text
-                .to_ascii_lowercase()
-                .contains("invalid csrf token")
-        );
+async fn preview_image(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<ImagePreviewTemplate, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?
+        .to_owned();
 
-        // File should remain unchanged
-        let content = fs::read_to_string(&test_file)
-            .await
-            .expect("Failed to read test file");
-        assert_eq!(content, "# Test");
+    if !is_image_file(&file_path) {
+        return Err(WebError::BadRequest(
+            "File is not an image file".to_string(),
+        ));
     }
 
-    #[tokio::test]
-    async fn test_save_endpoint_with_valid_csrf_token() {
-        let (app, temp_dir, csrf_secret) = create_test_app().await;
-
-        // Create a test markdown file
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
-            .await
-            .expect("Failed to write test file");
+    let full_path = validate_file_path(&state.target_dir, &file_path)?;
 
-        // Generate valid CSRF token
-        let csrf_token = generate_csrf_token(&csrf_secret);
+    let csrf_token = generate_csrf_token(&state.csrf_secret);
+    let parent_path = parent_url(&file_path);
+    let encoded_path = urlencoding::encode(&file_path).into_owned();
+    let file_size = get_file_size(&full_path).await.map(format_file_size)?;
+    let modified_time = get_file_modification_time(&full_path)
+        .await
+        .unwrap_or_default();
+    Ok(ImagePreviewTemplate {
+        theme,
+        assets: state.assets.clone(),
+        file_name: base_name(&file_path).to_string(),
+        encoded_path,
+        parent_path,
+        file_path,
+        file_size,
+        csrf_token,
+        modified_time,
+        confirm_delete_name: state.confirm_delete_name,
+    })
+}
 
-        // Save with valid CSRF token
-        let body = format!(
-            "path=test.md&content=# Updated Content&csrf_token={}",
-            urlencoding::encode(&csrf_token)
-        );
+/// Waits for a free file-serving slot. Requests beyond the limit queue
+/// rather than fail.
+async fn acquire_file_serve(state: &AppState) -> Result<OwnedSemaphorePermit, WebError> {
+    state
+        .file_serves
+        .clone()
+        .acquire_owned()
+        .await
+        .map_err(|_| WebError::Internal("File serving is unavailable".to_string()))
+}
 
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/save")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body))
-            .expect("failed to build save request with valid csrf token");
+/// Response body that keeps its file-serving slot until it has been sent.
+struct PermitBody {
+    body: axum::body::Body,
+    _permit: OwnedSemaphorePermit,
+}
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("failed to send save request with valid csrf token");
+impl http_body::Body for PermitBody {
+    type Data = axum::body::Bytes;
+    type Error = axum::Error;
 
-        // Should succeed
-        assert_eq!(response.status(), StatusCode::OK);
+    fn poll_frame(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<http_body::Frame<Self::Data>, Self::Error>>> {
+        std::pin::Pin::new(&mut self.body).poll_frame(cx)
+    }
 
-        // Verify file content was updated
-        let content = fs::read_to_string(&test_file)
-            .await
-            .expect("Failed to read test file");
-        assert_eq!(content, "# Updated Content");
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
     }
 
-    #[tokio::test]
-    async fn test_delete_endpoint_without_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
+    fn size_hint(&self) -> http_body::SizeHint {
+        self.body.size_hint()
+    }
+}
 
-        // Create a test markdown file
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
-            .await
-            .expect("Failed to write test file");
+async fn serve_image(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<axum::response::Response, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
 
-        // Try to delete without CSRF token
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/delete")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from("path=test.md"))
-            .expect("failed to build delete request without csrf token");
+    if !is_image_file(file_path) {
+        return Err(WebError::BadRequest(
+            "File is not an image file".to_string(),
+        ));
+    }
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("failed to send delete request without csrf token");
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
+    let file_contents = {
+        let _permit = acquire_file_serve(&state).await?;
+        fs::read(&full_path).await?
+    };
+    // Determine content type based on file extension
+    let content_type = match full_path.extension().and_then(|s| s.to_str()) {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        Some("bmp") => "image/bmp",
+        Some("tiff") | Some("tif") => "image/tiff",
+        _ => "application/octet-stream",
+    };
 
-        // Should return 422 Unprocessable Entity due to missing CSRF token
-        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let mut response = Response::new(axum::body::Body::from(file_contents));
 
-        // File should still exist
-        assert!(test_file.exists());
-    }
+    let header_value = match HeaderValue::from_str(content_type) {
+        Ok(value) => value,
+        Err(err) => {
+            warn!(
+                "Invalid content type '{}' for image response, using fallback: {}",
+                content_type, err
+            );
+            HeaderValue::from_static("application/octet-stream")
+        }
+    };
+    response.headers_mut().insert("Content-Type", header_value);
 
-    #[tokio::test]
-    async fn test_delete_endpoint_with_valid_csrf_token() {
-        let (app, temp_dir, csrf_secret) = create_test_app().await;
+    Ok(response)
+}
 
-        // Create a test markdown file
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
-            .await
-            .expect("Failed to write test file");
+async fn preview_file(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<FilePreviewTemplate, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
 
-        // Generate valid CSRF token
-        let csrf_token = generate_csrf_token(&csrf_secret);
+    // Don't preview editable or image files with this handler
+    if is_editable_file(file_path) || is_image_file(file_path) {
+        return Err(WebError::BadRequest(
+            "Use specific handlers for markdown and image files".to_string(),
+        ));
+    }
 
-        // Delete with valid CSRF token
-        let body = format!(
-            "path=test.md&csrf_token={}",
-            urlencoding::encode(&csrf_token)
-        );
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
+    let csrf_token = generate_csrf_token(&state.csrf_secret);
+    let modified_time = get_file_modification_time(&full_path)
+        .await
+        .unwrap_or_default();
+    let size_bytes = get_file_size(&full_path)
+        .await
+        .inspect_err(|err| warn!("Failed to get file size: {}", err))
+        .ok();
 
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/delete")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body))
-            .expect("failed to build delete request with valid csrf token");
+    let command_preview = match &state.preview_commands {
+        Some(commands) if commands.handles(file_path) => {
+            let _permit = acquire_file_serve(&state).await?;
+            commands.render(file_path, &full_path).await
+        }
+        _ => None,
+    };
+    if let Some(preview_commands::PreviewOutput::Failed { reason, .. }) = &command_preview {
+        warn!("Preview command failed for {}: {}", file_path, reason);
+    }
 
-        let response = app
-            .oneshot(request)
+    let mut pdf_page_count = None;
+    let mut pdf_title = None;
+    let mut download_only_reason = None;
+    if is_pdf_file(file_path) && size_bytes.is_some_and(|size| size <= pdf::MAX_PDF_INSPECT_BYTES) {
+        let bytes = fs::read(&full_path).await?;
+        let inspection = tokio::task::spawn_blocking(move || pdf::inspect_pdf(&bytes))
             .await
-            .expect("failed to send delete request with valid csrf token");
-
-        // Should succeed
-        assert_eq!(response.status(), StatusCode::OK);
-
-        // File should be deleted
-        assert!(!test_file.exists());
+            .map_err(|err| WebError::Internal(format!("PDF inspection failed: {err}")))?;
+        match inspection {
+            pdf::PdfInspection::Viewable { page_count, title } => {
+                pdf_page_count = page_count;
+                pdf_title = title;
+            }
+            pdf::PdfInspection::Unavailable(reason) => {
+                info!("PDF preview unavailable for {}: {}", file_path, reason);
+                download_only_reason = Some(reason);
+            }
+        }
     }
 
-    #[tokio::test]
-    async fn test_delete_endpoint_with_invalid_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
+    Ok(FilePreviewTemplate {
+        theme,
+        assets: state.assets.clone(),
+        file_path: file_path.to_string(),
+        file_name: base_name(file_path).to_string(),
+        encoded_path: urlencoding::encode(file_path).into_owned(),
+        // Fall back to "Unknown" when the size could not be read
+        file_size: size_bytes.map_or_else(|| "Unknown".to_string(), format_file_size),
+        file_type: get_file_type_description(file_path).to_string(),
+        csrf_token,
+        can_iframe: !state.disable_iframe_preview
+            && is_safe_for_iframe(file_path)
+            && download_only_reason.is_none(),
+        iframe_preview_disabled: state.disable_iframe_preview && is_safe_for_iframe(file_path),
+        modified_time,
+        pdf_page_count,
+        pdf_title,
+        download_only_reason,
+        command_preview,
+        confirm_delete_name: state.confirm_delete_name,
+    })
+}
 
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
-            .await
-            .expect("Failed to write test file");
+fn is_pdf_file(path: &str) -> bool {
+    path.to_lowercase().ends_with(".pdf")
+}
 
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/delete")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from("path=test.md&csrf_token=invalid"))
-            .expect("Failed to build request");
+/// Parses a single `bytes=` range against a body of `len` bytes, returning
+/// the inclusive start/end. `None` means the header should be ignored and the
+/// full body served; `Some(Err(()))` means the range is unsatisfiable.
+fn parse_byte_range(value: &str, len: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        // Multipart ranges are not supported; serving the whole body is allowed.
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let range = if start.is_empty() {
+        let suffix: u64 = end.parse().ok()?;
+        if suffix == 0 || len == 0 {
+            return Some(Err(()));
+        }
+        (len.saturating_sub(suffix), len - 1)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            len.saturating_sub(1)
+        } else {
+            end.parse::<u64>().ok()?.min(len.saturating_sub(1))
+        };
+        if start >= len || start > end {
+            return Some(Err(()));
+        }
+        (start, end)
+    };
+    Some(Ok(range))
+}
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
-        assert!(
-            body_text
-                .to_ascii_lowercase()
-                .contains("invalid csrf token")
-        );
+/// Builds a `Content-Disposition` value with a quoted, ASCII-only filename.
+fn content_disposition(disposition: &str, file_path: &str) -> String {
+    let file_name: String = Path::new(file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file")
+        .chars()
+        .filter(|ch| ch.is_ascii_graphic() || *ch == ' ')
+        .filter(|ch| !matches!(ch, '"' | '\\'))
+        .collect();
+    format!("{disposition}; filename=\"{file_name}\"")
+}
 
-        // File should still exist
-        assert!(test_file.exists());
+async fn serve_file(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+) -> Result<axum::response::Response, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+
+    // Only serve safe files
+    if !is_safe_for_iframe(file_path) || is_executable_file(file_path) {
+        return Err(WebError::Forbidden(
+            "File type not allowed for security reasons".to_string(),
+        ));
     }
 
-    #[tokio::test]
-    async fn test_delete_endpoint_with_expired_csrf_token() {
-        let (app, temp_dir, csrf_secret) = create_test_app().await;
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
 
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test")
-            .await
-            .expect("Failed to write test file");
+    let file_contents = {
+        let _permit = acquire_file_serve(&state).await?;
+        fs::read(&full_path).await?
+    };
+    // Determine content type based on file extension
+    let content_type = match full_path.extension().and_then(|s| s.to_str()) {
+        Some("txt") | Some("log") => "text/plain; charset=utf-8",
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "application/javascript; charset=utf-8",
+        Some("json") => "application/json; charset=utf-8",
+        Some("xml") => "application/xml; charset=utf-8",
+        Some("pdf") => "application/pdf",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("yml") | Some("yaml") => "text/yaml; charset=utf-8",
+        Some("toml") => "text/plain; charset=utf-8",
+        Some("ini") | Some("conf") | Some("cfg") => "text/plain; charset=utf-8",
+        _ => "text/plain; charset=utf-8",
+    };
 
-        let expired_token = create_expired_csrf_token(&csrf_secret);
-        let body = format!(
-            "path=test.md&csrf_token={}",
-            urlencoding::encode(&expired_token)
-        );
+    let modified = fs::metadata(&full_path).await?.modified()?;
+    let etag = content_etag(&file_contents);
+    let total_len = file_contents.len() as u64;
+
+    // A range only applies while the client's cached copy is still current.
+    let range = request_headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| {
+            request_headers
+                .get(header::IF_RANGE)
+                .and_then(|value| value.to_str().ok())
+                .is_none_or(|if_range| if_range.trim() == etag)
+        })
+        .and_then(|value| parse_byte_range(value, total_len));
 
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/delete")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body))
-            .expect("failed to build delete request with expired csrf token");
+    let mut response = if is_not_modified(&request_headers, &etag, modified) {
+        let mut response = Response::new(axum::body::Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        response
+    } else {
+        match range {
+            Some(Ok((start, end))) => {
+                let body = file_contents
+                    .get(start as usize..=end as usize)
+                    .unwrap_or_default()
+                    .to_vec();
+                let mut response = Response::new(axum::body::Body::from(body));
+                *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                if let Ok(value) =
+                    HeaderValue::from_str(&format!("bytes {start}-{end}/{total_len}"))
+                {
+                    response.headers_mut().insert(header::CONTENT_RANGE, value);
+                }
+                response
+            }
+            Some(Err(())) => {
+                let mut response = Response::new(axum::body::Body::empty());
+                *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+                if let Ok(value) = HeaderValue::from_str(&format!("bytes */{total_len}")) {
+                    response.headers_mut().insert(header::CONTENT_RANGE, value);
+                }
+                response
+            }
+            None => Response::new(axum::body::Body::from(file_contents)),
+        }
+    };
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("failed to send delete request with expired csrf token");
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let headers = response.headers_mut();
+    headers.insert("Content-Type", HeaderValue::from_static(content_type));
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
-        assert!(
-            body_text
-                .to_ascii_lowercase()
-                .contains("invalid csrf token")
+    headers.insert(
+        "X-Content-Type-Options",
+        HeaderValue::from_static("nosniff"),
+    );
+    headers.insert("X-Frame-Options", HeaderValue::from_static("SAMEORIGIN"));
+    headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static("private, no-cache"),
+    );
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format_http_date(modified)) {
+        headers.insert(header::LAST_MODIFIED, value);
+    }
+    let disposition = if params.get("download").is_some_and(|value| value == "1") {
+        Some("attachment")
+    } else if is_pdf_file(file_path) {
+        Some("inline")
+    } else {
+        None
+    };
+    if let Some(disposition) = disposition
+        && let Ok(value) = HeaderValue::from_str(&content_disposition(disposition, file_path))
+    {
+        headers.insert(header::CONTENT_DISPOSITION, value);
+    }
+    if state.disable_iframe_preview {
+        headers.insert("X-Frame-Options", HeaderValue::from_static("DENY"));
+        headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("frame-ancestors 'none'"),
         );
-
-        // File should still exist
-        assert!(test_file.exists());
     }
 
-    #[tokio::test]
-    async fn test_edit_page_contains_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
+    Ok(response)
+}
 
-        // Create a test markdown file
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(&test_file, "# Test Content")
-            .await
-            .expect("Failed to write test file");
+/// Strong entity tag derived from the file bytes: the quoted
+/// `content_hash` that `/file-info` reports.
+fn content_etag(bytes: &[u8]) -> String {
+    format!("\"{}\"", content_hash::hash_bytes(bytes))
+}
 
-        // Request the edit page
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/edit?path=test.md")
-            .body(Body::empty())
-            .expect("Failed to build request");
+/// Formats a timestamp as an HTTP date (RFC 7231 IMF-fixdate).
+fn format_http_date(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    chrono::DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(SystemTime::from)
+}
 
-        // Get response body
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+/// Truncates to whole seconds, the precision of HTTP dates.
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
 
-        // Verify CSRF token is present in both forms
-        assert!(html.contains(r#"name="csrf_token""#));
+fn etag_list_matches(header_value: &str, etag: &str) -> bool {
+    header_value
+        .split(',')
+        .map(|candidate| candidate.trim().trim_start_matches("W/"))
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
 
-        // Should have at least 2 CSRF token fields (save form and delete form)
-        let csrf_count = html.matches(r#"name="csrf_token""#).count();
-        assert_eq!(csrf_count, 2);
+/// Evaluates `If-None-Match` / `If-Modified-Since` for a conditional GET.
+/// `If-None-Match` takes precedence when both are present.
+fn is_not_modified(headers: &HeaderMap, etag: &str, modified: SystemTime) -> bool {
+    if let Some(if_none_match) = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+    {
+        return etag_list_matches(if_none_match, etag);
+    }
 
-        // Root-level files should still cancel back to root.
-        assert!(html.contains(r#"class="button-link cancel" href="/""#));
-        assert!(!html.contains("← Back to file browser"));
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date)
+        .is_some_and(|since| unix_seconds(modified) <= unix_seconds(since))
+}
+
+async fn serve_raw(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+) -> Result<Response, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+
+    if is_executable_file(file_path) || !is_raw_text_file(file_path) {
+        return Err(WebError::Forbidden(
+            "File type not allowed for raw access".to_string(),
+        ));
     }
 
-    #[tokio::test]
-    async fn test_edit_page_cancel_link_points_to_parent_directory() {
-        let (app, temp_dir, _) = create_test_app().await;
-        let nested_dir = temp_dir.path().join("content/post/2026");
-        fs::create_dir_all(&nested_dir)
-            .await
-            .expect("Failed to create nested test directory");
-        let test_file = nested_dir.join("nested.md");
-        fs::write(&test_file, "# Nested")
-            .await
-            .expect("Failed to write nested test file");
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
+    let file_contents = {
+        let _permit = acquire_file_serve(&state).await?;
+        fs::read(&full_path).await?
+    };
+    let modified = fs::metadata(&full_path).await?.modified()?;
+    let etag = content_etag(&file_contents);
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/edit?path=content/post/2026/nested.md")
-            .body(Body::empty())
-            .expect("Failed to build nested edit request");
+    let content_type = if is_markdown_file(file_path) {
+        "text/markdown; charset=utf-8"
+    } else {
+        "text/plain; charset=utf-8"
+    };
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("Failed to send nested edit request");
-        assert_eq!(response.status(), StatusCode::OK);
+    let mut response = if is_not_modified(&request_headers, &etag, modified) {
+        let mut response = Response::new(axum::body::Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        response
+    } else {
+        let mut response = Response::new(axum::body::Body::from(file_contents));
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        response
+    };
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect nested edit response body")
-            .to_bytes();
-        let html = String::from_utf8(body.to_vec())
-            .expect("Failed to parse nested edit response body as UTF-8");
-
-        assert!(html.contains(r#"class="button-link cancel" href="/?path=content%2Fpost%2F2026""#));
-        assert!(!html.contains("← Back to file browser"));
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format_http_date(modified)) {
+        headers.insert(header::LAST_MODIFIED, value);
     }
+    headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
 
-    #[tokio::test]
-    async fn test_edit_page_shows_draft_flag_for_yaml_frontmatter() {
-        let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(
-            &test_file,
-            "---\ntitle: Test\ndraft: true\n---\n# Test Content",
-        )
-        .await
-        .expect("Failed to write test file with yaml frontmatter");
+    Ok(response)
+}
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/edit?path=test.md")
-            .body(Body::empty())
-            .expect("Failed to build request");
+/// Evaluates `If-Match` / `If-Unmodified-Since` before a write. `current` is
+/// the ETag and modification time of the existing file, or `None` when the
+/// file does not exist yet.
+fn write_precondition_failed(headers: &HeaderMap, current: Option<(&str, SystemTime)>) -> bool {
+    if let Some(if_match) = headers
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok())
+    {
+        return current.is_none_or(|(etag, _)| !etag_list_matches(if_match, etag));
+    }
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+    headers
+        .get(header::IF_UNMODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_http_date)
+        .zip(current)
+        .is_some_and(|(since, (_, modified))| unix_seconds(modified) > unix_seconds(since))
+}
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(html.contains(r#"class="draft-flag""#));
+/// Merges a JSON object into a markdown file's frontmatter (`null` deletes a
+/// key). Like `PUT /raw` this is an API write: it needs the bearer token and
+/// honours `If-Match` / `If-Unmodified-Since`.
+async fn patch_frontmatter_api(
+    _auth: ApiToken,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    Json(patch): Json<serde_json::Value>,
+) -> Result<Response, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+    let file_path = sanitize_relative_path(file_path)?;
+    let file_path = file_path.as_ref();
+    if !is_markdown_file(file_path) {
+        return Err(WebError::BadRequest(
+            "File is not a markdown file".to_string(),
+        ));
     }
+    let serde_json::Value::Object(patch) = patch else {
+        return Err(WebError::BadRequest(
+            "Patch must be a JSON object".to_string(),
+        ));
+    };
 
-    #[tokio::test]
-    async fn test_edit_page_shows_draft_flag_for_json_frontmatter() {
-        let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(
-            &test_file,
-            "{\n  \"title\": \"Test\",\n  \"draft\": true\n}\n# Test Content",
-        )
-        .await
-        .expect("Failed to write test file with json frontmatter");
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
+    let content = fs::read_to_string(&full_path).await?;
+    let modified = fs::metadata(&full_path).await?.modified()?;
+    let current_etag = content_etag(content.as_bytes());
+    if write_precondition_failed(&request_headers, Some((&current_etag, modified))) {
+        return Err(WebError::PreconditionFailed(
+            "File has changed since it was fetched".to_string(),
+        ));
+    }
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/edit?path=test.md")
-            .body(Body::empty())
-            .expect("Failed to build request");
+    let (patched, frontmatter) = patch_frontmatter(&content, patch, state.max_frontmatter_bytes)?;
+    if patched != content {
+        durable::write_atomic(&full_path, patched.as_bytes(), state.durability).await?;
+        info!("Frontmatter patched via API: {}", file_path);
+        record_audit(&state, "write", file_path, "api").await;
+    }
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+    let etag = content_etag(patched.as_bytes());
+    let mut response = Json(FrontmatterPatchResult {
+        path: file_path.to_string(),
+        etag: etag.clone(),
+        modified: timefmt::ModifiedTimes::new(get_file_modified(&full_path).await?),
+        frontmatter,
+    })
+    .into_response();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+    Ok(response)
+}
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(html.contains(r#"class="draft-flag""#));
+async fn put_raw(
+    _auth: ApiToken,
+    Query(params): Query<RawWriteParams>,
+    State(state): State<AppState>,
+    request_headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<Response, WebError> {
+    let file_path = sanitize_relative_path(&params.path)?;
+    let file_path = file_path.as_ref();
+    if file_path.split('/').any(|part| part.starts_with('.')) {
+        return Err(WebError::NotFound("Not found".to_string()));
     }
 
-    #[tokio::test]
-    async fn test_edit_page_hides_draft_flag_when_not_draft() {
-        let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("test.md");
-        fs::write(
-            &test_file,
-            "---\ntitle: Test\ndraft: false\n---\n# Test Content",
-        )
-        .await
-        .expect("Failed to write test file with non-draft frontmatter");
+    if is_executable_file(file_path) || !is_raw_text_file(file_path) {
+        return Err(WebError::Forbidden(
+            "File type not allowed for raw access".to_string(),
+        ));
+    }
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/edit?path=test.md")
-            .body(Body::empty())
-            .expect("Failed to build request");
+    if std::str::from_utf8(&body).is_err() {
+        return Err(WebError::BadRequest(
+            "Request body must be valid UTF-8".to_string(),
+        ));
+    }
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+    let (parent, filename) = file_path.rsplit_once('/').unwrap_or(("", file_path));
+    let canonical_dir = validate_directory_path(&state.target_dir, parent)?;
+    let exists = fs::try_exists(canonical_dir.join(filename)).await?;
+
+    let full_path = if exists {
+        let full_path = validate_file_path(&state.target_dir, file_path)?;
+        let current_etag = content_etag(&fs::read(&full_path).await?);
+        let modified = fs::metadata(&full_path).await?.modified()?;
+        if write_precondition_failed(&request_headers, Some((&current_etag, modified))) {
+            return Err(WebError::PreconditionFailed(
+                "File has changed since it was fetched".to_string(),
+            ));
+        }
+        durable::write_atomic(&full_path, &body, state.durability).await?;
+        full_path
+    } else {
+        if !matches!(params.create.as_deref(), Some("1" | "true")) {
+            return Err(WebError::NotFound(format!("File not found: {file_path}")));
+        }
+        if write_precondition_failed(&request_headers, None) {
+            return Err(WebError::PreconditionFailed(
+                "File does not exist".to_string(),
+            ));
+        }
 
-        let body = response
-            .into_body()
-            .collect()
+        let full_path = canonical_dir.join(filename);
+        durable::write_new(&full_path, &body, state.durability)
             .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(!html.contains(r#"class="draft-flag""#));
-    }
-
-    #[tokio::test]
-    async fn test_index_page_contains_new_file_link() {
-        let (app, _temp_dir, _) = create_test_app().await;
+            .map_err(|err| match err.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    WebError::Conflict("File already exists".to_string())
+                }
+                _ => WebError::from(err),
+            })?;
+        full_path
+    };
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/")
-            .body(Body::empty())
-            .expect("Failed to build index request");
+    info!("File written via API: {}", file_path);
+    let operation = if exists { "write" } else { "create" };
+    record_audit(&state, operation, file_path, "api").await;
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+    let etag = content_etag(&body);
+    let result = RawWriteResult {
+        path: file_path.to_string(),
+        etag: etag.clone(),
+        modified: timefmt::ModifiedTimes::new(get_file_modified(&full_path).await?),
+    };
+    let status = if exists {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(html.contains(r#"href="/new-file""#));
+    let mut response = (status, Json(result)).into_response();
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(header::ETAG, value);
     }
+    Ok(response)
+}
 
-    #[tokio::test]
-    async fn test_new_file_form_contains_path_and_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
-
-        let nested_dir = temp_dir.path().join("posts");
-        fs::create_dir(&nested_dir)
-            .await
-            .expect("Failed to create nested directory");
+#[derive(Deserialize)]
+pub struct ParamsWithPath {
+    path: String,
+}
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/new-file?path=posts")
-            .body(Body::empty())
-            .expect("Failed to build new-file request");
+#[derive(Deserialize)]
+struct ExportFrontmatterParams {
+    /// `csv` or `json` (the default).
+    #[serde(default)]
+    format: Option<String>,
+    /// Directory to export; the whole tree when empty.
+    #[serde(default)]
+    path: String,
+    /// Comma-separated globs matched against each file's name and path.
+    #[serde(default)]
+    exclude: String,
+    /// Start CSV output with a UTF-8 BOM for Excel.
+    #[serde(default)]
+    bom: bool,
+}
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+/// Reads an image's dimensions from its header, best-effort. Only the
+/// header is read, however large the file.
+async fn image_dimensions(path: PathBuf) -> Option<(u32, u32)> {
+    tokio::task::spawn_blocking(move || {
+        image::ImageReader::open(path)
+            .ok()?
+            .with_guessed_format()
+            .ok()?
+            .into_dimensions()
+            .ok()
+    })
+    .await
+    .ok()
+    .flatten()
+}
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(html.contains(r#"name="csrf_token""#));
-        assert!(html.contains(r#"name="path" value="posts""#));
+async fn list_api(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<Json<ListResponse>, WebError> {
+    let path = sanitize_relative_path(params.get("path").map(|s| s.as_str()).unwrap_or(""))?;
+    let directory = validate_directory_path(&state.target_dir, &path)?;
+
+    let mut entries = Vec::new();
+    for entry in list_directory(&state.target_dir, &path).await? {
+        let full_path = directory.join(&entry.name);
+        let size = if entry.is_directory {
+            None
+        } else {
+            Some(fs::metadata(&full_path).await?.len())
+        };
+        let dimensions = match size {
+            Some(_) if is_image_file(&entry.name) => image_dimensions(full_path).await,
+            _ => None,
+        };
+        entries.push(ListEntry {
+            name: entry.name,
+            path: entry.path,
+            is_directory: entry.is_directory,
+            size,
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+        });
     }
 
-    #[tokio::test]
-    async fn test_upload_image_form_contains_path_csrf_and_size_hint() {
-        let (app, temp_dir, _) = create_test_app().await;
+    Ok(Json(ListResponse {
+        path: path.to_string(),
+        entries,
+    }))
+}
 
-        let nested_dir = temp_dir.path().join("posts");
-        fs::create_dir(&nested_dir)
-            .await
-            .expect("Failed to create nested directory");
+/// Previous and next markdown files beside `path`, in the order its
+/// directory is listed (`.wranglersort`, else by name).
+async fn get_neighbors(
+    Query(params): Query<ParamsWithPath>,
+    State(state): State<AppState>,
+) -> Result<Json<NeighborsResponse>, WebError> {
+    let path = sanitize_relative_path(&params.path)?;
+    if !is_markdown_file(&path) {
+        return Err(WebError::BadRequest(
+            "Neighbors are only available for markdown files".to_string(),
+        ));
+    }
+    validate_file_path(&state.target_dir, &path)?;
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/upload-image?path=posts")
-            .body(Body::empty())
-            .expect("Failed to build upload-image request");
+    let directory = path.rsplit_once('/').map_or("", |(directory, _)| directory);
+    let siblings: Vec<DirectoryEntry> = list_directory(&state.target_dir, directory)
+        .await?
+        .into_iter()
+        .filter(|entry| !entry.is_directory && is_markdown_file(&entry.name))
+        .collect();
+    let position = siblings
+        .iter()
+        .position(|entry| entry.path == path)
+        .ok_or_else(|| WebError::NotFound(format!("{path} is not in its directory listing")))?;
+
+    let neighbor = |index: Option<usize>| {
+        index
+            .and_then(|index| siblings.get(index))
+            .map(|entry| Neighbor {
+                name: entry.name.clone(),
+                edit_url: route_url("/edit", &entry.path),
+                path: entry.path.clone(),
+            })
+    };
+    Ok(Json(NeighborsResponse {
+        previous: neighbor(position.checked_sub(1)),
+        next: neighbor(position.checked_add(1)),
+        path: path.into_owned(),
+    }))
+}
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+async fn get_file_info(
+    Query(params): Query<ParamsWithPath>,
+    State(state): State<AppState>,
+) -> Result<Json<FileInfo>, WebError> {
+    let full_path = validate_file_path(&state.target_dir, &params.path)?;
+    let modified = get_file_modified(&full_path).await?;
+    let size = get_file_size(&full_path).await?;
+    let content_hash = state.content_hashes.hash_file(&full_path).await?;
+    Ok(Json(FileInfo {
+        modified: timefmt::ModifiedTimes::new(modified),
+        size,
+        content_hash,
+    }))
+}
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(html.contains(r#"name="csrf_token""#));
-        assert!(html.contains(r#"name="path" value="posts""#));
-        assert!(html.contains("Maximum upload size: 1.0 MB"));
-    }
+async fn get_file_stats(
+    Query(params): Query<ParamsWithPath>,
+    State(state): State<AppState>,
+) -> Result<Json<FileStatsResponse>, WebError> {
+    let Some(audit) = &state.audit else {
+        return Ok(Json(FileStatsResponse::Disabled {
+            message: "Edit tracking is disabled; start the server with --audit-journal to enable it",
+        }));
+    };
 
-    #[tokio::test]
-    async fn test_upload_image_with_valid_png_redirects_to_preview() {
-        let (app, temp_dir, _) = create_test_app().await;
+    let path = sanitize_relative_path(&params.path)?;
+    let full_path = validate_file_path(&state.target_dir, &path)?;
+    let stats = audit.file_stats(&path).await?;
 
-        let nested_dir = temp_dir.path().join("posts");
+    let (created, created_source) = match &stats {
+        Some(stats) => (stats.first_seen, "journal"),
+        None => {
+            let metadata = fs::metadata(&full_path).await?;
+            let created = metadata.created().or_else(|_| metadata.modified())?;
+            (unix_seconds(created), "filesystem")
+        }
+    };
+
+    Ok(Json(FileStatsResponse::Enabled {
+        path: path.to_string(),
+        edits: stats.as_ref().map_or(0, |stats| stats.edits),
+        created,
+        created_source,
+        last_operation: stats.map(|stats| stats.last),
+    }))
+}
+
+/// `GET /frontmatter`: one markdown file's parsed frontmatter as a JSON
+/// object. The standard fields come out as `parse_frontmatter` reads them
+/// (`tags`/`categories` as string lists, missing scalars as `null`), with
+/// every other key alongside them as written. A file without frontmatter
+/// gives `{}`.
+async fn get_frontmatter(
+    Query(params): Query<ParamsWithPath>,
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Map<String, serde_json::Value>>, WebError> {
+    let path = sanitize_relative_path(&params.path)?;
+    if !is_markdown_file(&path) {
+        return Err(WebError::BadRequest(
+            "Only markdown files are supported".to_string(),
+        ));
+    }
+    let full_path = validate_file_path(&state.target_dir, &path)?;
+    let content = fs::read_to_string(&full_path).await?;
+
+    if let Some(err) = frontmatter_syntax_error(&content, state.max_frontmatter_bytes) {
+        return Err(WebError::BadRequest(format!(
+            "Frontmatter could not be parsed: {}",
+            err.message
+        )));
+    }
+    let Some((draft, title, date, tags, categories, extra)) = parse_frontmatter(
+        &content,
+        state.max_frontmatter_bytes,
+        state.max_frontmatter_list_items,
+    ) else {
+        return Ok(Json(serde_json::Map::new()));
+    };
+
+    let mut frontmatter: serde_json::Map<String, serde_json::Value> = extra.into_iter().collect();
+    frontmatter.insert("draft".to_string(), draft.into());
+    frontmatter.insert("title".to_string(), title.into());
+    frontmatter.insert("date".to_string(), date.into());
+    frontmatter.insert("tags".to_string(), tags.into());
+    frontmatter.insert("categories".to_string(), categories.into());
+    Ok(Json(frontmatter))
+}
+
+/// Outgoing links of one markdown file, grouped by kind. The file is read
+/// fresh; targets are resolved against the cached content index.
+async fn get_links(
+    RequestDeadline(deadline): RequestDeadline,
+    Query(params): Query<ParamsWithPath>,
+    State(state): State<AppState>,
+) -> Result<Json<LinksResponse>, WebError> {
+    let path = sanitize_relative_path(&params.path)?;
+    if !is_markdown_file(&path) {
+        return Err(WebError::BadRequest(
+            "Only markdown files are supported".to_string(),
+        ));
+    }
+    let full_path = validate_file_path(&state.target_dir, &path)?;
+    let content = fs::read_to_string(&full_path).await?;
+    let index = complete_index(&state, &deadline, false).await?;
+
+    let mut response = LinksResponse {
+        path: path.to_string(),
+        ..Default::default()
+    };
+    for link in checks::extract_links(&index, &path, &content) {
+        let bucket = match link.kind {
+            checks::LinkKind::Internal => &mut response.internal,
+            checks::LinkKind::Broken => &mut response.broken,
+            checks::LinkKind::External => &mut response.external,
+            checks::LinkKind::Anchor => &mut response.anchors,
+            checks::LinkKind::Mailto => &mut response.mailto,
+        };
+        bucket.push(link);
+    }
+    Ok(Json(response))
+}
+
+/// Groups markdown files with identical content, using the cached content
+/// index so repeated calls do not re-read the tree.
+async fn get_duplicates(
+    RequestDeadline(deadline): RequestDeadline,
+    State(state): State<AppState>,
+) -> Result<Json<DuplicatesResponse>, WebError> {
+    let index = complete_index(&state, &deadline, false).await?;
+
+    let mut by_hash: HashMap<String, DuplicateGroup> = HashMap::new();
+    for document in &index.documents {
+        let hash = hex::encode(Sha256::digest(document.content.as_bytes()));
+        by_hash
+            .entry(hash.clone())
+            .or_insert_with(|| DuplicateGroup {
+                hash,
+                size: document.content.len(),
+                paths: Vec::new(),
+            })
+            .paths
+            .push(document.path.clone());
+    }
+
+    // Documents are sorted by path, so each group's paths already are too.
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_values()
+        .filter(|group| group.paths.len() > 1)
+        .collect();
+    groups.sort_by(|a, b| a.paths.first().cmp(&b.paths.first()));
+    Ok(Json(DuplicatesResponse { groups }))
+}
+
+/// Lists every non-draft markdown file with its mtime as `lastmod`.
+async fn get_robots_txt(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        state.robots_txt,
+    )
+}
+
+async fn get_sitemap(
+    RequestDeadline(deadline): RequestDeadline,
+    State(state): State<AppState>,
+) -> Result<Response, WebError> {
+    let Some(base_url) = &state.site_base_url else {
+        return Err(WebError::NotFound(
+            "Sitemap is disabled; start the server with --site-base-url".to_string(),
+        ));
+    };
+    let index = complete_index(&state, &deadline, false).await?;
+
+    let mut entries = Vec::new();
+    for document in &index.documents {
+        let draft = parse_frontmatter(&document.content, state.max_frontmatter_bytes, SCALARS_ONLY)
+            .and_then(|(draft, ..)| draft);
+        if draft == Some(true) {
+            continue;
+        }
+        let modified = fs::metadata(state.target_dir.join(&document.path))
+            .await
+            .and_then(|metadata| metadata.modified())
+            .inspect_err(|err| warn!("Failed to read mtime of {}: {}", document.path, err))
+            .ok();
+        entries.push(sitemap::SitemapEntry {
+            path: document.path.clone(),
+            modified,
+        });
+    }
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        sitemap::render(base_url, &entries),
+    )
+        .into_response())
+}
+
+/// Every markdown file's frontmatter in scope, one row each, from the
+/// cached content index.
+async fn export_frontmatter(
+    RequestDeadline(deadline): RequestDeadline,
+    Query(params): Query<ExportFrontmatterParams>,
+    State(state): State<AppState>,
+) -> Result<Response, WebError> {
+    let csv = match params.format.as_deref().unwrap_or("json") {
+        "csv" => true,
+        "json" => false,
+        other => {
+            return Err(WebError::BadRequest(format!(
+                "Unknown export format '{other}'; use csv or json"
+            )));
+        }
+    };
+    let scope = sanitize_relative_path(&params.path)?;
+    if !scope.is_empty() {
+        validate_directory_path(&state.target_dir, &scope)?;
+    }
+    let excludes: Vec<&str> = params
+        .exclude
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .collect();
+    let index = complete_index(&state, &deadline, false).await?;
+
+    let mut rows = Vec::new();
+    for document in &index.documents {
+        let in_scope = scope.is_empty()
+            || document
+                .path
+                .strip_prefix(scope.as_ref())
+                .is_some_and(|rest| rest.starts_with('/'));
+        if !in_scope
+            || excludes.iter().any(|pattern| {
+                glob_matches(pattern, base_name(&document.path))
+                    || glob_matches(pattern, &document.path)
+            })
+        {
+            continue;
+        }
+        let (draft, title, date, tags, categories, extra) = parse_frontmatter(
+            &document.content,
+            state.max_frontmatter_bytes,
+            state.max_frontmatter_list_items,
+        )
+        .unwrap_or_default();
+        let body = document
+            .content
+            .get(frontmatter_len(&document.content, state.max_frontmatter_bytes).unwrap_or(0)..)
+            .unwrap_or_default();
+        let modified = fs::metadata(state.target_dir.join(&document.path))
+            .await
+            .and_then(|metadata| metadata.modified())
+            .inspect_err(|err| warn!("Failed to read mtime of {}: {}", document.path, err))
+            .ok();
+        rows.push(export::FrontmatterRow {
+            path: document.path.clone(),
+            title,
+            date,
+            draft,
+            tags,
+            categories,
+            word_count: export::word_count(body),
+            modified: modified.map(timefmt::rfc3339),
+            extra,
+        });
+    }
+
+    if !csv {
+        return Ok(Json(rows).into_response());
+    }
+    let body = export::to_csv(&rows, params.bom)
+        .map_err(|err| WebError::Internal(format!("Failed to write CSV: {err}")))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"frontmatter.csv\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn format_content(
+    State(state): State<AppState>,
+    Json(request): Json<FormatRequest>,
+) -> Result<Json<FormatResponse>, WebError> {
+    let content = format::format_markdown(&request.content, state.max_frontmatter_bytes)
+        .map_err(|err| WebError::Internal(format!("Failed to format markdown: {err}")))?;
+    Ok(Json(FormatResponse {
+        changed: content != request.content,
+        content,
+    }))
+}
+
+/// Markdown `content` of the document at `path`, with its frontmatter
+/// removed, as HTML that is safe to insert into a page: raw HTML is escaped
+/// and links are mapped to app routes by [`markdown::render_html`].
+fn render_markdown_body(state: &AppState, content: &str, path: &str) -> Html<String> {
+    let body = content
+        .get(frontmatter_len(content, state.max_frontmatter_bytes).unwrap_or(0)..)
+        .unwrap_or_default();
+    Html(markdown::render_html(
+        body,
+        path,
+        state.markdown_extensions,
+        state.emoji_shortcodes,
+    ))
+}
+
+async fn render_file(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<Html<String>, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+    if !is_markdown_file(file_path) {
+        return Err(WebError::BadRequest(
+            "Only markdown files can be rendered".to_string(),
+        ));
+    }
+
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
+    let content = fs::read_to_string(&full_path).await?;
+    let path = sanitize_relative_path(file_path)?;
+    Ok(render_markdown_body(&state, &content, &path))
+}
+
+/// Renders unsaved editor content, for the live preview.
+async fn render_content(
+    State(state): State<AppState>,
+    Json(request): Json<RenderRequest>,
+) -> Result<Html<String>, WebError> {
+    let path = sanitize_relative_path(&request.path)?;
+    Ok(render_markdown_body(&state, &request.content, &path))
+}
+
+async fn get_file_content(
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<Json<FileContent>, WebError> {
+    let file_path = params
+        .get("path")
+        .ok_or(WebError::BadRequest("Missing path parameter".to_string()))?;
+
+    // Only allow files the editor can open
+    if !is_editable_file(file_path) {
+        return Err(WebError::BadRequest(
+            "Only markdown and text document files are supported".to_string(),
+        ));
+    }
+
+    let full_path = validate_file_path(&state.target_dir, file_path)?;
+    let metadata = fs::metadata(&full_path).await?;
+    let content = fs::read_to_string(&full_path).await?;
+    let content_hash =
+        state
+            .content_hashes
+            .hash_loaded(&full_path, &metadata, content.as_bytes())?;
+    Ok(Json(FileContent {
+        content,
+        modified: timefmt::ModifiedTimes::new(metadata.modified()?),
+        content_hash,
+    }))
+}
+
+async fn delete_file(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    Form(form): Form<DeleteForm>,
+) -> Result<Response, WebError> {
+    // Validate CSRF token
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+
+    // Validate the file path
+    let full_path = validate_file_path(&state.target_dir, &form.path)?;
+    ensure_not_locked(&state, &form.path)?;
+
+    if state.confirm_delete_name {
+        let path = sanitize_relative_path(&form.path)?;
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+        if form.confirm_name.as_deref().map(str::trim) != Some(file_name) {
+            return Err(WebError::BadRequest(format!(
+                "Type the file name ({file_name}) as confirm_name to delete it"
+            )));
+        }
+    }
+
+    if let Some(expected_mtime) = form.expected_mtime.as_deref()
+        && !expected_mtime.is_empty()
+        && !form.force
+    {
+        let modified_time = get_file_modification_time(&full_path).await?;
+        if modified_time != expected_mtime {
+            warn!(
+                "Refusing to delete {}: modified at {} but client expected {}",
+                form.path, modified_time, expected_mtime
+            );
+            let file_size = get_file_size(&full_path).await.map(format_file_size)?;
+            let conflict_page = DeleteConflictTemplate {
+                theme,
+                assets: state.assets.clone(),
+                back_url: parent_url(&form.path),
+                file_path: form.path,
+                file_size,
+                modified_display: timefmt::absolute(
+                    get_file_modified(&full_path).await?,
+                    state.display_offset,
+                ),
+                modified_time,
+                csrf_token: generate_csrf_token(&state.csrf_secret),
+                confirm_delete_name: state.confirm_delete_name,
+            };
+            return Ok((StatusCode::CONFLICT, conflict_page).into_response());
+        }
+    }
+
+    let detail_text = if state.hard_delete {
+        fs::remove_file(&full_path).await?;
+        "has been deleted.".to_string()
+    } else {
+        let folder =
+            trash::move_to_trash(&state.target_dir, &full_path, &form.path, state.durability)
+                .await?;
+        format!("has been moved to {folder}/ and can be restored from there.")
+    };
+    info!("File deleted successfully: {}", form.path);
+    record_audit(&state, "delete", &form.path, "web").await;
+    if let Ok(path) = sanitize_relative_path(&form.path) {
+        state
+            .redirects
+            .record(&path, redirects::PathFate::Deleted)
+            .await;
+    }
+    let back_url = parent_url(&form.path);
+    Ok(StatusPageTemplate {
+        theme,
+        assets: state.assets.clone(),
+        title: "File Deleted - Markdown Wrangler".to_string(),
+        heading: "🗑️ File Deleted Successfully!".to_string(),
+        heading_class: "success".to_string(),
+        file_path: form.path,
+        detail_text,
+        show_edit_button: false,
+        show_trash_link: !state.hard_delete,
+        edit_url: "".to_string(),
+        back_url,
+    }
+    .into_response())
+}
+
+async fn list_trash(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<TrashTemplate, WebError> {
+    let items = trash::list(&state.target_dir)
+        .await?
+        .into_iter()
+        .map(|item| TrashView {
+            has_original: item.original_path.is_some(),
+            is_dir: item.is_dir,
+            original_path: item.original_path.unwrap_or_else(|| {
+                item.path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            }),
+            deleted: format_http_date(UNIX_EPOCH + Duration::from_secs(item.deleted_at)),
+            id: item.id,
+        })
+        .collect();
+    Ok(TrashTemplate {
+        theme,
+        assets: state.assets.clone(),
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        items,
+        emptied: params.get("emptied").and_then(|count| count.parse().ok()),
+    })
+}
+
+/// Moves a trashed file or folder back to where it was deleted from, or to
+/// `destination`. Nothing that exists is ever overwritten.
+async fn restore_from_trash(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    Form(form): Form<RestoreTrashForm>,
+) -> Result<StatusPageTemplate, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+    let item = trash::find(&state.target_dir, &form.id)
+        .await?
+        .ok_or_else(|| WebError::NotFound("No such file in the trash".to_string()))?;
+    let destination = match form.destination.as_deref().map(str::trim) {
+        Some(destination) if !destination.is_empty() => destination.to_string(),
+        _ => item.original_path.clone().ok_or_else(|| {
+            WebError::BadRequest(
+                "The original path of this file is unknown; give a destination".to_string(),
+            )
+        })?,
+    };
+    let destination = sanitize_relative_path(&destination)?.into_owned();
+    let (directory, file_name) = destination
+        .rsplit_once('/')
+        .unwrap_or(("", destination.as_str()));
+    if file_name.is_empty() || destination.split('/').any(|part| part.starts_with('.')) {
+        return Err(WebError::BadRequest(
+            "Destination must be a visible path".to_string(),
+        ));
+    }
+    let canonical_dir = validate_directory_path(&state.target_dir, directory)?;
+    ensure_not_locked(&state, &destination)?;
+    if let Some(existing) =
+        find_case_collision(state.case_insensitive_fs, &canonical_dir, file_name).await?
+    {
+        return Err(case_collision_error(&existing));
+    }
+    if fs::try_exists(canonical_dir.join(file_name)).await? {
+        return Err(WebError::Conflict(format!(
+            "{destination} already exists; restore to another path"
+        )));
+    }
+
+    trash::restore(
+        &state.target_dir,
+        &item,
+        &canonical_dir.join(file_name),
+        state.durability,
+    )
+    .await?;
+    info!("Restored {} from the trash to {}", item.id, destination);
+    record_audit(&state, "restore", &destination, "web").await;
+
+    let kind = if item.is_dir { "Folder" } else { "File" };
+    Ok(StatusPageTemplate {
+        theme,
+        assets: state.assets.clone(),
+        title: format!("{kind} Restored - Markdown Wrangler"),
+        heading: format!("♻️ {kind} Restored"),
+        heading_class: "success".to_string(),
+        detail_text: "has been restored from the trash.".to_string(),
+        show_edit_button: !item.is_dir && is_editable_file(&destination),
+        show_trash_link: false,
+        edit_url: route_url("/edit", &destination),
+        back_url: if item.is_dir {
+            route_url("/", &destination)
+        } else {
+            parent_url(&destination)
+        },
+        file_path: destination,
+    })
+}
+
+async fn empty_trash(
+    State(state): State<AppState>,
+    Form(form): Form<EmptyTrashForm>,
+) -> Result<Redirect, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+    let removed = trash::empty(&state.target_dir).await?;
+    info!("Emptied the trash ({} entries)", removed);
+    Ok(Redirect::to(&app_url(&format!("/trash?emptied={removed}"))))
+}
+
+/// Where a rename puts `path`. Markdown filenames go through
+/// `normalize_markdown_filename`; other files keep their extension and go
+/// through `normalize_renamed_filename`.
+fn rename_destination(
+    path: &str,
+    form: &RenameForm,
+    max_filename_length: usize,
+) -> Result<String, WebError> {
+    let (directory, filename) = match (&form.new_name, &form.new_path) {
+        (Some(new_name), None) => {
+            if new_name.contains(['/', '\\']) {
+                return Err(WebError::BadRequest(
+                    "New name must not include directory components".to_string(),
+                ));
+            }
+            let directory = path.rsplit_once('/').map_or("", |(directory, _)| directory);
+            (directory.to_string(), new_name.clone())
+        }
+        (None, Some(new_path)) => {
+            let new_path = sanitize_relative_path(new_path.trim())?;
+            match new_path.rsplit_once('/') {
+                Some((directory, filename)) => (directory.to_string(), filename.to_string()),
+                None => (String::new(), new_path.into_owned()),
+            }
+        }
+        _ => {
+            return Err(WebError::BadRequest(
+                "Give either a new name or a new path".to_string(),
+            ));
+        }
+    };
+    let filename = if is_markdown_file(path) {
+        normalize_markdown_filename(&filename, max_filename_length)?
+    } else {
+        normalize_renamed_filename(path, &filename, max_filename_length)?
+    };
+    Ok(join_relative_path(&directory, &filename))
+}
+
+/// Renames or moves a file within the content root. The content,
+/// frontmatter included, is moved as is.
+async fn rename_file(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    Form(form): Form<RenameForm>,
+) -> Result<StatusPageTemplate, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+
+    validate_file_path(&state.target_dir, &form.path)?;
+    let path = sanitize_relative_path(&form.path)?;
+    let new_path = rename_destination(&path, &form, state.max_filename_length)?;
+    ensure_not_locked(&state, &path)?;
+    ensure_not_locked(&state, &new_path)?;
+
+    move_file(
+        &state.target_dir,
+        &path,
+        &new_path,
+        state.case_insensitive_fs,
+        state.durability,
+    )
+    .await?;
+    info!("File renamed: {} -> {}", path, new_path);
+    state
+        .redirects
+        .record(
+            &path,
+            redirects::PathFate::Moved {
+                to: new_path.clone(),
+            },
+        )
+        .await;
+    if let Some(audit) = &state.audit {
+        audit.record("move", &new_path, "web", Some(&path)).await;
+    }
+
+    Ok(StatusPageTemplate {
+        theme,
+        assets: state.assets.clone(),
+        title: "File Renamed - Markdown Wrangler".to_string(),
+        heading: "✏️ File Renamed Successfully!".to_string(),
+        heading_class: "success".to_string(),
+        detail_text: format!("was renamed from {path}."),
+        show_edit_button: is_editable_file(&new_path),
+        show_trash_link: false,
+        edit_url: route_url("/edit", &new_path),
+        back_url: parent_url(&new_path),
+        file_path: new_path,
+    })
+}
+
+/// Copies a markdown post under a new name next to it and opens the copy.
+/// The copy starts over as a draft: `draft: true` and no `date`, with every
+/// other frontmatter key kept and the format (YAML or JSON) unchanged.
+async fn duplicate_file(
+    State(state): State<AppState>,
+    Form(form): Form<DuplicateForm>,
+) -> Result<Response, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+
+    if !is_markdown_file(&form.path) {
+        return Err(WebError::BadRequest(
+            "Only markdown files can be duplicated".to_string(),
+        ));
+    }
+    let source_path = validate_file_path(&state.target_dir, &form.path)?;
+    let path = sanitize_relative_path(&form.path)?;
+    let directory = path.rsplit_once('/').map_or("", |(directory, _)| directory);
+    let canonical_dir = validate_directory_path(&state.target_dir, directory)?;
+    let filename = normalize_markdown_filename(&form.filename, state.max_filename_length)?;
+    let new_path = join_relative_path(directory, &filename);
+    if let Some(err) =
+        new_file_conflict(state.case_insensitive_fs, &canonical_dir, &filename).await?
+    {
+        return Err(err);
+    }
+    ensure_not_locked(&state, &new_path)?;
+
+    let content = fs::read_to_string(&source_path).await?;
+    let mut patch = serde_json::Map::new();
+    patch.insert("draft".to_string(), serde_json::Value::Bool(true));
+    patch.insert("date".to_string(), serde_json::Value::Null);
+    let (copy, _) = patch_frontmatter(&content, patch, state.max_frontmatter_bytes)?;
+
+    durable::write_new(
+        &canonical_dir.join(&filename),
+        copy.as_bytes(),
+        state.durability,
+    )
+    .await
+    .map_err(|err| match err.kind() {
+        std::io::ErrorKind::AlreadyExists => WebError::Conflict("File already exists".to_string()),
+        _ => err.into(),
+    })?;
+
+    info!("File duplicated: {} -> {}", path, new_path);
+    record_audit(&state, "create", &new_path, "web").await;
+    Ok(Redirect::to(&route_url("/edit", &new_path)).into_response())
+}
+
+/// Where `/save-as` writes. The filename goes through
+/// `normalize_markdown_filename`, or `normalize_renamed_filename` when the
+/// source is a text document, so the copy stays editable.
+fn save_as_destination(
+    src: Option<&str>,
+    new_path: &str,
+    max_filename_length: usize,
+) -> Result<(String, String), WebError> {
+    let new_path = sanitize_relative_path(new_path.trim())?;
+    let (directory, filename) = new_path.rsplit_once('/').unwrap_or(("", &new_path));
+    let filename = match src {
+        Some(src) if !is_markdown_file(src) => {
+            normalize_renamed_filename(src, filename, max_filename_length)?
+        }
+        _ => normalize_markdown_filename(filename, max_filename_length)?,
+    };
+    Ok((directory.to_string(), filename))
+}
+
+/// Writes the editor's content to a new file and opens it, leaving the
+/// source untouched. Unlike a rename, what is written is the submitted
+/// content, unsaved edits included. An existing file is never overwritten.
+async fn save_as(
+    State(state): State<AppState>,
+    Form(form): Form<SaveAsForm>,
+) -> Result<Response, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+
+    let src = match form.src.as_deref().filter(|src| !src.is_empty()) {
+        Some(src) => {
+            if !is_editable_file(src) {
+                return Err(WebError::BadRequest(
+                    "File is not a markdown or text document file".to_string(),
+                ));
+            }
+            validate_file_path(&state.target_dir, src)?;
+            Some(sanitize_relative_path(src)?.into_owned())
+        }
+        None => None,
+    };
+    let (directory, filename) =
+        save_as_destination(src.as_deref(), &form.new_path, state.max_filename_length)?;
+    let canonical_dir = validate_directory_path(&state.target_dir, &directory)?;
+    let new_path = join_relative_path(&directory, &filename);
+    if let Some(err) =
+        new_file_conflict(state.case_insensitive_fs, &canonical_dir, &filename).await?
+    {
+        return Err(err);
+    }
+    ensure_not_locked(&state, &new_path)?;
+
+    let content = save_normalize::normalize(&form.content, state.save_normalize);
+    durable::write_new(
+        &canonical_dir.join(&filename),
+        content.as_bytes(),
+        state.durability,
+    )
+    .await
+    .map_err(|err| match err.kind() {
+        std::io::ErrorKind::AlreadyExists => WebError::Conflict("File already exists".to_string()),
+        _ => err.into(),
+    })?;
+
+    info!("File saved as {} (from {:?})", new_path, src);
+    if let Some(audit) = &state.audit {
+        audit
+            .record("create", &new_path, "web", src.as_deref())
+            .await;
+    }
+    Ok(Redirect::to(&route_url("/edit", &new_path)).into_response())
+}
+
+/// Files named on the folder delete confirmation; the rest are counted.
+const MAX_LISTED_DELETE_FILES: usize = 100;
+
+/// What a recursive directory delete would remove.
+#[derive(Debug, Default)]
+struct DirectoryTreeSummary {
+    /// Files and symlinks, relative to the content root.
+    files: Vec<String>,
+    directories: usize,
+    bytes: u64,
+}
+
+/// Walks `dir` (at `relative` under the content root) without following
+/// symlinks.
+async fn summarize_directory_tree(
+    dir: &Path,
+    relative: &str,
+) -> std::io::Result<DirectoryTreeSummary> {
+    let mut summary = DirectoryTreeSummary::default();
+    let mut pending = vec![(dir.to_path_buf(), relative.to_string())];
+    while let Some((dir, relative)) = pending.pop() {
+        let mut entries = fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let child = join_relative_path(&relative, &entry.file_name().to_string_lossy());
+            let metadata = fs::symlink_metadata(entry.path()).await?;
+            if metadata.is_dir() {
+                summary.directories += 1;
+                pending.push((entry.path(), child));
+            } else {
+                summary.bytes += metadata.len();
+                summary.files.push(child);
+            }
+        }
+    }
+    summary.files.sort();
+    Ok(summary)
+}
+
+/// Deletes `dir` and everything under it. Refuses a symlink, which
+/// `remove_dir_all` would otherwise resolve to a directory elsewhere; links
+/// inside the tree are removed, never followed.
+async fn remove_directory_tree(dir: &Path) -> std::io::Result<()> {
+    if fs::symlink_metadata(dir).await?.file_type().is_symlink() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "refusing to recursively delete a symlink",
+        ));
+    }
+    fs::remove_dir_all(dir).await
+}
+
+/// Resolves a `/delete-dir` path to a directory that may be deleted.
+async fn deletable_directory(state: &AppState, path: &str) -> Result<(String, PathBuf), WebError> {
+    let path = sanitize_relative_path(path)?;
+    if path.is_empty() {
+        return Err(WebError::BadRequest(
+            "Cannot delete the root directory".to_string(),
+        ));
+    }
+    let full_path = validate_directory_path(&state.target_dir, &path)?;
+    if fs::symlink_metadata(state.target_dir.join(path.as_ref()))
+        .await?
+        .file_type()
+        .is_symlink()
+    {
+        return Err(WebError::BadRequest(
+            "Cannot delete a symlinked directory".to_string(),
+        ));
+    }
+    if full_path == state.target_dir.canonicalize()? {
+        return Err(WebError::BadRequest(
+            "Cannot delete the root directory".to_string(),
+        ));
+    }
+    Ok((path.into_owned(), full_path))
+}
+
+async fn confirm_delete_directory(
+    ThemePreference(theme): ThemePreference,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+) -> Result<DeleteDirectoryTemplate, WebError> {
+    let requested = params.get("path").map(String::as_str).unwrap_or_default();
+    let (path, full_path) = deletable_directory(&state, requested).await?;
+    let summary = summarize_directory_tree(&full_path, &path).await?;
+    let prefix = format!("{path}/");
+    let listed_files = summary
+        .files
+        .iter()
+        .take(MAX_LISTED_DELETE_FILES)
+        .map(|file| file.strip_prefix(&prefix).unwrap_or(file).to_string())
+        .collect();
+    Ok(DeleteDirectoryTemplate {
+        theme,
+        assets: state.assets.clone(),
+        total_size: format_file_size(summary.bytes),
+        listed_files,
+        unlisted_files: summary.files.len().saturating_sub(MAX_LISTED_DELETE_FILES),
+        summary,
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        cancel_url: route_url("/", &path),
+        back_url: parent_url(&path),
+        dir_path: path,
+        deleted: false,
+        hard_delete: state.hard_delete,
+        trash_folder: None,
+    })
+}
+
+async fn delete_directory(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    Form(form): Form<DeleteDirectoryForm>,
+) -> Result<DeleteDirectoryTemplate, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+    let (path, full_path) = deletable_directory(&state, &form.path).await?;
+    ensure_not_locked(&state, &path)?;
+
+    let _operation = state
+        .operations
+        .begin("directory delete", vec![path.clone()]);
+    let summary = summarize_directory_tree(&full_path, &path).await?;
+    let trash_folder = if state.hard_delete {
+        remove_directory_tree(&full_path).await?;
+        None
+    } else {
+        Some(trash::move_to_trash(&state.target_dir, &full_path, &path, state.durability).await?)
+    };
+    info!(
+        "Directory deleted: {} ({} files, {} subdirectories){}",
+        path,
+        summary.files.len(),
+        summary.directories,
+        trash_folder
+            .as_deref()
+            .map(|folder| format!(", moved to {folder}"))
+            .unwrap_or_default()
+    );
+    for file in &summary.files {
+        record_audit(&state, "delete", file, "web").await;
+        state
+            .redirects
+            .record(file, redirects::PathFate::Deleted)
+            .await;
+    }
+
+    Ok(DeleteDirectoryTemplate {
+        theme,
+        assets: state.assets.clone(),
+        total_size: format_file_size(summary.bytes),
+        summary,
+        listed_files: Vec::new(),
+        unlisted_files: 0,
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        cancel_url: route_url("/", &path),
+        back_url: parent_url(&path),
+        dir_path: path,
+        deleted: true,
+        hard_delete: state.hard_delete,
+        trash_folder,
+    })
+}
+
+/// Removes an empty folder. With `recursive`, hands over to the `/delete-dir`
+/// confirmation page, which is the only way to delete contents.
+async fn delete_empty_directory(
+    ThemePreference(theme): ThemePreference,
+    State(state): State<AppState>,
+    Form(form): Form<DeleteEmptyDirectoryForm>,
+) -> Result<Response, WebError> {
+    validate_csrf_token(&form.csrf_token, &state.csrf_secret)?;
+    let (path, full_path) = deletable_directory(&state, &form.path).await?;
+    if form.recursive {
+        return Ok(Redirect::to(&route_url("/delete-dir", &path)).into_response());
+    }
+    ensure_not_locked(&state, &path)?;
+
+    match fs::remove_dir(&full_path).await {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
+            return Err(WebError::BadRequest(format!(
+                "{path} is not empty, so it was not deleted. Send recursive=true to review its contents and delete everything in it."
+            )));
+        }
+        Err(err) => return Err(err.into()),
+    }
+    info!("Empty directory deleted: {}", path);
+
+    Ok(DeleteDirectoryTemplate {
+        theme,
+        assets: state.assets.clone(),
+        total_size: format_file_size(0),
+        summary: DirectoryTreeSummary::default(),
+        listed_files: Vec::new(),
+        unlisted_files: 0,
+        csrf_token: generate_csrf_token(&state.csrf_secret),
+        cancel_url: route_url("/", &path),
+        back_url: parent_url(&path),
+        dir_path: path,
+        deleted: true,
+        hard_delete: state.hard_delete,
+        trash_folder: None,
+    }
+    .into_response())
+}
+
+/// Accepts an incoming `X-Request-Id` when it looks sane, otherwise generates
+/// one, records it on the request span and echoes it on the response.
+async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| {
+            !value.is_empty()
+                && value.len() <= MAX_REQUEST_ID_LENGTH
+                && value
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+        })
+        .map(ToString::to_string)
+        .unwrap_or_else(|| hex::encode(rand::rng().random::<[u8; 16]>()));
+
+    let Ok(header_value) = HeaderValue::from_str(&request_id) else {
+        return next.run(request).await;
+    };
+    request
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value.clone());
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %request.method(),
+        uri = %request.uri()
+    );
+    let mut response = next.run(request).instrument(span).await;
+    response
+        .headers_mut()
+        .insert(REQUEST_ID_HEADER, header_value);
+    response
+}
+
+/// Drops cached check results after any successful non-read request, since it
+/// may have changed the content tree. Read-only POST routes such as `/render`
+/// leave the cache alone.
+async fn invalidate_checks_on_write(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_write = !matches!(
+        *request.method(),
+        axum::http::Method::GET | axum::http::Method::HEAD | axum::http::Method::OPTIONS
+    ) && !routes::is_read_only(request.uri().path());
+    let response = next.run(request).await;
+    let status = response.status();
+    if is_write && !status.is_client_error() && !status.is_server_error() {
+        state.checks.invalidate();
+        state.content_hashes.clear();
+    }
+    response
+}
+
+/// Whether a query value as sent decodes cleanly: every `%` starts a
+/// two-digit hex escape and the decoded bytes are UTF-8.
+fn is_well_formed_query_value(raw: &str) -> bool {
+    let mut bytes = raw.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            match (bytes.next(), bytes.next()) {
+                (Some(high), Some(low)) if high.is_ascii_hexdigit() && low.is_ascii_hexdigit() => {}
+                _ => return false,
+            }
+        }
+    }
+    urlencoding::decode(raw).is_ok()
+}
+
+/// Answers 400 when a `path` query parameter is malformed. `Query` keeps a
+/// bad escape such as `%zz` literally and replaces invalid UTF-8, so the
+/// request would otherwise fail later with a misleading "does not exist".
+async fn reject_malformed_path_encoding(request: Request, next: Next) -> Response {
+    let malformed = request.uri().query().is_some_and(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .any(|(key, value)| key == "path" && !is_well_formed_query_value(value))
+    });
+    if malformed {
+        return WebError::BadRequest("Invalid path encoding".to_string()).into_response();
+    }
+    next.run(request).await
+}
+
+/// Routes whose `path` query parameter names a file that may have moved.
+const FILE_PAGE_ROUTES: &[routes::Route] = &[
+    routes::Route::Edit,
+    routes::Route::FilePreview,
+    routes::Route::ImagePreview,
+];
+const FILE_DATA_ROUTES: &[routes::Route] = &[
+    routes::Route::Image,
+    routes::Route::File,
+    routes::Route::Raw,
+    routes::Route::FileInfo,
+    routes::Route::FileContent,
+    routes::Route::Links,
+    routes::Route::FileStats,
+    routes::Route::Frontmatter,
+];
+
+/// Replaces the `path` parameter in a query string, keeping the others.
+fn replace_path_param(query: &str, new_path: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| {
+            if pair.split('=').next() == Some("path") {
+                format!("path={}", urlencoding::encode(new_path))
+            } else {
+                pair.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// When a GET for a file fails and the file is known to have moved, redirects
+/// to the same route at its new path; known deletions get a 410 instead.
+async fn redirect_moved_paths(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request.uri().path().to_string();
+    let Some(known_route) = routes::Route::at(&route) else {
+        return next.run(request).await;
+    };
+    let is_page = FILE_PAGE_ROUTES.contains(&known_route);
+    if request.method() != axum::http::Method::GET
+        || !(is_page || FILE_DATA_ROUTES.contains(&known_route))
+    {
+        return next.run(request).await;
+    }
+    let uri = request.uri().clone();
+    let theme = theme_from_headers(request.headers());
+
+    let response = next.run(request).await;
+    if !response.status().is_client_error() {
+        return response;
+    }
+    let Ok(Query(params)) = Query::<HashMap<String, String>>::try_from_uri(&uri) else {
+        return response;
+    };
+    let Some(Ok(path)) = params.get("path").map(|path| sanitize_relative_path(path)) else {
+        return response;
+    };
+    if path.is_empty()
+        || fs::try_exists(state.target_dir.join(path.as_ref()))
+            .await
+            .unwrap_or(true)
+    {
+        return response;
+    }
+
+    match state.redirects.lookup(&path).await {
+        Some(redirects::PathFate::Moved { to }) => {
+            let location = app_url(&format!(
+                "{route}?{}",
+                replace_path_param(uri.query().unwrap_or_default(), &to)
+            ));
+            info!("Redirecting moved path {} to {}", path, location);
+            match HeaderValue::from_str(&location) {
+                Ok(location) => (StatusCode::FOUND, [(header::LOCATION, location)]).into_response(),
+                Err(_) => response,
+            }
+        }
+        Some(redirects::PathFate::Deleted) if is_page => (
+            StatusCode::GONE,
+            StatusPageTemplate {
+                theme,
+                assets: state.assets.clone(),
+                title: "File Deleted - Markdown Wrangler".to_string(),
+                heading: "🗑️ File Was Deleted".to_string(),
+                heading_class: "warning".to_string(),
+                back_url: parent_url(&path),
+                file_path: path.to_string(),
+                detail_text: if state.hard_delete {
+                    "was deleted and is no longer available.".to_string()
+                } else {
+                    "was deleted. It can be restored from the trash unless the trash has been emptied since.".to_string()
+                },
+                show_edit_button: false,
+                edit_url: String::new(),
+                show_trash_link: !state.hard_delete,
+            },
+        )
+            .into_response(),
+        Some(redirects::PathFate::Deleted) => {
+            (StatusCode::GONE, format!("{path} was deleted")).into_response()
+        }
+        None => response,
+    }
+}
+
+/// Serves a file from a `--mount` directory. Mounts are read-only (only GET
+/// is routed) and confined to their root, including through symlinks.
+async fn serve_mount(
+    UrlPath((name, path)): UrlPath<(String, String)>,
+    State(state): State<AppState>,
+    request: Request,
+) -> Result<Response, WebError> {
+    let mount = state
+        .mounts
+        .iter()
+        .find(|mount| mount.name == name)
+        .ok_or_else(|| WebError::NotFound(format!("Unknown mount: {name}")))?;
+
+    let relative_path = sanitize_relative_path(&path)?;
+    if relative_path.split('/').any(|part| part.starts_with('.')) {
+        return Err(WebError::NotFound("Not found".to_string()));
+    }
+    let full_path = validate_file_path(&mount.path, &relative_path).map_err(|err| {
+        warn!("Rejected mount path {}/{}: {}", name, path, err);
+        WebError::NotFound("Not found".to_string())
+    })?;
+
+    let permit = acquire_file_serve(&state).await?;
+    let mut response = ServeFile::new(full_path)
+        .try_call(request)
+        .await?
+        .map(|body| {
+            axum::body::Body::new(PermitBody {
+                body: axum::body::Body::new(body),
+                _permit: permit,
+            })
+        });
+    let headers = response.headers_mut();
+    headers.insert(
+        "X-Content-Type-Options",
+        HeaderValue::from_static("nosniff"),
+    );
+    // Mounted HTML must not run scripts with this origin's cookies.
+    headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_static("sandbox"),
+    );
+    Ok(response)
+}
+
+/// Serves `static/` assets. The decoded path is checked explicitly: `..`,
+/// backslashes, hidden files and symlinks leaving the directory are refused.
+/// Requests carrying the asset's current `?v=` hash may be cached for good;
+/// anything else must revalidate.
+async fn serve_static(
+    UrlPath(path): UrlPath<String>,
+    Query(params): Query<HashMap<String, String>>,
+    State(state): State<AppState>,
+    request: Request,
+) -> Result<Response, WebError> {
+    let relative_path = sanitize_relative_path(&path)
+        .map_err(|err| WebError::BadRequest(format!("Invalid asset path: {err}")))?;
+    if relative_path.is_empty() || relative_path.split('/').any(|part| part.starts_with('.')) {
+        return Err(WebError::NotFound("Asset not found".to_string()));
+    }
+    let full_path = validate_file_path(state.assets.root(), &relative_path)
+        .map_err(|_| WebError::NotFound("Asset not found".to_string()))?;
+
+    let mut response = ServeFile::new(full_path)
+        .try_call(request)
+        .await?
+        .map(axum::body::Body::new);
+    let cache_control = if params
+        .get("v")
+        .is_some_and(|version| state.assets.is_current(&relative_path, version))
+    {
+        "public, max-age=31536000, immutable"
+    } else {
+        "no-cache"
+    };
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    );
+    headers.insert(
+        "X-Content-Type-Options",
+        HeaderValue::from_static("nosniff"),
+    );
+    Ok(response)
+}
+
+async fn handler_404() -> WebError {
+    WebError::NotFound("Not found".to_string())
+}
+
+/// Handlers for each entry of [`routes::ROUTES`]. The match is exhaustive, so
+/// a new [`routes::Route`] cannot be added without wiring up its handler.
+fn route_handlers(route: routes::Route) -> MethodRouter<AppState> {
+    use routes::Route;
+    match route {
+        Route::Index => get(index),
+        Route::Preferences => post(update_preferences),
+        Route::NewFile => get(new_file_form).post(create_new_file),
+        Route::UploadImage => get(upload_image_form).post(upload_image),
+        Route::Upload => post(upload_files),
+        Route::ImportUrl => post(import_url),
+        Route::PasteImage => post(paste_image),
+        Route::CopyDirectory => post(copy_directory),
+        Route::Edit => get(edit_file),
+        Route::Save => post(save_file),
+        Route::SaveAs => post(save_as),
+        Route::SetDraft => post(set_draft),
+        Route::Delete => post(delete_file),
+        Route::Rename => post(rename_file),
+        Route::Duplicate => post(duplicate_file),
+        Route::DeleteDirectory => get(confirm_delete_directory).post(delete_directory),
+        Route::DeleteEmptyDirectory => post(delete_empty_directory),
+        Route::ImagePreview => get(preview_image),
+        Route::Image => get(serve_image),
+        Route::FilePreview => get(preview_file),
+        Route::File => get(serve_file),
+        Route::Raw => get(serve_raw).put(put_raw),
+        Route::FileInfo => get(get_file_info),
+        Route::List => get(list_api),
+        Route::FileStats => get(get_file_stats),
+        Route::Format => post(format_content),
+        Route::Links => get(get_links),
+        Route::Frontmatter => get(get_frontmatter),
+        Route::FileContent => get(get_file_content),
+        Route::Render => get(render_file).post(render_content),
+        Route::Problems => get(problems),
+        Route::Snapshot => post(create_snapshot),
+        Route::Snapshots => get(list_snapshots),
+        Route::RestoreSnapshot => get(confirm_restore_snapshot).post(restore_snapshot),
+        Route::QuickSwitch => get(quickswitch_page),
+        Route::QuickSwitchApi => get(quickswitch_api),
+        Route::Breadcrumbs => get(get_breadcrumbs),
+        Route::Neighbors => get(get_neighbors),
+        Route::CsrfRefresh => get(refresh_csrf_token),
+        Route::MoveMany => post(move_many),
+        Route::Routes => get(routes_manifest),
+        Route::Duplicates => get(get_duplicates),
+        Route::Config => get(get_client_config),
+        Route::Status => get(get_status),
+        Route::Ping => get(ping),
+        Route::Maintenance => post(set_maintenance),
+        Route::Sitemap => get(get_sitemap),
+        Route::Trash => get(list_trash),
+        Route::RestoreTrash => post(restore_from_trash),
+        Route::EmptyTrash => post(empty_trash),
+        Route::RobotsTxt => get(get_robots_txt),
+        Route::ExportFrontmatter => get(export_frontmatter),
+        Route::FrontmatterPatch => post(patch_frontmatter_api),
+        Route::PublishByTag => post(publish_by_tag),
+        Route::Mount => get(serve_mount),
+        Route::Static => get(serve_static),
+    }
+}
+
+fn create_router(state: AppState) -> Router {
+    routes::ROUTES
+        .iter()
+        .fold(Router::new(), |router, spec| {
+            router.route(spec.path, route_handlers(spec.route))
+        })
+        .fallback(handler_404)
+        // Inflate `Content-Encoding: gzip` request bodies before extraction;
+        // body size limits apply to the inflated bytes.
+        .layer(RequestDecompressionLayer::new())
+        .layer(middleware::from_fn(deadline::scope))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            invalidate_checks_on_write,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            redirect_moved_paths,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            maintenance::enforce,
+        ))
+        .layer(middleware::from_fn(reject_malformed_path_encoding))
+        .layer(middleware::from_fn(request_id_middleware))
+        .layer(middleware::from_fn(error::negotiate))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            base_path::scope,
+        ))
+        .with_state(state)
+}
+
+/// Settings for [`router`], usually taken from the command line.
+pub struct WranglerConfig {
+    pub target_dir: PathBuf,
+    /// Secret CSRF tokens are signed with, from [`resolve_csrf_secret`].
+    pub csrf_secret: String,
+    pub max_upload_size_bytes: usize,
+    pub max_frontmatter_bytes: usize,
+    pub max_frontmatter_list_items: usize,
+    pub frontmatter_asset_keys: Vec<String>,
+    pub max_filename_length: usize,
+    pub api_token: Option<String>,
+    pub allow_private_import_urls: bool,
+    pub mounts: Vec<Mount>,
+    pub audit_journal: Option<PathBuf>,
+    pub disable_iframe_preview: bool,
+    pub remember_path: bool,
+    pub show_snippets: bool,
+    pub max_concurrent_file_serves: usize,
+    pub preview_handlers: Vec<PreviewHandler>,
+    pub enable_preview_commands: bool,
+    pub site_base_url: Option<String>,
+    pub paste_image_dir: Option<String>,
+    /// Contents of `/robots.txt`; `None` serves [`DEFAULT_ROBOTS_TXT`].
+    pub robots_txt: Option<String>,
+    pub display_offset: chrono::FixedOffset,
+    pub optimize_uploads: bool,
+    pub convert_png_uploads_to_webp: bool,
+    pub markdown_extensions: MarkdownExtensions,
+    pub emoji_shortcodes: bool,
+    pub save_normalize: SaveNormalizations,
+    pub save_comparison: SaveComparison,
+    pub durability: Durability,
+    pub hard_delete: bool,
+    pub confirm_delete_name: bool,
+    /// Path the router is nested under with `Router::nest`, e.g. `/editor`;
+    /// empty when it is served at the root. Links, redirects and form
+    /// actions are generated under it.
+    pub base_path: String,
+}
+
+impl WranglerConfig {
+    /// The command line defaults for `target_dir`, with a random CSRF secret.
+    pub fn new(target_dir: impl Into<PathBuf>) -> Self {
+        WranglerConfig {
+            target_dir: target_dir.into(),
+            csrf_secret: random_csrf_secret(),
+            max_upload_size_bytes: 1_048_576,
+            max_frontmatter_bytes: 65_536,
+            max_frontmatter_list_items: 256,
+            frontmatter_asset_keys: checks::default_asset_keys(),
+            max_filename_length: crate::paths::DEFAULT_MAX_FILENAME_LENGTH,
+            api_token: None,
+            allow_private_import_urls: false,
+            mounts: Vec::new(),
+            audit_journal: None,
+            disable_iframe_preview: false,
+            remember_path: false,
+            show_snippets: false,
+            max_concurrent_file_serves: 64,
+            preview_handlers: Vec::new(),
+            enable_preview_commands: false,
+            site_base_url: None,
+            paste_image_dir: None,
+            robots_txt: None,
+            display_offset: chrono::Offset::fix(&chrono::Utc),
+            optimize_uploads: false,
+            convert_png_uploads_to_webp: false,
+            markdown_extensions: MarkdownExtensions::default(),
+            emoji_shortcodes: false,
+            save_normalize: SaveNormalizations::default(),
+            save_comparison: SaveComparison::Exact,
+            durability: Durability::default(),
+            hard_delete: false,
+            confirm_delete_name: false,
+            base_path: String::new(),
+        }
+    }
+}
+
+/// Builds the complete application (state, middleware and `/static` assets)
+/// without binding a listener, for embedding in another axum app:
+///
+/// ```no_run
+/// # async fn example() {
+/// use markdown_wrangler::web::{WranglerConfig, router};
+///
+/// let mut config = WranglerConfig::new("content");
+/// config.base_path = "/editor".to_string();
+/// let app = axum::Router::new().nest("/editor", router(config).await);
+/// # }
+/// ```
+///
+/// `base_path` must match the nesting path. Static assets are read from
+/// `static/` relative to the working directory, as for the binary.
+pub async fn router(config: WranglerConfig) -> Router {
+    let WranglerConfig {
+        target_dir,
+        csrf_secret,
+        max_upload_size_bytes,
+        max_frontmatter_bytes,
+        max_frontmatter_list_items,
+        frontmatter_asset_keys,
+        max_filename_length,
+        api_token,
+        allow_private_import_urls,
+        mounts,
+        audit_journal,
+        disable_iframe_preview,
+        remember_path,
+        show_snippets,
+        max_concurrent_file_serves,
+        preview_handlers,
+        enable_preview_commands,
+        site_base_url,
+        paste_image_dir,
+        robots_txt,
+        display_offset,
+        optimize_uploads,
+        convert_png_uploads_to_webp,
+        markdown_extensions,
+        emoji_shortcodes,
+        save_normalize,
+        save_comparison,
+        durability,
+        hard_delete,
+        confirm_delete_name,
+        base_path,
+    } = config;
+    let preview_commands = match (enable_preview_commands, preview_handlers.is_empty()) {
+        (true, false) => Some(preview_commands::PreviewCommands::new(&preview_handlers)),
+        (false, false) => {
+            warn!("Preview handlers are configured but ignored without --enable-preview-commands");
+            None
+        }
+        (_, true) => None,
+    };
+
+    if convert_png_uploads_to_webp && !optimize_uploads {
+        warn!("--convert-png-uploads-to-webp is ignored without --optimize-uploads");
+    }
+    let upload_optimizer =
+        optimize_uploads.then(|| image_optimize::UploadOptimizer::new(convert_png_uploads_to_webp));
+
+    let case_insensitive_fs = probe_case_insensitive(&target_dir)
+        .await
+        .unwrap_or_else(|err| {
+            warn!("Failed to probe filesystem case sensitivity: {}", err);
+            false
+        });
+    if case_insensitive_fs {
+        info!("Target directory is case-insensitive; guarding against case-only collisions");
+    }
+
+    let path_redirects = redirects::PathRedirects::new(target_dir.join(redirects::REDIRECTS_FILE));
+    let maintenance =
+        maintenance::Maintenance::load(target_dir.join(maintenance::MAINTENANCE_FILE), durability)
+            .await;
+    let interrupted_operations = journal::find_interrupted(&target_dir).await;
+    if !interrupted_operations.is_empty() {
+        warn!(
+            "Found {} interrupted bulk operation(s); see /problems",
+            interrupted_operations.len()
+        );
+    }
+
+    let state = AppState {
+        target_dir,
+        csrf_secret,
+        max_upload_size_bytes,
+        max_frontmatter_bytes,
+        max_frontmatter_list_items,
+        max_filename_length,
+        api_token,
+        allow_private_import_urls,
+        checks: checks::ChecksCache::new(checks::default_checks(&frontmatter_asset_keys)),
+        frontmatter_asset_keys,
+        mounts,
+        audit: audit_journal.map(audit::AuditJournal::new),
+        disable_iframe_preview,
+        case_insensitive_fs,
+        remember_path,
+        show_snippets,
+        redirects: path_redirects,
+        file_serves: Arc::new(Semaphore::new(max_concurrent_file_serves.max(1))),
+        operations: operations::OperationRegistry::default(),
+        assets: assets::AssetManifest::load(Path::new(assets::STATIC_DIR)).await,
+        preview_commands,
+        site_base_url,
+        paste_image_dir,
+        robots_txt: robots_txt.unwrap_or_else(|| DEFAULT_ROBOTS_TXT.to_string()),
+        display_offset,
+        upload_optimizer,
+        markdown_extensions,
+        emoji_shortcodes,
+        save_normalize,
+        save_comparison,
+        durability,
+        hard_delete,
+        confirm_delete_name,
+        save_latency: durable::SaveLatency::default(),
+        started_at: std::time::Instant::now(),
+        readme_cache: markdown::RenderCache::default(),
+        content_hashes: content_hash::ContentHashCache::default(),
+        interrupted_operations: Arc::new(interrupted_operations),
+        base_path: Arc::from(base_path::normalize(&base_path)),
+        maintenance,
+    };
+    create_router(state)
+}
+
+/// Settings for [`start_server`]: the app and the address to serve it on.
+pub struct ServerOptions {
+    pub config: WranglerConfig,
+    /// Address and port the listener binds to.
+    pub bind_addr: SocketAddr,
+}
+
+pub async fn start_server(options: ServerOptions) -> Result<(), ServerError> {
+    let ServerOptions { config, bind_addr } = options;
+    let app = router(config).await;
+
+    let listener = bind_listener(bind_addr).await?;
+    let local_addr = listener.local_addr().map_err(|source| ServerError::Bind {
+        address: bind_addr.to_string(),
+        source,
+    })?;
+    if !local_addr.ip().is_loopback() {
+        warn!(
+            "Listening on non-loopback address {}; the server is reachable from the network",
+            local_addr.ip()
+        );
+    }
+    info!(
+        "Web server listening on http://{}, press Ctrl+C to stop",
+        local_addr
+    );
+
+    axum::serve(listener, app).await.map_err(ServerError::Serve)
+}
+
+async fn bind_listener(address: SocketAddr) -> Result<TcpListener, ServerError> {
+    TcpListener::bind(address)
+        .await
+        .map_err(|source| match source.kind() {
+            std::io::ErrorKind::AddrInUse => ServerError::AddrInUse { address },
+            _ => ServerError::Bind {
+                address: address.to_string(),
+                source,
+            },
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paths::path_url;
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use http_body_util::BodyExt;
+    use sha2::Digest;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    const DEFAULT_MAX_UPLOAD_SIZE_BYTES: usize = 1_048_576;
+    const DEFAULT_MAX_FRONTMATTER_BYTES: usize = 65_536;
+    const DEFAULT_MAX_FRONTMATTER_LIST_ITEMS: usize = 256;
+    const DEFAULT_MAX_CONCURRENT_FILE_SERVES: usize = 64;
+    const TEST_API_TOKEN: &str = "test-api-token";
+    async fn create_test_app_with(
+        configure: impl FnOnce(&mut AppState),
+    ) -> (Router, TempDir, String) {
+        let temp_dir = TempDir::new().expect("failed to create temporary test directory");
+        let csrf_secret = "test_secret_key_for_csrf_testing".to_string();
+        let mut state = AppState {
+            target_dir: temp_dir.path().to_path_buf(),
+            csrf_secret: csrf_secret.clone(),
+            max_upload_size_bytes: DEFAULT_MAX_UPLOAD_SIZE_BYTES,
+            max_frontmatter_bytes: DEFAULT_MAX_FRONTMATTER_BYTES,
+            max_frontmatter_list_items: DEFAULT_MAX_FRONTMATTER_LIST_ITEMS,
+            frontmatter_asset_keys: checks::default_asset_keys(),
+            max_filename_length: crate::paths::DEFAULT_MAX_FILENAME_LENGTH,
+            api_token: Some(TEST_API_TOKEN.to_string()),
+            allow_private_import_urls: false,
+            checks: checks::ChecksCache::default(),
+            mounts: Vec::new(),
+            audit: None,
+            disable_iframe_preview: false,
+            case_insensitive_fs: false,
+            remember_path: false,
+            show_snippets: false,
+            redirects: redirects::PathRedirects::new(
+                temp_dir.path().join(redirects::REDIRECTS_FILE),
+            ),
+            file_serves: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_FILE_SERVES)),
+            operations: operations::OperationRegistry::default(),
+            assets: assets::AssetManifest::load(Path::new(assets::STATIC_DIR)).await,
+            preview_commands: None,
+            site_base_url: None,
+            paste_image_dir: None,
+            robots_txt: DEFAULT_ROBOTS_TXT.to_string(),
+            display_offset: chrono::Offset::fix(&chrono::Utc),
+            upload_optimizer: None,
+            markdown_extensions: MarkdownExtensions::default(),
+            emoji_shortcodes: false,
+            save_normalize: SaveNormalizations::default(),
+            save_comparison: SaveComparison::Exact,
+            durability: Durability::default(),
+            hard_delete: false,
+            confirm_delete_name: false,
+            save_latency: durable::SaveLatency::default(),
+            started_at: std::time::Instant::now(),
+            readme_cache: markdown::RenderCache::default(),
+            content_hashes: content_hash::ContentHashCache::default(),
+            interrupted_operations: Arc::default(),
+            base_path: Arc::from(""),
+            maintenance: maintenance::Maintenance::load(
+                temp_dir.path().join(maintenance::MAINTENANCE_FILE),
+                Durability::default(),
+            )
+            .await,
+        };
+        configure(&mut state);
+        let app = create_router(state);
+        (app, temp_dir, csrf_secret)
+    }
+
+    async fn create_test_app_with_max_upload_size(
+        max_upload_size_bytes: usize,
+    ) -> (Router, TempDir, String) {
+        create_test_app_with(|state| state.max_upload_size_bytes = max_upload_size_bytes).await
+    }
+
+    async fn create_test_app() -> (Router, TempDir, String) {
+        create_test_app_with_max_upload_size(DEFAULT_MAX_UPLOAD_SIZE_BYTES).await
+    }
+
+    fn create_expired_csrf_token(secret: &str) -> String {
+        create_csrf_token_with_age(secret, 7200) // 2 hours ago
+    }
+
+    fn create_csrf_token_with_age(secret: &str, age_seconds: u64) -> String {
+        let issued_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system clock should be after UNIX_EPOCH")
+            .as_secs()
+            - age_seconds;
+        let payload = format!("{issued_timestamp}:12345");
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+            .expect("failed to initialize HMAC for expired CSRF token");
+        mac.update(payload.as_bytes());
+        let signature = hex::encode(mac.finalize().into_bytes());
+        format!("{payload}:{signature}")
+    }
+
+    fn create_legacy_keyed_csrf_token(secret: &str) -> String {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system clock should be after UNIX_EPOCH")
+            .as_secs();
+        let payload = format!("{timestamp}:12345");
+        let mut hasher = Sha256::new();
+        hasher.update(payload.as_bytes());
+        hasher.update(secret.as_bytes());
+        let signature = hex::encode(hasher.finalize());
+        format!("{payload}:{signature}")
+    }
+
+    fn extract_csrf_token_from_html(html: &str) -> Option<String> {
+        let marker = r#"name="csrf_token" value=""#;
+        let start = html.find(marker)? + marker.len();
+        let remainder = &html[start..];
+        let end = remainder.find('"')?;
+        Some(remainder[..end].to_string())
+    }
+
+    fn build_upload_multipart_body(
+        boundary: &str,
+        path: &str,
+        csrf_token: &str,
+        file_name: &str,
+        file_bytes: &[u8],
+    ) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"path\"\r\n\r\n{path}\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{csrf_token}\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(
+            format!(
+                "--{boundary}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"{file_name}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(file_bytes);
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+        body
+    }
+
+    fn create_valid_png_bytes() -> Vec<u8> {
+        let image = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 0, 0, 255]));
+        let dynamic_image = image::DynamicImage::ImageRgba8(image);
+        let mut bytes = Vec::new();
+        let mut cursor = std::io::Cursor::new(&mut bytes);
+        dynamic_image
+            .write_to(&mut cursor, image::ImageFormat::Png)
+            .expect("failed to encode valid png bytes for test");
+        bytes
+    }
+
+    #[test]
+    fn test_csrf_token_generation() {
+        let secret = "test_secret";
+        let token = generate_csrf_token(secret);
+
+        // Token should have 3 parts separated by colons
+        let parts: Vec<&str> = token.split(':').collect();
+        assert_eq!(parts.len(), 3);
+
+        // All parts should be non-empty
+        assert!(!parts[0].is_empty()); // timestamp
+        assert!(!parts[1].is_empty()); // nonce
+        assert!(!parts[2].is_empty()); // signature
+    }
+
+    #[test]
+    fn test_csrf_token_validation_valid() {
+        let secret = "test_secret";
+        let token = generate_csrf_token(secret);
+
+        // Valid token should pass validation
+        assert!(validate_csrf_token(&token, secret).is_ok());
+    }
+
+    #[test]
+    fn test_csrf_token_validation_wrong_secret() {
+        let secret = "test_secret";
+        let wrong_secret = "wrong_secret";
+        let token = generate_csrf_token(secret);
+
+        // Token with wrong secret should fail validation
+        assert!(validate_csrf_token(&token, wrong_secret).is_err());
+    }
+
+    #[test]
+    fn test_csrf_token_validation_malformed() {
+        let secret = "test_secret";
+
+        // Various malformed tokens should fail validation
+        assert!(validate_csrf_token("", secret).is_err());
+        assert!(validate_csrf_token("invalid", secret).is_err());
+        assert!(validate_csrf_token("only:two", secret).is_err());
+        assert!(validate_csrf_token("too:many:parts:here", secret).is_err());
+        assert!(validate_csrf_token(":::", secret).is_err());
+        assert!(validate_csrf_token("1:2:not_hex_signature_@", secret).is_err());
+    }
+
+    #[test]
+    fn test_csrf_token_validation_expired() {
+        let secret = "test_secret";
+
+        let expired_token = create_expired_csrf_token(secret);
+
+        // Expired token should fail validation
+        assert!(validate_csrf_token(&expired_token, secret).is_err());
+    }
+
+    #[test]
+    fn test_csrf_token_validation_rejects_legacy_keyed_hash_token() {
+        let secret = "test_secret";
+        let legacy_token = create_legacy_keyed_csrf_token(secret);
+        assert!(validate_csrf_token(&legacy_token, secret).is_err());
+    }
+
+    #[test]
+    fn test_csrf_token_validation_invalid_timestamp() {
+        let secret = "test_secret";
+
+        // Token with invalid timestamp should fail validation
+        let invalid_token = "invalid_timestamp:12345:signature";
+        assert!(validate_csrf_token(invalid_token, secret).is_err());
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_yaml_true() {
+        let content = r#"---
+title: Post
+draft: true
+---
+# Hello
+"#;
+        assert!(has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_yaml_false() {
+        let content = r#"---
+title: Post
+draft: false
+---
+# Hello
+"#;
+        assert!(!has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_json_true() {
+        let content = r#"{
+  "title": "Post",
+  "draft": true
+}
+# Hello
+"#;
+        assert!(has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_json_false() {
+        let content = r#"{
+  "title": "Post",
+  "draft": false
+}
+# Hello
+"#;
+        assert!(!has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_toml_true() {
+        let content = r#"+++
+title = "Post"
+draft = true
++++
+# Hello
+"#;
+        assert!(has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_toml_false() {
+        let content = r#"+++
+title = "Post"
+draft = false
++++
+# Hello
+"#;
+        assert!(!has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_parse_frontmatter_yaml_collects_standard_fields_and_extra() {
+        let content = r#"---
+title: "Post Title"
+date: "2026-02-15"
+draft: true
+tags:
+  - rust
+  - web
+categories: docs
+custom_score: 42
+author:
+  name: James
+---
+# Hello
+"#;
+
+        let parsed = parse_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES,
+            DEFAULT_MAX_FRONTMATTER_LIST_ITEMS,
+        )
+        .expect("yaml frontmatter should parse");
+        let (draft, title, date, tags, categories, extra) = parsed;
+
+        assert_eq!(draft, Some(true));
+        assert_eq!(title, Some("Post Title".to_string()));
+        assert_eq!(date, Some("2026-02-15".to_string()));
+        assert_eq!(tags, vec!["rust".to_string(), "web".to_string()]);
+        assert_eq!(categories, vec!["docs".to_string()]);
+        assert_eq!(
+            extra.get("custom_score").and_then(|value| value.as_i64()),
+            Some(42)
+        );
+        assert!(extra.contains_key("author"));
+    }
+
+    #[test]
+    fn test_parse_frontmatter_json_collects_standard_fields_and_extra() {
+        let content = r#"{
+  "title": "Post Title",
+  "date": "2026-02-15",
+  "draft": "true",
+  "tags": "rust, web",
+  "categories": ["docs", "guides"],
+  "custom_score": 42
+}
+# Hello
+"#;
+
+        let parsed = parse_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES,
+            DEFAULT_MAX_FRONTMATTER_LIST_ITEMS,
+        )
+        .expect("json frontmatter should parse");
+        let (draft, title, date, tags, categories, extra) = parsed;
+
+        assert_eq!(draft, Some(true));
+        assert_eq!(title, Some("Post Title".to_string()));
+        assert_eq!(date, Some("2026-02-15".to_string()));
+        assert_eq!(tags, vec!["rust".to_string(), "web".to_string()]);
+        assert_eq!(categories, vec!["docs".to_string(), "guides".to_string()]);
+        assert_eq!(
+            extra.get("custom_score").and_then(|value| value.as_i64()),
+            Some(42)
+        );
+    }
+
+    #[test]
+    fn test_parse_frontmatter_toml_collects_standard_fields_and_extra() {
+        let content = "+++\r\n\
+title = \"Post Title\"\r\n\
+date = 2026-02-15\r\n\
+draft = true\r\n\
+tags = [\"rust\", \"web\"]\r\n\
+categories = \"docs\"\r\n\
+custom_score = 42\r\n\
+\r\n\
+[author]\r\n\
+name = \"James\"\r\n\
++++\r\n\
+# Hello\r\n";
+
+        let parsed = parse_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES,
+            DEFAULT_MAX_FRONTMATTER_LIST_ITEMS,
+        )
+        .expect("toml frontmatter should parse");
+        let (draft, title, date, tags, categories, extra) = parsed;
+
+        assert_eq!(draft, Some(true));
+        assert_eq!(title, Some("Post Title".to_string()));
+        assert_eq!(date, Some("2026-02-15".to_string()));
+        assert_eq!(tags, vec!["rust".to_string(), "web".to_string()]);
+        assert_eq!(categories, vec!["docs".to_string()]);
+        assert_eq!(
+            extra.get("custom_score").and_then(|value| value.as_i64()),
+            Some(42)
+        );
+        assert_eq!(extra["author"]["name"], "James");
+        assert_eq!(
+            content.get(frontmatter_len(content, DEFAULT_MAX_FRONTMATTER_BYTES).unwrap_or(0)..),
+            Some("# Hello\r\n")
+        );
+    }
+
+    #[test]
+    fn test_parse_frontmatter_without_frontmatter_returns_none() {
+        let content = "# Just markdown\n\nNo frontmatter.";
+        assert!(
+            parse_frontmatter(
+                content,
+                DEFAULT_MAX_FRONTMATTER_BYTES,
+                DEFAULT_MAX_FRONTMATTER_LIST_ITEMS
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_parse_frontmatter_rejects_pathological_nesting_quickly() {
+        const MAX_BYTES: usize = 4 * 1024 * 1024;
+        let depth = 100_000;
+        let flow = format!(
+            "---\ntitle: Deep\nnested: {}{}\n---\nBody",
+            "[".repeat(depth),
+            "]".repeat(depth)
+        );
+        let json = format!(
+            "{{\"title\": \"Deep\", \"nested\": {}1{}}}\nBody",
+            "{\"a\": ".repeat(depth),
+            "}".repeat(depth)
+        );
+        let block = (0..200)
+            .map(|level| format!("{}a:", " ".repeat(level)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let indented = format!("---\n{block} 1\n---\nBody");
+
+        let started = std::time::Instant::now();
+        for content in [&flow, &json, &indented] {
+            assert!(parse_frontmatter(content, MAX_BYTES, SCALARS_ONLY).is_none());
+            let err = frontmatter_syntax_error(content, MAX_BYTES)
+                .expect("deep nesting should be reported");
+            assert!(err.message.contains("nested"));
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+
+        // Ordinary nesting, brackets inside quotes and flow lists still parse.
+        let normal = "---\ntitle: \"[[[[\"\ntags: [a, b]\nparams:\n  images:\n    - src: a.png\n      alt: \"{x}\"\n---\nBody";
+        let parsed = parse_frontmatter(normal, MAX_BYTES, DEFAULT_MAX_FRONTMATTER_LIST_ITEMS)
+            .expect("normal frontmatter should parse");
+        assert_eq!(parsed.1.as_deref(), Some("[[[["));
+        assert_eq!(parsed.3, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn test_frontmatter_syntax_error_reports_file_position() {
+        let broken_yaml = "---\ntitle: Post\ntags: [one, two\n---\nBody";
+        let err = frontmatter_syntax_error(broken_yaml, DEFAULT_MAX_FRONTMATTER_BYTES)
+            .expect("unclosed flow sequence should not parse");
+        let (line, _) = err.location.expect("YAML errors carry a location");
+        assert!(line >= 3, "line {line} should be inside the block");
+        assert!(err.location_text().starts_with("line "));
+
+        let broken_json = "{\n  \"title\": \"Post\"\n  \"draft\": true\n}\nBody";
+        let err = frontmatter_syntax_error(broken_json, DEFAULT_MAX_FRONTMATTER_BYTES)
+            .expect("missing comma should not parse");
+        assert_eq!(err.location.map(|(line, _)| line), Some(3));
+
+        let broken_toml = "+++\ntitle = \"Post\"\ndraft = tru\n+++\nBody";
+        let err = frontmatter_syntax_error(broken_toml, DEFAULT_MAX_FRONTMATTER_BYTES)
+            .expect("bad boolean should not parse");
+        assert_eq!(err.location, Some((3, 9)));
+
+        for fine in [
+            "---\ntitle: Post\n---\nBody",
+            "+++\ntitle = \"Post\"\n+++\nBody",
+            "# No frontmatter\n",
+            "",
+        ] {
+            assert_eq!(
+                frontmatter_syntax_error(fine, DEFAULT_MAX_FRONTMATTER_BYTES),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_unterminated_yaml() {
+        let content = r#"---
+title: Post
+draft: true
+# Hello
+"#;
+        assert!(!has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_unterminated_json() {
+        let content = r#"{
+  "title": "Post",
+  "draft": true
+# Hello
+"#;
+        assert!(!has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[test]
+    fn test_has_draft_frontmatter_with_invalid_json_syntax() {
+        let content = r#"{
+  "title": "Post",
+  "draft": tru
+}
+# Hello
+"#;
+        assert!(!has_draft_frontmatter(
+            content,
+            DEFAULT_MAX_FRONTMATTER_BYTES
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_save_endpoint_without_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        // Create a test markdown file
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        // Try to save without CSRF token
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/save")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from("path=test.md&content=# Updated Content"))
+            .expect("failed to build save request without csrf token");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send save request without csrf token");
+
+        // Should return 422 Unprocessable Entity due to missing CSRF token
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_save_endpoint_with_invalid_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        // Create a test markdown file
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        // Try to save with invalid CSRF token
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/save")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(
+                "path=test.md&content=# Updated Content&csrf_token=invalid",
+            ))
+            .expect("failed to build save request with invalid csrf token");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send save request with invalid csrf token");
+
+        // Should return 403 Forbidden due to invalid CSRF token
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("failed to collect response body for invalid csrf save")
+            .to_bytes();
+        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
+        assert!(
+            body_text
+                .to_ascii_lowercase()
+                .contains("invalid csrf token")
+        );
+
+        // File should remain unchanged
+        let content = fs::read_to_string(&test_file)
+            .await
+            .expect("failed to read file");
+        assert_eq!(content, "# Test");
+    }
+
+    #[tokio::test]
+    async fn test_save_endpoint_with_expired_csrf_token() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        let expired_token = create_expired_csrf_token(&csrf_secret);
+        let body = format!(
+            "path=test.md&content=# Updated Content&csrf_token={}",
+            urlencoding::encode(&expired_token)
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/save")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("failed to build save request with expired csrf token");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send save request with expired csrf token");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("failed to collect response body for expired csrf save")
+            .to_bytes();
+        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
+        assert!(
+            body_text
+                .to_ascii_lowercase()
+                .contains("invalid csrf token")
+        );
+
+        // File should remain unchanged
+        let content = fs::read_to_string(&test_file)
+            .await
+            .expect("Failed to read test file");
+        assert_eq!(content, "# Test");
+    }
+
+    #[tokio::test]
+    async fn test_save_endpoint_with_valid_csrf_token() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+
+        // Create a test markdown file
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        // Generate valid CSRF token
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        // Save with valid CSRF token
+        let body = format!(
+            "path=test.md&content=# Updated Content&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/save")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("failed to build save request with valid csrf token");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send save request with valid csrf token");
+
+        // Should succeed
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Verify file content was updated
+        let content = fs::read_to_string(&test_file)
+            .await
+            .expect("Failed to read test file");
+        assert_eq!(content, "# Updated Content");
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_mode_refuses_writes_until_it_ends() {
+        let mut handle = None;
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| handle = Some(state.maintenance.clone())).await;
+        let maintenance = handle.expect("maintenance handle should be captured");
+        fs::write(temp_dir.path().join("post.md"), "# Post\n")
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = generate_csrf_token(&csrf_secret);
+        let toggle = |authorization: Option<&str>, body: serde_json::Value| {
+            let mut request = Request::builder()
+                .method(Method::POST)
+                .uri("/admin/maintenance")
+                .header("content-type", "application/json");
+            if let Some(authorization) = authorization {
+                request = request.header(header::AUTHORIZATION, authorization);
+            }
+            app.clone().oneshot(
+                request
+                    .body(Body::from(body.to_string()))
+                    .expect("Failed to build maintenance request"),
+            )
+        };
+        let bearer = format!("Bearer {TEST_API_TOKEN}");
+        let enable = serde_json::json!({
+            "csrf_token": csrf_token,
+            "enabled": true,
+            "message": "Restoring last night's snapshot",
+            "expires_in_secs": 600,
+        });
+
+        let response = toggle(None, enable.clone())
+            .await
+            .expect("Failed to send maintenance request");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let response = toggle(
+            Some(&bearer),
+            serde_json::json!({ "csrf_token": "bogus", "enabled": true }),
+        )
+        .await
+        .expect("Failed to send maintenance request");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(maintenance.active(), None);
+
+        let response = toggle(Some(&bearer), enable)
+            .await
+            .expect("Failed to send maintenance request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(temp_dir.path().join(maintenance::MAINTENANCE_FILE).exists());
+
+        let save_body = format!(
+            "path=post.md&content=%23+Edited%0A&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        assert_eq!(
+            post_form(&app, "/save", save_body.clone()).await.status(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        // Read-only POST routes stay open.
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/render")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({ "path": "post.md", "content": "# Draft" }).to_string(),
+                    ))
+                    .expect("Failed to build render request"),
+            )
+            .await
+            .expect("Failed to send render request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let (status, html) = get_html(app.clone(), "/edit?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Maintenance in progress: Restoring last night&#39;s snapshot"));
+
+        // Let the notice run out rather than waiting ten minutes.
+        let mut notice = maintenance.active().expect("maintenance should be on");
+        notice.expires_at = Some(timefmt::epoch_seconds(SystemTime::now()) - 1);
+        maintenance
+            .set(Some(notice))
+            .await
+            .expect("Failed to expire maintenance");
+
+        assert_eq!(
+            post_form(&app, "/save", save_body).await.status(),
+            StatusCode::OK
+        );
+        let (_, html) = get_html(app, "/edit?path=post.md").await;
+        assert!(!html.contains("maintenance-banner"));
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("post.md"))
+                .await
+                .expect("Failed to read saved file"),
+            "# Edited\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_flag_is_unreachable_through_file_routes() {
+        let mut handle = None;
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| handle = Some(state.maintenance.clone())).await;
+        let maintenance = handle.expect("maintenance handle should be captured");
+        // An expired notice leaves the file in place without refusing writes.
+        maintenance
+            .set(Some(maintenance::MaintenanceNotice {
+                message: "Finished".to_string(),
+                expires_at: Some(timefmt::epoch_seconds(SystemTime::now()) - 1),
+            }))
+            .await
+            .expect("Failed to write maintenance flag");
+        let flag = temp_dir.path().join(maintenance::MAINTENANCE_FILE);
+        let stored = fs::read(&flag)
+            .await
+            .expect("Maintenance flag should exist");
+
+        let encoded = urlencoding::encode(maintenance::MAINTENANCE_FILE);
+        let response = get_index(&app, &format!("/file?path={encoded}"), None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = format!(
+            "path={encoded}&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let response = post_form(&app, "/delete", body).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app
+            .clone()
+            .oneshot(build_put_raw_request(
+                &format!("/raw?path={encoded}"),
+                r#"{"message":"Forged"}"#,
+                &[],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        assert_eq!(
+            fs::read(&flag)
+                .await
+                .expect("Maintenance flag should remain"),
+            stored
+        );
+    }
+
+    async fn directory_names(dir: &Path) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut entries = fs::read_dir(dir).await.expect("Failed to read directory");
+        while let Some(entry) = entries.next_entry().await.expect("Failed to read entry") {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_save_writes_atomically_without_leaving_temp_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let path = temp_dir.path().join("post.md");
+        fs::write(&path, "# Post\n")
+            .await
+            .expect("Failed to write markdown file");
+        fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640))
+            .await
+            .expect("Failed to set permissions");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let body = format!("path=post.md&content=%23+Saved%0A&csrf_token={csrf_token}");
+        assert_eq!(
+            post_form(&app, "/save", body).await.status(),
+            StatusCode::OK
+        );
+
+        assert_eq!(
+            fs::read_to_string(&path)
+                .await
+                .expect("Failed to read saved file"),
+            "# Saved\n"
+        );
+        let mode = fs::metadata(&path)
+            .await
+            .expect("Failed to read metadata")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(directory_names(temp_dir.path()).await, vec!["post.md"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_atomic_write_keeps_original_and_cleans_up() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        // Renaming a file over a non-empty directory fails after the
+        // temporary file has been written.
+        let target = temp_dir.path().join("post.md");
+        fs::create_dir(&target)
+            .await
+            .expect("Failed to create directory");
+        fs::write(target.join("keep.txt"), "kept")
+            .await
+            .expect("Failed to write file");
+
+        assert!(
+            durable::write_atomic(&target, b"# New\n", Durability::Fsync)
+                .await
+                .is_err()
+        );
+        assert_eq!(directory_names(temp_dir.path()).await, vec!["post.md"]);
+        assert_eq!(directory_names(&target).await, vec!["keep.txt"]);
+    }
+
+    fn hidden_field(html: &str, name: &str) -> String {
+        let marker = format!(r#"name="{name}" value=""#);
+        let start = html.find(&marker).expect("hidden field should be present") + marker.len();
+        html[start..]
+            .split('"')
+            .next()
+            .expect("hidden field value should be closed")
+            .to_string()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_save_detects_file_replaced_by_older_file_since_editor_loaded() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let path = temp_dir.path().join("post.md");
+        fs::write(&path, "# Current\n")
+            .await
+            .expect("Failed to write markdown file");
+        let (status, html) = get_html(app.clone(), "/edit?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        let file_identity = hidden_field(&html, "file_identity");
+
+        // A checkout of an old branch: same size, different inode, and an
+        // mtime earlier than the file the editor loaded.
+        let replacement = temp_dir.path().join("old-branch.md");
+        fs::write(&replacement, "# Ancient\n")
+            .await
+            .expect("Failed to write replacement");
+        fs::File::options()
+            .write(true)
+            .open(&replacement)
+            .await
+            .expect("Failed to open replacement")
+            .into_std()
+            .await
+            .set_modified(SystemTime::now() - Duration::from_secs(86_400))
+            .expect("Failed to set mtime");
+        fs::rename(&replacement, &path)
+            .await
+            .expect("Failed to replace file");
+
+        let save = |file_identity: &str| {
+            post_form(
+                &app,
+                "/save",
+                format!(
+                    "path=post.md&content=%23+Mine%0A&csrf_token={}&file_identity={}",
+                    urlencoding::encode(&generate_csrf_token(&csrf_secret)),
+                    urlencoding::encode(file_identity)
+                ),
+            )
+        };
+
+        let response = save(&file_identity).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Failed to read response body");
+        let html = String::from_utf8_lossy(&body);
+        assert!(html.contains("File Changed Since You Opened It"));
+        assert!(html.contains("# Mine"));
+        assert_eq!(
+            fs::read_to_string(&path)
+                .await
+                .expect("Failed to read file"),
+            "# Ancient\n"
+        );
+
+        // A forged identity is refused outright.
+        let forged = format!("{}0", file_identity);
+        let response = save(&forged).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The conflict page carries the identity of the file now on disk,
+        // so saving from it overwrites deliberately.
+        let response = save(&hidden_field(&html, "file_identity")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(&path)
+                .await
+                .expect("Failed to read file"),
+            "# Mine\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_with_base_modified_time_checks_for_concurrent_edits() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let path = temp_dir.path().join("post.md");
+        fs::write(&path, "# Post\n")
+            .await
+            .expect("Failed to write markdown file");
+        let (_, html) = get_html(app.clone(), "/edit?path=post.md").await;
+        let base_modified_time = hidden_field(&html, "base_modified_time");
+        let save_body = |content: &str| {
+            format!(
+                "path=post.md&content={content}&csrf_token={}&base_modified_time={base_modified_time}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            )
+        };
+
+        // Nothing else touched the file: the save goes through.
+        assert_eq!(
+            post_form(&app, "/save", save_body("%23+First+tab%0A"))
+                .await
+                .status(),
+            StatusCode::OK
+        );
+
+        // The first tab's save moved the mtime on, so a second tab still
+        // holding the original load time is refused.
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .await
+            .expect("Failed to open file")
+            .into_std()
+            .await
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .expect("Failed to set mtime");
+        let response = post_form(&app, "/save", save_body("%23+Second+tab%0A")).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Failed to read response body");
+        assert!(String::from_utf8_lossy(&body).contains("changed by someone else"));
+        assert_eq!(
+            fs::read_to_string(&path)
+                .await
+                .expect("Failed to read file"),
+            "# First tab\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_comparison_mode_decides_whitespace_only_saves() {
+        for (comparison, expect_write) in [
+            (SaveComparison::Exact, true),
+            (SaveComparison::Normalized, false),
+        ] {
+            let (app, temp_dir, csrf_secret) = create_test_app_with(|state| {
+                state.save_normalize = SaveNormalizations {
+                    line_endings: true,
+                    trailing_newline: true,
+                };
+                state.save_comparison = comparison;
+            })
+            .await;
+            let original = "# Post\n\nBody\n";
+            fs::write(temp_dir.path().join("post.md"), original)
+                .await
+                .expect("Failed to write markdown file");
+            let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+            let response = post_form(
+                &app,
+                "/save",
+                format!(
+                    "path=post.md&content={}&csrf_token={csrf_token}",
+                    urlencoding::encode("# Post\r\n\r\nBody")
+                ),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("Failed to read response body");
+            let html = String::from_utf8_lossy(&body);
+
+            if expect_write {
+                assert!(html.contains("File Saved Successfully"));
+                assert!(
+                    html.contains("exact comparison; normalized: line endings, trailing newline")
+                );
+            } else {
+                assert!(html.contains("No Changes to Save"));
+                assert!(
+                    html.contains(
+                        "normalized comparison; normalized: line endings, trailing newline"
+                    )
+                );
+            }
+            // Whatever the mode, the file holds the normalized form.
+            assert_eq!(
+                fs::read_to_string(temp_dir.path().join("post.md"))
+                    .await
+                    .expect("Failed to read saved file"),
+                original
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping_reports_version_and_increasing_uptime() {
+        let (app, _temp_dir, _csrf_secret) = create_test_app_with(|state| {
+            state.started_at = std::time::Instant::now()
+                .checked_sub(Duration::from_secs(60))
+                .expect("Instant too close to its origin");
+        })
+        .await;
+        let ping = |app: Router| async move {
+            let (status, _, body) = get_body(&app, "/api/ping").await;
+            assert_eq!(status, StatusCode::OK);
+            serde_json::from_slice::<serde_json::Value>(&body).expect("ping should be JSON")
+        };
+
+        let first = ping(app.clone()).await;
+        assert_eq!(first["version"], env!("CARGO_PKG_VERSION"));
+        let first_uptime = first["uptime_secs"]
+            .as_u64()
+            .expect("uptime_secs should be a non-negative integer");
+        assert!(first_uptime >= 60);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        let second_uptime = ping(app).await["uptime_secs"]
+            .as_u64()
+            .expect("uptime_secs should be a non-negative integer");
+        assert!(second_uptime > first_uptime);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_durability_and_save_latency() {
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| state.durability = Durability::DirSync).await;
+        fs::write(temp_dir.path().join("post.md"), "# Before\n")
+            .await
+            .expect("Failed to write markdown file");
+
+        let status = |app: Router| async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/status")
+                        .body(Body::empty())
+                        .expect("failed to build status request"),
+                )
+                .await
+                .expect("failed to fetch status");
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("Failed to read response body");
+            serde_json::from_slice::<serde_json::Value>(&body).expect("status should be JSON")
+        };
+        let before = status(app.clone()).await;
+        assert_eq!(before["durability"], "dir-sync");
+        assert_eq!(before["saves"], 0);
+        assert!(before["average_save_ms"].is_null());
+
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let response = post_form(
+            &app,
+            "/save",
+            format!("path=post.md&content=%23+After%0A&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("post.md"))
+                .await
+                .expect("Failed to read saved file"),
+            "# After\n"
+        );
+
+        let after = status(app).await;
+        assert_eq!(after["saves"], 1);
+        assert!(after["average_save_ms"].is_number());
+    }
+
+    #[tokio::test]
+    async fn test_save_blocks_broken_frontmatter_until_forced() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let original = "---\ntitle: Post\n---\n# Post\n";
+        fs::write(temp_dir.path().join("post.md"), original)
+            .await
+            .expect("Failed to write markdown file");
+        let broken = "---\ntitle: \"Post\ndraft: true\n---\n# Post <edited>\n";
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let body = format!(
+            "path=post.md&content={}&csrf_token={csrf_token}",
+            urlencoding::encode(broken)
+        );
+
+        let response = post_form(&app, "/save", body.clone()).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html = String::from_utf8_lossy(&bytes);
+        assert!(html.contains("Frontmatter Would Not Parse"));
+        assert!(html.contains("line "));
+        // The submitted content comes back so nothing is lost.
+        assert!(html.contains("# Post &#60;edited&#62;"));
+        assert!(html.contains(r#"name="force" value="true""#));
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("post.md"))
+                .await
+                .expect("Failed to read file"),
+            original
+        );
+
+        let status = post_form(&app, "/save", format!("{body}&force=true"))
+            .await
+            .status();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("post.md"))
+                .await
+                .expect("Failed to read file"),
+            broken
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_without_original_frontmatter_is_not_checked() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::write(temp_dir.path().join("notes.md"), "# Notes\n")
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+
+        let status = post_form(
+            &app,
+            "/save",
+            format!(
+                "path=notes.md&content={}&csrf_token={csrf_token}",
+                urlencoding::encode("# Notes\n\nMore text.\n")
+            ),
+        )
+        .await
+        .status();
+        assert_eq!(status, StatusCode::OK);
+
+        // Adding a first, broken block is not blocked either: there was no
+        // working frontmatter to lose.
+        let broken = "---\ntitle: [\n---\n# Notes\n";
+        let status = post_form(
+            &app,
+            "/save",
+            format!(
+                "path=notes.md&content={}&csrf_token={csrf_token}",
+                urlencoding::encode(broken)
+            ),
+        )
+        .await
+        .status();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("notes.md"))
+                .await
+                .expect("Failed to read file"),
+            broken
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_accepts_gzip_encoded_body() {
+        use std::io::Write;
+
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::write(temp_dir.path().join("about.md"), "# Old")
+            .await
+            .expect("Failed to write markdown file");
+        let content = "# Compressed\n\nSaved through a gzip body.\n";
+        let form = format!(
+            "path=about.md&content={}&csrf_token={}",
+            urlencoding::encode(content),
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder
+            .write_all(form.as_bytes())
+            .expect("Failed to gzip save body");
+        let compressed = encoder.finish().expect("Failed to finish gzip stream");
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/save")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("content-encoding", "gzip")
+            .body(Body::from(compressed))
+            .expect("failed to build gzip save request");
+        let response = app
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("failed to send gzip save request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("about.md"))
+                .await
+                .expect("Failed to read saved file"),
+            content
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/save")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .header("content-encoding", "br")
+            .body(Body::from("not brotli"))
+            .expect("failed to build brotli save request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send brotli save request");
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_operation_locks_its_paths_against_saves_and_deletes() {
+        let registry = operations::OperationRegistry::default();
+        let operations = registry.clone();
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(move |state| state.operations = operations).await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+        for name in ["posts/a.md", "about.md"] {
+            fs::write(temp_dir.path().join(name), "# Old")
+                .await
+                .expect("Failed to write markdown file");
+        }
+        let save_body = |path: &str| {
+            format!(
+                "path={}&content=%23+New&csrf_token={}",
+                urlencoding::encode(path),
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            )
+        };
+
+        // A slow bulk operation that runs until told to finish.
+        let (finish, finished) = tokio::sync::oneshot::channel::<()>();
+        let (started_tx, started) = tokio::sync::oneshot::channel();
+        let bulk = tokio::spawn(async move {
+            let _operation = registry.begin("tag rename", vec!["posts".to_string()]);
+            let _ = started_tx.send(());
+            let _ = finished.await;
+        });
+        started.await.expect("bulk operation should start");
+
+        let response = post_form(&app, "/save", save_body("posts/a.md")).await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("failed to collect locked response")
+            .to_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains("tag rename"));
+
+        let delete_body = format!(
+            "path=posts%2Fa.md&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let response = post_form(&app, "/delete", delete_body).await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        assert!(temp_dir.path().join("posts/a.md").exists());
+
+        assert_eq!(
+            post_form(&app, "/save", save_body("about.md"))
+                .await
+                .status(),
+            StatusCode::OK
+        );
+
+        finish
+            .send(())
+            .expect("bulk operation should still be running");
+        bulk.await.expect("bulk operation should finish");
+        assert_eq!(
+            post_form(&app, "/save", save_body("posts/a.md"))
+                .await
+                .status(),
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn test_org_file_opens_in_editor_and_saves() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let org_file = temp_dir.path().join("notes.org");
+        fs::write(&org_file, "* Heading\nSome text")
+            .await
+            .expect("Failed to write org file");
+
+        let response = get_index(&app, "/", None).await;
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect directory page")
+            .to_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains("/edit?path=notes.org"));
+
+        let response = get_index(&app, "/edit?path=notes.org", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect editor page")
+            .to_bytes();
+        let html = String::from_utf8_lossy(&bytes);
+        assert!(html.contains("Edit Text File"));
+        assert!(html.contains("* Heading"));
+        assert!(!html.contains(r#"id="preview""#));
+
+        let body = format!(
+            "path=notes.org&content={}&csrf_token={}",
+            urlencoding::encode("* Heading\n** Subheading"),
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        assert_eq!(
+            post_form(&app, "/save", body).await.status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            fs::read_to_string(&org_file)
+                .await
+                .expect("Failed to read org file"),
+            "* Heading\n** Subheading"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_endpoint_rejects_missing_content_field() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        let body = format!(
+            "path=test.md&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        assert_eq!(
+            post_form(&app, "/save", body).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let content = fs::read_to_string(&test_file)
+            .await
+            .expect("Failed to read test file");
+        assert_eq!(content, "# Test");
+    }
+
+    #[tokio::test]
+    async fn test_save_endpoint_accepts_empty_content() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        let body = format!(
+            "path=test.md&content=&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        assert_eq!(
+            post_form(&app, "/save", body).await.status(),
+            StatusCode::OK
+        );
+
+        let content = fs::read_to_string(&test_file)
+            .await
+            .expect("Failed to read test file");
+        assert_eq!(content, "");
+    }
+
+    #[tokio::test]
+    async fn test_save_actions_choose_side_effects_and_redirects() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+        let post = temp_dir.path().join("posts/post.md");
+        fs::write(&post, "---\ntitle: Post\n---\n# Old\n")
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let save = |content: &str, action: &str| {
+            let body = format!(
+                "path=posts%2Fpost.md&content={}&action={action}&csrf_token={csrf_token}",
+                urlencoding::encode(content)
+            );
+            post_form(&app, "/save", body)
+        };
+        let location = |response: &Response| {
+            response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let read = || fs::read_to_string(&post);
+
+        let response = save("---\ntitle: Post\n---\n# One\n", "save").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            read().await.expect("Failed to read post"),
+            "---\ntitle: Post\n---\n# One\n"
+        );
+
+        let response = save("---\ntitle: Post\n---\n# Two\n", "save-and-close").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(location(&response).as_deref(), Some("/?path=posts"));
+        assert_eq!(
+            read().await.expect("Failed to read post"),
+            "---\ntitle: Post\n---\n# Two\n"
+        );
+
+        // The edited content and the flipped flag land in one write.
+        let response = save("---\ntitle: Post\n---\n# Three\n", "toggle-draft").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            location(&response).as_deref(),
+            Some("/edit?path=posts%2Fpost.md")
+        );
+        assert_eq!(
+            read().await.expect("Failed to read post"),
+            "---\ntitle: Post\ndraft: true\n---\n# Three\n"
+        );
+
+        // Unchanged content still toggles back.
+        let response = save(
+            "---\ntitle: Post\ndraft: true\n---\n# Three\n",
+            "toggle-draft",
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            read().await.expect("Failed to read post"),
+            "---\ntitle: Post\ndraft: false\n---\n# Three\n"
+        );
+
+        let response = save("---\ntitle: Post\ndraft: false\n---\n# Four\n", "explode").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            read().await.expect("Failed to read post"),
+            "---\ntitle: Post\ndraft: false\n---\n# Four\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resubmitted_saves_keep_the_clicked_action() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let post = temp_dir.path().join("post.md");
+        fs::write(&post, "---\ntitle: Post\n---\n# Old\n")
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let save = |content: &str, fields: String| {
+            let body = format!(
+                "path=post.md&content={}&{fields}&csrf_token={csrf_token}",
+                urlencoding::encode(content)
+            );
+            post_form(&app, "/save", body)
+        };
+        let (_, html) = get_html(app.clone(), "/edit?path=post.md").await;
+        let file_identity = hidden_field(&html, "file_identity");
+        fs::write(&post, "---\ntitle: Post\n---\n# Theirs, longer\n")
+            .await
+            .expect("Failed to replace markdown file");
+
+        // Overwriting from the conflict page still toggles the draft flag.
+        let (status, page) = response_text(
+            save(
+                "---\ntitle: Post\n---\n# Mine\n",
+                format!(
+                    "file_identity={}&action=toggle-draft",
+                    urlencoding::encode(&file_identity)
+                ),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(hidden_field(&page, "action"), "toggle-draft");
+        let response = save(
+            "---\ntitle: Post\n---\n# Mine\n",
+            format!(
+                "file_identity={}&action={}",
+                urlencoding::encode(&hidden_field(&page, "file_identity")),
+                hidden_field(&page, "action")
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            fs::read_to_string(&post)
+                .await
+                .expect("Failed to read post"),
+            "---\ntitle: Post\ndraft: true\n---\n# Mine\n"
+        );
+
+        // "Save Anyway" on the frontmatter error page still closes.
+        let broken = "---\ntitle: [Post\n---\n# Broken\n";
+        let (status, page) =
+            response_text(save(broken, "action=save-and-close".to_string()).await).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(hidden_field(&page, "action"), "save-and-close");
+        let response = save(
+            broken,
+            format!("force=true&action={}", hidden_field(&page, "action")),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok()),
+            Some("/")
+        );
+        assert_eq!(
+            fs::read_to_string(&post)
+                .await
+                .expect("Failed to read post"),
+            broken
+        );
+    }
+
+    #[test]
+    fn test_csrf_token_remaining_seconds_decreases_with_age() {
+        let secret = "test_secret";
+        let fresh = csrf_token_remaining_seconds(&generate_csrf_token(secret));
+        let older = csrf_token_remaining_seconds(&create_csrf_token_with_age(secret, 600));
+
+        assert!(fresh > 0 && fresh <= CSRF_TOKEN_LIFETIME_SECONDS);
+        assert!(older > 0 && older <= CSRF_TOKEN_LIFETIME_SECONDS - 600);
+        assert!(older < fresh);
+        assert_eq!(
+            csrf_token_remaining_seconds(&create_expired_csrf_token(secret)),
+            0
+        );
+        assert_eq!(csrf_token_remaining_seconds("garbage"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_refresh_endpoint_issues_valid_token() {
+        let (app, _temp_dir, csrf_secret) = create_test_app().await;
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/csrf/refresh")
+            .body(Body::empty())
+            .expect("failed to build csrf refresh request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send csrf refresh request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("failed to collect csrf refresh body")
+            .to_bytes();
+        let json: serde_json::Value =
+            serde_json::from_slice(&body).expect("csrf refresh response should be JSON");
+        let token = json["csrf_token"]
+            .as_str()
+            .expect("csrf refresh should include a token");
+        assert!(validate_csrf_token(token, &csrf_secret).is_ok());
+        assert!(
+            json["expires_in"]
+                .as_u64()
+                .is_some_and(|seconds| seconds > 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_routes_manifest_lists_registered_routes() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        let response = get_index(&app, "/api/routes", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("failed to collect route manifest body")
+            .to_bytes();
+        let json: serde_json::Value =
+            serde_json::from_slice(&body).expect("route manifest should be JSON");
+        assert_eq!(json["version"], routes::ROUTES_MANIFEST_VERSION);
+        assert_eq!(json["csrf_field"], "csrf_token");
+        assert_eq!(json["routes"]["file_content"]["path"], "/file-content");
+        assert_eq!(json["routes"]["raw"]["methods"][1], "PUT");
+
+        // Every manifest entry must reach a handler for each listed method
+        // rather than the 404 fallback or a 405.
+        for spec in routes::ROUTES {
+            let uri = spec
+                .path
+                .split('/')
+                .map(|part| if part.starts_with('{') { "probe" } else { part })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in spec.methods {
+                let request = Request::builder()
+                    .method(*method)
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .expect("failed to build route probe request");
+                let response = app
+                    .clone()
+                    .oneshot(request)
+                    .await
+                    .expect("failed to send route probe request");
+                let status = response.status();
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{method} {uri}");
+                let body = response
+                    .into_body()
+                    .collect()
+                    .await
+                    .expect("failed to collect route probe body")
+                    .to_bytes();
+                assert!(
+                    !(status == StatusCode::NOT_FOUND && body.as_ref() == b"Not found"),
+                    "{method} {uri} fell through to the fallback"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_edit_page_includes_csrf_expiry_hint() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(temp_dir.path().join("test.md"), "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/edit?path=test.md")
+            .body(Body::empty())
+            .expect("failed to build edit request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send edit request");
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("failed to collect edit body")
+            .to_bytes();
+        let html = String::from_utf8_lossy(&body);
+
+        let expires_in = html
+            .split("data-csrf-expires-in=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .and_then(|value| value.parse::<u64>().ok())
+            .expect("edit page should include the CSRF expiry hint");
+        assert!(expires_in > 0 && expires_in <= CSRF_TOKEN_LIFETIME_SECONDS);
+    }
+
+    #[tokio::test]
+    async fn test_delete_endpoint_without_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        // Create a test markdown file
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        // Try to delete without CSRF token
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/delete")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from("path=test.md"))
+            .expect("failed to build delete request without csrf token");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send delete request without csrf token");
+
+        // Should return 422 Unprocessable Entity due to missing CSRF token
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        // File should still exist
+        assert!(test_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_endpoint_with_valid_csrf_token() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+
+        // Create a test markdown file
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        // Generate valid CSRF token
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        // Delete with valid CSRF token
+        let body = format!(
+            "path=test.md&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/delete")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("failed to build delete request with valid csrf token");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send delete request with valid csrf token");
+
+        // Should succeed
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // File should be deleted
+        assert!(!test_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_moves_file_to_trash_unless_hard_delete() {
+        for hard_delete in [false, true] {
+            let (app, temp_dir, csrf_secret) =
+                create_test_app_with(|state| state.hard_delete = hard_delete).await;
+            fs::create_dir_all(temp_dir.path().join("posts"))
+                .await
+                .expect("Failed to create directory");
+            let test_file = temp_dir.path().join("posts/test.md");
+            fs::write(&test_file, "# Test")
+                .await
+                .expect("Failed to write test file");
+
+            let body = format!(
+                "path=posts/test.md&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            );
+            let response = post_form(&app, "/delete", body).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let bytes = response
+                .into_body()
+                .collect()
+                .await
+                .expect("Failed to collect delete response body")
+                .to_bytes();
+            let html = String::from_utf8_lossy(&bytes);
+            assert!(!test_file.exists());
+
+            let trash_dir = temp_dir.path().join(trash::TRASH_DIR);
+            if hard_delete {
+                assert!(!trash_dir.exists());
+                assert!(!html.contains("can be restored"));
+                continue;
+            }
+            assert!(html.contains("can be restored"));
+            let folders = directory_names(&trash_dir)
+                .await
+                .into_iter()
+                .filter(|name| !name.ends_with(".json"))
+                .collect::<Vec<_>>();
+            assert_eq!(folders.len(), 1);
+            assert_eq!(
+                fs::read_to_string(trash_dir.join(&folders[0]).join("test.md"))
+                    .await
+                    .expect("Failed to read trashed file"),
+                "# Test"
+            );
+            let sidecar = fs::read(trash_dir.join(format!("{}.json", folders[0])))
+                .await
+                .expect("Failed to read trash sidecar");
+            let sidecar: serde_json::Value =
+                serde_json::from_slice(&sidecar).expect("Sidecar is not JSON");
+            assert_eq!(sidecar["original_path"], "posts/test.md");
+
+            let (status, listing) = get_html(app, "/").await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(listing.contains("posts"));
+            assert!(!listing.contains(trash::TRASH_DIR));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_confirm_delete_name_requires_matching_file_name() {
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| state.confirm_delete_name = true).await;
+        fs::create_dir_all(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+        let test_file = temp_dir.path().join("posts/test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+
+        for (confirm_name, expected) in [
+            (None, StatusCode::BAD_REQUEST),
+            (Some("other.md"), StatusCode::BAD_REQUEST),
+            (Some("posts/test.md"), StatusCode::BAD_REQUEST),
+            (Some("test.md"), StatusCode::OK),
+        ] {
+            let mut body = format!("path=posts%2Ftest.md&csrf_token={csrf_token}");
+            if let Some(confirm_name) = confirm_name {
+                body.push_str(&format!(
+                    "&confirm_name={}",
+                    urlencoding::encode(confirm_name)
+                ));
+            }
+            let response = post_form(&app, "/delete", body).await;
+            assert_eq!(response.status(), expected, "{confirm_name:?}");
+            assert_eq!(test_file.exists(), expected != StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_delete_dir_removes_populated_directory() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let posts = temp_dir.path().join("posts");
+        fs::create_dir_all(posts.join("2025/drafts"))
+            .await
+            .expect("Failed to create nested directories");
+        fs::write(posts.join("index.md"), "# Posts")
+            .await
+            .expect("Failed to write index");
+        fs::write(posts.join("2025/drafts/idea.md"), "# Idea")
+            .await
+            .expect("Failed to write nested file");
+        fs::write(temp_dir.path().join("keep.md"), "# Keep")
+            .await
+            .expect("Failed to write sibling file");
+
+        let (status, html) = get_html(app.clone(), "/delete-dir?path=posts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<strong>Files:</strong> 2"));
+        assert!(html.contains("<strong>Subfolders:</strong> 2"));
+        assert!(posts.join("index.md").exists());
+
+        let body = format!(
+            "path=posts&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, html) = response_text(post_form(&app, "/delete-dir", body).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Folder Deleted"));
+        assert!(!posts.exists());
+        assert!(temp_dir.path().join("keep.md").exists());
+
+        // Links to files that were inside now report them as deleted.
+        let response = get_index(&app, "/edit?path=posts/2025/drafts/idea.md", None).await;
+        assert_eq!(response.status(), StatusCode::GONE);
+    }
+
+    #[tokio::test]
+    async fn test_leaf_bundle_editor_deletes_the_whole_folder() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let bundle = temp_dir.path().join("posts/trip");
+        fs::create_dir_all(bundle.join("gallery"))
+            .await
+            .expect("Failed to create bundle directories");
+        for (name, content) in [
+            ("index.md", "# Trip"),
+            ("cover.png", "png"),
+            ("gallery/beach.png", "png"),
+        ] {
+            fs::write(bundle.join(name), content)
+                .await
+                .expect("Failed to write bundle file");
+        }
+        fs::write(temp_dir.path().join("posts/other.md"), "# Other")
+            .await
+            .expect("Failed to write sibling post");
+
+        let (status, html) = get_html(app.clone(), "/edit?path=posts/trip/index.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"href="/delete-dir?path=posts%2Ftrip""#));
+        assert!(html.contains("Delete Bundle"));
+        assert!(!html.contains(r#"form="deleteForm""#));
+
+        // Ordinary pages keep the single-file delete.
+        let (_, html) = get_html(app.clone(), "/edit?path=posts/other.md").await;
+        assert!(html.contains(r#"form="deleteForm""#));
+        assert!(!html.contains("Delete Bundle"));
+
+        let (status, html) = get_html(app.clone(), "/delete-dir?path=posts%2Ftrip").await;
+        assert_eq!(status, StatusCode::OK);
+        for file in ["index.md", "cover.png", "gallery/beach.png"] {
+            assert!(html.contains(&format!("<li>{file}</li>")), "{file} listed");
+        }
+
+        let body = format!(
+            "path=posts%2Ftrip&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, _) = response_text(post_form(&app, "/delete-dir", body).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!bundle.exists(), "no bundle resources are left behind");
+        assert!(temp_dir.path().join("posts/other.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_bundle_is_trashed_and_restored_unless_hard_delete() {
+        for hard_delete in [false, true] {
+            let (app, temp_dir, csrf_secret) =
+                create_test_app_with(|state| state.hard_delete = hard_delete).await;
+            let bundle = temp_dir.path().join("posts/trip");
+            fs::create_dir_all(bundle.join("gallery"))
+                .await
+                .expect("Failed to create bundle directories");
+            for (name, content) in [("index.md", "# Trip"), ("gallery/beach.png", "png")] {
+                fs::write(bundle.join(name), content)
+                    .await
+                    .expect("Failed to write bundle file");
+            }
+            let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+
+            let (status, html) = response_text(
+                post_form(
+                    &app,
+                    "/delete-dir",
+                    format!("path=posts%2Ftrip&csrf_token={csrf_token}"),
+                )
+                .await,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(!bundle.exists());
+            let items = trash::list(temp_dir.path())
+                .await
+                .expect("Failed to list trash");
+            if hard_delete {
+                assert!(items.is_empty());
+                continue;
+            }
+            assert!(html.contains("can be restored"));
+            assert_eq!(items.len(), 1);
+            assert!(items[0].is_dir);
+            let (_, page) = get_html(app.clone(), "/trash").await;
+            assert!(page.contains("📁 posts/trip/"));
+
+            let (status, page) = response_text(
+                post_form(
+                    &app,
+                    "/restore",
+                    format!("id={}&csrf_token={csrf_token}", items[0].id),
+                )
+                .await,
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(page.contains("Folder Restored"));
+            assert_eq!(
+                fs::read_to_string(bundle.join("gallery/beach.png"))
+                    .await
+                    .expect("Bundle resource should be back"),
+                "png"
+            );
+            let (status, _) = get_html(app, "/edit?path=posts/trip/index.md").await;
+            assert_eq!(status, StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn test_leaf_bundle_directory() {
+        assert_eq!(
+            leaf_bundle_directory("posts/trip/index.md"),
+            Some("posts/trip")
+        );
+        assert_eq!(leaf_bundle_directory("trip/INDEX.markdown"), Some("trip"));
+        assert_eq!(leaf_bundle_directory("index.md"), None);
+        assert_eq!(leaf_bundle_directory("posts/_index.md"), None);
+        assert_eq!(leaf_bundle_directory("posts/trip.md"), None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_dir_refuses_root_and_outside_paths() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::write(temp_dir.path().join("keep.md"), "# Keep")
+            .await
+            .expect("Failed to write file");
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        for path in ["", "/", ".", "./", "..", "keep.md"] {
+            let body = format!(
+                "path={}&csrf_token={}",
+                urlencoding::encode(path),
+                urlencoding::encode(&csrf_token)
+            );
+            let (status, _) = response_text(post_form(&app, "/delete-dir", body).await).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "path {path:?}");
+        }
+        let (status, _) = get_html(app, "/delete-dir?path=").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(temp_dir.path().join("keep.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_directory_removes_only_empty_directories() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let posts = temp_dir.path().join("posts");
+        fs::create_dir_all(posts.join("empty"))
+            .await
+            .expect("Failed to create empty directory");
+        fs::create_dir_all(posts.join("full"))
+            .await
+            .expect("Failed to create directory");
+        fs::write(posts.join("full/post.md"), "# Post")
+            .await
+            .expect("Failed to write file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+
+        let (_, html) = get_html(app.clone(), "/?path=posts").await;
+        assert!(html.contains(r#"action="/delete-directory""#));
+        assert!(html.contains(r#"name="path" value="posts/empty""#));
+
+        let response = post_form(
+            &app,
+            "/delete-directory",
+            format!("path=posts%2Fempty&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!posts.join("empty").exists());
+
+        let response = post_form(
+            &app,
+            "/delete-directory",
+            format!("path=posts%2Ffull&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Failed to read response body");
+        let message = String::from_utf8_lossy(&body);
+        assert!(message.contains("posts/full is not empty"), "{message}");
+        assert!(message.contains("recursive=true"), "{message}");
+        assert!(posts.join("full/post.md").exists());
+
+        // Recursive deletes go through the confirmation page first.
+        let response = post_form(
+            &app,
+            "/delete-directory",
+            format!("path=posts%2Ffull&recursive=true&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response.headers().get(header::LOCATION),
+            Some(&HeaderValue::from_static("/delete-dir?path=posts%2Ffull"))
+        );
+        assert!(posts.join("full/post.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_directory_never_leaves_the_content_root() {
+        let outside = TempDir::new().expect("failed to create outside directory");
+        fs::create_dir(outside.path().join("empty"))
+            .await
+            .expect("Failed to create outside directory");
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let escape = format!("../{}", outside.path().display());
+
+        for recursive in [false, true] {
+            for path in ["", "/", ".", "..", "../", escape.as_str()] {
+                let response = post_form(
+                    &app,
+                    "/delete-directory",
+                    format!(
+                        "path={}&recursive={recursive}&csrf_token={csrf_token}",
+                        urlencoding::encode(path)
+                    ),
+                )
+                .await;
+                assert_eq!(
+                    response.status(),
+                    StatusCode::BAD_REQUEST,
+                    "path {path:?}, recursive {recursive}"
+                );
+            }
+        }
+        assert!(temp_dir.path().exists());
+        assert!(outside.path().join("empty").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_delete_dir_refuses_symlinked_directory() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let real = temp_dir.path().join("real");
+        fs::create_dir(&real)
+            .await
+            .expect("Failed to create directory");
+        fs::write(real.join("post.md"), "# Post")
+            .await
+            .expect("Failed to write file");
+        fs::symlink(&real, temp_dir.path().join("alias"))
+            .await
+            .expect("Failed to create symlink");
+
+        let body = format!(
+            "path=alias&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, _) = response_text(post_form(&app, "/delete-dir", body).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(real.join("post.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_dir_requires_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let posts = temp_dir.path().join("posts");
+        fs::create_dir(&posts)
+            .await
+            .expect("Failed to create directory");
+        fs::write(posts.join("post.md"), "# Post")
+            .await
+            .expect("Failed to write file");
+
+        let (status, _) =
+            response_text(post_form(&app, "/delete-dir", "path=posts".to_string()).await).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (status, _) = response_text(
+            post_form(
+                &app,
+                "/delete-dir",
+                "path=posts&csrf_token=invalid".to_string(),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(posts.join("post.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_endpoint_with_invalid_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/delete")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from("path=test.md&csrf_token=invalid"))
+            .expect("Failed to build request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
+        assert!(
+            body_text
+                .to_ascii_lowercase()
+                .contains("invalid csrf token")
+        );
+
+        // File should still exist
+        assert!(test_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_endpoint_with_expired_csrf_token() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        let expired_token = create_expired_csrf_token(&csrf_secret);
+        let body = format!(
+            "path=test.md&csrf_token={}",
+            urlencoding::encode(&expired_token)
+        );
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/delete")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("failed to build delete request with expired csrf token");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("failed to send delete request with expired csrf token");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let body_text = String::from_utf8(body.to_vec()).expect("Failed to get response body");
+        assert!(
+            body_text
+                .to_ascii_lowercase()
+                .contains("invalid csrf token")
+        );
+
+        // File should still exist
+        assert!(test_file.exists());
+    }
+
+    /// Id of the single item on `/trash`.
+    async fn only_trash_id(temp_dir: &TempDir) -> String {
+        let items = trash::list(temp_dir.path())
+            .await
+            .expect("Failed to list trash");
+        assert_eq!(items.len(), 1);
+        items[0].id.clone()
+    }
+
+    #[tokio::test]
+    async fn test_delete_then_restore_round_trips() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+        let post = temp_dir.path().join("posts/post.md");
+        fs::write(&post, "# Original\n")
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let delete = || {
+            post_form(
+                &app,
+                "/delete",
+                format!("path=posts%2Fpost.md&csrf_token={csrf_token}"),
+            )
+        };
+
+        assert_eq!(delete().await.status(), StatusCode::OK);
+        let (status, page) = get_html(app.clone(), "/trash").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("posts/post.md"));
+
+        // Back to where it was.
+        let id = only_trash_id(&temp_dir).await;
+        let (status, page) = response_text(
+            post_form(&app, "/restore", format!("id={id}&csrf_token={csrf_token}")).await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("restored"));
+        assert_eq!(
+            fs::read_to_string(&post)
+                .await
+                .expect("Post should be back"),
+            "# Original\n"
+        );
+        assert!(
+            trash::list(temp_dir.path())
+                .await
+                .expect("Failed to list trash")
+                .is_empty()
+        );
+
+        // The original path is taken again: refuse, then restore elsewhere.
+        assert_eq!(delete().await.status(), StatusCode::OK);
+        fs::write(&post, "# Replacement\n")
+            .await
+            .expect("Failed to write markdown file");
+        let id = only_trash_id(&temp_dir).await;
+        let (status, _) = response_text(
+            post_form(&app, "/restore", format!("id={id}&csrf_token={csrf_token}")).await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            fs::read_to_string(&post).await.expect("Post should exist"),
+            "# Replacement\n"
+        );
+        for (destination, expected) in [
+            ("../escape.md", StatusCode::BAD_REQUEST),
+            ("missing/post.md", StatusCode::BAD_REQUEST),
+            (".hidden.md", StatusCode::BAD_REQUEST),
+        ] {
+            let (status, _) = response_text(
+                post_form(
+                    &app,
+                    "/restore",
+                    format!(
+                        "id={id}&destination={}&csrf_token={csrf_token}",
+                        urlencoding::encode(destination)
+                    ),
+                )
+                .await,
+            )
+            .await;
+            assert_eq!(status, expected, "{destination}");
+        }
+        let (status, _) = response_text(
+            post_form(
+                &app,
+                "/restore",
+                format!("id={id}&destination=posts%2Frecovered.md&csrf_token={csrf_token}"),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("posts/recovered.md"))
+                .await
+                .expect("Restored copy should exist"),
+            "# Original\n"
+        );
+
+        let (status, _) = response_text(
+            post_form(
+                &app,
+                "/restore",
+                format!("id=1700000000-0a1b2c3d&csrf_token={csrf_token}"),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_trash_and_state_folders_are_unreachable_through_file_routes() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        fs::write(temp_dir.path().join("post.md"), "# Trashed\n")
+            .await
+            .expect("Failed to write markdown file");
+        let response = post_form(
+            &app,
+            "/delete",
+            format!("path=post.md&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let id = only_trash_id(&temp_dir).await;
+        fs::create_dir_all(temp_dir.path().join(".wrangler"))
+            .await
+            .expect("Failed to create state directory");
+        fs::write(temp_dir.path().join(".wrangler/notes.md"), "# State\n")
+            .await
+            .expect("Failed to write markdown file");
+
+        for directory in [trash::TRASH_DIR, ".wrangler"] {
+            let response = get_index(&app, &format!("/?path={directory}"), None).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{directory}");
+        }
+
+        for path in [
+            format!("{}/{id}/post.md", trash::TRASH_DIR),
+            ".wrangler/notes.md".to_string(),
+        ] {
+            let encoded = urlencoding::encode(&path);
+            for uri in ["/", "/edit", "/raw"] {
+                let response = get_index(&app, &format!("{uri}?path={encoded}"), None).await;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{uri} {path}");
+            }
+            for (uri, fields) in [("/save", "&content=%23+Overwritten%0A"), ("/delete", "")] {
+                let response = post_form(
+                    &app,
+                    uri,
+                    format!("path={encoded}{fields}&csrf_token={csrf_token}"),
+                )
+                .await;
+                assert_eq!(response.status(), StatusCode::NOT_FOUND, "{uri} {path}");
+            }
+            let response = app
+                .clone()
+                .oneshot(build_put_raw_request(
+                    &format!("/raw?path={encoded}"),
+                    "# Overwritten\n",
+                    &[],
+                ))
+                .await
+                .expect("Failed to send PUT /raw request");
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "PUT {path}");
+            let content = fs::read_to_string(temp_dir.path().join(&path))
+                .await
+                .expect("Hidden file should still exist");
+            assert!(!content.contains("Overwritten"), "{path}");
+        }
+
+        // The sidecar is not markdown, so `/file` would serve it.
+        let sidecar = urlencoding::encode(&format!("{}/{id}.json", trash::TRASH_DIR)).into_owned();
+        let response = get_index(&app, &format!("/file?path={sidecar}"), None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = post_form(
+            &app,
+            "/delete",
+            format!("path={sidecar}&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // A new dotfile cannot be created either.
+        let response = app
+            .clone()
+            .oneshot(build_put_raw_request(
+                "/raw?path=.hidden.md&create=1",
+                "# Hidden\n",
+                &[],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(!temp_dir.path().join(".hidden.md").exists());
+        assert_eq!(only_trash_id(&temp_dir).await, id);
+    }
+
+    #[tokio::test]
+    async fn test_empty_trash_requires_csrf_and_removes_everything() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        for name in ["one.md", "two.md"] {
+            fs::write(temp_dir.path().join(name), "# Gone\n")
+                .await
+                .expect("Failed to write markdown file");
+            let response = post_form(
+                &app,
+                "/delete",
+                format!("path={name}&csrf_token={csrf_token}"),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let (status, _) =
+            response_text(post_form(&app, "/trash/empty", "csrf_token=bad".to_string()).await)
+                .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            trash::list(temp_dir.path())
+                .await
+                .expect("Failed to list trash")
+                .len(),
+            2
+        );
+
+        let (status, _) = response_text(
+            post_form(&app, "/trash/empty", format!("csrf_token={csrf_token}")).await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert!(!temp_dir.path().join(trash::TRASH_DIR).exists());
+        let (status, page) = get_html(app, "/trash?emptied=2").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("Permanently deleted 2 item(s)"));
+        assert!(page.contains("The trash is empty."));
+    }
+
+    #[tokio::test]
+    async fn test_delete_with_matching_expected_mtime_deletes_file() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+        let modified_time = get_file_modification_time(&test_file)
+            .await
+            .expect("Failed to read test file mtime");
+
+        let body = format!(
+            "path=test.md&expected_mtime={modified_time}&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let response = post_form(&app, "/delete", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!test_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_delete_with_stale_expected_mtime_returns_conflict() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Rewritten by a teammate")
+            .await
+            .expect("Failed to write test file");
+
+        let body = format!(
+            "path=test.md&expected_mtime=1&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let response = post_form(&app, "/delete", body).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        assert!(test_file.exists());
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect conflict response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse conflict page as UTF-8");
+        assert!(html.contains("File Changed Since You Opened It"));
+        assert!(html.contains("25 B"));
+        assert!(html.contains(r#"name="force" value="true""#));
+        assert!(extract_csrf_token_from_html(&html).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_delete_with_stale_expected_mtime_and_force_deletes_file() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test")
+            .await
+            .expect("Failed to write test file");
+
+        let body = format!(
+            "path=test.md&expected_mtime=1&force=true&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let response = post_form(&app, "/delete", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!test_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_edit_page_contains_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        // Create a test markdown file
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(&test_file, "# Test Content")
+            .await
+            .expect("Failed to write test file");
+
+        // Request the edit page
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/edit?path=test.md")
+            .body(Body::empty())
+            .expect("Failed to build request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // Get response body
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+
+        // Verify CSRF token is present in every form
+        assert!(html.contains(r#"name="csrf_token""#));
+
+        // One CSRF token field each for the save, rename, duplicate,
+        // set-draft and delete forms
+        let csrf_count = html.matches(r#"name="csrf_token""#).count();
+        assert_eq!(csrf_count, 5);
+        assert!(html.contains(r#"name="expected_mtime" value=""#));
+
+        // Root-level files should still cancel back to root.
+        assert!(html.contains(r#"class="button-link cancel" href="/""#));
+        assert!(!html.contains("← Back to file browser"));
+    }
+
+    #[tokio::test]
+    async fn test_edit_page_cancel_link_points_to_parent_directory() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let nested_dir = temp_dir.path().join("content/post/2026");
+        fs::create_dir_all(&nested_dir)
+            .await
+            .expect("Failed to create nested test directory");
+        let test_file = nested_dir.join("nested.md");
+        fs::write(&test_file, "# Nested")
+            .await
+            .expect("Failed to write nested test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/edit?path=content/post/2026/nested.md")
+            .body(Body::empty())
+            .expect("Failed to build nested edit request");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send nested edit request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect nested edit response body")
+            .to_bytes();
+        let html = String::from_utf8(body.to_vec())
+            .expect("Failed to parse nested edit response body as UTF-8");
+
+        assert!(html.contains(r#"class="button-link cancel" href="/?path=content%2Fpost%2F2026""#));
+        assert!(!html.contains("← Back to file browser"));
+    }
+
+    #[tokio::test]
+    async fn test_edit_page_shows_draft_flag_for_yaml_frontmatter() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(
+            &test_file,
+            "---\ntitle: Test\ndraft: true\n---\n# Test Content",
+        )
+        .await
+        .expect("Failed to write test file with yaml frontmatter");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/edit?path=test.md")
+            .body(Body::empty())
+            .expect("Failed to build request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"class="draft-flag""#));
+    }
+
+    #[tokio::test]
+    async fn test_missing_frontmatter_images_are_flagged_in_editor_and_problems() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let bundle = temp_dir.path().join("posts/trip");
+        fs::create_dir_all(&bundle)
+            .await
+            .expect("Failed to create bundle directory");
+        fs::write(bundle.join("hero.jpg"), b"jpg")
+            .await
+            .expect("Failed to write bundle image");
+        fs::write(
+            bundle.join("index.md"),
+            "---\ncover: hero.jpg\nthumbnail: thumbs/trip.jpg\n---\n# Trip",
+        )
+        .await
+        .expect("Failed to write bundle page");
+
+        let (status, html) = get_html(app.clone(), "/edit?path=posts/trip/index.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            html.contains("⚠️ Missing thumbnail: thumbs/trip.jpg"),
+            "{html}"
+        );
+        assert!(!html.contains("Missing cover"), "{html}");
+
+        let (status, html) = get_html(app.clone(), "/problems").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Missing frontmatter images</a>: <strong>1</strong>"));
+        let (_, html) = get_html(app, "/problems?check=missing-assets").await;
+        assert!(
+            html.contains("&#39;thumbnail&#39; points at &#39;thumbs/trip.jpg&#39;"),
+            "{html}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edit_page_lists_custom_frontmatter_read_only() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let long = "x".repeat(300);
+        fs::write(
+            temp_dir.path().join("post.md"),
+            format!(
+                "---\ntitle: Post\ndraft: false\nseries: <Rust & Web>\nweight: 10\n\
+                 params:\n  toc: true\nnotes: {long}\n---\n# Post"
+            ),
+        )
+        .await
+        .expect("Failed to write post");
+        fs::write(temp_dir.path().join("plain.md"), "---\ntitle: Plain\n---\n")
+            .await
+            .expect("Failed to write plain post");
+
+        let (status, html) = get_html(app.clone(), "/edit?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Other frontmatter (4)"), "{html}");
+        assert!(html.contains("<dt>series</dt>"));
+        assert!(html.contains("<dd>&#60;Rust &#38; Web&#62;</dd>"), "{html}");
+        assert!(
+            html.contains("<dt>weight</dt>\n        <dd>10</dd>"),
+            "{html}"
+        );
+        assert!(html.contains("<dd>{&#34;toc&#34;:true}</dd>"), "{html}");
+        assert!(html.contains(&format!("<dd>{}…</dd>", "x".repeat(200))));
+        assert!(!html.contains("<dt>title</dt>"));
+        // Keys are listed in order.
+        let positions: Vec<usize> = ["<dt>notes", "<dt>params", "<dt>series", "<dt>weight"]
+            .iter()
+            .map(|key| html.find(key).expect("key should be listed"))
+            .collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+
+        let (_, html) = get_html(app, "/edit?path=plain.md").await;
+        assert!(!html.contains("frontmatter-extra"));
+    }
+
+    #[tokio::test]
+    async fn test_edit_page_shows_draft_flag_for_json_frontmatter() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(
+            &test_file,
+            "{\n  \"title\": \"Test\",\n  \"draft\": true\n}\n# Test Content",
+        )
+        .await
+        .expect("Failed to write test file with json frontmatter");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/edit?path=test.md")
+            .body(Body::empty())
+            .expect("Failed to build request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"class="draft-flag""#));
+    }
+
+    #[tokio::test]
+    async fn test_edit_page_hides_draft_flag_when_not_draft() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("test.md");
+        fs::write(
+            &test_file,
+            "---\ntitle: Test\ndraft: false\n---\n# Test Content",
+        )
+        .await
+        .expect("Failed to write test file with non-draft frontmatter");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/edit?path=test.md")
+            .body(Body::empty())
+            .expect("Failed to build request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(!html.contains(r#"class="draft-flag""#));
+    }
+
+    #[tokio::test]
+    async fn test_index_page_contains_new_file_link() {
+        let (app, _temp_dir, _) = create_test_app().await;
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/")
+            .body(Body::empty())
+            .expect("Failed to build index request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"href="/new-file""#));
+    }
+
+    #[tokio::test]
+    async fn test_index_page_renders_directory_readme_and_keeps_it_listed() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let posts = temp_dir.path().join("posts");
+        fs::create_dir(&posts)
+            .await
+            .expect("Failed to create directory");
+        fs::write(
+            posts.join("README.md"),
+            "---\ntitle: Notes\n---\n# Section notes\n\nSee [the first post](first.md).\n\n<script>alert(1)</script>\n",
+        )
+        .await
+        .expect("Failed to write README");
+        fs::write(posts.join("_index.md"), "# Section index\n")
+            .await
+            .expect("Failed to write _index");
+        fs::write(posts.join("first.md"), "# First")
+            .await
+            .expect("Failed to write post");
+
+        let (status, html) = get_html(app.clone(), "/?path=posts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"<section class="directory-readme">"#));
+        assert!(html.contains("<h1>Section notes</h1>"));
+        // README wins over _index.md; frontmatter is not rendered.
+        assert!(!html.contains("Section index"));
+        assert!(!html.contains("title: Notes"));
+        assert!(html.contains(r#"<a href="/edit?path=posts%2Ffirst.md">the first post</a>"#));
+        assert!(!html.contains("<script>alert(1)</script>"));
+        assert!(!html.contains("<details>"));
+        assert!(html.contains(r#"<a class="readme-edit" href="/edit?path=posts%2FREADME.md">"#));
+        // The README itself stays in the listing.
+        assert!(html.contains(r#"<a href="/edit?path=posts%2FREADME.md"><span class="icon">"#));
+
+        // Without a README, _index.md is used; long notes start collapsed.
+        fs::remove_file(posts.join("README.md"))
+            .await
+            .expect("Failed to remove README");
+        fs::write(
+            posts.join("_index.md"),
+            format!(
+                "# Section index\n{}",
+                "\nline".repeat(README_COLLAPSE_LINES)
+            ),
+        )
+        .await
+        .expect("Failed to rewrite _index");
+        let (_, html) = get_html(app.clone(), "/?path=posts").await;
+        assert!(html.contains("<details>"));
+        assert!(html.contains("<h1>Section index</h1>"));
+
+        // No notes at the root.
+        let (_, html) = get_html(app, "/").await;
+        assert!(!html.contains("directory-readme"));
+    }
+
+    #[tokio::test]
+    async fn test_index_page_skips_unreadable_readme() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(temp_dir.path().join("README.md"), [0xff, 0xfe, b'#'])
+            .await
+            .expect("Failed to write README");
+
+        let (status, html) = get_html(app, "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!html.contains("directory-readme"));
+        assert!(html.contains("README.md"));
+    }
+
+    #[tokio::test]
+    async fn test_directory_readme_cache_follows_mtime() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let readme = temp_dir.path().join("README.md");
+        fs::write(&readme, "First version")
+            .await
+            .expect("Failed to write README");
+        let (_, html) = get_html(app.clone(), "/").await;
+        assert!(html.contains("First version"));
+
+        fs::write(&readme, "Second version")
+            .await
+            .expect("Failed to rewrite README");
+        let later = SystemTime::now() + Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&readme)
+            .await
+            .expect("Failed to open README")
+            .into_std()
+            .await
+            .set_modified(later)
+            .expect("Failed to set mtime");
+        let (_, html) = get_html(app, "/").await;
+        assert!(html.contains("Second version"));
+        assert!(!html.contains("First version"));
+    }
+
+    #[test]
+    fn test_markdown_snippet_skips_frontmatter_and_is_capped() {
+        let post = format!(
+            "---\ntitle: Secret title\ndraft: true\n---\n# Heading\n\n{}",
+            "Body text. ".repeat(40)
+        );
+        let snippet = markdown_snippet(&post, 65_536).expect("post should have a snippet");
+        assert!(snippet.starts_with("Heading Body text."), "{snippet}");
+        assert!(!snippet.contains("Secret title"));
+        assert!(!snippet.contains("draft"));
+        assert_eq!(snippet.chars().count(), SNIPPET_CHARS);
+        assert!(snippet.ends_with('…'));
+
+        assert_eq!(
+            markdown_snippet("{\n  \"title\": \"JSON\"\n}\nShort body", 65_536).as_deref(),
+            Some("Short body")
+        );
+        // Frontmatter the prefix does not close, or no body at all.
+        assert_eq!(markdown_snippet("---\ntitle: Cut off\n", 65_536), None);
+        assert_eq!(markdown_snippet("---\ntitle: Empty\n---\n", 65_536), None);
+    }
+
+    #[tokio::test]
+    async fn test_index_shows_snippets_only_when_enabled() {
+        for show_snippets in [false, true] {
+            let (app, temp_dir, _) =
+                create_test_app_with(|state| state.show_snippets = show_snippets).await;
+            fs::write(
+                temp_dir.path().join("post.md"),
+                "---\ntitle: Hidden\n---\nFirst *words* of the post.\n",
+            )
+            .await
+            .expect("Failed to write markdown file");
+            fs::write(temp_dir.path().join("notes.txt"), "Not markdown")
+                .await
+                .expect("Failed to write text file");
+
+            let (status, html) = get_html(app, "/").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(
+                html.contains(r#"<p class="snippet">First words of the post.</p>"#),
+                show_snippets
+            );
+            assert!(!html.contains("Not markdown"));
+            assert!(!html.contains("Hidden"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_index_page_reports_draft_count_for_current_directory() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let files = [
+            ("one.md", "---\ndraft: true\n---\n# One"),
+            ("two.md", "{\n  \"draft\": true\n}\n# Two"),
+            ("three.md", "---\ndraft: false\n---\n# Three"),
+            ("notes.txt", "---\ndraft: true\n---\n"),
+        ];
+        for (name, content) in files {
+            fs::write(temp_dir.path().join(name), content)
+                .await
+                .expect("Failed to write draft count test file");
+        }
+        let nested_dir = temp_dir.path().join("posts");
+        fs::create_dir(&nested_dir)
+            .await
+            .expect("Failed to create nested directory");
+        fs::write(nested_dir.join("nested.md"), "---\ndraft: true\n---\n")
+            .await
+            .expect("Failed to write nested draft file");
+
+        let entries = list_directory(temp_dir.path(), "")
+            .await
+            .expect("Failed to list test directory");
+        assert_eq!(
+            count_draft_entries(temp_dir.path(), &entries, DEFAULT_MAX_FRONTMATTER_BYTES).await,
+            2
+        );
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/")
+            .body(Body::empty())
+            .expect("Failed to build index request");
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains("🚩 2 drafts"));
+    }
+
+    #[tokio::test]
+    async fn test_list_directory_applies_per_directory_sort_preference() {
+        let temp_dir = TempDir::new().expect("failed to create temporary test directory");
+        for (name, content) in [("a.md", "large content"), ("b.md", "mid"), ("c.md", "")] {
+            fs::write(temp_dir.path().join(name), content)
+                .await
+                .expect("Failed to write sort test file");
+        }
+        let sorted_dir = temp_dir.path().join("sorted");
+        fs::create_dir(&sorted_dir)
+            .await
+            .expect("Failed to create sorted directory");
+        for (name, content) in [("a.md", ""), ("b.md", "large content"), ("c.md", "mid")] {
+            fs::write(sorted_dir.join(name), content)
+                .await
+                .expect("Failed to write sort test file");
+        }
+        fs::write(
+            sorted_dir.join(".wranglersort"),
+            "field = size\norder = desc\n",
+        )
+        .await
+        .expect("Failed to write sort preference");
+
+        let names = |entries: Vec<DirectoryEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.name).collect()
+        };
+        let root = list_directory(temp_dir.path(), "")
+            .await
+            .expect("Failed to list root directory");
+        assert_eq!(names(root), vec!["sorted", "a.md", "b.md", "c.md"]);
+
+        let sorted = list_directory(temp_dir.path(), "sorted")
+            .await
+            .expect("Failed to list sorted directory");
+        assert_eq!(names(sorted), vec!["b.md", "c.md", "a.md"]);
+    }
+
+    async fn get_index(app: &Router, uri: &str, cookie: Option<&str>) -> Response {
+        let mut request = Request::builder().method(Method::GET).uri(uri);
+        if let Some(cookie) = cookie {
+            request = request.header("cookie", cookie);
+        }
+        let request = request
+            .body(Body::empty())
+            .expect("Failed to build index request");
+        app.clone()
+            .oneshot(request)
+            .await
+            .expect("Failed to send index request")
+    }
+
+    async fn post_form(app: &Router, uri: &str, body: impl Into<Body>) -> Response {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(body.into())
+            .expect("Failed to build form request");
+        app.clone()
+            .oneshot(request)
+            .await
+            .expect("Failed to send form request")
+    }
+
+    async fn response_bytes(response: Response) -> (StatusCode, Vec<u8>) {
+        let status = response.status();
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        (status, bytes.to_vec())
+    }
+
+    async fn response_text(response: Response) -> (StatusCode, String) {
+        let (status, bytes) = response_bytes(response).await;
+        (status, String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    async fn get_html(app: Router, uri: &str) -> (StatusCode, String) {
+        response_text(get_index(&app, uri, None).await).await
+    }
+
+    fn set_cookie_pair(response: &Response) -> String {
+        response
+            .headers()
+            .get("set-cookie")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(';').next())
+            .expect("response should set a cookie")
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_index_with_file_path_redirects_to_its_page() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+        for name in ["posts/x.md", "notes.txt", "run.sh"] {
+            fs::write(temp_dir.path().join(name), "content")
+                .await
+                .expect("Failed to write test file");
+        }
+
+        for (uri, location) in [
+            ("/?path=posts/x.md", "/edit?path=posts%2Fx.md"),
+            ("/?path=notes.txt", "/file-preview?path=notes.txt"),
+        ] {
+            let response = get_index(&app, uri, None).await;
+            assert_eq!(response.status(), StatusCode::SEE_OTHER, "{uri}");
+            assert_eq!(
+                response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|value| value.to_str().ok()),
+                Some(location)
+            );
+        }
+
+        let response = get_index(&app, "/?path=run.sh", None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        assert_eq!(bytes.as_ref(), b"run.sh is a file, not a directory");
+    }
+
+    #[tokio::test]
+    async fn test_remember_path_redirects_root_to_last_directory() {
+        let (app, temp_dir, _) = create_test_app_with(|state| state.remember_path = true).await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+        fs::create_dir(temp_dir.path().join("posts/2026"))
+            .await
+            .expect("Failed to create nested directory");
+
+        let response = get_index(&app, "/?path=posts%2F2026", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = set_cookie_pair(&response);
+        assert_eq!(cookie, "last_path=posts%2F2026");
+
+        let response = get_index(&app, "/", Some(&cookie)).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response
+                .headers()
+                .get("location")
+                .and_then(|value| value.to_str().ok()),
+            Some("/?path=posts%2F2026")
+        );
+
+        // An explicit root visit renders the root and forgets the path.
+        let response = get_index(&app, "/?path=", Some(&cookie)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(set_cookie_pair(&response), "last_path=");
+
+        // Stale or unsafe remembered paths are ignored.
+        for stale in ["last_path=missing", "last_path=..%2F..%2Fetc"] {
+            let response = get_index(&app, "/", Some(stale)).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remember_path_disabled_ignores_cookie() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+
+        let response = get_index(&app, "/?path=posts", None).await;
+        assert!(response.headers().get("set-cookie").is_none());
+
+        let response = get_index(&app, "/", Some("last_path=posts")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_list_api_reports_image_dimensions() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let image = image::RgbaImage::from_pixel(3, 2, image::Rgba([0, 128, 255, 255]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::DynamicImage::ImageRgba8(image)
+            .write_to(&mut png, image::ImageFormat::Png)
+            .expect("Failed to encode test PNG");
+        let png = png.into_inner();
+        fs::write(temp_dir.path().join("photo.png"), &png)
+            .await
+            .expect("Failed to write test image");
+        fs::write(temp_dir.path().join("broken.png"), "not an image")
+            .await
+            .expect("Failed to write broken image");
+        fs::write(temp_dir.path().join("notes.md"), "# Notes")
+            .await
+            .expect("Failed to write markdown file");
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+
+        let response = get_index(&app, "/api/list", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect list response")
+            .to_bytes();
+        let json: serde_json::Value =
+            serde_json::from_slice(&bytes).expect("list response should be JSON");
+        let entry = |name: &str| {
+            json["entries"]
+                .as_array()
+                .expect("entries should be an array")
+                .iter()
+                .find(|entry| entry["name"] == name)
+                .cloned()
+                .expect("entry should be listed")
+        };
+
+        let photo = entry("photo.png");
+        assert_eq!(photo["width"], 3);
+        assert_eq!(photo["height"], 2);
+        assert_eq!(photo["size"], png.len());
+
+        let notes = entry("notes.md");
+        assert_eq!(notes["size"], 7);
+        assert!(notes.get("width").is_none());
+        assert!(notes.get("height").is_none());
+
+        assert!(entry("broken.png").get("width").is_none());
+        let posts = entry("posts");
+        assert_eq!(posts["is_directory"], true);
+        assert!(posts.get("size").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_theme_preference_sets_cookie_and_body_class() {
+        let (app, _temp_dir, csrf_secret) = create_test_app().await;
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        let body = format!(
+            "theme=dark&return_to=%2F&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        let response = post_form(&app, "/preferences", body).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let set_cookie = response
+            .headers()
+            .get("set-cookie")
+            .and_then(|value| value.to_str().ok())
+            .expect("preferences response should set a cookie");
+        assert!(set_cookie.starts_with("theme=dark;"));
+        let cookie = set_cookie
+            .split(';')
+            .next()
+            .expect("set-cookie header should contain a name=value pair")
+            .to_string();
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/")
+            .header("cookie", cookie)
+            .body(Body::empty())
+            .expect("Failed to build index request with theme cookie");
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"<body class="directory-page theme-dark">"#));
+    }
+
+    #[tokio::test]
+    async fn test_theme_preference_invalid_cookie_falls_back_to_auto() {
+        let (app, _temp_dir, _) = create_test_app().await;
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/")
+            .header("cookie", "other=1; theme=purple")
+            .body(Body::empty())
+            .expect("Failed to build index request with invalid theme cookie");
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"<body class="directory-page theme-auto">"#));
+    }
+
+    #[tokio::test]
+    async fn test_theme_preference_rejects_unknown_theme() {
+        let (app, _temp_dir, csrf_secret) = create_test_app().await;
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        let body = format!(
+            "theme=purple&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        let response = post_form(&app, "/preferences", body).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response.headers().get("set-cookie").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_new_file_form_contains_path_and_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let nested_dir = temp_dir.path().join("posts");
+        fs::create_dir(&nested_dir)
+            .await
+            .expect("Failed to create nested directory");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/new-file?path=posts")
+            .body(Body::empty())
+            .expect("Failed to build new-file request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"name="csrf_token""#));
+        assert!(html.contains(r#"name="path" value="posts""#));
+    }
+
+    /// Serves a PNG, an HTML page and a redirect to the PNG on a loopback port.
+    async fn spawn_image_server() -> String {
+        let png = create_valid_png_bytes();
+        let router = Router::new()
+            .route(
+                "/photo.png",
+                get(move || async move { ([(header::CONTENT_TYPE, "image/png")], png) }),
+            )
+            .route(
+                "/page",
+                get(|| async { ([(header::CONTENT_TYPE, "text/html")], "<html></html>") }),
+            )
+            .route(
+                "/moved",
+                get(|| async { Redirect::temporary("/photo.png") }),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind test image server");
+        let address = listener
+            .local_addr()
+            .expect("failed to read test image server address");
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+        format!("http://{address}")
+    }
+
+    #[tokio::test]
+    async fn test_import_url_fetches_image_into_directory() {
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| state.allow_private_import_urls = true).await;
+        fs::create_dir(temp_dir.path().join("images"))
+            .await
+            .expect("Failed to create images directory");
+        let base_url = spawn_image_server().await;
+
+        let body = format!(
+            "url={}&path=images&csrf_token={}",
+            urlencoding::encode(&format!("{base_url}/moved")),
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, body) = response_text(post_form(&app, "/import-url", body).await).await;
+
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let json: serde_json::Value =
+            serde_json::from_str(&body).expect("import-url response should be JSON");
+        assert_eq!(json["path"], "images/photo.png");
+        assert_eq!(json["markdown"], "![photo](/images/photo.png)");
+        assert_eq!(
+            fs::read(temp_dir.path().join("images/photo.png"))
+                .await
+                .expect("imported image should exist"),
+            create_valid_png_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_url_reports_wrong_type_and_size() {
+        let (app, temp_dir, csrf_secret) = create_test_app_with(|state| {
+            state.allow_private_import_urls = true;
+            state.max_upload_size_bytes = 16;
+        })
+        .await;
+        let base_url = spawn_image_server().await;
+
+        for (path, expected_status, expected) in [
+            ("/page", StatusCode::BAD_REQUEST, "Wrong type"),
+            ("/photo.png", StatusCode::PAYLOAD_TOO_LARGE, "Too big"),
+        ] {
+            let body = format!(
+                "url={}&filename=out.png&csrf_token={}",
+                urlencoding::encode(&format!("{base_url}{path}")),
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            );
+            let (status, body) = response_text(post_form(&app, "/import-url", body).await).await;
+            assert_eq!(status, expected_status);
+            assert!(body.contains(expected), "{body}");
+        }
+        assert!(!temp_dir.path().join("out.png").exists());
+    }
+
+    #[tokio::test]
+    async fn test_import_url_blocks_private_addresses() {
+        let (app, _temp_dir, csrf_secret) = create_test_app().await;
+
+        for url in ["http://10.0.0.1/image.png", "http://127.0.0.1:9/image.png"] {
+            let body = format!(
+                "url={}&csrf_token={}",
+                urlencoding::encode(url),
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            );
+            let (status, body) = response_text(post_form(&app, "/import-url", body).await).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert!(body.contains("Blocked address"), "{body}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_url_requires_csrf_token() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        let (status, _) = response_text(
+            post_form(
+                &app,
+                "/import-url",
+                "url=http%3A%2F%2Fexample.com%2Fa.png&csrf_token=bad".to_string(),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+
+    async fn create_copy_source(root: &Path) {
+        fs::create_dir_all(root.join("posts/2025/january"))
+            .await
+            .expect("Failed to create source tree");
+        fs::create_dir_all(root.join("posts/2025/.cache"))
+            .await
+            .expect("Failed to create hidden directory");
+        fs::write(
+            root.join("posts/2025/index.md"),
+            "---\ntitle: Year\ndate: 2025-01-01\ndraft: false\n---\n# 2025",
+        )
+        .await
+        .expect("Failed to write index");
+        fs::write(
+            root.join("posts/2025/january/post.md"),
+            "{\"title\": \"Jan\", \"date\": \"2025-01-02\"}\n# Jan",
+        )
+        .await
+        .expect("Failed to write nested post");
+        fs::write(root.join("posts/2025/january/scratch.tmp"), "temp")
+            .await
+            .expect("Failed to write excluded file");
+        fs::write(root.join("posts/2025/.cache/state"), "hidden")
+            .await
+            .expect("Failed to write hidden file");
+    }
+
+    #[tokio::test]
+    async fn test_copy_directory_copies_structure_and_skips_hidden_and_excluded() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        create_copy_source(temp_dir.path()).await;
+
+        let body = format!(
+            "source=posts%2F2025&destination=2026&exclude=*.tmp&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, body) = response_text(post_form(&app, "/copy-directory", body).await).await;
+
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let json: serde_json::Value =
+            serde_json::from_str(&body).expect("copy-directory response should be JSON");
+        assert_eq!(json["destination"], "posts/2026");
+        assert_eq!(json["directories"], 1);
+        assert_eq!(json["files"], 2);
+        assert_eq!(json["skipped"], 2);
+
+        let copied = temp_dir.path().join("posts/2026");
+        assert_eq!(
+            fs::read_to_string(copied.join("index.md"))
+                .await
+                .expect("index should be copied"),
+            "---\ntitle: Year\ndate: 2025-01-01\ndraft: false\n---\n# 2025"
+        );
+        assert!(copied.join("january/post.md").exists());
+        assert!(!copied.join("january/scratch.tmp").exists());
+        assert!(!copied.join(".cache").exists());
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_then_restore_returns_tree_to_snapshot() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let root = temp_dir.path();
+        create_copy_source(root).await;
+        fs::write(root.join("about.md"), "# About")
+            .await
+            .expect("Failed to write about page");
+
+        let response = post_form(
+            &app,
+            "/snapshot",
+            format!(
+                "csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let location = response
+            .headers()
+            .get("location")
+            .and_then(|value| value.to_str().ok())
+            .expect("snapshot should redirect to the list")
+            .to_string();
+        let name = location
+            .strip_prefix("/snapshots?created=")
+            .expect("redirect should name the snapshot")
+            .to_string();
+        assert!(snapshot::is_snapshot_name(&name));
+
+        let listing = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri("/snapshots")
+                    .body(Body::empty())
+                    .expect("Failed to build snapshots request"),
+            )
+            .await
+            .expect("Failed to list snapshots");
+        let bytes = listing
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect snapshots page")
+            .to_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains(&name));
+
+        // Mutate: edit, delete, and add a file that is not in the snapshot.
+        fs::write(root.join("about.md"), "# Changed")
+            .await
+            .expect("Failed to modify about page");
+        fs::remove_file(root.join("posts/2025/january/post.md"))
+            .await
+            .expect("Failed to delete post");
+        fs::write(root.join("new.md"), "# New")
+            .await
+            .expect("Failed to write new file");
+
+        let confirm = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/snapshots/restore?name={name}"))
+                    .body(Body::empty())
+                    .expect("Failed to build confirmation request"),
+            )
+            .await
+            .expect("Failed to load confirmation page");
+        assert_eq!(confirm.status(), StatusCode::OK);
+        let bytes = confirm
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect confirmation page")
+            .to_bytes();
+        assert!(String::from_utf8_lossy(&bytes).contains("will be overwritten"));
+
+        let response = post_form(
+            &app,
+            "/snapshots/restore",
+            format!(
+                "name={name}&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect restore page")
+            .to_bytes();
+        let html = String::from_utf8_lossy(&bytes);
+        assert!(html.contains("<strong>Restored:</strong> 1 file<"));
+        assert!(html.contains("<strong>Added back:</strong> 1 file<"));
+        assert!(html.contains("<strong>Left alone:</strong> 2 unchanged"));
+
+        assert_eq!(
+            fs::read_to_string(root.join("about.md"))
+                .await
+                .expect("about page should exist"),
+            "# About"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("posts/2025/january/post.md"))
+                .await
+                .expect("deleted post should be restored"),
+            "{\"title\": \"Jan\", \"date\": \"2025-01-02\"}\n# Jan"
+        );
+        assert!(root.join("new.md").exists());
+        // Hidden files were never part of the snapshot.
+        assert_eq!(
+            fs::read_to_string(root.join("posts/2025/.cache/state"))
+                .await
+                .expect("hidden file should be untouched"),
+            "hidden"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_snapshot_refuses_paths_outside_root() {
+        // Serve a subdirectory so an escaping entry would land somewhere
+        // observable inside the temporary directory.
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| state.target_dir = state.target_dir.join("content")).await;
+        let content_dir = temp_dir.path().join("content");
+        fs::create_dir(&content_dir)
+            .await
+            .expect("Failed to create content directory");
+        let name = snapshot::snapshot_name(1, 0);
+
+        let snapshot_dir = content_dir.join(snapshot::SNAPSHOT_DIR);
+        fs::create_dir_all(&snapshot_dir)
+            .await
+            .expect("Failed to create snapshot directory");
+        let file = fs::File::create(snapshot_dir.join(&name))
+            .await
+            .expect("Failed to create malicious archive");
+        let mut writer = async_zip::tokio::write::ZipFileWriter::with_tokio(file);
+        for (entry_name, data) in [("ok.md", "fine"), ("../escaped.md", "evil")] {
+            writer
+                .write_entry_whole(
+                    async_zip::ZipEntryBuilder::new(
+                        entry_name.into(),
+                        async_zip::Compression::Stored,
+                    ),
+                    data.as_bytes(),
+                )
+                .await
+                .expect("Failed to write archive entry");
+        }
+        writer.close().await.expect("Failed to finish archive");
+
+        let response = post_form(
+            &app,
+            "/snapshots/restore",
+            format!(
+                "name={name}&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(!temp_dir.path().join("escaped.md").exists());
+        assert!(!content_dir.join("ok.md").exists());
+
+        let response = post_form(
+            &app,
+            "/snapshots/restore",
+            format!(
+                "name=..%2F..%2Fsecret.zip&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_archives_are_unreachable_through_file_routes() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::write(temp_dir.path().join("about.md"), "# About")
+            .await
+            .expect("Failed to write about page");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let response = post_form(&app, "/snapshot", format!("csrf_token={csrf_token}")).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let name = response
+            .headers()
+            .get("location")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|location| location.strip_prefix("/snapshots?created="))
+            .expect("redirect should name the snapshot")
+            .to_string();
+        let archive = temp_dir.path().join(snapshot::SNAPSHOT_DIR).join(&name);
+        let stored = fs::read(&archive).await.expect("Snapshot should exist");
+
+        let encoded =
+            urlencoding::encode(&format!("{}/{name}", snapshot::SNAPSHOT_DIR)).into_owned();
+        // `/file` already refuses archives by type; either refusal will do.
+        let response = get_index(&app, &format!("/file?path={encoded}"), None).await;
+        assert!(response.status().is_client_error());
+        let response = post_form(
+            &app,
+            "/delete",
+            format!("path={encoded}&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app
+            .clone()
+            .oneshot(build_put_raw_request(
+                &format!("/raw?path={encoded}"),
+                "not a zip",
+                &[],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        assert_eq!(
+            fs::read(&archive).await.expect("Snapshot should remain"),
+            stored
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copy_directory_can_reset_drafts_and_dates() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        create_copy_source(temp_dir.path()).await;
+
+        let body = format!(
+            "source=posts%2F2025&destination=2026&reset_drafts=true&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, body) = response_text(post_form(&app, "/copy-directory", body).await).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+
+        let copied = temp_dir.path().join("posts/2026");
+        let index = fs::read_to_string(copied.join("index.md"))
+            .await
+            .expect("index should be copied");
+        assert_eq!(index, "---\ntitle: Year\ndraft: true\n---\n# 2025");
+
+        let post = fs::read_to_string(copied.join("january/post.md"))
+            .await
+            .expect("nested post should be copied");
+        let parsed = parse_frontmatter(
+            &post,
+            DEFAULT_MAX_FRONTMATTER_BYTES,
+            DEFAULT_MAX_FRONTMATTER_LIST_ITEMS,
+        )
+        .expect("copied json frontmatter should parse");
+        assert_eq!(parsed.0, Some(true));
+        assert_eq!(parsed.1.as_deref(), Some("Jan"));
+        assert_eq!(parsed.2, None);
+        assert!(post.ends_with("\n# Jan"));
+
+        // The scratch file is not markdown and is copied verbatim.
+        assert_eq!(
+            fs::read_to_string(copied.join("january/scratch.tmp"))
+                .await
+                .expect("non-markdown file should be copied"),
+            "temp"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copy_directory_rejects_existing_destination() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        create_copy_source(temp_dir.path()).await;
+        fs::create_dir(temp_dir.path().join("posts/2026"))
+            .await
+            .expect("Failed to create existing destination");
+
+        let body = format!(
+            "source=posts%2F2025&destination=2026&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, _) = response_text(post_form(&app, "/copy-directory", body).await).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(!temp_dir.path().join("posts/2026/index.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_upload_image_form_contains_path_csrf_and_size_hint() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let nested_dir = temp_dir.path().join("posts");
+        fs::create_dir(&nested_dir)
+            .await
+            .expect("Failed to create nested directory");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/upload-image?path=posts")
+            .body(Body::empty())
+            .expect("Failed to build upload-image request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"name="csrf_token""#));
+        assert!(html.contains(r#"name="path" value="posts""#));
+        assert!(html.contains("Maximum upload size: 1.0 MB"));
+    }
+
+    #[tokio::test]
+    async fn test_upload_image_with_valid_png_redirects_to_preview() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let nested_dir = temp_dir.path().join("posts");
         fs::create_dir(&nested_dir)
             .await
-            .expect("Failed to create nested directory");
+            .expect("Failed to create nested directory");
+
+        let get_form_request = Request::builder()
+            .method(Method::GET)
+            .uri("/upload-image?path=posts")
+            .body(Body::empty())
+            .expect("Failed to build upload-image form request");
+        let get_form_response = app
+            .clone()
+            .oneshot(get_form_request)
+            .await
+            .expect("Failed to request upload-image form");
+        assert_eq!(get_form_response.status(), StatusCode::OK);
+        let get_form_body = get_form_response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect upload-image form response body")
+            .to_bytes();
+        let get_form_html = String::from_utf8(get_form_body.to_vec())
+            .expect("Failed to parse upload-image form response body");
+        let csrf_token = extract_csrf_token_from_html(&get_form_html)
+            .expect("Upload-image form should include csrf token");
+
+        let boundary = "----markdownwranglerupload";
+        let valid_png_bytes = create_valid_png_bytes();
+        let body = build_upload_multipart_body(
+            boundary,
+            "posts",
+            &csrf_token,
+            "kitten.png",
+            &valid_png_bytes,
+        );
+        let upload_request = Request::builder()
+            .method(Method::POST)
+            .uri("/upload-image")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("Failed to build upload-image post request");
+        let upload_response = app
+            .oneshot(upload_request)
+            .await
+            .expect("Failed to send upload-image post request");
+        assert_eq!(upload_response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            upload_response
+                .headers()
+                .get("location")
+                .and_then(|value| value.to_str().ok()),
+            Some("/preview?path=posts%2Fkitten.png")
+        );
+
+        let uploaded_path = nested_dir.join("kitten.png");
+        assert!(uploaded_path.exists());
+        let uploaded_bytes = fs::read(&uploaded_path)
+            .await
+            .expect("Failed to read uploaded image");
+        assert_eq!(uploaded_bytes, valid_png_bytes);
+    }
+
+    /// Posts `files` (name and bytes) to `/upload` for `path`.
+    async fn post_upload_files(
+        app: Router,
+        csrf_secret: &str,
+        path: &str,
+        files: &[(&str, &[u8])],
+    ) -> (StatusCode, String) {
+        let boundary = "----markdownwranglerfiles";
+        let mut body = Vec::new();
+        for (name, value) in [
+            ("path", path.to_string()),
+            ("csrf_token", generate_csrf_token(csrf_secret)),
+        ] {
+            body.extend_from_slice(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        for (file_name, bytes) in files {
+            body.extend_from_slice(
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"files\"; filename=\"{file_name}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+                )
+                .as_bytes(),
+            );
+            body.extend_from_slice(bytes);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(format!("--{boundary}--\r\n").as_bytes());
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/upload")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("Failed to build upload request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send upload request");
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect upload response body")
+            .to_bytes();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    async fn post_pasted_image(
+        app: Router,
+        content_type: String,
+        body: Vec<u8>,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/upload-image")
+            .header("content-type", content_type)
+            .body(Body::from(body))
+            .expect("Failed to build paste request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send paste request");
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Failed to read response body");
+        let json = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn test_paste_image_stores_next_to_post_or_in_paste_dir() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+        fs::write(temp_dir.path().join("posts/my-post.md"), "# Post")
+            .await
+            .expect("Failed to write markdown file");
+
+        let boundary = "----markdownwranglerpaste";
+        let mut body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"path\"\r\n\r\nposts/my-post.md\r\n\
+             --{boundary}\r\nContent-Disposition: form-data; name=\"csrf_token\"\r\n\r\n{}\r\n\
+             --{boundary}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"image.png\"\r\nContent-Type: image/png\r\n\r\n",
+            generate_csrf_token(&csrf_secret)
+        )
+        .into_bytes();
+        body.extend_from_slice(&create_valid_png_bytes());
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+        let (status, json) = post_pasted_image(
+            app,
+            format!("multipart/form-data; boundary={boundary}"),
+            body,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let path = json["path"].as_str().expect("path should be a string");
+        let file_name = path
+            .strip_prefix("posts/")
+            .expect("image should be stored next to the post");
+        assert!(file_name.starts_with("my-post-") && file_name.ends_with(".png"));
+        assert!(is_git_compatible_ascii_filename_stem(
+            file_name.trim_end_matches(".png")
+        ));
+        assert_eq!(json["markdown"], format!("![]({file_name})"));
+        assert!(temp_dir.path().join(path).is_file());
+
+        let (app, temp_dir, csrf_secret) = create_test_app_with(|state| {
+            state.paste_image_dir = Some("assets/pasted".to_string());
+        })
+        .await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+        fs::write(temp_dir.path().join("posts/my-post.md"), "# Post")
+            .await
+            .expect("Failed to write markdown file");
+        let data = format!(
+            "data:image/png;base64,{}",
+            base64::Engine::encode(
+                &base64::engine::general_purpose::STANDARD,
+                create_valid_png_bytes()
+            )
+        );
+        let body = serde_json::json!({
+            "path": "posts/my-post.md",
+            "csrf_token": generate_csrf_token(&csrf_secret),
+            "data": data,
+        });
+        let (status, json) = post_pasted_image(
+            app,
+            "application/json".to_string(),
+            body.to_string().into_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let path = json["path"].as_str().expect("path should be a string");
+        assert!(path.starts_with("assets/pasted/my-post-"));
+        assert_eq!(json["markdown"], format!("![](../{path})"));
+        assert!(temp_dir.path().join(path).is_file());
+    }
+
+    #[tokio::test]
+    async fn test_paste_image_rejects_non_image_payload() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::write(temp_dir.path().join("post.md"), "# Post")
+            .await
+            .expect("Failed to write markdown file");
+
+        let body = serde_json::json!({
+            "path": "post.md",
+            "csrf_token": generate_csrf_token(&csrf_secret),
+            "data": base64::Engine::encode(
+                &base64::engine::general_purpose::STANDARD,
+                b"#!/bin/sh\necho not an image\n"
+            ),
+        });
+        let (status, _) = post_pasted_image(
+            app,
+            "application/json".to_string(),
+            body.to_string().into_bytes(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(directory_names(temp_dir.path()).await, vec!["post.md"]);
+    }
+
+    #[tokio::test]
+    async fn test_upload_stores_files_with_sanitized_names() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+
+        let (status, html) = get_html(app.clone(), "/?path=posts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"action="/upload""#));
+        assert_eq!(hidden_field(&html, "path"), "posts");
+
+        let png = create_valid_png_bytes();
+        let (status, html) = post_upload_files(
+            app,
+            &csrf_secret,
+            "posts",
+            &[
+                ("../../My Notes (draft).PDF", b"%PDF-1.4"),
+                ("C:\\photos\\cover.png", &png),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{html}");
+        assert!(html.contains("posts/My-Notes-draft.pdf"), "{html}");
+        assert!(html.contains("posts/cover.png"), "{html}");
+
+        let stored = fs::read(temp_dir.path().join("posts/My-Notes-draft.pdf"))
+            .await
+            .expect("Failed to read uploaded file");
+        assert_eq!(stored, b"%PDF-1.4");
+        assert!(temp_dir.path().join("posts/cover.png").exists());
+        assert!(!temp_dir.path().join("My-Notes-draft.pdf").exists());
+    }
+
+    #[tokio::test]
+    async fn test_upload_rejects_executables_and_oversized_files_without_writing() {
+        let (app, temp_dir, csrf_secret) = create_test_app_with_max_upload_size(16).await;
+
+        for (files, expected) in [
+            (
+                [("notes.txt", &b"hello"[..]), ("setup.EXE", b"MZ")],
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                [("notes.txt", b"hello"), ("script.sh ", b"#!/bin/sh")],
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                [("notes.txt", b"hello"), ("big.txt", &[b'a'; 17])],
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+        ] {
+            let (status, body) = post_upload_files(app.clone(), &csrf_secret, "", &files).await;
+            assert_eq!(status, expected, "{body}");
+            assert!(!temp_dir.path().join("notes.txt").exists());
+        }
+
+        let (status, body) =
+            post_upload_files(app, &csrf_secret, "", &[("notes.txt", b"hello")]).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert!(temp_dir.path().join("notes.txt").exists());
+    }
+
+    /// Uploads `bytes` as `file_name` into the root and returns the redirect.
+    async fn upload_image_bytes(
+        app: Router,
+        csrf_secret: &str,
+        file_name: &str,
+        bytes: &[u8],
+    ) -> Option<String> {
+        let boundary = "----markdownwranglerupload";
+        let body = build_upload_multipart_body(
+            boundary,
+            "",
+            &generate_csrf_token(csrf_secret),
+            file_name,
+            bytes,
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/upload-image")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("Failed to build upload-image post request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send upload-image post request");
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        response
+            .headers()
+            .get("location")
+            .and_then(|value| value.to_str().ok())
+            .map(ToString::to_string)
+    }
+
+    /// A large, barely compressed photo-like JPEG.
+    fn create_large_jpeg_bytes() -> Vec<u8> {
+        let image = image::RgbImage::from_fn(512, 384, |x, y| {
+            let noise = (x.wrapping_mul(7919) ^ y.wrapping_mul(104_729)) % 32;
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, (noise * 8) as u8])
+        });
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgb8(image)
+            .write_with_encoder(image::codecs::jpeg::JpegEncoder::new_with_quality(
+                &mut bytes, 100,
+            ))
+            .expect("Failed to encode JPEG");
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_upload_image_optimization_shrinks_jpeg_when_enabled() {
+        let original = create_large_jpeg_bytes();
+
+        let (app, temp_dir, csrf_secret) = create_test_app_with(|state| {
+            state.upload_optimizer = Some(image_optimize::UploadOptimizer::new(false));
+        })
+        .await;
+        let (status, html) = get_html(app.clone(), "/upload-image").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("metadata (EXIF) removed"));
+        let location = upload_image_bytes(app, &csrf_secret, "photo.jpg", &original).await;
+        assert_eq!(location.as_deref(), Some("/preview?path=photo.jpg"));
+        let stored = fs::read(temp_dir.path().join("photo.jpg"))
+            .await
+            .expect("Failed to read optimized upload");
+        assert!(stored.len() < original.len());
+        assert_eq!(
+            image::guess_format(&stored).ok(),
+            Some(image::ImageFormat::Jpeg)
+        );
+
+        // Disabled (the default), the upload is stored byte for byte.
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let (_, html) = get_html(app.clone(), "/upload-image").await;
+        assert!(!html.contains("metadata (EXIF) removed"));
+        upload_image_bytes(app, &csrf_secret, "photo.jpg", &original).await;
+        let stored = fs::read(temp_dir.path().join("photo.jpg"))
+            .await
+            .expect("Failed to read upload");
+        assert_eq!(stored, original);
+    }
+
+    #[tokio::test]
+    async fn test_upload_image_optimization_converts_png_to_webp() {
+        let image = image::RgbImage::from_fn(256, 256, |x, y| {
+            image::Rgb([(x % 256) as u8, (y % 256) as u8, 0])
+        });
+        let mut original = Vec::new();
+        image::DynamicImage::ImageRgb8(image)
+            .write_with_encoder(image::codecs::png::PngEncoder::new_with_quality(
+                &mut original,
+                image::codecs::png::CompressionType::Fast,
+                image::codecs::png::FilterType::NoFilter,
+            ))
+            .expect("Failed to encode PNG");
+
+        let (app, temp_dir, csrf_secret) = create_test_app_with(|state| {
+            state.upload_optimizer = Some(image_optimize::UploadOptimizer::new(true));
+        })
+        .await;
+        let location = upload_image_bytes(app, &csrf_secret, "diagram.png", &original).await;
+        assert_eq!(location.as_deref(), Some("/preview?path=diagram.webp"));
+        assert!(!temp_dir.path().join("diagram.png").exists());
+        let stored = fs::read(temp_dir.path().join("diagram.webp"))
+            .await
+            .expect("Failed to read converted upload");
+        assert_eq!(
+            image::guess_format(&stored).ok(),
+            Some(image::ImageFormat::WebP)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_image_rejects_files_larger_than_limit() {
+        let (app, temp_dir, _) = create_test_app_with_max_upload_size(8).await;
+
+        let get_form_request = Request::builder()
+            .method(Method::GET)
+            .uri("/upload-image")
+            .body(Body::empty())
+            .expect("Failed to build upload-image form request");
+        let get_form_response = app
+            .clone()
+            .oneshot(get_form_request)
+            .await
+            .expect("Failed to request upload-image form");
+        let get_form_body = get_form_response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect upload-image form response body")
+            .to_bytes();
+        let get_form_html = String::from_utf8(get_form_body.to_vec())
+            .expect("Failed to parse upload-image form response body");
+        let csrf_token = extract_csrf_token_from_html(&get_form_html)
+            .expect("Upload-image form should include csrf token");
+
+        let boundary = "----markdownwrangleruploadlimit";
+        let valid_png_bytes = create_valid_png_bytes();
+        let body =
+            build_upload_multipart_body(boundary, "", &csrf_token, "big.png", &valid_png_bytes);
+        let upload_request = Request::builder()
+            .method(Method::POST)
+            .uri("/upload-image")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("Failed to build upload-image post request");
+        let upload_response = app
+            .oneshot(upload_request)
+            .await
+            .expect("Failed to send upload-image post request");
+        assert_eq!(upload_response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let upload_body = upload_response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect upload-image rejection body")
+            .to_bytes();
+        let body_text = String::from_utf8(upload_body.to_vec())
+            .expect("Failed to decode upload-image rejection body");
+        assert!(body_text.contains("maximum size"));
+
+        let uploaded_path = temp_dir.path().join("big.png");
+        assert!(!uploaded_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_upload_image_rejects_invalid_image_bytes() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let get_form_request = Request::builder()
+            .method(Method::GET)
+            .uri("/upload-image")
+            .body(Body::empty())
+            .expect("Failed to build upload-image form request");
+        let get_form_response = app
+            .clone()
+            .oneshot(get_form_request)
+            .await
+            .expect("Failed to request upload-image form");
+        let get_form_body = get_form_response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect upload-image form response body")
+            .to_bytes();
+        let get_form_html = String::from_utf8(get_form_body.to_vec())
+            .expect("Failed to parse upload-image form response body");
+        let csrf_token = extract_csrf_token_from_html(&get_form_html)
+            .expect("Upload-image form should include csrf token");
+
+        let boundary = "----markdownwrangleruploadinvalid";
+        let body = build_upload_multipart_body(
+            boundary,
+            "",
+            &csrf_token,
+            "broken.png",
+            b"this is not an image",
+        );
+        let upload_request = Request::builder()
+            .method(Method::POST)
+            .uri("/upload-image")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(Body::from(body))
+            .expect("Failed to build upload-image post request");
+        let upload_response = app
+            .oneshot(upload_request)
+            .await
+            .expect("Failed to send upload-image post request");
+        assert_eq!(upload_response.status(), StatusCode::BAD_REQUEST);
+
+        let uploaded_path = temp_dir.path().join("broken.png");
+        assert!(!uploaded_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_redirects_to_editor() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        let body = format!(
+            "path=&filename=new-post&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/new-file")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("Failed to build create-new-file request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response
+                .headers()
+                .get("location")
+                .and_then(|h| h.to_str().ok()),
+            Some("/edit?path=new-post.md")
+        );
+
+        let new_file = temp_dir.path().join("new-post.md");
+        assert!(new_file.exists());
+        let content = fs::read_to_string(&new_file)
+            .await
+            .expect("Failed to read created markdown file");
+        assert!(content.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_rejects_existing_file() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let existing_file = temp_dir.path().join("existing.md");
+        fs::write(&existing_file, "# Existing")
+            .await
+            .expect("Failed to write existing markdown file");
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        let body = format!(
+            "path=&filename=existing&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/new-file")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("Failed to build create-new-file request for existing file");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let content = fs::read_to_string(&existing_file)
+            .await
+            .expect("Failed to read existing markdown file");
+        assert_eq!(content, "# Existing");
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_concurrent_requests_only_create_once() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+
+        let build_request = || {
+            let body = format!(
+                "path=&filename=race&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            );
+            Request::builder()
+                .method(Method::POST)
+                .uri("/new-file")
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .expect("Failed to build concurrent create-new-file request")
+        };
+
+        let (first, second) = tokio::join!(
+            app.clone().oneshot(build_request()),
+            app.oneshot(build_request())
+        );
+        let mut statuses = [
+            first.expect("Failed to send first request").status(),
+            second.expect("Failed to send second request").status(),
+        ];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::SEE_OTHER, StatusCode::CONFLICT]);
+        assert!(temp_dir.path().join("race.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_rejects_case_only_collision_on_case_insensitive_fs() {
+        for (case_insensitive_fs, expected_status) in
+            [(true, StatusCode::CONFLICT), (false, StatusCode::SEE_OTHER)]
+        {
+            let (app, temp_dir, csrf_secret) =
+                create_test_app_with(|state| state.case_insensitive_fs = case_insensitive_fs).await;
+            fs::write(temp_dir.path().join("post.md"), "# Post")
+                .await
+                .expect("Failed to write existing markdown file");
+
+            let body = format!(
+                "path=&filename=Post&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            );
+            let response = post_form(&app, "/new-file", body).await;
+            assert_eq!(response.status(), expected_status);
+            if case_insensitive_fs {
+                let bytes = response
+                    .into_body()
+                    .collect()
+                    .await
+                    .expect("Failed to collect response body")
+                    .to_bytes();
+                assert!(String::from_utf8_lossy(&bytes).contains("post.md"));
+                assert!(!temp_dir.path().join("Post.md").exists());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_rejects_invalid_filename() {
+        let (app, _temp_dir, csrf_secret) = create_test_app().await;
+        let csrf_token = generate_csrf_token(&csrf_secret);
+
+        let body = format!(
+            "path=&filename=bad%2Fname&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/new-file")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .expect("Failed to build create-new-file request with invalid filename");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_respects_max_filename_length() {
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| state.max_filename_length = 12).await;
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+
+        let (status, html) = response_text(
+            post_form(
+                &app,
+                "/new-file",
+                format!("path=&filename=much-too-long&csrf_token={csrf_token}"),
+            )
+            .await,
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(html.contains("at most 12 characters"));
+        assert!(!temp_dir.path().join("much-too-long.md").exists());
+
+        let (status, _) = response_text(
+            post_form(
+                &app,
+                "/new-file",
+                format!("path=&filename=short&csrf_token={csrf_token}"),
+            )
+            .await,
+        )
+        .await;
+        assert!(status.is_redirection());
+        assert!(temp_dir.path().join("short.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_dry_run_confirms_path_without_creating() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir_all(temp_dir.path().join("posts/2026"))
+            .await
+            .expect("Failed to create nested directory");
+
+        let body = format!(
+            "path=posts%2F2026&filename=my-post&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, page) =
+            response_text(post_form(&app, "/new-file?dry_run=1", body).await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("Will create <strong>posts/2026/my-post.md</strong>"));
+        assert!(page.contains(r#"value="my-post""#));
+        assert!(page.contains("Create and Edit"));
+        assert!(!temp_dir.path().join("posts/2026/my-post.md").exists());
+
+        let body = format!(
+            "path=posts%2F2026&filename=bad%2Fname&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let (status, page) =
+            response_text(post_form(&app, "/new-file?dry_run=1", body).await).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(page.contains("New Markdown File"));
+        assert!(!page.contains("Will create"));
+    }
+
+    #[tokio::test]
+    async fn test_create_new_file_conflict_suggests_free_name() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        for name in ["post.md", "post-2.md"] {
+            fs::write(temp_dir.path().join(name), "# Post")
+                .await
+                .expect("Failed to write existing markdown file");
+        }
+
+        for uri in ["/new-file", "/new-file?dry_run=1"] {
+            let body = format!(
+                "path=&filename=post&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            );
+            let (status, page) = response_text(post_form(&app, uri, body).await).await;
+            assert_eq!(status, StatusCode::CONFLICT);
+            assert!(page.contains("File already exists"));
+            assert!(page.contains("would have created <strong>post.md</strong>"));
+            assert!(page.contains(r#"name="filename" value="post-3.md""#));
+        }
+        assert!(!temp_dir.path().join("post-3.md").exists());
+    }
+
+    #[tokio::test]
+    async fn test_image_preview_page_contains_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let test_file = temp_dir.path().join("image.png");
+        fs::write(&test_file, "fake image data")
+            .await
+            .expect("Failed to write test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/preview?path=image.png")
+            .body(Body::empty())
+            .expect("Failed to build request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"name="csrf_token""#));
+        assert!(!html.contains("← Back to file browser"));
+        let buttons_pos = html
+            .find(r#"<div class="buttons inline-actions">"#)
+            .expect("expected image preview action buttons");
+        let preview_pos = html
+            .find(r#"<div class="image-preview-container">"#)
+            .expect("expected image preview container");
+        assert!(
+            buttons_pos < preview_pos,
+            "image preview actions should render above preview container"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_from_image_preview_context_with_valid_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let test_file = temp_dir.path().join("image.png");
+        fs::write(&test_file, "fake image data")
+            .await
+            .expect("Failed to write test image file");
+
+        let preview_request = Request::builder()
+            .method(Method::GET)
+            .uri("/preview?path=image.png")
+            .body(Body::empty())
+            .expect("Failed to build image preview request");
+        let preview_response = app
+            .clone()
+            .oneshot(preview_request)
+            .await
+            .expect("Failed to send image preview request");
+        assert_eq!(preview_response.status(), StatusCode::OK);
+
+        let preview_body = preview_response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect image preview body")
+            .to_bytes();
+        let preview_html = String::from_utf8(preview_body.to_vec())
+            .expect("Failed to parse image preview body as UTF-8");
+        let csrf_token = extract_csrf_token_from_html(&preview_html)
+            .expect("Image preview HTML should contain csrf_token");
+
+        let delete_body = format!(
+            "path=image.png&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        let delete_request = Request::builder()
+            .method(Method::POST)
+            .uri("/delete")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(delete_body))
+            .expect("Failed to build delete request from image preview context");
+        let delete_response = app
+            .oneshot(delete_request)
+            .await
+            .expect("Failed to send delete request from image preview context");
+        assert_eq!(delete_response.status(), StatusCode::OK);
+        assert!(!test_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_page_contains_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let test_file = temp_dir.path().join("notes.txt");
+        fs::write(&test_file, "hello")
+            .await
+            .expect("Failed to write test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/file-preview?path=notes.txt")
+            .body(Body::empty())
+            .expect("Failed to build request");
+
+        let response = app.oneshot(request).await.expect("Failed to send request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
+        assert!(html.contains(r#"name="csrf_token""#));
+    }
+
+    #[tokio::test]
+    async fn test_delete_from_file_preview_context_with_valid_csrf_token() {
+        let (app, temp_dir, _) = create_test_app().await;
+
+        let test_file = temp_dir.path().join("notes.txt");
+        fs::write(&test_file, "hello")
+            .await
+            .expect("Failed to write test text file");
+
+        let preview_request = Request::builder()
+            .method(Method::GET)
+            .uri("/file-preview?path=notes.txt")
+            .body(Body::empty())
+            .expect("Failed to build file preview request");
+        let preview_response = app
+            .clone()
+            .oneshot(preview_request)
+            .await
+            .expect("Failed to send file preview request");
+        assert_eq!(preview_response.status(), StatusCode::OK);
+
+        let preview_body = preview_response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect file preview body")
+            .to_bytes();
+        let preview_html =
+            String::from_utf8(preview_body.to_vec()).expect("Failed to parse file preview HTML");
+        let csrf_token = extract_csrf_token_from_html(&preview_html)
+            .expect("File preview HTML should contain csrf_token");
+
+        let delete_body = format!(
+            "path=notes.txt&csrf_token={}",
+            urlencoding::encode(&csrf_token)
+        );
+        let delete_request = Request::builder()
+            .method(Method::POST)
+            .uri("/delete")
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(delete_body))
+            .expect("Failed to build delete request from file preview context");
+        let delete_response = app
+            .oneshot(delete_request)
+            .await
+            .expect("Failed to send delete request from file preview context");
+        assert_eq!(delete_response.status(), StatusCode::OK);
+        assert!(!test_file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_errors_are_json_when_the_client_prefers_json() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        for (accept, expect_json) in [
+            (None, false),
+            (Some("text/html,application/xhtml+xml,*/*;q=0.8"), false),
+            (Some("application/json"), true),
+            (Some("text/plain;q=0.5, application/json"), true),
+        ] {
+            for uri in [
+                "/file-info?path=missing.md",
+                "/file-content?path=missing.md",
+            ] {
+                let mut request = Request::builder().method(Method::GET).uri(uri);
+                if let Some(accept) = accept {
+                    request = request.header(header::ACCEPT, accept);
+                }
+                let response = app
+                    .clone()
+                    .oneshot(
+                        request
+                            .body(Body::empty())
+                            .expect("Failed to build request"),
+                    )
+                    .await
+                    .expect("Failed to send request");
+                assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+                let content_type = response
+                    .headers()
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string();
+                let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                    .await
+                    .expect("Failed to read response body");
+
+                if expect_json {
+                    assert_eq!(content_type, "application/json", "{accept:?}");
+                    let error: serde_json::Value =
+                        serde_json::from_slice(&body).expect("error body should be JSON");
+                    assert_eq!(error["status"], 400);
+                    assert!(
+                        error["error"]
+                            .as_str()
+                            .is_some_and(|message| !message.is_empty()),
+                        "{error}"
+                    );
+                    assert_eq!(error.as_object().map(|object| object.len()), Some(2));
+                } else {
+                    assert!(content_type.starts_with("text/plain"), "{accept:?}");
+                    assert!(serde_json::from_slice::<serde_json::Value>(&body).is_err());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_info_endpoint_returns_json_metadata() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("meta.md");
+        fs::write(&test_file, "# Meta")
+            .await
+            .expect("Failed to write metadata test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/file-info?path=meta.md")
+            .body(Body::empty())
+            .expect("Failed to build file-info request");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send file-info request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect file-info response body")
+            .to_bytes();
+        let json: serde_json::Value =
+            serde_json::from_slice(&body).expect("Failed to decode file-info JSON");
+        assert_eq!(
+            json.get("size").and_then(serde_json::Value::as_u64),
+            Some(6)
+        );
+        assert!(
+            json.get("modified_time")
+                .and_then(serde_json::Value::as_str)
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_info_content_hash_tracks_content_not_mtime() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let test_file = temp_dir.path().join("meta.md");
+        let fixed = SystemTime::now() - Duration::from_secs(60);
+        let set_fixed_mtime = || async {
+            fs::File::options()
+                .write(true)
+                .open(&test_file)
+                .await
+                .expect("Failed to open file")
+                .into_std()
+                .await
+                .set_modified(fixed)
+                .expect("Failed to set mtime");
+        };
+        let content_hash = |app: Router, uri: &'static str| async move {
+            let (status, body) = get_html(app, uri).await;
+            assert_eq!(status, StatusCode::OK);
+            let json: serde_json::Value =
+                serde_json::from_str(&body).expect("Failed to decode JSON");
+            json["content_hash"].as_str().map(ToString::to_string)
+        };
+        fs::write(&test_file, "# One")
+            .await
+            .expect("Failed to write file");
+        set_fixed_mtime().await;
+
+        let first = content_hash(app.clone(), "/file-info?path=meta.md").await;
+        assert_eq!(first, Some(content_hash::hash_bytes(b"# One")));
+        assert_eq!(
+            content_hash(app.clone(), "/file-info?path=meta.md").await,
+            first
+        );
+        assert_eq!(
+            content_hash(app.clone(), "/file-content?path=meta.md").await,
+            first
+        );
+
+        // A save of the same length, left with the same mtime, still shows
+        // up because the save clears the cache.
+        let status = post_form(
+            &app,
+            "/save",
+            format!(
+                "path=meta.md&content=%23+Two&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            ),
+        )
+        .await
+        .status();
+        assert_eq!(status, StatusCode::OK);
+        set_fixed_mtime().await;
+        let second = content_hash(app.clone(), "/file-info?path=meta.md").await;
+        assert_eq!(second, Some(content_hash::hash_bytes(b"# Two")));
+
+        // The raw route's strong ETag is the same hash.
+        let response = get_index(&app, "/raw?path=meta.md", None).await;
+        assert_eq!(
+            response
+                .headers()
+                .get("etag")
+                .and_then(|value| value.to_str().ok()),
+            second.map(|hash| format!("\"{hash}\"")).as_deref()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_neighbors_follow_directory_order() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let posts = temp_dir.path().join("posts");
+        fs::create_dir_all(posts.join("archive"))
+            .await
+            .expect("Failed to create directories");
+        for name in ["a.md", "b.md", "c.md", "b.png", ".hidden.md"] {
+            fs::write(posts.join(name), "# Post")
+                .await
+                .expect("Failed to write file");
+        }
+        let neighbors = |uri: &'static str| {
+            let app = app.clone();
+            async move {
+                let (status, body) = get_html(app, uri).await;
+                assert_eq!(status, StatusCode::OK, "{uri}");
+                serde_json::from_str::<serde_json::Value>(&body).expect("Failed to decode JSON")
+            }
+        };
+
+        let middle = neighbors("/api/neighbors?path=posts/b.md").await;
+        assert_eq!(middle["previous"]["path"], "posts/a.md");
+        assert_eq!(middle["next"]["path"], "posts/c.md");
+        assert_eq!(middle["next"]["edit_url"], "/edit?path=posts%2Fc.md");
+
+        let first = neighbors("/api/neighbors?path=posts/a.md").await;
+        assert!(first["previous"].is_null());
+        assert_eq!(first["next"]["path"], "posts/b.md");
+        let last = neighbors("/api/neighbors?path=posts/c.md").await;
+        assert!(last["next"].is_null());
+
+        // The directory's .wranglersort decides the order.
+        fs::write(
+            posts.join(sort::SORT_PREFERENCE_FILE),
+            "field = name\norder = desc\n",
+        )
+        .await
+        .expect("Failed to write sort preference");
+        let first = neighbors("/api/neighbors?path=posts/a.md").await;
+        assert_eq!(first["previous"]["path"], "posts/b.md");
+        assert!(first["next"].is_null());
+
+        let (status, _) = get_html(app.clone(), "/api/neighbors?path=posts/b.png").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        // Like the other file routes, a missing file fails path validation.
+        let (status, _) = get_html(app, "/api/neighbors?path=posts/missing.md").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_api_emits_rfc3339_and_epoch_modified_times() {
+        let (app, temp_dir, _) = create_test_app_with(|state| {
+            state.display_offset = timefmt::parse_utc_offset("+10:00").expect("offset should parse")
+        })
+        .await;
+        let file_path = temp_dir.path().join("dated.md");
+        fs::write(&file_path, "# Dated")
+            .await
+            .expect("Failed to write dated file");
+        let modified = fs::metadata(&file_path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .expect("Failed to read mtime");
+
+        for uri in ["/file-info?path=dated.md", "/file-content?path=dated.md"] {
+            let (status, body) = get_html(app.clone(), uri).await;
+            assert_eq!(status, StatusCode::OK);
+            let json: serde_json::Value =
+                serde_json::from_str(&body).expect("response should be JSON");
+            assert_eq!(json["modified_time"], timefmt::rfc3339(modified));
+            assert_eq!(
+                json["modified_epoch"],
+                timefmt::epoch_seconds(modified).to_string()
+            );
+        }
+
+        let (status, html) = get_html(app.clone(), "/edit?path=dated.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(&format!(
+            r#"<time datetime="{}" title="{}">just now</time>"#,
+            timefmt::rfc3339(modified),
+            timefmt::absolute(
+                modified,
+                timefmt::parse_utc_offset("+10:00").expect("offset should parse")
+            )
+        )));
+        assert!(html.contains("+10:00"));
+
+        let (status, html) = get_html(app, "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"<time class="modified-time""#));
+    }
+
+    #[tokio::test]
+    async fn test_file_content_endpoint_returns_json_content() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("content.md");
+        fs::write(&test_file, "# Hello")
+            .await
+            .expect("Failed to write content test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/file-content?path=content.md")
+            .body(Body::empty())
+            .expect("Failed to build file-content request");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send file-content request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect file-content response body")
+            .to_bytes();
+        let json: serde_json::Value =
+            serde_json::from_slice(&body).expect("Failed to decode file-content JSON");
+        assert_eq!(
+            json.get("content").and_then(serde_json::Value::as_str),
+            Some("# Hello")
+        );
+        assert!(
+            json.get("modified_time")
+                .and_then(serde_json::Value::as_str)
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_content_endpoint_rejects_non_markdown() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("notes.txt");
+        fs::write(&test_file, "hello")
+            .await
+            .expect("Failed to write text test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/file-content?path=notes.txt")
+            .body(Body::empty())
+            .expect("Failed to build file-content request for non-markdown");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send file-content request for non-markdown");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_serve_image_sets_expected_content_type() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("pixel.png");
+        fs::write(&test_file, vec![0x89, b'P', b'N', b'G'])
+            .await
+            .expect("Failed to write png test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/image?path=pixel.png")
+            .body(Body::empty())
+            .expect("Failed to build image request");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send image request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok());
+        assert_eq!(content_type, Some("image/png"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_file_sets_security_headers_for_safe_file() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("notes.txt");
+        fs::write(&test_file, "safe preview")
+            .await
+            .expect("Failed to write safe file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/file?path=notes.txt")
+            .body(Body::empty())
+            .expect("Failed to build safe file request");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send safe file request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let content_type = response
+            .headers()
+            .get("content-type")
+            .and_then(|value| value.to_str().ok());
+        assert_eq!(content_type, Some("text/plain; charset=utf-8"));
+        let nosniff = response
+            .headers()
+            .get("x-content-type-options")
+            .and_then(|value| value.to_str().ok());
+        assert_eq!(nosniff, Some("nosniff"));
+        let frame_options = response
+            .headers()
+            .get("x-frame-options")
+            .and_then(|value| value.to_str().ok());
+        assert_eq!(frame_options, Some("SAMEORIGIN"));
+    }
+
+    #[tokio::test]
+    async fn test_file_serves_queue_beyond_concurrency_limit() {
+        let semaphore = Arc::new(Semaphore::new(1));
+        let limit = semaphore.clone();
+        let (app, temp_dir, _) = create_test_app_with(move |state| state.file_serves = limit).await;
+        fs::write(temp_dir.path().join("notes.txt"), "queued")
+            .await
+            .expect("Failed to write test file");
+
+        let held = semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore should be open");
+        let requests: Vec<_> = (0..3)
+            .map(|_| {
+                let app = app.clone();
+                tokio::spawn(async move { get_index(&app, "/file?path=notes.txt", None).await })
+            })
+            .collect();
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(requests.iter().all(|request| !request.is_finished()));
+        drop(held);
+
+        for request in requests {
+            let response = request.await.expect("request task should not panic");
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        assert_eq!(semaphore.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_serve_file_forbids_unsafe_file_type() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("script.sh");
+        fs::write(&test_file, "echo hi")
+            .await
+            .expect("Failed to write executable test file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/file?path=script.sh")
+            .body(Body::empty())
+            .expect("Failed to build executable file request");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send executable file request");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_raw_endpoint_serves_markdown_with_validators() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(temp_dir.path().join("post.md"), "# Raw")
+            .await
+            .expect("Failed to write markdown file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/raw?path=post.md")
+            .body(Body::empty())
+            .expect("Failed to build raw request");
+        let response = app
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("Failed to send raw request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string)
+        };
+        assert_eq!(
+            header("content-type").as_deref(),
+            Some("text/markdown; charset=utf-8")
+        );
+        assert_eq!(header("x-content-type-options").as_deref(), Some("nosniff"));
+        let etag = header("etag").expect("raw response should include an ETag");
+        let last_modified =
+            header("last-modified").expect("raw response should include Last-Modified");
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect raw response body")
+            .to_bytes();
+        assert_eq!(&body[..], b"# Raw");
+
+        let request = Request::builder()
+            .method(Method::HEAD)
+            .uri("/raw?path=post.md")
+            .body(Body::empty())
+            .expect("Failed to build raw HEAD request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send raw HEAD request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get("etag")
+                .and_then(|value| value.to_str().ok()),
+            Some(etag.as_str())
+        );
+        assert!(!last_modified.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_raw_endpoint_supports_conditional_get() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(temp_dir.path().join("post.md"), "# Raw")
+            .await
+            .expect("Failed to write markdown file");
+
+        let send = |header: (&'static str, String)| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .method(Method::GET)
+                    .uri("/raw?path=post.md")
+                    .header(header.0, header.1)
+                    .body(Body::empty())
+                    .expect("Failed to build conditional raw request");
+                app.oneshot(request)
+                    .await
+                    .expect("Failed to send conditional raw request")
+            }
+        };
+
+        let etag = content_etag(b"# Raw");
+        let response = send(("if-none-match", etag.clone())).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = send(("if-none-match", "\"stale\"".to_string())).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let future = format_http_date(SystemTime::now() + std::time::Duration::from_secs(60));
+        let response = send(("if-modified-since", future)).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = send((
+            "if-modified-since",
+            "Thu, 01 Jan 1970 00:00:00 GMT".to_string(),
+        ))
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    async fn post_frontmatter_patch(app: &Router, path: &str, patch: &str) -> Response {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/api/frontmatter-patch?path={path}"))
+            .header("authorization", format!("Bearer {TEST_API_TOKEN}"))
+            .header("content-type", "application/json")
+            .body(Body::from(patch.to_string()))
+            .expect("Failed to build frontmatter patch request");
+        app.clone()
+            .oneshot(request)
+            .await
+            .expect("Failed to send frontmatter patch request")
+    }
+
+    #[tokio::test]
+    async fn test_frontmatter_patch_adds_updates_and_deletes_keys() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let file_path = temp_dir.path().join("post.md");
+        let body = "# Post\n\n---\n\nBody text stays.\n";
+        fs::write(
+            &file_path,
+            format!("---\ntitle: Old\ndraft: true\ntags:\n- a\n---\n{body}"),
+        )
+        .await
+        .expect("Failed to write markdown file");
+
+        let response = post_frontmatter_patch(&app, "post.md", r#"{"author": "Sam"}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = post_frontmatter_patch(&app, "post.md", r#"{"title": "New"}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = post_frontmatter_patch(&app, "post.md", r#"{"draft": null}"#).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value = serde_json::from_slice(
+            &response
+                .into_body()
+                .collect()
+                .await
+                .expect("Failed to collect patch response")
+                .to_bytes(),
+        )
+        .expect("patch response should be JSON");
+        assert_eq!(
+            json["frontmatter"],
+            serde_json::json!({"title": "New", "tags": ["a"], "author": "Sam"})
+        );
+
+        let content = fs::read_to_string(&file_path)
+            .await
+            .expect("Failed to read patched file");
+        assert_eq!(
+            content,
+            format!("---\ntitle: New\ntags:\n- a\nauthor: Sam\n---\n{body}")
+        );
+
+        let response = post_frontmatter_patch(&app, "post.md", "[1, 2]").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post_frontmatter_patch(&app, "image.png", "{}").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_set_draft_flag_rewrites_only_the_draft_line() {
+        let set = |content: &str, draft: bool| {
+            set_draft_flag(content, draft, 65_536).expect("draft flag should be set")
+        };
+
+        // YAML: the line is replaced in place, or appended to the block.
+        assert_eq!(
+            set(
+                "---\ntitle:  Post # keep\ndraft: true\ntags: [a]\n---\n# Post\n",
+                false
+            ),
+            "---\ntitle:  Post # keep\ndraft: false\ntags: [a]\n---\n# Post\n"
+        );
+        assert_eq!(
+            set(
+                "---\r\ntitle: Post\r\nparams:\r\n  draft: x\r\n---\r\nBody",
+                true
+            ),
+            "---\r\ntitle: Post\r\nparams:\r\n  draft: x\r\ndraft: true\r\n---\r\nBody"
+        );
+
+        // TOML: a new key goes before the first table.
+        assert_eq!(
+            set(
+                "+++\ntitle = \"Post\"\n[params]\ndraft = 1\n+++\nBody\n",
+                true
+            ),
+            "+++\ndraft = true\ntitle = \"Post\"\n[params]\ndraft = 1\n+++\nBody\n"
+        );
+
+        // JSON: the value is replaced, or a key is added in the object's layout.
+        assert_eq!(
+            set(
+                "{\n    \"title\": \"Draft\",\n    \"params\": {\"draft\": true},\n    \"draft\":   true\n}\nBody\n",
+                false
+            ),
+            "{\n    \"title\": \"Draft\",\n    \"params\": {\"draft\": true},\n    \"draft\":   false\n}\nBody\n"
+        );
+        assert_eq!(
+            set("{\n  \"title\": \"Post\"\n}\nBody\n", true),
+            "{\n  \"draft\": true,\n  \"title\": \"Post\"\n}\nBody\n"
+        );
+        assert_eq!(
+            set("{\"title\": \"Post\"}\nBody\n", true),
+            "{\"draft\": true, \"title\": \"Post\"}\nBody\n"
+        );
+
+        // No frontmatter: a minimal YAML block is prepended.
+        assert_eq!(set("# Post\n", true), "---\ndraft: true\n---\n# Post\n");
+
+        // A value the line rewrite cannot handle is refused.
+        assert!(set_draft_flag("---\ndraft: >\n  yes\n---\n", false, 65_536).is_err());
+    }
+
+    #[test]
+    fn test_patch_frontmatter_keeps_json_format_and_creates_yaml() {
+        let patch = |value: serde_json::Value| match value {
+            serde_json::Value::Object(object) => object,
+            _ => serde_json::Map::new(),
+        };
+
+        let (patched, _) = patch_frontmatter(
+            "{\n  \"title\": \"Post\",\n  \"meta\": {\"a\": 1, \"b\": 2}\n}\nBody\n",
+            patch(serde_json::json!({"meta": {"a": null, "c": 3}})),
+            65_536,
+        )
+        .expect("JSON frontmatter should patch");
+        assert_eq!(
+            patched,
+            "{\n  \"meta\": {\n    \"b\": 2,\n    \"c\": 3\n  },\n  \"title\": \"Post\"\n}\nBody\n"
+        );
+
+        let (patched, _) =
+            patch_frontmatter("Body only\n", patch(serde_json::json!({"x": null})), 65_536)
+                .expect("deleting from missing frontmatter is a no-op");
+        assert_eq!(patched, "Body only\n");
+
+        let (patched, merged) = patch_frontmatter(
+            "+++\ntitle = \"Post\"\ndate = 2026-02-15\n[params]\nauthor = \"Ann\"\n+++\nBody\n",
+            patch(serde_json::json!({"draft": false, "date": null, "params": {"lang": "en"}})),
+            65_536,
+        )
+        .expect("TOML frontmatter should patch");
+        assert_eq!(
+            patched,
+            "+++\ndraft = false\ntitle = \"Post\"\n\n[params]\nauthor = \"Ann\"\nlang = \"en\"\n+++\nBody\n"
+        );
+        assert_eq!(merged["params"]["lang"], "en");
+
+        assert_eq!(
+            reset_draft_frontmatter(
+                "+++\ntitle = \"Post\"\ndate = 2026-02-15\n+++\nBody\n",
+                65_536
+            ),
+            "+++\ndraft = true\ntitle = \"Post\"\n+++\nBody\n"
+        );
+    }
+
+    fn build_put_raw_request(uri: &str, body: &str, headers: &[(&str, &str)]) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(Method::PUT)
+            .uri(uri)
+            .header("authorization", format!("Bearer {TEST_API_TOKEN}"));
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder
+            .body(Body::from(body.to_string()))
+            .expect("Failed to build PUT /raw request")
+    }
+
+    #[tokio::test]
+    async fn test_put_raw_updates_existing_file_and_returns_etag() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let file_path = temp_dir.path().join("post.md");
+        fs::write(&file_path, "# Old")
+            .await
+            .expect("Failed to write markdown file");
+
+        let current_etag = content_etag(b"# Old");
+        let response = app
+            .oneshot(build_put_raw_request(
+                "/raw?path=post.md",
+                "# New",
+                &[("if-match", &current_etag)],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let new_etag = content_etag(b"# New");
+        assert_eq!(
+            response
+                .headers()
+                .get("etag")
+                .and_then(|value| value.to_str().ok()),
+            Some(new_etag.as_str())
+        );
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect PUT /raw response body")
+            .to_bytes();
+        let json: serde_json::Value =
+            serde_json::from_slice(&body).expect("PUT /raw response should be JSON");
+        assert_eq!(json["etag"], new_etag.as_str());
+        assert!(
+            json["modified_time"]
+                .as_str()
+                .is_some_and(|t| !t.is_empty())
+        );
+        assert_eq!(
+            fs::read_to_string(&file_path)
+                .await
+                .expect("Failed to read updated file"),
+            "# New"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_raw_rejects_stale_preconditions() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let file_path = temp_dir.path().join("post.md");
+        fs::write(&file_path, "# Current")
+            .await
+            .expect("Failed to write markdown file");
+
+        let response = app
+            .clone()
+            .oneshot(build_put_raw_request(
+                "/raw?path=post.md",
+                "# Clobber",
+                &[("if-match", "\"stale\"")],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let response = app
+            .oneshot(build_put_raw_request(
+                "/raw?path=post.md",
+                "# Clobber",
+                &[("if-unmodified-since", "Thu, 01 Jan 1970 00:00:00 GMT")],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        assert_eq!(
+            fs::read_to_string(&file_path)
+                .await
+                .expect("Failed to read file"),
+            "# Current"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_raw_creates_file_only_when_requested() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+
+        let response = app
+            .clone()
+            .oneshot(build_put_raw_request(
+                "/raw?path=posts/new.md",
+                "# New",
+                &[],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(!temp_dir.path().join("posts/new.md").exists());
+
+        let response = app
+            .clone()
+            .oneshot(build_put_raw_request(
+                "/raw?path=missing/new.md&create=1",
+                "# New",
+                &[],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app
+            .oneshot(build_put_raw_request(
+                "/raw?path=posts/new.md&create=1",
+                "# New",
+                &[],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("posts/new.md"))
+                .await
+                .expect("Failed to read created file"),
+            "# New"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_put_raw_requires_bearer_token() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let file_path = temp_dir.path().join("post.md");
+        fs::write(&file_path, "# Original")
+            .await
+            .expect("Failed to write markdown file");
+
+        let requests = [
+            Request::builder()
+                .method(Method::PUT)
+                .uri("/raw?path=post.md")
+                .header(
+                    "cookie",
+                    format!("csrf_token={}", generate_csrf_token(&csrf_secret)),
+                )
+                .body(Body::from("# Changed"))
+                .expect("Failed to build PUT /raw request"),
+            Request::builder()
+                .method(Method::PUT)
+                .uri("/raw?path=post.md")
+                .header("authorization", "Bearer wrong-token")
+                .body(Body::from("# Changed"))
+                .expect("Failed to build PUT /raw request"),
+        ];
+
+        for request in requests {
+            let response = app
+                .clone()
+                .oneshot(request)
+                .await
+                .expect("Failed to send PUT /raw request");
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        assert_eq!(
+            fs::read_to_string(&file_path)
+                .await
+                .expect("Failed to read file"),
+            "# Original"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_raw_endpoint_rejects_executable_and_binary_files() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(temp_dir.path().join("run.sh"), "echo hi")
+            .await
+            .expect("Failed to write executable file");
+        fs::write(temp_dir.path().join("photo.png"), "not really png")
+            .await
+            .expect("Failed to write image file");
+
+        for uri in ["/raw?path=run.sh", "/raw?path=photo.png"] {
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(uri)
+                .body(Body::empty())
+                .expect("Failed to build raw request");
+            let response = app
+                .clone()
+                .oneshot(request)
+                .await
+                .expect("Failed to send raw request");
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_shows_iframe_for_safe_file_type() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let test_file = temp_dir.path().join("notes.txt");
+        fs::write(&test_file, "hello")
+            .await
+            .expect("Failed to write safe preview file");
+
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/file-preview?path=notes.txt")
+            .body(Body::empty())
+            .expect("Failed to build file preview request");
+
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send file preview request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect file preview response")
+            .to_bytes();
+        let html =
+            String::from_utf8(body.to_vec()).expect("Failed to parse file preview HTML as UTF-8");
+        assert!(html.contains("<iframe "));
+        assert!(!html.contains("📁 Back to Files"));
+        assert!(!html.contains("← Back to file browser"));
+        let buttons_pos = html
+            .find(r#"<div class="buttons inline-actions">"#)
+            .expect("expected buttons block in file preview");
+        let preview_pos = html
+            .find(r#"<div class="file-preview-container">"#)
+            .expect("expected preview container in file preview");
+        assert!(
+            buttons_pos < preview_pos,
+            "buttons should render above preview container"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_csrf_secret_file_is_created_once_and_reused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let path = temp_dir.path().join("csrf-secret");
+
+        let created = load_or_create_csrf_secret(&path)
+            .await
+            .expect("Failed to create CSRF secret");
+        assert_eq!(created.len(), 64);
+        let mode = fs::metadata(&path)
+            .await
+            .expect("Secret file should exist")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A restart reads the same secret, so earlier tokens stay valid.
+        let token = generate_csrf_token(&created);
+        let reloaded = load_or_create_csrf_secret(&path)
+            .await
+            .expect("Failed to reload CSRF secret");
+        assert_eq!(reloaded, created);
+        assert!(validate_csrf_token(&token, &reloaded).is_ok());
 
-        let get_form_request = Request::builder()
+        fs::write(&path, "not hex\n")
+            .await
+            .expect("Failed to overwrite secret file");
+        let err = load_or_create_csrf_secret(&path)
+            .await
+            .expect_err("a malformed secret should be rejected");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn test_csrf_token_survives_restart_with_same_secret_file() {
+        let secret_dir = TempDir::new().expect("failed to create temporary directory");
+        let path = secret_dir.path().join("csrf-secret");
+
+        let first_boot = resolve_csrf_secret(None, Some(path.clone()))
+            .await
+            .expect("Failed to create CSRF secret");
+        let (app, _temp_dir, _) =
+            create_test_app_with(|state| state.csrf_secret = first_boot).await;
+        let request = Request::builder()
             .method(Method::GET)
-            .uri("/upload-image?path=posts")
+            .uri("/api/csrf/refresh")
             .body(Body::empty())
-            .expect("Failed to build upload-image form request");
-        let get_form_response = app
-            .clone()
-            .oneshot(get_form_request)
+            .expect("failed to build csrf refresh request");
+        let body = app
+            .oneshot(request)
             .await
-            .expect("Failed to request upload-image form");
-        assert_eq!(get_form_response.status(), StatusCode::OK);
-        let get_form_body = get_form_response
+            .expect("failed to send csrf refresh request")
             .into_body()
             .collect()
             .await
-            .expect("Failed to collect upload-image form response body")
+            .expect("failed to collect csrf refresh body")
             .to_bytes();
-        let get_form_html = String::from_utf8(get_form_body.to_vec())
-            .expect("Failed to parse upload-image form response body");
-        let csrf_token = extract_csrf_token_from_html(&get_form_html)
-            .expect("Upload-image form should include csrf token");
+        let json: serde_json::Value =
+            serde_json::from_slice(&body).expect("csrf refresh response should be JSON");
+        let token = json["csrf_token"]
+            .as_str()
+            .expect("csrf refresh should include a token")
+            .to_string();
+
+        // A new process reading the same file accepts the old page's token.
+        let second_boot = resolve_csrf_secret(None, Some(path))
+            .await
+            .expect("Failed to reload CSRF secret");
+        let (app, temp_dir, _) =
+            create_test_app_with(|state| state.csrf_secret = second_boot).await;
+        fs::write(temp_dir.path().join("post.md"), "# Before\n")
+            .await
+            .expect("Failed to write markdown file");
+        let status = post_form(
+            &app,
+            "/save",
+            format!(
+                "path=post.md&content=%23+After%0A&csrf_token={}",
+                urlencoding::encode(&token)
+            ),
+        )
+        .await
+        .status();
+        assert_eq!(status, StatusCode::OK);
 
-        let boundary = "----markdownwranglerupload";
-        let valid_png_bytes = create_valid_png_bytes();
-        let body = build_upload_multipart_body(
-            boundary,
-            "posts",
-            &csrf_token,
-            "kitten.png",
-            &valid_png_bytes,
+        // Without a file, each boot gets its own secret.
+        let (app, _temp_dir, _) = create_test_app_with(|state| {
+            state.csrf_secret = random_csrf_secret();
+        })
+        .await;
+        let status = post_form(
+            &app,
+            "/save",
+            format!(
+                "path=post.md&content=x&csrf_token={}",
+                urlencoding::encode(&token)
+            ),
+        )
+        .await
+        .status();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let provided = "ab".repeat(32);
+        assert_eq!(
+            resolve_csrf_secret(Some(format!("{provided}\n")), None)
+                .await
+                .expect("a provided secret should be used"),
+            provided
         );
-        let upload_request = Request::builder()
-            .method(Method::POST)
-            .uri("/upload-image")
-            .header(
-                "content-type",
-                format!("multipart/form-data; boundary={boundary}"),
-            )
-            .body(Body::from(body))
-            .expect("Failed to build upload-image post request");
-        let upload_response = app
-            .oneshot(upload_request)
+    }
+
+    #[tokio::test]
+    async fn test_bind_listener_reports_port_in_use() {
+        let occupied = TcpListener::bind("127.0.0.1:0")
             .await
-            .expect("Failed to send upload-image post request");
-        assert_eq!(upload_response.status(), StatusCode::SEE_OTHER);
+            .expect("Failed to bind test listener");
+        let address = occupied
+            .local_addr()
+            .expect("Failed to read test listener address");
+
+        let err = bind_listener(address)
+            .await
+            .expect_err("binding an occupied port should fail");
+        assert!(matches!(err, ServerError::AddrInUse { address: reported } if reported == address));
         assert_eq!(
-            upload_response
-                .headers()
-                .get("location")
-                .and_then(|value| value.to_str().ok()),
-            Some("/preview?path=posts%2Fkitten.png")
+            err.to_string(),
+            format!("{address} is already in use; choose another port with --port")
         );
+    }
 
-        let uploaded_path = nested_dir.join("kitten.png");
-        assert!(uploaded_path.exists());
-        let uploaded_bytes = fs::read(&uploaded_path)
+    async fn create_test_app_with_mount() -> (Router, TempDir, TempDir) {
+        let mount_dir = TempDir::new().expect("failed to create mount directory");
+        let mount_path = mount_dir.path().to_path_buf();
+        let (app, temp_dir, _) = create_test_app_with(|state| {
+            state.mounts = vec![Mount {
+                name: "assets".to_string(),
+                path: mount_path,
+            }];
+        })
+        .await;
+        (app, temp_dir, mount_dir)
+    }
+
+    async fn send_mount_request(app: Router, method: Method, uri: &str) -> Response {
+        app.oneshot(
+            Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .expect("Failed to build mount request"),
+        )
+        .await
+        .expect("Failed to send mount request")
+    }
+
+    #[tokio::test]
+    async fn test_static_assets_are_versioned_and_cached_by_hash() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        let response = get_index(&app, "/", None).await;
+        let bytes = response
+            .into_body()
+            .collect()
             .await
-            .expect("Failed to read uploaded image");
-        assert_eq!(uploaded_bytes, valid_png_bytes);
+            .expect("Failed to collect directory page")
+            .to_bytes();
+        let page = String::from_utf8_lossy(&bytes).to_string();
+        let start = page
+            .find("/static/styles.css?v=")
+            .expect("stylesheet link should carry a content hash");
+        let styles_url = page
+            .get(start..)
+            .and_then(|rest| rest.split('"').next())
+            .expect("stylesheet link should be quoted")
+            .to_string();
+
+        let expected_len = fs::metadata("static/styles.css")
+            .await
+            .expect("styles.css should exist")
+            .len();
+        let response = get_index(&app, &styles_url, None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let header_value = |response: &Response, name: header::HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string)
+        };
+        assert_eq!(
+            header_value(&response, header::CACHE_CONTROL).as_deref(),
+            Some("public, max-age=31536000, immutable")
+        );
+        assert_eq!(
+            header_value(&response, header::CONTENT_LENGTH),
+            Some(expected_len.to_string())
+        );
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect stylesheet")
+            .to_bytes();
+        assert_eq!(body.len() as u64, expected_len);
+
+        for uri in ["/static/styles.css", "/static/styles.css?v=stale"] {
+            let response = get_index(&app, uri, None).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(
+                header_value(&response, header::CACHE_CONTROL).as_deref(),
+                Some("no-cache"),
+                "{uri}"
+            );
+        }
     }
 
     #[tokio::test]
-    async fn test_upload_image_rejects_files_larger_than_limit() {
-        let (app, temp_dir, _) = create_test_app_with_max_upload_size(8).await;
+    async fn test_static_rejects_encoded_traversal() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        for uri in [
+            "/static/..%2f..%2fCargo.toml",
+            "/static/..%2fCargo.toml",
+            "/static/%2e%2e/Cargo.toml",
+            "/static/vendor/..%2f..%2fCargo.toml",
+            "/static/..%5cCargo.toml",
+            "/static/.hidden",
+        ] {
+            let response = get_index(&app, uri, None).await;
+            assert!(
+                matches!(
+                    response.status(),
+                    StatusCode::BAD_REQUEST | StatusCode::NOT_FOUND
+                ),
+                "{uri} answered {}",
+                response.status()
+            );
+            let bytes = response
+                .into_body()
+                .collect()
+                .await
+                .expect("Failed to collect response")
+                .to_bytes();
+            assert!(
+                !String::from_utf8_lossy(&bytes).contains("[package]"),
+                "{uri}"
+            );
+        }
+    }
 
-        let get_form_request = Request::builder()
-            .method(Method::GET)
-            .uri("/upload-image")
-            .body(Body::empty())
-            .expect("Failed to build upload-image form request");
-        let get_form_response = app
-            .clone()
-            .oneshot(get_form_request)
+    #[tokio::test]
+    async fn test_mount_serves_files_read_only() {
+        let (app, _temp_dir, mount_dir) = create_test_app_with_mount().await;
+        fs::create_dir(mount_dir.path().join("css"))
             .await
-            .expect("Failed to request upload-image form");
-        let get_form_body = get_form_response
+            .expect("Failed to create css directory");
+        fs::write(
+            mount_dir.path().join("css/site.css"),
+            "body { color: red; }",
+        )
+        .await
+        .expect("Failed to write mounted file");
+
+        let response =
+            send_mount_request(app.clone(), Method::GET, "/mounts/assets/css/site.css").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get("content-type")
+                .and_then(|value| value.to_str().ok()),
+            Some("text/css")
+        );
+        let body = response
             .into_body()
             .collect()
             .await
-            .expect("Failed to collect upload-image form response body")
+            .expect("Failed to collect mounted file")
             .to_bytes();
-        let get_form_html = String::from_utf8(get_form_body.to_vec())
-            .expect("Failed to parse upload-image form response body");
-        let csrf_token = extract_csrf_token_from_html(&get_form_html)
-            .expect("Upload-image form should include csrf token");
+        assert_eq!(&body[..], b"body { color: red; }");
 
-        let boundary = "----markdownwrangleruploadlimit";
-        let valid_png_bytes = create_valid_png_bytes();
-        let body =
-            build_upload_multipart_body(boundary, "", &csrf_token, "big.png", &valid_png_bytes);
-        let upload_request = Request::builder()
-            .method(Method::POST)
-            .uri("/upload-image")
-            .header(
-                "content-type",
-                format!("multipart/form-data; boundary={boundary}"),
+        let response =
+            send_mount_request(app.clone(), Method::PUT, "/mounts/assets/css/site.css").await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let response = send_mount_request(app, Method::GET, "/mounts/other/css/site.css").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_mount_blocks_traversal_out_of_mount() {
+        let (app, temp_dir, mount_dir) = create_test_app_with_mount().await;
+        fs::write(temp_dir.path().join("secret.md"), "secret")
+            .await
+            .expect("Failed to write file outside mount");
+        tokio::fs::symlink(
+            temp_dir.path().join("secret.md"),
+            mount_dir.path().join("link.md"),
+        )
+        .await
+        .expect("Failed to create symlink");
+
+        for uri in [
+            "/mounts/assets/../secret.md",
+            "/mounts/assets/%2e%2e/secret.md",
+            "/mounts/assets/%2E%2E%2Fsecret.md",
+        ] {
+            let response = send_mount_request(app.clone(), Method::GET, uri).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri}");
+        }
+
+        let response = send_mount_request(app, Method::GET, "/mounts/assets/link.md").await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Writes a shell script and returns a preview handler running it.
+    async fn preview_script(dir: &Path, name: &str, extension: &str, body: &str) -> PreviewHandler {
+        let script = dir.join(name);
+        fs::write(&script, format!("#!/bin/sh\n{body}\n"))
+            .await
+            .expect("Failed to write preview script");
+        PreviewHandler {
+            extension: extension.to_string(),
+            command: vec!["sh".to_string(), script.to_string_lossy().into_owned()],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_renders_with_preview_command() {
+        let scripts = TempDir::new().expect("failed to create script directory");
+        let runs = scripts.path().join("runs");
+        let handlers = vec![
+            preview_script(
+                scripts.path(),
+                "render.sh",
+                "dot",
+                &format!(
+                    "cat > /dev/null\necho run >> '{}'\necho '<svg xmlns=\"http://www.w3.org/2000/svg\"><text>rendered</text></svg>'",
+                    runs.display()
+                ),
             )
-            .body(Body::from(body))
-            .expect("Failed to build upload-image post request");
-        let upload_response = app
-            .oneshot(upload_request)
+            .await,
+            preview_script(
+                scripts.path(),
+                "broken.sh",
+                "puml",
+                "echo 'syntax error on line 3' >&2\nexit 2",
+            )
+            .await,
+        ];
+        let (app, temp_dir, _) = create_test_app_with(|state| {
+            state.preview_commands = Some(preview_commands::PreviewCommands::new(&handlers));
+        })
+        .await;
+        fs::write(temp_dir.path().join("graph.dot"), "digraph { a -> b }")
             .await
-            .expect("Failed to send upload-image post request");
-        assert_eq!(upload_response.status(), StatusCode::BAD_REQUEST);
-        let upload_body = upload_response
+            .expect("Failed to write dot file");
+        fs::write(temp_dir.path().join("seq.puml"), "@startuml")
+            .await
+            .expect("Failed to write puml file");
+
+        let (status, html) = get_html(app.clone(), "/file-preview?path=graph.dot").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("srcdoc="));
+        assert!(html.contains(r#"sandbox="""#));
+        assert!(html.contains("rendered"));
+        // The output is only ever embedded escaped inside srcdoc.
+        assert!(!html.contains("<text>rendered"));
+
+        let (status, _) = get_html(app.clone(), "/file-preview?path=graph.dot").await;
+        assert_eq!(status, StatusCode::OK);
+        let run_count = fs::read_to_string(&runs)
+            .await
+            .expect("Preview command should have run")
+            .lines()
+            .count();
+        assert_eq!(run_count, 1, "unchanged files are served from the cache");
+
+        let (status, html) = get_html(app, "/file-preview?path=seq.puml").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Preview command failed"));
+        assert!(html.contains("syntax error on line 3"));
+        assert!(!html.contains("srcdoc="));
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_command_times_out() {
+        let scripts = TempDir::new().expect("failed to create script directory");
+        let handlers = vec![preview_script(scripts.path(), "slow.sh", "dot", "sleep 5").await];
+        let (app, temp_dir, _) = create_test_app_with(|state| {
+            let mut commands = preview_commands::PreviewCommands::new(&handlers);
+            commands.timeout = Duration::from_millis(200);
+            state.preview_commands = Some(commands);
+        })
+        .await;
+        fs::write(temp_dir.path().join("graph.dot"), "digraph {}")
+            .await
+            .expect("Failed to write dot file");
+
+        let started = std::time::Instant::now();
+        let (status, html) = get_html(app, "/file-preview?path=graph.dot").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_ignores_commands_when_not_enabled() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(temp_dir.path().join("graph.dot"), "digraph {}")
+            .await
+            .expect("Failed to write dot file");
+
+        let (status, html) = get_html(app, "/file-preview?path=graph.dot").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!html.contains("srcdoc="));
+        assert!(html.contains("File preview not available"));
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_without_iframe_when_disabled() {
+        let (app, temp_dir, _) =
+            create_test_app_with(|state| state.disable_iframe_preview = true).await;
+        fs::write(temp_dir.path().join("notes.txt"), "plain text")
+            .await
+            .expect("Failed to write text file");
+
+        let (status, html) = get_html(app.clone(), "/file-preview?path=notes.txt").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!html.contains("<iframe"));
+        assert!(html.contains("Inline file previews are disabled on this server."));
+        assert!(html.contains("/file?path=notes.txt&amp;download=1"));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::GET)
+                    .uri("/file?path=notes.txt&download=1")
+                    .body(Body::empty())
+                    .expect("Failed to build file request"),
+            )
+            .await
+            .expect("Failed to send file request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(
+            headers
+                .get("x-frame-options")
+                .and_then(|value| value.to_str().ok()),
+            Some("DENY")
+        );
+        assert_eq!(
+            headers
+                .get("content-disposition")
+                .and_then(|value| value.to_str().ok()),
+            Some("attachment; filename=\"notes.txt\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_shows_pdf_page_count_and_title() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(
+            temp_dir.path().join("report.pdf"),
+            pdf::tests::build_test_pdf(2, Some("Quarterly Report")),
+        )
+        .await
+        .expect("Failed to write pdf fixture");
+
+        let (status, html) = get_html(app, "/file-preview?path=report.pdf").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<strong>Pages:</strong> 2"));
+        assert!(html.contains("<strong>Title:</strong> Quarterly Report"));
+        assert!(html.contains("<iframe src=\"/file?path=report.pdf\""));
+    }
+
+    #[tokio::test]
+    async fn test_file_preview_degrades_to_download_for_malformed_pdf() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(
+            temp_dir.path().join("broken.pdf"),
+            "%PDF-1.4\n1 0 obj << /Type",
+        )
+        .await
+        .expect("Failed to write broken pdf");
+
+        let (status, html) = get_html(app, "/file-preview?path=broken.pdf").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!html.contains("<iframe"));
+        assert!(html.contains("/file?path=broken.pdf&amp;download=1"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_file_supports_range_requests_for_pdf() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let pdf_bytes = pdf::tests::build_test_pdf(1, None);
+        fs::write(temp_dir.path().join("doc.pdf"), &pdf_bytes)
+            .await
+            .expect("Failed to write pdf fixture");
+
+        let send = |range: Option<&'static str>| {
+            let app = app.clone();
+            async move {
+                let mut builder = Request::builder()
+                    .method(Method::GET)
+                    .uri("/file?path=doc.pdf");
+                if let Some(range) = range {
+                    builder = builder.header("range", range);
+                }
+                app.oneshot(
+                    builder
+                        .body(Body::empty())
+                        .expect("Failed to build request"),
+                )
+                .await
+                .expect("Failed to send request")
+            }
+        };
+
+        let response = send(None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let header = |response: &Response, name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(ToString::to_string)
+        };
+        assert_eq!(header(&response, "accept-ranges").as_deref(), Some("bytes"));
+        assert_eq!(
+            header(&response, "content-disposition").as_deref(),
+            Some("inline; filename=\"doc.pdf\"")
+        );
+        assert!(header(&response, "etag").is_some());
+
+        let response = send(Some("bytes=0-7")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(
+            header(&response, "content-range"),
+            Some(format!("bytes 0-7/{}", pdf_bytes.len()))
+        );
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect partial body")
+            .to_bytes();
+        assert_eq!(&body[..], b"%PDF-1.4");
+
+        let response = send(Some("bytes=-6")).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let body = response
             .into_body()
             .collect()
             .await
-            .expect("Failed to collect upload-image rejection body")
+            .expect("Failed to collect suffix body")
             .to_bytes();
-        let body_text = String::from_utf8(upload_body.to_vec())
-            .expect("Failed to decode upload-image rejection body");
-        assert!(body_text.contains("maximum size"));
+        assert_eq!(&body[..], b"%%EOF\n");
 
-        let uploaded_path = temp_dir.path().join("big.png");
-        assert!(!uploaded_path.exists());
+        let response = send(Some("bytes=999999-")).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            header(&response, "content-range"),
+            Some(format!("bytes */{}", pdf_bytes.len()))
+        );
     }
 
     #[tokio::test]
-    async fn test_upload_image_rejects_invalid_image_bytes() {
+    async fn test_file_preview_rejects_markdown_and_images() {
         let (app, temp_dir, _) = create_test_app().await;
+        let markdown_file = temp_dir.path().join("post.md");
+        let image_file = temp_dir.path().join("photo.png");
+        fs::write(&markdown_file, "# Post")
+            .await
+            .expect("Failed to write markdown file");
+        fs::write(&image_file, "not really png")
+            .await
+            .expect("Failed to write image file");
 
-        let get_form_request = Request::builder()
+        let markdown_request = Request::builder()
             .method(Method::GET)
-            .uri("/upload-image")
+            .uri("/file-preview?path=post.md")
             .body(Body::empty())
-            .expect("Failed to build upload-image form request");
-        let get_form_response = app
+            .expect("Failed to build markdown file-preview request");
+        let markdown_response = app
             .clone()
-            .oneshot(get_form_request)
+            .oneshot(markdown_request)
             .await
-            .expect("Failed to request upload-image form");
-        let get_form_body = get_form_response
-            .into_body()
-            .collect()
+            .expect("Failed to send markdown file-preview request");
+        assert_eq!(markdown_response.status(), StatusCode::BAD_REQUEST);
+
+        let image_request = Request::builder()
+            .method(Method::GET)
+            .uri("/file-preview?path=photo.png")
+            .body(Body::empty())
+            .expect("Failed to build image file-preview request");
+        let image_response = app
+            .oneshot(image_request)
             .await
-            .expect("Failed to collect upload-image form response body")
-            .to_bytes();
-        let get_form_html = String::from_utf8(get_form_body.to_vec())
-            .expect("Failed to parse upload-image form response body");
-        let csrf_token = extract_csrf_token_from_html(&get_form_html)
-            .expect("Upload-image form should include csrf token");
+            .expect("Failed to send image file-preview request");
+        assert_eq!(image_response.status(), StatusCode::BAD_REQUEST);
+    }
 
-        let boundary = "----markdownwrangleruploadinvalid";
-        let body = build_upload_multipart_body(
-            boundary,
-            "",
-            &csrf_token,
-            "broken.png",
-            b"this is not an image",
+    #[test]
+    fn test_csrf_tokens_are_unique() {
+        let secret = "test_secret";
+
+        // Generate multiple tokens
+        let token1 = generate_csrf_token(secret);
+        let token2 = generate_csrf_token(secret);
+        let token3 = generate_csrf_token(secret);
+
+        // All tokens should be different (due to timestamp and nonce)
+        assert_ne!(token1, token2);
+        assert_ne!(token2, token3);
+        assert_ne!(token1, token3);
+
+        // But all should validate with the same secret
+        assert!(validate_csrf_token(&token1, secret).is_ok());
+        assert!(validate_csrf_token(&token2, secret).is_ok());
+        assert!(validate_csrf_token(&token3, secret).is_ok());
+    }
+
+    #[test]
+    fn test_normalize_image_filename_handles_valid_and_invalid_inputs() {
+        assert_eq!(
+            normalize_image_filename("photo.png").ok(),
+            Some("photo.png".to_string())
         );
-        let upload_request = Request::builder()
-            .method(Method::POST)
-            .uri("/upload-image")
-            .header(
-                "content-type",
-                format!("multipart/form-data; boundary={boundary}"),
+        assert_eq!(
+            normalize_image_filename("PHOTO.JPG").ok(),
+            Some("PHOTO.JPG".to_string())
+        );
+        assert!(normalize_image_filename("photo").is_err());
+        assert!(normalize_image_filename("photo.txt").is_err());
+        assert!(normalize_image_filename("../photo.png").is_err());
+        assert!(normalize_image_filename(".hidden.png").is_err());
+        assert!(normalize_image_filename("bad name.png").is_err());
+    }
+
+    #[test]
+    fn test_validate_image_bytes_accepts_valid_png_and_svg() {
+        let valid_png_bytes = create_valid_png_bytes();
+        assert!(validate_image_bytes("pixel.png", &valid_png_bytes).is_ok());
+        assert!(
+            validate_image_bytes(
+                "icon.svg",
+                br#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#
             )
-            .body(Body::from(body))
-            .expect("Failed to build upload-image post request");
-        let upload_response = app
-            .oneshot(upload_request)
-            .await
-            .expect("Failed to send upload-image post request");
-        assert_eq!(upload_response.status(), StatusCode::BAD_REQUEST);
+            .is_ok()
+        );
+    }
 
-        let uploaded_path = temp_dir.path().join("broken.png");
-        assert!(!uploaded_path.exists());
+    #[test]
+    fn test_validate_image_bytes_rejects_invalid_data() {
+        assert!(validate_image_bytes("pixel.png", b"not a png").is_err());
+        assert!(validate_image_bytes("icon.svg", b"<html></html>").is_err());
+        assert!(validate_image_bytes("icon.svg", b"").is_err());
+    }
+
+    #[test]
+    fn test_extract_frontmatter_detects_yaml_and_json_blocks() {
+        let yaml_content = "---\ndraft: true\n---\n# Post\n";
+        let json_content = "{\n  \"draft\": true\n}\n# Post\n";
+        let no_frontmatter = "# Post";
+
+        let yaml = extract_frontmatter(yaml_content, DEFAULT_MAX_FRONTMATTER_BYTES)
+            .expect("yaml frontmatter should be found");
+        assert!(matches!(yaml.0, FrontmatterFormat::Yaml));
+        assert_eq!(yaml.1, "draft: true\n");
+
+        let json = extract_frontmatter(json_content, DEFAULT_MAX_FRONTMATTER_BYTES)
+            .expect("json frontmatter should be found");
+        assert!(matches!(json.0, FrontmatterFormat::Json));
+        assert!(json.1.contains("\"draft\": true"));
+
+        assert!(extract_frontmatter(no_frontmatter, DEFAULT_MAX_FRONTMATTER_BYTES).is_none());
+    }
+
+    #[test]
+    fn test_extract_frontmatter_detects_toml_blocks() {
+        let toml_content = "+++\ndraft = true\n+++\n# Post\n";
+        let toml = extract_frontmatter(toml_content, DEFAULT_MAX_FRONTMATTER_BYTES)
+            .expect("toml frontmatter should be found");
+        assert!(matches!(toml.0, FrontmatterFormat::Toml));
+        assert_eq!(toml.1, "draft = true\n");
+
+        // A fence must be a line of its own, and YAML fences do not close TOML.
+        for content in ["+++draft = true\n+++\n", "+++\ndraft = true\n---\n# Post"] {
+            assert!(extract_frontmatter(content, DEFAULT_MAX_FRONTMATTER_BYTES).is_none());
+        }
+        assert_eq!(
+            probe_frontmatter("+++\ntitle = 'a'\n++", 64, true),
+            FrontmatterProbe::Truncated
+        );
+        assert_eq!(
+            probe_frontmatter("+++\ntitle = 'a'\n+++\nbo", 64, true),
+            FrontmatterProbe::Found((FrontmatterFormat::Toml, "title = 'a'\n"))
+        );
+    }
+
+    #[test]
+    fn test_detect_frontmatter_format() {
+        for (content, expected) in [
+            (
+                "---\ntitle: Post\n---\n# Post\n",
+                Some(FrontmatterFormat::Yaml),
+            ),
+            (
+                "{\n  \"title\": \"Post\"\n}\n# Post\n",
+                Some(FrontmatterFormat::Json),
+            ),
+            (
+                "+++\ntitle = \"Post\"\n+++\n# Post\n",
+                Some(FrontmatterFormat::Toml),
+            ),
+            ("# Post\n\n---\n", None),
+            ("", None),
+        ] {
+            assert_eq!(detect_frontmatter_format(content), expected, "{content:?}");
+        }
+
+        // Large blocks are still detected; only the caller-bounded scans cap them.
+        let large = format!(
+            "---\nnotes: {}\n---\n",
+            "x".repeat(DEFAULT_MAX_FRONTMATTER_BYTES)
+        );
+        assert_eq!(
+            detect_frontmatter_format(&large),
+            Some(FrontmatterFormat::Yaml)
+        );
+    }
+
+    #[test]
+    fn test_oversized_frontmatter_is_treated_as_missing() {
+        let padding = "x".repeat(256);
+        let yaml_content = format!("---\ndraft: true\nnotes: {padding}\n---\n# Post");
+        let json_content = format!("{{\"draft\": true, \"notes\": \"{padding}\"}}\n# Post");
+        let toml_content = format!("+++\ndraft = true\nnotes = \"{padding}\"\n+++\n# Post");
+
+        assert!(extract_frontmatter(&yaml_content, 64).is_none());
+        assert!(extract_frontmatter(&json_content, 64).is_none());
+        assert!(extract_frontmatter(&toml_content, 64).is_none());
+        assert!(!has_draft_frontmatter(&yaml_content, 64));
+        assert!(!has_draft_frontmatter(&json_content, 64));
+        assert!(!has_draft_frontmatter(&toml_content, 64));
+
+        assert!(has_draft_frontmatter(&yaml_content, 1024));
+        assert!(has_draft_frontmatter(&json_content, 1024));
+        assert!(has_draft_frontmatter(&toml_content, 1024));
+    }
+
+    #[test]
+    fn test_extract_json_frontmatter_requires_newline_or_eof_after_block() {
+        let valid_with_newline = "{\n  \"draft\": true\n}\n# post";
+        let valid_eof = "{\n  \"draft\": true\n}";
+        let invalid_trailing_text = "{\"draft\":true}#post";
+
+        assert!(
+            extract_json_frontmatter(valid_with_newline, DEFAULT_MAX_FRONTMATTER_BYTES).is_some()
+        );
+        assert!(extract_json_frontmatter(valid_eof, DEFAULT_MAX_FRONTMATTER_BYTES).is_some());
+        assert!(
+            extract_json_frontmatter(invalid_trailing_text, DEFAULT_MAX_FRONTMATTER_BYTES)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_probe_frontmatter_reports_truncated_prefixes() {
+        // The closing fence is cut off mid-line: it may still be a fence.
+        assert_eq!(
+            probe_frontmatter("---\ntitle: a\n--", 64, true),
+            FrontmatterProbe::Truncated
+        );
+        assert_eq!(
+            probe_frontmatter("---\ntitle: a\n---", 64, true),
+            FrontmatterProbe::Truncated
+        );
+        // The same text as a whole file is a closed block.
+        assert_eq!(
+            probe_frontmatter("---\ntitle: a\n---", 64, false),
+            FrontmatterProbe::Found((FrontmatterFormat::Yaml, "title: a\n"))
+        );
+        assert_eq!(
+            probe_frontmatter("---\ntitle: a\n---\nbo", 64, true),
+            FrontmatterProbe::Found((FrontmatterFormat::Yaml, "title: a\n"))
+        );
+        // Past the byte limit the block is oversized, not unknown.
+        let long = format!("---\n{}", "key: value\n".repeat(20));
+        assert_eq!(probe_frontmatter(&long, 64, true), FrontmatterProbe::Absent);
+        // JSON needs to see what follows the closing brace.
+        assert_eq!(
+            probe_frontmatter("{\"draft\": tr", 64, true),
+            FrontmatterProbe::Truncated
+        );
+        assert_eq!(
+            probe_frontmatter("{\"draft\": true}  ", 64, true),
+            FrontmatterProbe::Truncated
+        );
+        assert_eq!(
+            probe_frontmatter("# Title\n", 64, true),
+            FrontmatterProbe::Absent
+        );
+    }
+
+    #[test]
+    fn test_probe_frontmatter_stops_after_line_limit() {
+        let many_lines = format!("---\n{}---\n", "a: 1\n".repeat(MAX_FRONTMATTER_LINES));
+        let max_bytes = many_lines.len() * 2;
+        assert_eq!(
+            probe_frontmatter(&many_lines, max_bytes, false),
+            FrontmatterProbe::Absent
+        );
+        // The editor's full-content parser has no line limit.
+        assert!(extract_frontmatter(&many_lines, max_bytes).is_some());
     }
 
     #[tokio::test]
-    async fn test_create_new_file_redirects_to_editor() {
-        let (app, temp_dir, csrf_secret) = create_test_app().await;
-        let csrf_token = generate_csrf_token(&csrf_secret);
+    async fn test_read_frontmatter_prefix_finds_fence_straddling_limit() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let max_bytes = 64;
+        // The closing fence starts inside the limit and ends past it.
+        let mut frontmatter = String::from("---\ndraft: true\n");
+        while frontmatter.len() < max_bytes - 1 {
+            frontmatter.push_str("# pad\n");
+        }
+        frontmatter.truncate(max_bytes - 1);
+        let mut content = format!("{}\n---\n", frontmatter.trim_end());
+        content.push_str(&"body text\n".repeat(1000));
+        assert!(
+            content
+                .find("\n---\n")
+                .is_some_and(|fence| fence < max_bytes)
+        );
+        let path = temp_dir.path().join("straddle.md");
+        fs::write(&path, &content)
+            .await
+            .expect("Failed to write straddling file");
 
-        let body = format!(
-            "path=&filename=new-post&csrf_token={}",
-            urlencoding::encode(&csrf_token)
+        let (prefix, truncated) = read_frontmatter_prefix(&path, max_bytes)
+            .await
+            .expect("Failed to read prefix");
+        assert!(truncated);
+        assert_eq!(prefix.len(), max_bytes + FRONTMATTER_PREFIX_SLACK);
+        assert!(matches!(
+            parse_frontmatter_prefix(&prefix, max_bytes, SCALARS_ONLY, truncated),
+            FrontmatterProbe::Found((Some(true), ..))
+        ));
+
+        // An unterminated fence is not scanned to EOF.
+        let unterminated = temp_dir.path().join("unterminated.md");
+        fs::write(&unterminated, format!("---\n{}", "x: y\n".repeat(100_000)))
+            .await
+            .expect("Failed to write unterminated file");
+        let (prefix, truncated) = read_frontmatter_prefix(&unterminated, max_bytes)
+            .await
+            .expect("Failed to read prefix");
+        assert!(truncated);
+        assert_eq!(
+            parse_frontmatter_prefix(&prefix, max_bytes, SCALARS_ONLY, truncated),
+            FrontmatterProbe::Absent
         );
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/new-file")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body))
-            .expect("Failed to build create-new-file request");
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        // A multi-byte character cut at the limit is dropped, not an error.
+        let multibyte = temp_dir.path().join("multibyte.md");
+        fs::write(&multibyte, "é".repeat(max_bytes + FRONTMATTER_PREFIX_SLACK))
+            .await
+            .expect("Failed to write multibyte file");
+        let (prefix, truncated) = read_frontmatter_prefix(&multibyte, max_bytes)
+            .await
+            .expect("Failed to read prefix");
+        assert!(truncated);
+        assert!(prefix.chars().all(|ch| ch == 'é'));
+    }
+
+    #[test]
+    fn test_parse_bool_value_coercions() {
+        assert_eq!(parse_bool_value(&serde_json::json!(true)), Some(true));
+        assert_eq!(parse_bool_value(&serde_json::json!(false)), Some(false));
+        assert_eq!(parse_bool_value(&serde_json::json!("yes")), Some(true));
+        assert_eq!(parse_bool_value(&serde_json::json!("off")), Some(false));
+        assert_eq!(parse_bool_value(&serde_json::json!(1)), Some(true));
+        assert_eq!(parse_bool_value(&serde_json::json!(0)), Some(false));
+        assert_eq!(parse_bool_value(&serde_json::json!(2)), None);
+        assert_eq!(parse_bool_value(&serde_json::json!("maybe")), None);
+    }
+
+    #[test]
+    fn test_parse_string_value_coercions() {
+        assert_eq!(
+            parse_string_value(&serde_json::json!(" hello ")),
+            Some("hello".to_string())
+        );
+        assert_eq!(
+            parse_string_value(&serde_json::json!(42)),
+            Some("42".to_string())
+        );
+        assert_eq!(
+            parse_string_value(&serde_json::json!(true)),
+            Some("true".to_string())
+        );
+        assert_eq!(parse_string_value(&serde_json::json!("  ")), None);
+        assert_eq!(parse_string_value(&serde_json::json!({"k": "v"})), None);
+    }
+
+    #[test]
+    fn test_parse_string_list_value_coercions() {
+        assert_eq!(
+            parse_string_list_value(&serde_json::json!(["a", " b ", 3]), 10),
+            vec!["a".to_string(), "b".to_string(), "3".to_string()]
+        );
         assert_eq!(
-            response
-                .headers()
-                .get("location")
-                .and_then(|h| h.to_str().ok()),
-            Some("/edit?path=new-post.md")
+            parse_string_list_value(&serde_json::json!("rust, web,  docs "), 10),
+            vec!["rust".to_string(), "web".to_string(), "docs".to_string()]
+        );
+        assert_eq!(
+            parse_string_list_value(&serde_json::json!("single"), 10),
+            vec!["single".to_string()]
+        );
+        assert_eq!(
+            parse_string_list_value(&serde_json::json!(9), 10),
+            vec!["9".to_string()]
         );
-
-        let new_file = temp_dir.path().join("new-post.md");
-        assert!(new_file.exists());
-        let content = fs::read_to_string(&new_file)
-            .await
-            .expect("Failed to read created markdown file");
-        assert!(content.is_empty());
     }
 
-    #[tokio::test]
-    async fn test_create_new_file_rejects_existing_file() {
-        let (app, temp_dir, csrf_secret) = create_test_app().await;
-        let existing_file = temp_dir.path().join("existing.md");
-        fs::write(&existing_file, "# Existing")
-            .await
-            .expect("Failed to write existing markdown file");
-        let csrf_token = generate_csrf_token(&csrf_secret);
-
-        let body = format!(
-            "path=&filename=existing&csrf_token={}",
-            urlencoding::encode(&csrf_token)
+    #[test]
+    fn test_parse_string_list_value_truncates_to_cap() {
+        let tags = (0..10_000).map(|n| format!("tag{n}")).collect::<Vec<_>>();
+        let expected = vec!["tag0".to_string(), "tag1".to_string(), "tag2".to_string()];
+        assert_eq!(
+            parse_string_list_value(&serde_json::json!(tags.join(",")), 3),
+            expected
         );
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/new-file")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body))
-            .expect("Failed to build create-new-file request for existing file");
+        assert_eq!(
+            parse_string_list_value(&serde_json::json!(tags), 3),
+            expected
+        );
+        assert!(parse_string_list_value(&serde_json::json!("single"), 0).is_empty());
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let content = format!("---\ntitle: Many\ntags: {}\n---\n", tags.join(", "));
+        let (_, title, _, tags, ..) = parse_frontmatter(&content, 1_048_576, 3)
+            .expect("frontmatter with many tags should parse");
+        assert_eq!(title.as_deref(), Some("Many"));
+        assert_eq!(tags, expected);
     }
 
-    #[tokio::test]
-    async fn test_create_new_file_rejects_invalid_filename() {
-        let (app, _temp_dir, csrf_secret) = create_test_app().await;
-        let csrf_token = generate_csrf_token(&csrf_secret);
-
-        let body = format!(
-            "path=&filename=bad%2Fname&csrf_token={}",
-            urlencoding::encode(&csrf_token)
-        );
-        let request = Request::builder()
-            .method(Method::POST)
-            .uri("/new-file")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(body))
-            .expect("Failed to build create-new-file request with invalid filename");
-
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    #[test]
+    fn test_format_file_size_units() {
+        assert_eq!(format_file_size(0), "0 B");
+        assert_eq!(format_file_size(1023), "1023 B");
+        assert_eq!(format_file_size(1024), "1.0 KB");
+        assert_eq!(format_file_size(1_048_576), "1.0 MB");
     }
 
     #[tokio::test]
-    async fn test_image_preview_page_contains_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
-
-        let test_file = temp_dir.path().join("image.png");
-        fs::write(&test_file, "fake image data")
+    async fn test_special_character_paths_round_trip_through_listing_edit_save_and_delete() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let directory_name = "c++ #notes? 100%";
+        let file_name = "faq?#1 + 50%.md";
+        let relative_path = format!("{directory_name}/{file_name}");
+        fs::create_dir(temp_dir.path().join(directory_name))
             .await
-            .expect("Failed to write test file");
-
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/preview?path=image.png")
-            .body(Body::empty())
-            .expect("Failed to build request");
-
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = response
-            .into_body()
-            .collect()
+            .expect("Failed to create directory with special characters");
+        let test_file = temp_dir.path().join(&relative_path);
+        fs::write(&test_file, "# Special")
             .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(html.contains(r#"name="csrf_token""#));
-        assert!(!html.contains("← Back to file browser"));
-        let buttons_pos = html
-            .find(r#"<div class="buttons inline-actions">"#)
-            .expect("expected image preview action buttons");
-        let preview_pos = html
-            .find(r#"<div class="image-preview-container">"#)
-            .expect("expected image preview container");
-        assert!(
-            buttons_pos < preview_pos,
-            "image preview actions should render above preview container"
-        );
-    }
+            .expect("Failed to write file with special characters");
+
+        let get_html = |uri: String| {
+            let app = app.clone();
+            async move {
+                let request = Request::builder()
+                    .method(Method::GET)
+                    .uri(uri)
+                    .body(Body::empty())
+                    .expect("Failed to build GET request");
+                let response = app.oneshot(request).await.expect("Failed to send request");
+                assert_eq!(response.status(), StatusCode::OK);
+                let body = response
+                    .into_body()
+                    .collect()
+                    .await
+                    .expect("Failed to collect response body")
+                    .to_bytes();
+                String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8")
+            }
+        };
 
-    #[tokio::test]
-    async fn test_delete_from_image_preview_context_with_valid_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
+        let root_html = get_html("/".to_string()).await;
+        let directory_url = path_url("/", directory_name);
+        assert!(root_html.contains(&format!(r#"href="{directory_url}""#)));
 
-        let test_file = temp_dir.path().join("image.png");
-        fs::write(&test_file, "fake image data")
-            .await
-            .expect("Failed to write test image file");
+        let directory_html = get_html(directory_url).await;
+        let edit_url = path_url("/edit", &relative_path);
+        assert!(directory_html.contains(&format!(r#"href="{edit_url}""#)));
+        assert!(directory_html.contains(r#"<a href="/">📁"#));
 
-        let preview_request = Request::builder()
-            .method(Method::GET)
-            .uri("/preview?path=image.png")
-            .body(Body::empty())
-            .expect("Failed to build image preview request");
-        let preview_response = app
-            .clone()
-            .oneshot(preview_request)
-            .await
-            .expect("Failed to send image preview request");
-        assert_eq!(preview_response.status(), StatusCode::OK);
+        let edit_html = get_html(edit_url).await;
+        assert!(edit_html.contains(&format!(
+            r#"class="button-link cancel" href="{}""#,
+            path_url("/", directory_name)
+        )));
 
-        let preview_body = preview_response
-            .into_body()
-            .collect()
+        let csrf_token = generate_csrf_token(&csrf_secret);
+        let save_body = format!(
+            "path={}&content={}&csrf_token={}",
+            urlencoding::encode(&relative_path),
+            urlencoding::encode("# Saved + kept"),
+            urlencoding::encode(&csrf_token)
+        );
+        let save_response = post_form(&app, "/save", save_body).await;
+        assert_eq!(save_response.status(), StatusCode::OK);
+        let saved = fs::read_to_string(&test_file)
             .await
-            .expect("Failed to collect image preview body")
-            .to_bytes();
-        let preview_html = String::from_utf8(preview_body.to_vec())
-            .expect("Failed to parse image preview body as UTF-8");
-        let csrf_token = extract_csrf_token_from_html(&preview_html)
-            .expect("Image preview HTML should contain csrf_token");
+            .expect("Failed to read saved file");
+        assert_eq!(saved, "# Saved + kept");
 
         let delete_body = format!(
-            "path=image.png&csrf_token={}",
+            "path={}&csrf_token={}",
+            urlencoding::encode(&relative_path),
             urlencoding::encode(&csrf_token)
         );
-        let delete_request = Request::builder()
-            .method(Method::POST)
-            .uri("/delete")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(delete_body))
-            .expect("Failed to build delete request from image preview context");
-        let delete_response = app
-            .oneshot(delete_request)
-            .await
-            .expect("Failed to send delete request from image preview context");
+        let delete_response = post_form(&app, "/delete", delete_body).await;
         assert_eq!(delete_response.status(), StatusCode::OK);
         assert!(!test_file.exists());
     }
 
-    #[tokio::test]
-    async fn test_file_preview_page_contains_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
-
-        let test_file = temp_dir.path().join("notes.txt");
-        fs::write(&test_file, "hello")
-            .await
-            .expect("Failed to write test file");
+    #[test]
+    fn test_get_file_type_description_covers_known_and_unknown_types() {
+        assert_eq!(get_file_type_description("notes.txt"), "Text file");
+        assert_eq!(get_file_type_description("index.html"), "HTML document");
+        assert_eq!(get_file_type_description("data.json"), "JSON data");
+        assert_eq!(get_file_type_description("script.sh"), "Executable file");
+        assert_eq!(
+            get_file_type_description("archive.unknown"),
+            "Unknown file type"
+        );
+    }
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-preview?path=notes.txt")
-            .body(Body::empty())
-            .expect("Failed to build request");
+    #[test]
+    fn test_build_breadcrumbs_generates_expected_paths() {
+        let breadcrumbs = build_breadcrumbs("posts/2026");
+        assert_eq!(breadcrumbs.len(), 2);
 
-        let response = app.oneshot(request).await.expect("Failed to send request");
-        assert_eq!(response.status(), StatusCode::OK);
+        let first = breadcrumbs
+            .first()
+            .expect("breadcrumbs should have a first element");
+        assert_eq!(first.name, "posts");
+        assert_eq!(first.url, "/?path=posts");
 
-        let body = response
-            .into_body()
-            .collect()
-            .await
-            .expect("Failed to collect response body")
-            .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse response body as UTF-8");
-        assert!(html.contains(r#"name="csrf_token""#));
+        let second = breadcrumbs
+            .get(1)
+            .expect("breadcrumbs should have a second element");
+        assert_eq!(second.name, "2026");
+        assert_eq!(second.url, "/?path=posts%2F2026");
     }
 
     #[tokio::test]
-    async fn test_delete_from_file_preview_context_with_valid_csrf_token() {
-        let (app, temp_dir, _) = create_test_app().await;
-
-        let test_file = temp_dir.path().join("notes.txt");
-        fs::write(&test_file, "hello")
-            .await
-            .expect("Failed to write test text file");
-
-        let preview_request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-preview?path=notes.txt")
-            .body(Body::empty())
-            .expect("Failed to build file preview request");
-        let preview_response = app
-            .clone()
-            .oneshot(preview_request)
-            .await
-            .expect("Failed to send file preview request");
-        assert_eq!(preview_response.status(), StatusCode::OK);
-
-        let preview_body = preview_response
-            .into_body()
-            .collect()
+    async fn test_set_draft_toggles_the_flag_and_reopens_the_editor() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let post = temp_dir.path().join("post.md");
+        fs::write(&post, "---\ntitle: Post\ndraft: true\n---\n# Post\n")
             .await
-            .expect("Failed to collect file preview body")
-            .to_bytes();
-        let preview_html =
-            String::from_utf8(preview_body.to_vec()).expect("Failed to parse file preview HTML");
-        let csrf_token = extract_csrf_token_from_html(&preview_html)
-            .expect("File preview HTML should contain csrf_token");
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let set_draft = |path: &str, draft: &str| {
+            post_form(
+                &app,
+                "/set-draft",
+                format!(
+                    "path={}&draft={draft}&csrf_token={csrf_token}",
+                    urlencoding::encode(path)
+                ),
+            )
+        };
 
-        let delete_body = format!(
-            "path=notes.txt&csrf_token={}",
-            urlencoding::encode(&csrf_token)
+        let response = set_draft("post.md", "false").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok()),
+            Some("/edit?path=post.md")
         );
-        let delete_request = Request::builder()
-            .method(Method::POST)
-            .uri("/delete")
-            .header("content-type", "application/x-www-form-urlencoded")
-            .body(Body::from(delete_body))
-            .expect("Failed to build delete request from file preview context");
-        let delete_response = app
-            .oneshot(delete_request)
-            .await
-            .expect("Failed to send delete request from file preview context");
-        assert_eq!(delete_response.status(), StatusCode::OK);
-        assert!(!test_file.exists());
+        assert_eq!(
+            fs::read_to_string(&post)
+                .await
+                .expect("Failed to read post"),
+            "---\ntitle: Post\ndraft: false\n---\n# Post\n"
+        );
+
+        for (path, draft) in [
+            ("post.md", "maybe"),
+            ("notes.txt", "true"),
+            ("missing.md", "true"),
+        ] {
+            let response = set_draft(path, draft).await;
+            assert!(response.status().is_client_error(), "{path} {draft}");
+        }
     }
 
     #[tokio::test]
-    async fn test_file_info_endpoint_returns_json_metadata() {
-        let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("meta.md");
-        fs::write(&test_file, "# Meta")
+    async fn test_publish_by_tag_publishes_only_tagged_drafts() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
             .await
-            .expect("Failed to write metadata test file");
-
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-info?path=meta.md")
-            .body(Body::empty())
-            .expect("Failed to build file-info request");
+            .expect("Failed to create posts directory");
+        let files = [
+            (
+                "posts/tagged.md",
+                "---\ntitle: A\ndraft: true\ntags: [launch, rust]\n---\n# A\n",
+            ),
+            (
+                "posts/json.md",
+                "{\"draft\": true, \"tags\": \"Launch\"}\n# B\n",
+            ),
+            (
+                "posts/other-tag.md",
+                "---\ndraft: true\ntags: [rust]\n---\n# C\n",
+            ),
+            (
+                "posts/published.md",
+                "---\ndraft: false\ntags: [launch]\n---\n# D\n",
+            ),
+            ("posts/untagged.md", "---\ndraft: true\n---\n# E\n"),
+        ];
+        for (path, content) in files {
+            fs::write(temp_dir.path().join(path), content)
+                .await
+                .expect("Failed to write markdown file");
+        }
 
-        let response = app
-            .oneshot(request)
+        let publish = |body: serde_json::Value| {
+            let request = Request::builder()
+                .method(Method::POST)
+                .uri("/api/publish-by-tag")
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("Failed to build publish request");
+            app.clone().oneshot(request)
+        };
+        let response = publish(serde_json::json!({ "csrf_token": "bogus", "tag": "launch" }))
             .await
-            .expect("Failed to send file-info request");
-        assert_eq!(response.status(), StatusCode::OK);
+            .expect("Failed to send publish request");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
 
-        let body = response
+        let response = publish(serde_json::json!({
+            "csrf_token": generate_csrf_token(&csrf_secret),
+            "tag": "launch",
+        }))
+        .await
+        .expect("Failed to send publish request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = response
             .into_body()
             .collect()
             .await
-            .expect("Failed to collect file-info response body")
+            .expect("Failed to collect publish response body")
             .to_bytes();
         let json: serde_json::Value =
-            serde_json::from_slice(&body).expect("Failed to decode file-info JSON");
+            serde_json::from_slice(&bytes).expect("Failed to parse publish response");
+        assert_eq!(json["published"], 2);
+        assert_eq!(json["failed"], 0);
+        let mut paths: Vec<&str> = json["results"]
+            .as_array()
+            .expect("results should be an array")
+            .iter()
+            .filter_map(|result| result["path"].as_str())
+            .collect();
+        paths.sort_unstable();
+        assert_eq!(paths, vec!["posts/json.md", "posts/tagged.md"]);
+
+        let read = |path: &str| {
+            let path = temp_dir.path().join(path);
+            async move { fs::read_to_string(path).await.expect("Failed to read file") }
+        };
         assert_eq!(
-            json.get("size").and_then(serde_json::Value::as_u64),
-            Some(6)
-        );
-        assert!(
-            json.get("modified_time")
-                .and_then(serde_json::Value::as_str)
-                .is_some()
+            read("posts/tagged.md").await,
+            "---\ntitle: A\ndraft: false\ntags:\n- launch\n- rust\n---\n# A\n"
         );
+        assert!(read("posts/json.md").await.contains("\"draft\": false"));
+        for (path, content) in files.into_iter().skip(2) {
+            assert_eq!(read(path).await, content, "{path}");
+        }
+        assert!(journal::find_interrupted(temp_dir.path()).await.is_empty());
     }
 
     #[tokio::test]
-    async fn test_file_content_endpoint_returns_json_content() {
+    async fn test_malformed_path_encoding_is_rejected_clearly() {
         let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("content.md");
-        fs::write(&test_file, "# Hello")
+        fs::write(temp_dir.path().join("100%zz.md"), "# Literal\n")
             .await
-            .expect("Failed to write content test file");
+            .expect("Failed to write markdown file");
+        for uri in [
+            "/edit?path=%zz",
+            "/edit?path=a%2",
+            "/edit?path=%ff.md",
+            "/file-info?foo=1&path=%zz",
+            "/?path=%zz",
+        ] {
+            let (status, body) = get_html(app.clone(), uri).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{uri}");
+            assert!(body.contains("Invalid path encoding"), "{uri}: {body}");
+        }
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-content?path=content.md")
-            .body(Body::empty())
-            .expect("Failed to build file-content request");
+        // Other parameters are not checked, and a correctly escaped `%`
+        // reaches the file.
+        let (status, _) = get_html(app.clone(), "/edit?path=100%25zz.md&x=%zz").await;
+        assert_eq!(status, StatusCode::OK);
+    }
 
+    async fn post_move_many(
+        app: Router,
+        body: serde_json::Value,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/move-many")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .expect("Failed to build move-many request");
         let response = app
             .oneshot(request)
             .await
-            .expect("Failed to send file-content request");
-        assert_eq!(response.status(), StatusCode::OK);
-
-        let body = response
+            .expect("Failed to send move-many request");
+        let status = response.status();
+        let bytes = response
             .into_body()
             .collect()
             .await
-            .expect("Failed to collect file-content response body")
+            .expect("Failed to collect move-many response body")
             .to_bytes();
-        let json: serde_json::Value =
-            serde_json::from_slice(&body).expect("Failed to decode file-content JSON");
+        let json = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn test_move_many_moves_files_and_reports_failures_per_item() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("archive"))
+            .await
+            .expect("Failed to create archive directory");
+        for name in ["one.md", "two.md", "taken.md"] {
+            fs::write(temp_dir.path().join(name), name)
+                .await
+                .expect("Failed to write test file");
+        }
+        fs::write(temp_dir.path().join("archive/taken.md"), "existing")
+            .await
+            .expect("Failed to write existing destination");
+
+        let (status, json) = post_move_many(
+            app,
+            serde_json::json!({
+                "csrf_token": generate_csrf_token(&csrf_secret),
+                "moves": [
+                    { "src": "one.md", "dest": "archive/one.md" },
+                    { "src": "missing.md", "dest": "archive/missing.md" },
+                    { "src": "taken.md", "dest": "archive/taken.md" },
+                    { "src": "two.md", "dest": "../two.md" },
+                    { "src": "two.md", "dest": "archive/renamed.md" },
+                ],
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["moved"], 2);
+        assert_eq!(json["failed"], 3);
+        let ok_flags: Vec<bool> = json["results"]
+            .as_array()
+            .expect("results should be an array")
+            .iter()
+            .map(|result| result["ok"].as_bool().unwrap_or(false))
+            .collect();
+        assert_eq!(ok_flags, vec![true, false, false, false, true]);
+        assert!(json["results"][2]["error"].as_str().is_some());
+
+        assert!(temp_dir.path().join("archive/one.md").exists());
+        assert!(!temp_dir.path().join("one.md").exists());
+        assert!(temp_dir.path().join("archive/renamed.md").exists());
+        assert!(temp_dir.path().join("taken.md").exists());
+        assert!(journal::find_interrupted(temp_dir.path()).await.is_empty());
         assert_eq!(
-            json.get("content").and_then(serde_json::Value::as_str),
-            Some("# Hello")
+            fs::read_to_string(temp_dir.path().join("archive/taken.md"))
+                .await
+                .expect("Failed to read existing destination"),
+            "existing"
         );
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_batch_move_is_detected_at_startup() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::create_dir(temp_dir.path().join("archive"))
+            .await
+            .expect("Failed to create archive directory");
+        for name in ["one.md", "two.md"] {
+            fs::write(temp_dir.path().join(name), name)
+                .await
+                .expect("Failed to write test file");
+        }
+
+        // The first half of a batch move, then a crash before `finish`.
+        let targets = ["one.md", "two.md"]
+            .into_iter()
+            .map(|name| journal::JournalTarget::new(format!("archive/{name}"), Some(name.into())))
+            .collect();
+        let mut operation_journal =
+            journal::OperationJournal::begin(temp_dir.path(), "batch move", targets)
+                .await
+                .expect("Failed to begin journal");
+        move_file(
+            temp_dir.path(),
+            "one.md",
+            "archive/one.md",
+            false,
+            Durability::default(),
+        )
+        .await
+        .expect("Failed to move first file");
+        operation_journal.mark_done(0).await;
+        drop(operation_journal);
+
+        // What start_server does on the next run.
+        let interrupted = journal::find_interrupted(temp_dir.path()).await;
+        assert_eq!(interrupted.len(), 1);
+        let journal_path = interrupted
+            .first()
+            .map(|operation| temp_dir.path().join(&operation.journal))
+            .expect("journal path");
+        let (app, app_dir, _) = create_test_app_with(|state| {
+            state.target_dir = temp_dir.path().to_path_buf();
+            state.interrupted_operations = Arc::new(interrupted);
+        })
+        .await;
+        drop(app_dir);
+
+        let (status, html) = get_html(app.clone(), "/problems").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Interrupted batch move"));
+        assert!(html.contains("1 file written, 1 pending"));
+        assert!(html.contains("✅ <span class=\"file\">one.md → archive/one.md</span>"));
+        assert!(html.contains("⏳ <span class=\"file\">two.md → archive/two.md</span>"));
+
+        // Deleting the journal clears the warning without a restart.
+        fs::remove_file(&journal_path)
+            .await
+            .expect("Failed to remove journal");
+        let (_, html) = get_html(app, "/problems").await;
+        assert!(!html.contains("Interrupted batch move"));
+    }
+
+    #[tokio::test]
+    async fn test_move_many_handles_case_only_renames_on_case_insensitive_fs() {
+        let (app, temp_dir, csrf_secret) =
+            create_test_app_with(|state| state.case_insensitive_fs = true).await;
+        for name in ["readme.md", "notes.md", "Other.md"] {
+            fs::write(temp_dir.path().join(name), name)
+                .await
+                .expect("Failed to write test file");
+        }
+
+        let (status, json) = post_move_many(
+            app,
+            serde_json::json!({
+                "csrf_token": generate_csrf_token(&csrf_secret),
+                "moves": [
+                    { "src": "readme.md", "dest": "README.md" },
+                    { "src": "notes.md", "dest": "other.md" },
+                ],
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["moved"], 1);
+        assert_eq!(json["failed"], 1);
         assert!(
-            json.get("modified_time")
-                .and_then(serde_json::Value::as_str)
-                .is_some()
+            json["results"][1]["error"]
+                .as_str()
+                .is_some_and(|error| error.contains("Other.md"))
         );
+        assert!(temp_dir.path().join("README.md").exists());
+        assert!(!temp_dir.path().join("readme.md").exists());
+        assert!(temp_dir.path().join("notes.md").exists());
     }
 
     #[tokio::test]
-    async fn test_file_content_endpoint_rejects_non_markdown() {
-        let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("notes.txt");
-        fs::write(&test_file, "hello")
+    async fn test_moved_and_deleted_paths_get_redirect_or_gone() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("archive"))
             .await
-            .expect("Failed to write text test file");
+            .expect("Failed to create archive directory");
+        fs::write(temp_dir.path().join("old.md"), "# Old")
+            .await
+            .expect("Failed to write test file");
+        fs::write(temp_dir.path().join("doomed.md"), "# Doomed")
+            .await
+            .expect("Failed to write test file");
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-content?path=notes.txt")
-            .body(Body::empty())
-            .expect("Failed to build file-content request for non-markdown");
+        let (status, _) = post_move_many(
+            app.clone(),
+            serde_json::json!({
+                "csrf_token": generate_csrf_token(&csrf_secret),
+                "moves": [{ "src": "old.md", "dest": "archive/new.md" }],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
 
-        let response = app
-            .oneshot(request)
+        let response = get_index(&app, "/edit?path=old.md", None).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(
+            response
+                .headers()
+                .get("location")
+                .and_then(|value| value.to_str().ok()),
+            Some("/edit?path=archive%2Fnew.md")
+        );
+        let response = get_index(&app, "/file?path=old.md&download=1", None).await;
+        assert_eq!(
+            response
+                .headers()
+                .get("location")
+                .and_then(|value| value.to_str().ok()),
+            Some("/file?path=archive%2Fnew.md&download=1")
+        );
+
+        let body = format!(
+            "path=doomed.md&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let response = post_form(&app, "/delete", body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = get_index(&app, "/edit?path=doomed.md", None).await;
+        assert_eq!(response.status(), StatusCode::GONE);
+        let bytes = response
+            .into_body()
+            .collect()
             .await
-            .expect("Failed to send file-content request for non-markdown");
+            .expect("Failed to collect gone page")
+            .to_bytes();
+        let page = String::from_utf8_lossy(&bytes);
+        assert!(page.contains("doomed.md"));
+        assert!(page.contains(r#"href="/trash""#));
+
+        // Unknown paths still fail as before.
+        let response = get_index(&app, "/edit?path=never.md", None).await;
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
     #[tokio::test]
-    async fn test_serve_image_sets_expected_content_type() {
+    async fn test_redirect_map_is_unreachable_through_file_routes() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::write(temp_dir.path().join("old.md"), "# Old")
+            .await
+            .expect("Failed to write test file");
+        let (status, _) = post_move_many(
+            app.clone(),
+            serde_json::json!({
+                "csrf_token": generate_csrf_token(&csrf_secret),
+                "moves": [{ "src": "old.md", "dest": "new.md" }],
+            }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let map = temp_dir.path().join(redirects::REDIRECTS_FILE);
+        let recorded = fs::read(&map).await.expect("Redirect map should exist");
+
+        let encoded = urlencoding::encode(redirects::REDIRECTS_FILE);
+        let response = get_index(&app, &format!("/file?path={encoded}"), None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let body = format!(
+            "path={encoded}&csrf_token={}",
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
+        );
+        let response = post_form(&app, "/delete", body).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = app
+            .clone()
+            .oneshot(build_put_raw_request(
+                &format!("/raw?path={encoded}"),
+                "{}",
+                &[],
+            ))
+            .await
+            .expect("Failed to send PUT /raw request");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        assert_eq!(
+            fs::read(&map).await.expect("Redirect map should remain"),
+            recorded
+        );
+        let response = get_index(&app, "/edit?path=old.md", None).await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_move_many_requires_valid_csrf_token() {
         let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("pixel.png");
-        fs::write(&test_file, vec![0x89, b'P', b'N', b'G'])
+        fs::write(temp_dir.path().join("one.md"), "one")
             .await
-            .expect("Failed to write png test file");
+            .expect("Failed to write test file");
+
+        let (status, _) = post_move_many(
+            app,
+            serde_json::json!({
+                "csrf_token": "invalid",
+                "moves": [{ "src": "one.md", "dest": "two.md" }],
+            }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(temp_dir.path().join("one.md").exists());
+        assert!(!temp_dir.path().join("two.md").exists());
+    }
 
+    #[tokio::test]
+    async fn test_request_id_is_echoed_when_provided() {
+        let (app, _temp_dir, _) = create_test_app().await;
         let request = Request::builder()
             .method(Method::GET)
-            .uri("/image?path=pixel.png")
+            .uri("/")
+            .header("x-request-id", "proxy-abc-123")
             .body(Body::empty())
-            .expect("Failed to build image request");
+            .expect("Failed to build request");
+        let response = app.oneshot(request).await.expect("Failed to send request");
 
-        let response = app
-            .oneshot(request)
+        assert_eq!(
+            response
+                .headers()
+                .get("x-request-id")
+                .and_then(|value| value.to_str().ok()),
+            Some("proxy-abc-123")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_generated_when_absent_or_invalid() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        for provided in [None, Some("bad id with spaces")] {
+            let mut builder = Request::builder().method(Method::GET).uri("/missing");
+            if let Some(value) = provided {
+                builder = builder.header("x-request-id", value);
+            }
+            let request = builder
+                .body(Body::empty())
+                .expect("Failed to build request");
+            let response = app
+                .clone()
+                .oneshot(request)
+                .await
+                .expect("Failed to send request");
+
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            let request_id = response
+                .headers()
+                .get("x-request-id")
+                .and_then(|value| value.to_str().ok())
+                .expect("response should carry a generated request id");
+            assert_eq!(request_id.len(), 32);
+            assert!(request_id.chars().all(|ch| ch.is_ascii_hexdigit()));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_stats_count_saves_from_audit_journal() {
+        let (app, temp_dir, csrf_secret) = create_test_app_with(|state| {
+            state.audit = Some(audit::AuditJournal::new(
+                state.target_dir.join(".audit.jsonl"),
+            ));
+        })
+        .await;
+        fs::write(temp_dir.path().join("post.md"), "# Draft")
             .await
-            .expect("Failed to send image request");
-        assert_eq!(response.status(), StatusCode::OK);
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|value| value.to_str().ok());
-        assert_eq!(content_type, Some("image/png"));
+            .expect("Failed to write test file");
+
+        for (index, content) in ["one", "two", "two", "three"].iter().enumerate() {
+            let body = format!(
+                "path=post.md&csrf_token={}&content={content}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            );
+            assert_eq!(
+                post_form(&app, "/save", body).await.status(),
+                StatusCode::OK,
+                "save {index} should succeed"
+            );
+        }
+
+        let (status, body) = get_html(app.clone(), "/api/file-stats?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        let stats: serde_json::Value =
+            serde_json::from_str(&body).expect("file stats should be JSON");
+        assert_eq!(stats["tracking"], "enabled");
+        // The unchanged save is not written, so it is not counted.
+        assert_eq!(stats["edits"], 3);
+        assert_eq!(stats["created_source"], "journal");
+        assert_eq!(stats["last_operation"]["operation"], "save");
+        assert_eq!(stats["last_operation"]["via"], "web");
+
+        let (status, html) = get_html(app, "/edit?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("Edited 3 times, last via web on"));
     }
 
     #[tokio::test]
-    async fn test_serve_file_sets_security_headers_for_safe_file() {
+    async fn test_file_stats_reports_tracking_disabled_without_journal() {
         let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("notes.txt");
-        fs::write(&test_file, "safe preview")
+        fs::write(temp_dir.path().join("post.md"), "# Post")
             .await
-            .expect("Failed to write safe file");
+            .expect("Failed to write test file");
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/file?path=notes.txt")
-            .body(Body::empty())
-            .expect("Failed to build safe file request");
+        let (status, body) = get_html(app.clone(), "/api/file-stats?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        let stats: serde_json::Value =
+            serde_json::from_str(&body).expect("file stats should be JSON");
+        assert_eq!(stats["tracking"], "disabled");
+        assert!(stats.get("edits").is_none());
 
-        let response = app
-            .oneshot(request)
-            .await
-            .expect("Failed to send safe file request");
-        assert_eq!(response.status(), StatusCode::OK);
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|value| value.to_str().ok());
-        assert_eq!(content_type, Some("text/plain; charset=utf-8"));
-        let nosniff = response
-            .headers()
-            .get("x-content-type-options")
-            .and_then(|value| value.to_str().ok());
-        assert_eq!(nosniff, Some("nosniff"));
-        let frame_options = response
-            .headers()
-            .get("x-frame-options")
-            .and_then(|value| value.to_str().ok());
-        assert_eq!(frame_options, Some("SAMEORIGIN"));
+        let (_, html) = get_html(app, "/edit?path=post.md").await;
+        assert!(!html.contains("edit-summary"));
     }
 
     #[tokio::test]
-    async fn test_serve_file_forbids_unsafe_file_type() {
+    async fn test_frontmatter_endpoint_returns_parsed_fields_and_extras() {
         let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("script.sh");
-        fs::write(&test_file, "echo hi")
+        fs::write(
+            temp_dir.path().join("post.md"),
+            "---\ntitle: Hello\ndraft: false\ntags:\n  - rust\n  - web\nseries:\n  name: Intro\n  part: 2\n---\n# Hello\n",
+        )
+        .await
+        .expect("Failed to write test file");
+        fs::write(temp_dir.path().join("plain.md"), "# Plain\n")
             .await
-            .expect("Failed to write executable test file");
+            .expect("Failed to write test file");
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/file?path=script.sh")
-            .body(Body::empty())
-            .expect("Failed to build executable file request");
+        let (status, body) = get_html(app.clone(), "/frontmatter?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        let frontmatter: serde_json::Value =
+            serde_json::from_str(&body).expect("frontmatter should be JSON");
+        assert_eq!(
+            frontmatter,
+            serde_json::json!({
+                "draft": false,
+                "title": "Hello",
+                "date": null,
+                "tags": ["rust", "web"],
+                "categories": [],
+                "series": {"name": "Intro", "part": 2},
+            })
+        );
 
-        let response = app
-            .oneshot(request)
+        let (status, body) = get_html(app.clone(), "/frontmatter?path=plain.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "{}");
+
+        let (status, _) = get_html(app, "/frontmatter?path=missing.md").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_links_endpoint_groups_links_by_kind() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
             .await
-            .expect("Failed to send executable file request");
-        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            .expect("Failed to create posts directory");
+        fs::write(temp_dir.path().join("about.md"), "# About")
+            .await
+            .expect("Failed to write about page");
+        fs::write(
+            temp_dir.path().join("posts/fixture.md"),
+            "---\ntitle: Fixture\n---\n[About](/about.md)\n[Gone](gone.md)\n\
+             [Site](https://example.com) [Top](#top) [Mail](mailto:a@example.com)\n",
+        )
+        .await
+        .expect("Failed to write fixture post");
+
+        let (status, body) = get_html(app, "/api/links?path=posts/fixture.md").await;
+        assert_eq!(status, StatusCode::OK);
+        let links: serde_json::Value =
+            serde_json::from_str(&body).expect("links response should be JSON");
+        assert_eq!(links["internal"][0]["target"], "about.md");
+        assert_eq!(links["internal"][0]["line"], 4);
+        assert_eq!(links["broken"][0]["destination"], "gone.md");
+        assert_eq!(links["broken"][0]["line"], 5);
+        assert_eq!(links["external"][0]["text"], "Site");
+        assert_eq!(links["anchors"][0]["destination"], "#top");
+        assert_eq!(links["mailto"][0]["kind"], "mailto");
     }
 
     #[tokio::test]
-    async fn test_file_preview_shows_iframe_for_safe_file_type() {
-        let (app, temp_dir, _) = create_test_app().await;
-        let test_file = temp_dir.path().join("notes.txt");
-        fs::write(&test_file, "hello")
-            .await
-            .expect("Failed to write safe preview file");
+    async fn test_client_config_never_exposes_secrets_or_paths() {
+        let mount_dir = TempDir::new().expect("failed to create mount directory");
+        let mount_path = mount_dir.path().to_path_buf();
+        let (app, temp_dir, csrf_secret) = create_test_app_with(|state| {
+            state.mounts = vec![Mount {
+                name: "assets".to_string(),
+                path: mount_path,
+            }];
+        })
+        .await;
+
+        let (status, body) = get_html(app.clone(), "/api/config").await;
+        assert_eq!(status, StatusCode::OK);
+        let config: serde_json::Value =
+            serde_json::from_str(&body).expect("config response should be JSON");
+        let mut keys: Vec<&str> = config
+            .as_object()
+            .expect("config should be an object")
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        // Adding a field here should be a deliberate decision about exposure.
+        assert_eq!(
+            keys,
+            [
+                "api_auth_header",
+                "api_version",
+                "api_writes_enabled",
+                "csrf_field",
+                "csrf_token_lifetime_seconds",
+                "draft_check_interval_ms",
+                "edit_tracking_enabled",
+                "iframe_preview_disabled",
+                "max_frontmatter_bytes",
+                "max_upload_size_bytes",
+                "mounts",
+                "preview_commands_enabled",
+                "remember_path",
+            ]
+        );
+        assert_eq!(config["api_writes_enabled"], true);
+        assert_eq!(config["mounts"], serde_json::json!(["assets"]));
+        assert_eq!(
+            config["max_upload_size_bytes"],
+            DEFAULT_MAX_UPLOAD_SIZE_BYTES
+        );
 
-        let request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-preview?path=notes.txt")
-            .body(Body::empty())
-            .expect("Failed to build file preview request");
+        let (status, page) = get_html(app, "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("data-client-config="));
+        for text in [&body, &page] {
+            assert!(!text.contains(&csrf_secret));
+            assert!(!text.contains(TEST_API_TOKEN));
+            assert!(!text.contains(&temp_dir.path().display().to_string()));
+            assert!(!text.contains(&mount_dir.path().display().to_string()));
+        }
+    }
 
-        let response = app
-            .oneshot(request)
+    #[tokio::test]
+    async fn test_sitemap_lists_published_pages_and_omits_drafts() {
+        let (app, temp_dir, _) = create_test_app_with(|state| {
+            state.site_base_url = Some("https://example.com/".to_string());
+        })
+        .await;
+        fs::create_dir(temp_dir.path().join("posts"))
             .await
-            .expect("Failed to send file preview request");
+            .expect("Failed to create posts directory");
+        for (path, content) in [
+            ("_index.md", "# Home\n"),
+            (
+                "posts/hello.md",
+                "---\ntitle: Hello\ndraft: false\n---\nHi\n",
+            ),
+            ("posts/wip.md", "---\ntitle: WIP\ndraft: true\n---\nLater\n"),
+            ("notes.txt", "not a page"),
+        ] {
+            fs::write(temp_dir.path().join(path), content)
+                .await
+                .expect("Failed to write fixture file");
+        }
+
+        let response = get_index(&app, "/sitemap.xml", None).await;
         assert_eq!(response.status(), StatusCode::OK);
-        let body = response
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE),
+            Some(&HeaderValue::from_static("application/xml; charset=utf-8"))
+        );
+        let bytes = response
             .into_body()
             .collect()
             .await
-            .expect("Failed to collect file preview response")
+            .expect("Failed to collect sitemap")
             .to_bytes();
-        let html =
-            String::from_utf8(body.to_vec()).expect("Failed to parse file preview HTML as UTF-8");
-        assert!(html.contains("<iframe "));
-        assert!(!html.contains("📁 Back to Files"));
-        assert!(!html.contains("← Back to file browser"));
-        let buttons_pos = html
-            .find(r#"<div class="buttons inline-actions">"#)
-            .expect("expected buttons block in file preview");
-        let preview_pos = html
-            .find(r#"<div class="file-preview-container">"#)
-            .expect("expected preview container in file preview");
-        assert!(
-            buttons_pos < preview_pos,
-            "buttons should render above preview container"
+        let xml = String::from_utf8_lossy(&bytes).to_string();
+        let document = roxmltree::Document::parse(&xml).expect("sitemap should be valid XML");
+        let locations: Vec<&str> = document
+            .descendants()
+            .filter(|node| node.has_tag_name("loc"))
+            .filter_map(|node| node.text())
+            .collect();
+        assert_eq!(
+            locations,
+            ["https://example.com/", "https://example.com/posts/hello/"]
+        );
+        assert_eq!(
+            document
+                .descendants()
+                .filter(|node| node.has_tag_name("lastmod"))
+                .count(),
+            2
         );
+        assert!(!xml.contains("wip"));
     }
 
     #[tokio::test]
-    async fn test_file_preview_rejects_markdown_and_images() {
+    async fn test_sitemap_requires_site_base_url() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        let response = get_index(&app, "/sitemap.xml", None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_robots_txt_disallows_everything_unless_configured() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        let (status, headers, body) = get_body(&app, "/robots.txt").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok()),
+            Some("text/plain; charset=utf-8")
+        );
+        assert_eq!(body, b"User-agent: *\nDisallow: /\n");
+
+        let (app, _temp_dir, _) = create_test_app_with(|state| {
+            state.robots_txt = "User-agent: *\nDisallow: /private/\n".to_string();
+        })
+        .await;
+        let (_, _, body) = get_body(&app, "/robots.txt").await;
+        assert_eq!(body, b"User-agent: *\nDisallow: /private/\n");
+    }
+
+    #[tokio::test]
+    async fn test_ignore_marker_hides_directory_everywhere() {
         let (app, temp_dir, _) = create_test_app().await;
-        let markdown_file = temp_dir.path().join("post.md");
-        let image_file = temp_dir.path().join("photo.png");
-        fs::write(&markdown_file, "# Post")
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("private/deeper"))
             .await
-            .expect("Failed to write markdown file");
-        fs::write(&image_file, "not really png")
+            .expect("Failed to create directory");
+        fs::write(root.join("private").join(ignore_marker::IGNORE_MARKER), "")
             .await
-            .expect("Failed to write image file");
-
-        let markdown_request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-preview?path=post.md")
-            .body(Body::empty())
-            .expect("Failed to build markdown file-preview request");
-        let markdown_response = app
-            .clone()
-            .oneshot(markdown_request)
+            .expect("Failed to write marker");
+        fs::write(root.join("private/roadmap.md"), "# Secret roadmap\n")
             .await
-            .expect("Failed to send markdown file-preview request");
-        assert_eq!(markdown_response.status(), StatusCode::BAD_REQUEST);
-
-        let image_request = Request::builder()
-            .method(Method::GET)
-            .uri("/file-preview?path=photo.png")
-            .body(Body::empty())
-            .expect("Failed to build image file-preview request");
-        let image_response = app
-            .oneshot(image_request)
+            .expect("Failed to write markdown file");
+        fs::write(root.join("private/deeper/notes.txt"), "notes\n")
             .await
-            .expect("Failed to send image file-preview request");
-        assert_eq!(image_response.status(), StatusCode::BAD_REQUEST);
-    }
-
-    #[test]
-    fn test_csrf_tokens_are_unique() {
-        let secret = "test_secret";
+            .expect("Failed to write text file");
+        fs::write(root.join("public-roadmap.md"), "# Public roadmap\n")
+            .await
+            .expect("Failed to write markdown file");
 
-        // Generate multiple tokens
-        let token1 = generate_csrf_token(secret);
-        let token2 = generate_csrf_token(secret);
-        let token3 = generate_csrf_token(secret);
+        for uri in [
+            "/?path=private",
+            "/?path=private/deeper",
+            "/edit?path=private/roadmap.md",
+            "/raw?path=private/roadmap.md",
+            "/file?path=private/deeper/notes.txt",
+        ] {
+            let (status, _) = get_html(app.clone(), uri).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{uri}");
+        }
 
-        // All tokens should be different (due to timestamp and nonce)
-        assert_ne!(token1, token2);
-        assert_ne!(token2, token3);
-        assert_ne!(token1, token3);
+        let (status, listing) = get_html(app.clone(), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(listing.contains("public-roadmap.md"));
+        assert!(!listing.contains("private"));
 
-        // But all should validate with the same secret
-        assert!(validate_csrf_token(&token1, secret).is_ok());
-        assert!(validate_csrf_token(&token2, secret).is_ok());
-        assert!(validate_csrf_token(&token3, secret).is_ok());
+        let (_, hits) = get_html(app, "/api/quickswitch?q=roadmap").await;
+        assert!(hits.contains("public-roadmap.md"));
+        assert!(!hits.contains("private"));
     }
 
-    #[test]
-    fn test_git_compatible_ascii_filename_stem_rules() {
-        assert!(is_git_compatible_ascii_filename_stem("post"));
-        assert!(is_git_compatible_ascii_filename_stem("post-1_2.test"));
-        assert!(!is_git_compatible_ascii_filename_stem(""));
-        assert!(!is_git_compatible_ascii_filename_stem(".post"));
-        assert!(!is_git_compatible_ascii_filename_stem("post."));
-        assert!(!is_git_compatible_ascii_filename_stem("post..v1"));
-        assert!(!is_git_compatible_ascii_filename_stem("bad/name"));
-        assert!(!is_git_compatible_ascii_filename_stem("two words"));
-        assert!(!is_git_compatible_ascii_filename_stem("ümlaut"));
+    async fn get_body(app: &Router, uri: &str) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let response = get_index(app, uri, None).await;
+        let headers = response.headers().clone();
+        let (status, body) = response_bytes(response).await;
+        (status, headers, body)
     }
 
-    #[test]
-    fn test_normalize_markdown_filename_handles_common_inputs() {
-        assert_eq!(
-            normalize_markdown_filename("post").ok(),
-            Some("post.md".to_string())
-        );
-        assert_eq!(
-            normalize_markdown_filename("post.md").ok(),
-            Some("post.md".to_string())
-        );
+    #[tokio::test]
+    async fn test_export_frontmatter_as_csv_and_json() {
+        let (app, temp_dir, _) = create_test_app().await;
+        for directory in ["posts", "posts/.private", "pages"] {
+            fs::create_dir_all(temp_dir.path().join(directory))
+                .await
+                .expect("Failed to create directory");
+        }
+        for (path, content) in [
+            (
+                "posts/a.md",
+                "---\ntitle: \"A, first\"\ndate: 2025-01-02\ndraft: false\ntags: [rust, web]\nauthor: Sam\n---\nOne two three\n",
+            ),
+            (
+                "posts/b.md",
+                "{\"draft\": true, \"categories\": \"notes\"}\nJust body\n",
+            ),
+            ("posts/scratch.tmp.md", "# Excluded\n"),
+            ("posts/.private/secret.md", "# Hidden\n"),
+            ("pages/about.md", "No frontmatter here\n"),
+            ("notes.txt", "Not markdown\n"),
+        ] {
+            fs::write(temp_dir.path().join(path), content)
+                .await
+                .expect("Failed to write file");
+        }
+
+        let (status, headers, body) =
+            get_body(&app, "/export/frontmatter?format=csv&exclude=*.tmp.md").await;
+        assert_eq!(status, StatusCode::OK);
         assert_eq!(
-            normalize_markdown_filename("post.markdown").ok(),
-            Some("post.md".to_string())
+            headers.get(header::CONTENT_TYPE),
+            Some(&HeaderValue::from_static("text/csv; charset=utf-8"))
         );
+        let text = String::from_utf8(body.clone()).expect("CSV should be UTF-8");
+        assert!(text.contains(r#","rust, web","#), "{text}");
+        let mut reader = csv::Reader::from_reader(body.as_slice());
+        let header_row = reader.headers().expect("CSV should have headers").clone();
         assert_eq!(
-            normalize_markdown_filename("  post-with-space-trim  ").ok(),
-            Some("post-with-space-trim.md".to_string())
+            header_row.iter().collect::<Vec<_>>(),
+            [
+                "path",
+                "title",
+                "date",
+                "draft",
+                "tags",
+                "categories",
+                "word_count",
+                "modified"
+            ]
         );
-        assert!(normalize_markdown_filename("").is_err());
-        assert!(normalize_markdown_filename("bad/name").is_err());
-        assert!(normalize_markdown_filename(".hidden").is_err());
-        assert!(normalize_markdown_filename("bad..name").is_err());
+        let records = reader
+            .records()
+            .collect::<Result<Vec<_>, _>>()
+            .expect("CSV should parse");
+        assert_eq!(records.len(), 3);
+        let paths: Vec<&str> = records.iter().filter_map(|record| record.get(0)).collect();
+        assert_eq!(paths, ["pages/about.md", "posts/a.md", "posts/b.md"]);
+        let a = &records[1];
+        assert_eq!(a.get(1), Some("A, first"));
+        assert_eq!(a.get(2), Some("2025-01-02"));
+        assert_eq!(a.get(3), Some("false"));
+        assert_eq!(a.get(4), Some("rust, web"));
+        assert_eq!(a.get(6), Some("3"));
+        assert!(a.get(7).is_some_and(|modified| modified.ends_with('Z')));
+        assert_eq!(records[2].get(5), Some("notes"));
+
+        let (_, _, body) = get_body(&app, "/export/frontmatter?format=csv&bom=true").await;
+        assert!(body.starts_with(b"\xEF\xBB\xBF"));
+
+        let (status, _, body) = get_body(&app, "/export/frontmatter?path=posts").await;
+        assert_eq!(status, StatusCode::OK);
+        let rows: Vec<serde_json::Value> =
+            serde_json::from_slice(&body).expect("JSON should parse");
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["path"], "posts/a.md");
+        assert_eq!(rows[0]["tags"], serde_json::json!(["rust", "web"]));
+        assert_eq!(rows[0]["extra"]["author"], "Sam");
+        assert_eq!(rows[1]["draft"], true);
+
+        let (status, _, _) = get_body(&app, "/export/frontmatter?format=xlsx").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _, _) = get_body(&app, "/export/frontmatter?path=..").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
     }
 
-    #[test]
-    fn test_normalize_image_filename_handles_valid_and_invalid_inputs() {
+    #[tokio::test]
+    async fn test_duplicates_api_groups_identical_markdown() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+        for (path, content) in [
+            ("about.md", "# About\n"),
+            ("posts/about-copy.md", "# About\n"),
+            ("posts/other.md", "# About us\n"),
+            ("notes.txt", "# About\n"),
+        ] {
+            fs::write(temp_dir.path().join(path), content)
+                .await
+                .expect("Failed to write fixture file");
+        }
+
+        let (status, body) = get_html(app, "/api/duplicates").await;
+        assert_eq!(status, StatusCode::OK);
+        let report: serde_json::Value =
+            serde_json::from_str(&body).expect("duplicates response should be JSON");
+        let groups = report["groups"]
+            .as_array()
+            .expect("groups should be an array");
+        assert_eq!(groups.len(), 1);
         assert_eq!(
-            normalize_image_filename("photo.png").ok(),
-            Some("photo.png".to_string())
+            groups[0]["paths"],
+            serde_json::json!(["about.md", "posts/about-copy.md"])
         );
+        assert_eq!(groups[0]["size"], 8);
+        assert_eq!(groups[0]["hash"], hex::encode(Sha256::digest(b"# About\n")));
+    }
+
+    #[tokio::test]
+    async fn test_format_endpoint_normalizes_body_and_keeps_frontmatter() {
+        let (app, _temp_dir, _) = create_test_app().await;
+        let frontmatter = "---\ntitle:  Untouched  \n---\n";
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/format")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({ "content": format!("{frontmatter}* a\n* b\n") }).to_string(),
+            ))
+            .expect("Failed to build format request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send format request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect format response")
+            .to_bytes();
+        let formatted: serde_json::Value =
+            serde_json::from_slice(&body).expect("format response should be JSON");
+        assert_eq!(formatted["changed"], true);
         assert_eq!(
-            normalize_image_filename("PHOTO.JPG").ok(),
-            Some("PHOTO.JPG".to_string())
+            formatted["content"],
+            format!("{frontmatter}\n- a\n- b\n").as_str()
         );
-        assert!(normalize_image_filename("photo").is_err());
-        assert!(normalize_image_filename("photo.txt").is_err());
-        assert!(normalize_image_filename("../photo.png").is_err());
-        assert!(normalize_image_filename(".hidden.png").is_err());
-        assert!(normalize_image_filename("bad name.png").is_err());
     }
 
-    #[test]
-    fn test_validate_image_bytes_accepts_valid_png_and_svg() {
-        let valid_png_bytes = create_valid_png_bytes();
-        assert!(validate_image_bytes("pixel.png", &valid_png_bytes).is_ok());
-        assert!(
-            validate_image_bytes(
-                "icon.svg",
-                br#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#
-            )
-            .is_ok()
+    #[tokio::test]
+    async fn test_quickswitch_api_ranks_titles_and_sees_new_files() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create posts directory");
+        fs::write(temp_dir.path().join("posts/2024-roadmap.md"), "# Plans")
+            .await
+            .expect("Failed to write roadmap post");
+        fs::write(
+            temp_dir.path().join("posts/misc.md"),
+            "---\ntitle: Roadmap notes\n---\n",
+        )
+        .await
+        .expect("Failed to write titled post");
+        fs::write(temp_dir.path().join("image.png"), create_valid_png_bytes())
+            .await
+            .expect("Failed to write image");
+
+        let (status, body) = get_html(app.clone(), "/api/quickswitch?q=roadmap").await;
+        assert_eq!(status, StatusCode::OK);
+        let response: serde_json::Value =
+            serde_json::from_str(&body).expect("quickswitch response should be JSON");
+        let paths: Vec<&str> = response["results"]
+            .as_array()
+            .expect("results should be an array")
+            .iter()
+            .filter_map(|result| result["path"].as_str())
+            .collect();
+        assert_eq!(paths, vec!["posts/misc.md", "posts/2024-roadmap.md"]);
+        assert_eq!(response["results"][0]["url"], "/edit?path=posts%2Fmisc.md");
+        assert_eq!(
+            response["results"][0]["title_positions"],
+            serde_json::json!([0, 1, 2, 3, 4, 5, 6])
         );
+
+        // Writes invalidate the cached index.
+        let response = post_form(
+            &app,
+            "/new-file",
+            format!(
+                "path=&filename=roadmap&csrf_token={}",
+                urlencoding::encode(&generate_csrf_token(&csrf_secret))
+            ),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+        let (_, body) = get_html(app, "/api/quickswitch?q=roadmap").await;
+        let response: serde_json::Value =
+            serde_json::from_str(&body).expect("quickswitch response should be JSON");
+        let results = response["results"]
+            .as_array()
+            .expect("results should be an array");
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().any(|result| result["path"] == "roadmap.md"));
     }
 
-    #[test]
-    fn test_validate_image_bytes_rejects_invalid_data() {
-        assert!(validate_image_bytes("pixel.png", b"not a png").is_err());
-        assert!(validate_image_bytes("icon.svg", b"<html></html>").is_err());
-        assert!(validate_image_bytes("icon.svg", b"").is_err());
+    #[tokio::test]
+    async fn test_quickswitch_page_highlights_matches_without_js() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(temp_dir.path().join("about.md"), "# About")
+            .await
+            .expect("Failed to write about page");
+
+        let (status, html) = get_html(app.clone(), "/quickswitch?q=abt").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("href=\"/edit?path=about.md\""));
+        assert!(html.contains("<mark>ab</mark>ou<mark>t</mark>.md"));
+
+        let (status, html) = get_html(app, "/quickswitch?q=zzz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("No files match"));
     }
 
-    #[test]
-    fn test_extract_frontmatter_detects_yaml_and_json_blocks() {
-        let yaml_content = "---\ndraft: true\n---\n# Post\n";
-        let json_content = "{\n  \"draft\": true\n}\n# Post\n";
-        let no_frontmatter = "# Post";
+    #[tokio::test]
+    async fn test_problems_dashboard_shows_happy_state_and_drill_down() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::write(
+            temp_dir.path().join("good.md"),
+            "---\ntitle: Good\n---\n# Good",
+        )
+        .await
+        .expect("Failed to write good file");
 
-        let yaml = extract_frontmatter(yaml_content).expect("yaml frontmatter should be found");
-        assert!(matches!(yaml.0, FrontmatterFormat::Yaml));
-        assert_eq!(yaml.1, "draft: true\n");
+        let (status, html) = get_html(app.clone(), "/problems").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("0 problems found"));
 
-        let json = extract_frontmatter(json_content).expect("json frontmatter should be found");
-        assert!(matches!(json.0, FrontmatterFormat::Json));
-        assert!(json.1.contains("\"draft\": true"));
+        fs::write(temp_dir.path().join("bad.md"), "---\ntitle: [\n---\n# Bad")
+            .await
+            .expect("Failed to write bad file");
+        let (_, html) = get_html(app.clone(), "/problems").await;
+        assert!(
+            html.contains("0 problems found"),
+            "results should stay cached until refreshed"
+        );
 
-        assert!(extract_frontmatter(no_frontmatter).is_none());
-    }
+        let (_, html) = get_html(app.clone(), "/problems?refresh=1").await;
+        assert!(html.contains("1 problem found"));
+        assert!(html.contains("/problems?check=invalid-frontmatter"));
 
-    #[test]
-    fn test_extract_json_frontmatter_requires_newline_or_eof_after_block() {
-        let valid_with_newline = "{\n  \"draft\": true\n}\n# post";
-        let valid_eof = "{\n  \"draft\": true\n}";
-        let invalid_trailing_text = "{\"draft\":true}#post";
+        let (status, html) = get_html(app.clone(), "/problems?check=invalid-frontmatter").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("bad.md"));
+        assert!(html.contains("/edit?path=bad.md"));
 
-        assert!(extract_json_frontmatter(valid_with_newline).is_some());
-        assert!(extract_json_frontmatter(valid_eof).is_some());
-        assert!(extract_json_frontmatter(invalid_trailing_text).is_none());
+        let (status, _) = get_html(app, "/problems?check=unknown").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
     }
 
-    #[test]
-    fn test_parse_bool_value_coercions() {
-        assert_eq!(parse_bool_value(&serde_json::json!(true)), Some(true));
-        assert_eq!(parse_bool_value(&serde_json::json!(false)), Some(false));
-        assert_eq!(parse_bool_value(&serde_json::json!("yes")), Some(true));
-        assert_eq!(parse_bool_value(&serde_json::json!("off")), Some(false));
-        assert_eq!(parse_bool_value(&serde_json::json!(1)), Some(true));
-        assert_eq!(parse_bool_value(&serde_json::json!(0)), Some(false));
-        assert_eq!(parse_bool_value(&serde_json::json!(2)), None);
-        assert_eq!(parse_bool_value(&serde_json::json!("maybe")), None);
-    }
+    #[tokio::test]
+    async fn test_problems_cache_is_invalidated_by_successful_writes() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::write(temp_dir.path().join("bad.md"), "---\ntitle: [\n---\n# Bad")
+            .await
+            .expect("Failed to write bad file");
 
-    #[test]
-    fn test_parse_string_value_coercions() {
-        assert_eq!(
-            parse_string_value(&serde_json::json!(" hello ")),
-            Some("hello".to_string())
-        );
-        assert_eq!(
-            parse_string_value(&serde_json::json!(42)),
-            Some("42".to_string())
-        );
-        assert_eq!(
-            parse_string_value(&serde_json::json!(true)),
-            Some("true".to_string())
-        );
-        assert_eq!(parse_string_value(&serde_json::json!("  ")), None);
-        assert_eq!(parse_string_value(&serde_json::json!({"k": "v"})), None);
-    }
+        let (_, html) = get_html(app.clone(), "/problems").await;
+        assert!(html.contains("1 problem found"));
 
-    #[test]
-    fn test_parse_string_list_value_coercions() {
-        assert_eq!(
-            parse_string_list_value(&serde_json::json!(["a", " b ", 3])),
-            vec!["a".to_string(), "b".to_string(), "3".to_string()]
-        );
-        assert_eq!(
-            parse_string_list_value(&serde_json::json!("rust, web,  docs ")),
-            vec!["rust".to_string(), "web".to_string(), "docs".to_string()]
-        );
-        assert_eq!(
-            parse_string_list_value(&serde_json::json!("single")),
-            vec!["single".to_string()]
+        let body = format!(
+            "path=bad.md&content={}&csrf_token={}",
+            urlencoding::encode("---\ntitle: Fixed\n---\n# Fixed"),
+            urlencoding::encode(&generate_csrf_token(&csrf_secret))
         );
         assert_eq!(
-            parse_string_list_value(&serde_json::json!(9)),
-            vec!["9".to_string()]
+            post_form(&app, "/save", body).await.status(),
+            StatusCode::OK
         );
-    }
 
-    #[test]
-    fn test_format_file_size_units() {
-        assert_eq!(format_file_size(0), "0 B");
-        assert_eq!(format_file_size(1023), "1023 B");
-        assert_eq!(format_file_size(1024), "1.0 KB");
-        assert_eq!(format_file_size(1_048_576), "1.0 MB");
+        let (_, html) = get_html(app, "/problems").await;
+        assert!(html.contains("0 problems found"));
     }
 
-    #[test]
-    fn test_get_parent_directory_path_formats_navigation_urls() {
-        assert_eq!(get_parent_directory_path("post.md"), "/");
-        assert_eq!(get_parent_directory_path("posts/post.md"), "/?path=posts");
-        assert_eq!(
-            get_parent_directory_path("posts/2026/post.md"),
-            "/?path=posts%2F2026"
-        );
-    }
+    #[tokio::test]
+    async fn test_problems_cache_survives_read_only_posts() {
+        let (app, temp_dir, _) = create_test_app().await;
+        let bad = temp_dir.path().join("bad.md");
+        fs::write(&bad, "---\ntitle: [\n---\n# Bad")
+            .await
+            .expect("Failed to write bad file");
+        let (_, html) = get_html(app.clone(), "/problems").await;
+        assert!(html.contains("1 problem found"));
 
-    #[test]
-    fn test_get_file_type_description_covers_known_and_unknown_types() {
-        assert_eq!(get_file_type_description("notes.txt"), "Text file");
-        assert_eq!(get_file_type_description("index.html"), "HTML document");
-        assert_eq!(get_file_type_description("data.json"), "JSON data");
-        assert_eq!(get_file_type_description("script.sh"), "Executable file");
-        assert_eq!(
-            get_file_type_description("archive.unknown"),
-            "Unknown file type"
-        );
+        // Fixed behind the server's back, so only a rebuild would notice.
+        fs::write(&bad, "---\ntitle: Fixed\n---\n# Fixed")
+            .await
+            .expect("Failed to fix bad file");
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/render")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        serde_json::json!({ "path": "bad.md", "content": "# Draft" }).to_string(),
+                    ))
+                    .expect("Failed to build render request"),
+            )
+            .await
+            .expect("Failed to send render request");
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let (_, html) = get_html(app, "/problems").await;
+        assert!(html.contains("1 problem found"));
     }
 
-    #[test]
-    fn test_build_breadcrumbs_generates_expected_paths() {
-        let breadcrumbs = build_breadcrumbs("posts/2026");
-        assert_eq!(breadcrumbs.len(), 2);
+    #[tokio::test]
+    async fn test_breadcrumbs_endpoint_returns_json_name_url_pairs() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::create_dir_all(temp_dir.path().join("posts/2026"))
+            .await
+            .expect("Failed to create nested directory");
 
-        let first = breadcrumbs
-            .first()
-            .expect("breadcrumbs should have a first element");
-        assert_eq!(first.name, "posts");
-        assert_eq!(first.url, "/?path=posts");
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/breadcrumbs?path=posts%2F2026")
+            .body(Body::empty())
+            .expect("Failed to build breadcrumbs request");
+        let response = app
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("Failed to send breadcrumbs request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect breadcrumbs response body")
+            .to_bytes();
+        let json: serde_json::Value =
+            serde_json::from_slice(&body).expect("Failed to decode breadcrumbs JSON");
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"name": "posts", "url": "/?path=posts"},
+                {"name": "2026", "url": "/?path=posts%2F2026"},
+            ])
+        );
 
-        let second = breadcrumbs
-            .get(1)
-            .expect("breadcrumbs should have a second element");
-        assert_eq!(second.name, "2026");
-        assert_eq!(second.url, "/?path=posts%2F2026");
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/api/breadcrumbs")
+            .body(Body::empty())
+            .expect("Failed to build root breadcrumbs request");
+        let response = app
+            .oneshot(request)
+            .await
+            .expect("Failed to send root breadcrumbs request");
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect root breadcrumbs response body")
+            .to_bytes();
+        assert_eq!(&body[..], b"[]");
     }
 
     #[test]
@@ -3203,30 +15463,39 @@ draft: true
                 name: "posts".to_string(),
                 is_directory: true,
                 path: "posts".to_string(),
+                modified: None,
             },
             DirectoryEntry {
                 name: "note.md".to_string(),
                 is_directory: false,
                 path: "posts/note.md".to_string(),
+                modified: None,
             },
             DirectoryEntry {
                 name: "photo.png".to_string(),
                 is_directory: false,
                 path: "posts/photo.png".to_string(),
+                modified: None,
             },
             DirectoryEntry {
                 name: "run.sh".to_string(),
                 is_directory: false,
                 path: "posts/run.sh".to_string(),
+                modified: None,
             },
             DirectoryEntry {
                 name: "notes.txt".to_string(),
                 is_directory: false,
                 path: "posts/notes.txt".to_string(),
+                modified: None,
             },
         ];
 
-        let views = build_directory_entry_views(&entries);
+        let views = build_directory_entry_views(
+            &entries,
+            &HashMap::new(),
+            chrono::Offset::fix(&chrono::Utc),
+        );
         assert_eq!(views.len(), 5);
 
         let directory_view = views.first().expect("expected directory view");
@@ -3496,4 +15765,503 @@ draft: true
         assert!(!is_safe_for_iframe("txtfile.exe"));
         assert!(!is_safe_for_iframe("not_html_file.doc"));
     }
+
+    #[tokio::test]
+    async fn test_router_nested_under_prefix_serves_edit_and_save() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        fs::write(temp_dir.path().join("post.md"), "# Before\n")
+            .await
+            .expect("Failed to write markdown file");
+        let mut config = WranglerConfig::new(temp_dir.path());
+        config.base_path = "/editor/".to_string();
+        let csrf_secret = config.csrf_secret.clone();
+        let app = Router::new()
+            .route("/", get(|| async { "host app" }))
+            .nest("/editor", router(config).await);
+
+        let (status, html) = get_html(app.clone(), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(html, "host app");
+
+        let (status, html) = get_html(app.clone(), "/editor").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"href="/editor/edit?path=post.md""#));
+        assert!(html.contains(r#"action="/editor/preferences""#));
+        assert!(html.contains(r#"data-base-path="/editor""#));
+        assert!(html.contains("/editor/static/styles.css"));
+
+        let (status, html) = get_html(app.clone(), "/editor/edit?path=post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"action="/editor/save""#));
+        assert!(html.contains(r#"href="/editor""#));
+
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let response = post_form(
+            &app,
+            "/editor/save",
+            format!("path=post.md&content=%23+After%0A&csrf_token={csrf_token}"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("post.md"))
+                .await
+                .expect("Failed to read file"),
+            "# After\n"
+        );
+
+        // Redirects point back under the prefix.
+        let (status, _) = response_text(
+            post_form(
+                &app,
+                "/editor/new-file",
+                format!("path=&filename=second&csrf_token={csrf_token}"),
+            )
+            .await,
+        )
+        .await;
+        assert!(status.is_redirection());
+        let request = Request::builder()
+            .method(Method::GET)
+            .uri("/editor?path=post.md")
+            .body(Body::empty())
+            .expect("failed to build request");
+        let response = app.oneshot(request).await.expect("failed to send request");
+        assert_eq!(
+            response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok()),
+            Some("/editor/edit?path=post.md")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_returns_sanitized_html_without_frontmatter() {
+        let (app, temp_dir, _) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+        fs::write(
+            temp_dir.path().join("posts/post.md"),
+            "---\ntitle: Secret Title\n---\n# Heading\n\n<script>alert(1)</script>\n\n[next](other.md)\n",
+        )
+        .await
+        .expect("Failed to write markdown file");
+
+        let (status, html) = get_html(app.clone(), "/render?path=posts/post.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<h1>Heading</h1>"));
+        assert!(!html.contains("Secret Title"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert!(html.contains(r#"href="/edit?path=posts%2Fother.md""#));
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/render")
+            .header("content-type", "application/json")
+            .body(Body::from(
+                serde_json::json!({
+                    "path": "posts/draft.md",
+                    "content": "{\"title\": \"Json\"}\n*unsaved* <img src=x onerror=alert(1)>",
+                })
+                .to_string(),
+            ))
+            .expect("failed to build render request");
+        let response = app
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("failed to send render request");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.starts_with("text/html"))
+        );
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Failed to collect response body")
+            .to_bytes();
+        let html = String::from_utf8_lossy(&bytes);
+        assert!(html.contains("<em>unsaved</em>"));
+        assert!(!html.contains("Json"));
+        assert!(!html.contains("<img"));
+
+        let (status, _) = get_html(app.clone(), "/render?path=notes.txt").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_html(app, "/render?path=../outside.md").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rename_moves_markdown_within_target_dir() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let content = "---\ntitle: Old\ndraft: true\n---\n# Old\n";
+        for dir in ["posts", "archive"] {
+            fs::create_dir(temp_dir.path().join(dir))
+                .await
+                .expect("Failed to create directory");
+        }
+        fs::write(temp_dir.path().join("posts/old.md"), content)
+            .await
+            .expect("Failed to write markdown file");
+        fs::write(temp_dir.path().join("archive/taken.md"), "# Taken\n")
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let rename = |path: &str, new_path: &str| {
+            post_form(
+                &app,
+                "/rename",
+                format!(
+                    "path={}&new_path={}&csrf_token={csrf_token}",
+                    urlencoding::encode(path),
+                    urlencoding::encode(new_path)
+                ),
+            )
+        };
+
+        for (new_path, expected) in [
+            ("../outside", StatusCode::BAD_REQUEST),
+            ("missing/new", StatusCode::BAD_REQUEST),
+            ("archive/bad name", StatusCode::BAD_REQUEST),
+            ("archive/taken", StatusCode::CONFLICT),
+        ] {
+            let response = rename("posts/old.md", new_path).await;
+            assert_eq!(response.status(), expected, "{new_path}");
+        }
+        let response = rename("posts/notes.txt", "archive/notes").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(temp_dir.path().join("posts/old.md").exists());
+
+        let response = rename("posts/old.md", " archive/Renamed.markdown ").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!temp_dir.path().join("posts/old.md").exists());
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("archive/Renamed.md"))
+                .await
+                .expect("Renamed file should exist"),
+            content
+        );
+
+        // Old links follow the file to its new name.
+        let response = get_index(&app, "/edit?path=posts/old.md", None).await;
+        assert_eq!(
+            response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok()),
+            Some("/edit?path=archive%2FRenamed.md")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rename_new_name_renames_in_place() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+        for name in ["posts/old.md", "posts/taken.md", "posts/cat.png"] {
+            fs::write(temp_dir.path().join(name), "# Post\n")
+                .await
+                .expect("Failed to write file");
+        }
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let rename = |path: &str, fields: &str| {
+            post_form(
+                &app,
+                "/rename",
+                format!(
+                    "path={}&{fields}&csrf_token={csrf_token}",
+                    urlencoding::encode(path)
+                ),
+            )
+        };
+
+        let (status, html) = get_html(app.clone(), "/edit?path=posts/old.md").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(
+            r#"name="new_name" type="text" required aria-label="New file name" value="old.md""#
+        ));
+
+        for (path, fields, expected) in [
+            (
+                "posts/old.md",
+                "new_name=..%2Fescaped",
+                StatusCode::BAD_REQUEST,
+            ),
+            ("posts/old.md", "new_name=..", StatusCode::BAD_REQUEST),
+            ("posts/old.md", "new_name=bad+name", StatusCode::BAD_REQUEST),
+            ("posts/old.md", "new_name=", StatusCode::BAD_REQUEST),
+            (
+                "posts/old.md",
+                "new_name=a&new_path=b",
+                StatusCode::BAD_REQUEST,
+            ),
+            ("posts/old.md", "", StatusCode::BAD_REQUEST),
+            ("posts/old.md", "new_name=taken", StatusCode::CONFLICT),
+            ("../outside.md", "new_name=inside", StatusCode::BAD_REQUEST),
+            (
+                "posts/cat.png",
+                "new_name=dog%2Fcat",
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let response = rename(path, fields).await;
+            assert_eq!(response.status(), expected, "{path} {fields}");
+        }
+        assert!(temp_dir.path().join("posts/old.md").exists());
+
+        let response = rename("posts/old.md", "new_name=fresh").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("Failed to read response body");
+        assert!(String::from_utf8_lossy(&body).contains(r#"href="/edit?path=posts%2Ffresh.md""#));
+        assert!(temp_dir.path().join("posts/fresh.md").exists());
+        assert!(!temp_dir.path().join("posts/old.md").exists());
+
+        // Assets keep their extension.
+        let response = rename("posts/cat.png", "new_name=dog").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(temp_dir.path().join("posts/dog.png").exists());
+        assert!(!temp_dir.path().join("posts/cat.png").exists());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_copies_post_as_fresh_draft() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+        let yaml =
+            "---\ntitle: Old\ndate: 2024-01-02\ndraft: false\nseries:\n  name: Rust\n---\n# Old\n";
+        let json = "{\"title\": \"Old\", \"date\": \"2024-01-02\", \"weight\": 3}\n# Old\n";
+        fs::write(temp_dir.path().join("posts/yaml.md"), yaml)
+            .await
+            .expect("Failed to write markdown file");
+        fs::write(temp_dir.path().join("posts/json.md"), json)
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let duplicate = |path: &str, filename: &str| {
+            post_form(
+                &app,
+                "/duplicate",
+                format!(
+                    "path={}&filename={}&csrf_token={csrf_token}",
+                    urlencoding::encode(path),
+                    urlencoding::encode(filename)
+                ),
+            )
+        };
+
+        let response = duplicate("posts/yaml.md", "new-yaml").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok()),
+            Some("/edit?path=posts%2Fnew-yaml.md")
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("posts/new-yaml.md"))
+                .await
+                .expect("Copy should exist"),
+            "---\ntitle: Old\ndraft: true\nseries:\n  name: Rust\n---\n# Old\n"
+        );
+
+        let response = duplicate("posts/json.md", "new-json.md").await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        let copy = fs::read_to_string(temp_dir.path().join("posts/new-json.md"))
+            .await
+            .expect("Copy should exist");
+        let (frontmatter, body) = copy.split_once("}\n").expect("JSON frontmatter");
+        let frontmatter: serde_json::Value =
+            serde_json::from_str(&format!("{frontmatter}}}")).expect("valid JSON frontmatter");
+        assert_eq!(
+            frontmatter,
+            serde_json::json!({ "title": "Old", "weight": 3, "draft": true })
+        );
+        assert_eq!(body, "# Old\n");
+
+        for (path, filename, expected) in [
+            ("posts/yaml.md", "json", StatusCode::CONFLICT),
+            ("posts/yaml.md", "bad name", StatusCode::BAD_REQUEST),
+            ("posts/yaml.md", "../escape", StatusCode::BAD_REQUEST),
+            ("posts/missing.md", "copy", StatusCode::BAD_REQUEST),
+            ("../outside.md", "copy", StatusCode::BAD_REQUEST),
+        ] {
+            let response = duplicate(path, filename).await;
+            assert_eq!(response.status(), expected, "{path} -> {filename}");
+        }
+        // The originals are untouched.
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("posts/yaml.md"))
+                .await
+                .expect("Original should exist"),
+            yaml
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("posts/json.md"))
+                .await
+                .expect("Original should exist"),
+            json
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_as_writes_content_to_a_new_file() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        fs::create_dir(temp_dir.path().join("posts"))
+            .await
+            .expect("Failed to create directory");
+        fs::write(temp_dir.path().join("posts/post.md"), "# Saved\n")
+            .await
+            .expect("Failed to write markdown file");
+        fs::write(temp_dir.path().join("posts/taken.md"), "# Taken\n")
+            .await
+            .expect("Failed to write markdown file");
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+        let save_as = |fields: String| {
+            post_form(
+                &app,
+                "/save-as",
+                format!("{fields}&csrf_token={csrf_token}"),
+            )
+        };
+
+        // The editor's form sends the source as `path`.
+        let response = save_as(
+            "path=posts%2Fpost.md&new_path=posts%2Fcopy&content=%23+Unsaved%0A".to_string(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|value| value.to_str().ok()),
+            Some("/edit?path=posts%2Fcopy.md")
+        );
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join("posts/copy.md"))
+                .await
+                .expect("Copy should exist"),
+            "# Unsaved\n"
+        );
+
+        for (new_path, expected) in [
+            ("posts/taken.md", StatusCode::CONFLICT),
+            ("posts/copy", StatusCode::CONFLICT),
+            ("../escape.md", StatusCode::BAD_REQUEST),
+            ("posts/bad name.md", StatusCode::BAD_REQUEST),
+            ("missing/post.md", StatusCode::BAD_REQUEST),
+            ("", StatusCode::BAD_REQUEST),
+        ] {
+            let response = save_as(format!(
+                "src=posts%2Fpost.md&new_path={}&content=overwritten",
+                urlencoding::encode(new_path)
+            ))
+            .await;
+            assert_eq!(response.status(), expected, "{new_path}");
+        }
+        // Neither the source nor an existing destination is touched.
+        for (path, content) in [
+            ("posts/post.md", "# Saved\n"),
+            ("posts/taken.md", "# Taken\n"),
+            ("posts/copy.md", "# Unsaved\n"),
+        ] {
+            assert_eq!(
+                fs::read_to_string(temp_dir.path().join(path))
+                    .await
+                    .expect("File should exist"),
+                content
+            );
+        }
+        assert!(
+            !temp_dir
+                .path()
+                .parent()
+                .expect("temp dir has a parent")
+                .join("escape.md")
+                .exists()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_paths_with_spaces_plus_and_percent_survive_edit_save_delete() {
+        let (app, temp_dir, csrf_secret) = create_test_app().await;
+        let names = ["a b.md", "a+b.md", "a%20b.md"];
+        for name in names {
+            fs::write(temp_dir.path().join(name), format!("# {name}\n"))
+                .await
+                .expect("Failed to write markdown file");
+        }
+        let csrf_token = urlencoding::encode(&generate_csrf_token(&csrf_secret)).into_owned();
+
+        let (status, listing) = get_html(app.clone(), "/").await;
+        assert_eq!(status, StatusCode::OK);
+        for name in names {
+            let edit_url = path_url("/edit", name);
+            assert!(listing.contains(&edit_url), "{edit_url}");
+
+            let (status, html) = get_html(app.clone(), &edit_url).await;
+            assert_eq!(status, StatusCode::OK, "{name}");
+            // The form carries the literal name; only the URL is encoded.
+            assert!(
+                html.contains(&format!(r#"name="path" value="{name}""#)),
+                "{name}"
+            );
+            assert!(html.contains(&format!("# {name}")), "{name}");
+
+            // Browsers submit the hidden field form-urlencoded, with spaces
+            // as `+`.
+            let form_path = urlencoding::encode(name).replace("%20", "+");
+            let status = post_form(
+                &app,
+                "/save",
+                format!("path={form_path}&content=saved&csrf_token={csrf_token}"),
+            )
+            .await
+            .status();
+            assert_eq!(status, StatusCode::OK, "{name}");
+        }
+        for name in names {
+            assert_eq!(
+                fs::read_to_string(temp_dir.path().join(name))
+                    .await
+                    .expect("Failed to read file"),
+                "saved",
+                "{name}"
+            );
+        }
+
+        for (index, name) in names.iter().enumerate() {
+            let response = post_form(
+                &app,
+                "/delete",
+                format!(
+                    "path={}&csrf_token={csrf_token}",
+                    urlencoding::encode(name).replace("%20", "+")
+                ),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK, "{name}");
+            for (other_index, other) in names.iter().enumerate() {
+                assert_eq!(
+                    temp_dir.path().join(other).exists(),
+                    other_index > index,
+                    "deleting {name} affected {other}"
+                );
+            }
+        }
+    }
 }
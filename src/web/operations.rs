@@ -0,0 +1,122 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Registry of bulk operations in progress.
+//!
+//! Bulk handlers (directory copy, batch moves, snapshot restore) register the
+//! path prefixes they touch for as long as they run. Single-file writers ask
+//! the registry first and refuse to write underneath a registered prefix, so
+//! the two cannot race. Registrations are dropped with their guard, or
+//! ignored once older than [`MAX_OPERATION_AGE`] in case a guard leaks.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Hard limit after which a registration no longer locks anything.
+pub(crate) const MAX_OPERATION_AGE: Duration = Duration::from_secs(10 * 60);
+
+struct ActiveOperation {
+    name: String,
+    /// Relative path prefixes; an empty prefix covers the whole tree.
+    prefixes: Vec<String>,
+    started: Instant,
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct OperationRegistry {
+    inner: Arc<Mutex<Registry>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    active: HashMap<u64, ActiveOperation>,
+}
+
+/// Keeps an operation registered until dropped.
+pub(crate) struct OperationGuard {
+    registry: OperationRegistry,
+    id: u64,
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = self.registry.inner.lock() {
+            registry.active.remove(&self.id);
+        }
+    }
+}
+
+fn covers(prefix: &str, path: &str) -> bool {
+    prefix.is_empty()
+        || path == prefix
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+impl OperationRegistry {
+    /// Registers `name` as touching everything under `prefixes`.
+    pub(crate) fn begin(&self, name: &str, prefixes: Vec<String>) -> OperationGuard {
+        let mut id = 0;
+        if let Ok(mut registry) = self.inner.lock() {
+            id = registry.next_id;
+            registry.next_id = registry.next_id.wrapping_add(1);
+            registry.active.insert(
+                id,
+                ActiveOperation {
+                    name: name.to_string(),
+                    prefixes,
+                    started: Instant::now(),
+                },
+            );
+        }
+        OperationGuard {
+            registry: self.clone(),
+            id,
+        }
+    }
+
+    /// Name of a live operation covering `path`, if any. Expired
+    /// registrations are dropped on the way.
+    pub(crate) fn conflict(&self, path: &str) -> Option<String> {
+        let mut registry = self.inner.lock().ok()?;
+        registry
+            .active
+            .retain(|_, operation| operation.started.elapsed() < MAX_OPERATION_AGE);
+        registry
+            .active
+            .values()
+            .find(|operation| operation.prefixes.iter().any(|prefix| covers(prefix, path)))
+            .map(|operation| operation.name.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_locks_prefixes_until_guard_drops() {
+        let registry = OperationRegistry::default();
+        let guard = registry.begin("directory copy", vec!["posts/2026".to_string()]);
+        assert_eq!(
+            registry.conflict("posts/2026/a.md").as_deref(),
+            Some("directory copy")
+        );
+        assert_eq!(
+            registry.conflict("posts/2026").as_deref(),
+            Some("directory copy")
+        );
+        assert_eq!(registry.conflict("posts/2026-old/a.md"), None);
+        assert_eq!(registry.conflict("about.md"), None);
+
+        let whole_tree = registry.begin("snapshot restore", vec![String::new()]);
+        assert!(registry.conflict("about.md").is_some());
+        drop(whole_tree);
+        drop(guard);
+        assert_eq!(registry.conflict("posts/2026/a.md"), None);
+    }
+}
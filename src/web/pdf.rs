@@ -0,0 +1,210 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! PDF inspection for the file preview page, using `lopdf`.
+//!
+//! Only documents up to [`MAX_PDF_INSPECT_BYTES`] are parsed, and no stream
+//! may inflate past [`MAX_PDF_DECOMPRESSED_BYTES`], so a hostile upload
+//! cannot exhaust memory. Page trees and document-info dictionaries kept in
+//! compressed object streams (the default for PDF 1.5+ writers) are read
+//! like any other object.
+
+use lopdf::{Document, LoadOptions, Object};
+use tracing::debug;
+
+/// Files larger than this are previewed without being inspected.
+pub(crate) const MAX_PDF_INSPECT_BYTES: u64 = 20 * 1024 * 1024;
+
+/// Largest size any one stream may decompress to while loading.
+const MAX_PDF_DECOMPRESSED_BYTES: usize = 32 * 1024 * 1024;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum PdfInspection {
+    /// Safe to hand to the browser's built-in viewer.
+    Viewable {
+        page_count: Option<usize>,
+        title: Option<String>,
+    },
+    /// Encrypted or malformed; offer a download instead of an iframe.
+    Unavailable(&'static str),
+}
+
+/// The document-info `/Title`, when present and not blank.
+fn title(document: &Document) -> Option<String> {
+    let info = match document.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => document.get_dictionary(*id).ok()?,
+        Object::Dictionary(info) => info,
+        _ => return None,
+    };
+    let title = match info.get(b"Title").ok()? {
+        Object::Reference(id) => document.get_object(*id).ok()?,
+        title => title,
+    };
+    let title = lopdf::decode_text_string(title).ok()?.trim().to_string();
+    (!title.is_empty()).then_some(title)
+}
+
+pub(crate) fn inspect_pdf(bytes: &[u8]) -> PdfInspection {
+    if !bytes.starts_with(b"%PDF-") {
+        return PdfInspection::Unavailable("File is not a valid PDF document");
+    }
+
+    let options = LoadOptions {
+        max_decompressed_size: Some(MAX_PDF_DECOMPRESSED_BYTES),
+        ..LoadOptions::default()
+    };
+    let document = match Document::load_mem_with_options(bytes, options) {
+        Ok(document) => document,
+        Err(err) => {
+            debug!("Failed to parse PDF: {}", err);
+            return PdfInspection::Unavailable("PDF document appears truncated or malformed");
+        }
+    };
+
+    // Without a usable password lopdf leaves the objects encrypted, so
+    // nothing in them can be trusted, and the browser could not show them.
+    if document.trailer.get(b"Encrypt").is_ok() {
+        return PdfInspection::Unavailable("PDF document is encrypted");
+    }
+
+    let page_count = document.get_pages().len();
+    PdfInspection::Viewable {
+        page_count: (page_count > 0).then_some(page_count),
+        title: title(&document),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Numbers `objects` from 1 and writes them with a classic xref table.
+    /// Object 1 is the catalog; `trailer` is added to the trailer dictionary.
+    fn assemble_pdf(objects: &[String], trailer: &str) -> Vec<u8> {
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend_from_slice(format!("{} 0 obj\n{object}\nendobj\n", index + 1).as_bytes());
+        }
+        let xref_offset = pdf.len();
+        pdf.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            pdf.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        pdf.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R{trailer} >>\nstartxref\n{xref_offset}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .as_bytes(),
+        );
+        pdf
+    }
+
+    /// A catalog, a page tree with `pages` blank pages, then object 3 as
+    /// `info` (the document-info dictionary when `with_info`).
+    fn pdf_with_info(pages: usize, info: &str, with_info: bool) -> Vec<u8> {
+        let mut objects = vec![
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {pages} >>",
+                (0..pages)
+                    .map(|page| format!("{} 0 R", page + 4))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+            info.to_string(),
+        ];
+        for _ in 0..pages {
+            objects.push("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] >>".to_string());
+        }
+        assemble_pdf(&objects, if with_info { " /Info 3 0 R" } else { "" })
+    }
+
+    /// Builds a small but well-formed PDF with `pages` blank pages and an
+    /// optional document-info title.
+    pub(crate) fn build_test_pdf(pages: usize, title: Option<&str>) -> Vec<u8> {
+        pdf_with_info(
+            pages,
+            &format!("<< /Title ({}) >>", title.unwrap_or_default()),
+            title.is_some(),
+        )
+    }
+
+    #[test]
+    fn test_inspect_pdf_reads_page_count_and_title() {
+        let pdf = build_test_pdf(3, Some("Annual \\(draft\\) report"));
+        assert_eq!(
+            inspect_pdf(&pdf),
+            PdfInspection::Viewable {
+                page_count: Some(3),
+                title: Some("Annual (draft) report".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_inspect_pdf_reads_object_streams() {
+        let mut document = Document::load_mem(&build_test_pdf(2, Some("Compressed report")))
+            .expect("fixture should load");
+        let mut pdf = Vec::new();
+        document
+            .save_modern(&mut pdf)
+            .expect("fixture should save with object streams");
+        // The page tree and title now only exist inside a compressed stream.
+        assert!(pdf.windows(7).any(|window| window == b"/ObjStm"));
+        assert!(!pdf.windows(10).any(|window| window == b"Compressed"));
+        assert!(!pdf.windows(6).any(|window| window == b"/Count"));
+
+        assert_eq!(
+            inspect_pdf(&pdf),
+            PdfInspection::Viewable {
+                page_count: Some(2),
+                title: Some("Compressed report".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_inspect_pdf_decodes_utf16_hex_title() {
+        let pdf = pdf_with_info(1, "<< /Title <FEFF00480069> >>", true);
+        assert_eq!(
+            inspect_pdf(&pdf),
+            PdfInspection::Viewable {
+                page_count: Some(1),
+                title: Some("Hi".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_inspect_pdf_rejects_encrypted_and_malformed_documents() {
+        assert!(matches!(
+            inspect_pdf(b"not a pdf"),
+            PdfInspection::Unavailable(_)
+        ));
+        assert!(matches!(
+            inspect_pdf(b"%PDF-1.4\n1 0 obj << /Type /Catalog"),
+            PdfInspection::Unavailable(reason) if reason.contains("truncated")
+        ));
+
+        // RC4 40-bit with a user password, so the empty password fails.
+        let hash = "00".repeat(32);
+        let encrypted = assemble_pdf(
+            &[
+                "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+                "<< /Type /Pages /Kids [] /Count 0 >>".to_string(),
+                format!("<< /Filter /Standard /V 1 /R 2 /O <{hash}> /U <{hash}> /P -4 >>"),
+            ],
+            " /Encrypt 3 0 R /ID [<00112233445566778899aabbccddeeff> <00112233445566778899aabbccddeeff>]",
+        );
+        assert!(matches!(
+            inspect_pdf(&encrypted),
+            PdfInspection::Unavailable(reason) if reason.contains("encrypted")
+        ));
+    }
+}
@@ -0,0 +1,363 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! External commands that render previews for custom file types.
+//!
+//! Each `--preview-handler EXT=COMMAND` maps a file extension to a command
+//! line. The command is split on whitespace and run directly, without a
+//! shell. If an argument is exactly `{input}` it is replaced with the path of
+//! a temporary copy of the file; otherwise the file is written to the
+//! command's stdin. Stdout must be SVG or HTML.
+//!
+//! Nothing runs unless `--enable-preview-commands` is given. Runs are bounded
+//! by [`PREVIEW_COMMAND_TIMEOUT`] and [`MAX_PREVIEW_OUTPUT_BYTES`], and
+//! successful output is cached until the source file's mtime changes.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use rand::RngExt;
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+
+use crate::cli::PreviewHandler;
+
+/// Placeholder argument replaced with the path of a temporary input file.
+pub(crate) const INPUT_PLACEHOLDER: &str = "{input}";
+
+/// How long a preview command may run before it is killed.
+pub(crate) const PREVIEW_COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest stdout accepted from a preview command.
+pub(crate) const MAX_PREVIEW_OUTPUT_BYTES: usize = 5 * 1024 * 1024;
+
+/// How much of a failed command's stderr is shown on the preview page.
+const STDERR_EXCERPT_BYTES: usize = 2048;
+
+/// Rendered previews kept in memory; the cache is emptied when full.
+const MAX_CACHED_PREVIEWS: usize = 128;
+
+/// Content Security Policy for rendered output: inline styles and data
+/// images only, so previews cannot load remote resources.
+const PREVIEW_CSP: &str = "default-src 'none'; style-src 'unsafe-inline'; img-src data:";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PreviewOutput {
+    /// A complete document for a sandboxed `srcdoc` iframe.
+    Rendered(String),
+    /// Why the command did not produce a usable preview, with its stderr.
+    Failed { reason: String, stderr: String },
+}
+
+struct CachedPreview {
+    modified: SystemTime,
+    document: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct PreviewCommands {
+    /// Lowercase extension (without the dot) to command line.
+    handlers: Arc<HashMap<String, Vec<String>>>,
+    pub(crate) timeout: Duration,
+    cache: Arc<Mutex<HashMap<String, CachedPreview>>>,
+}
+
+impl PreviewCommands {
+    pub(crate) fn new(handlers: &[PreviewHandler]) -> Self {
+        PreviewCommands {
+            handlers: Arc::new(
+                handlers
+                    .iter()
+                    .map(|handler| (handler.extension.clone(), handler.command.clone()))
+                    .collect(),
+            ),
+            timeout: PREVIEW_COMMAND_TIMEOUT,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn command_for(&self, path: &str) -> Option<&Vec<String>> {
+        let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        self.handlers.get(&extension)
+    }
+
+    pub(crate) fn handles(&self, path: &str) -> bool {
+        self.command_for(path).is_some()
+    }
+
+    /// Renders `full_path` with the handler for `path`'s extension, or `None`
+    /// when no handler is configured for it.
+    pub(crate) async fn render(&self, path: &str, full_path: &Path) -> Option<PreviewOutput> {
+        let command = self.command_for(path)?;
+        let modified = match fs::metadata(full_path)
+            .await
+            .and_then(|meta| meta.modified())
+        {
+            Ok(modified) => modified,
+            Err(err) => {
+                return Some(PreviewOutput::Failed {
+                    reason: format!("Failed to read file metadata: {err}"),
+                    stderr: String::new(),
+                });
+            }
+        };
+        if let Ok(cache) = self.cache.lock()
+            && let Some(cached) = cache.get(path)
+            && cached.modified == modified
+        {
+            return Some(PreviewOutput::Rendered(cached.document.clone()));
+        }
+
+        let output = match run_command(command, full_path, self.timeout).await {
+            Ok(stdout) => embed_output(&stdout),
+            Err(failure) => failure,
+        };
+        if let PreviewOutput::Rendered(document) = &output
+            && let Ok(mut cache) = self.cache.lock()
+        {
+            if cache.len() >= MAX_CACHED_PREVIEWS {
+                cache.clear();
+            }
+            cache.insert(
+                path.to_string(),
+                CachedPreview {
+                    modified,
+                    document: document.clone(),
+                },
+            );
+        }
+        Some(output)
+    }
+}
+
+fn failed(reason: impl Into<String>, stderr: &[u8]) -> PreviewOutput {
+    let excerpt = stderr.get(..STDERR_EXCERPT_BYTES).unwrap_or(stderr);
+    PreviewOutput::Failed {
+        reason: reason.into(),
+        stderr: String::from_utf8_lossy(excerpt).trim().to_string(),
+    }
+}
+
+/// Removes the temporary input file when the run finishes or is cancelled.
+struct TempInput(PathBuf);
+
+impl Drop for TempInput {
+    fn drop(&mut self) {
+        let path = self.0.clone();
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            handle.spawn(async move {
+                let _ = fs::remove_file(path).await;
+            });
+        }
+    }
+}
+
+async fn run_command(
+    command: &[String],
+    full_path: &Path,
+    timeout: Duration,
+) -> Result<Vec<u8>, PreviewOutput> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(failed("Preview command is empty", &[]));
+    };
+    let input = fs::read(full_path)
+        .await
+        .map_err(|err| failed(format!("Failed to read file: {err}"), &[]))?;
+
+    let mut temp_input = None;
+    let mut args = args.to_vec();
+    if args.iter().any(|arg| arg == INPUT_PLACEHOLDER) {
+        let extension = full_path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or("tmp");
+        let temp_path = std::env::temp_dir().join(format!(
+            "markdown-wrangler-preview-{}.{extension}",
+            hex::encode(rand::rng().random::<[u8; 8]>())
+        ));
+        fs::write(&temp_path, &input)
+            .await
+            .map_err(|err| failed(format!("Failed to write temporary input: {err}"), &[]))?;
+        for arg in &mut args {
+            if arg == INPUT_PLACEHOLDER {
+                *arg = temp_path.to_string_lossy().into_owned();
+            }
+        }
+        temp_input = Some(TempInput(temp_path));
+    }
+
+    let mut child = Command::new(program)
+        .args(&args)
+        .stdin(if temp_input.is_some() {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|err| failed(format!("Failed to start '{program}': {err}"), &[]))?;
+
+    let stdin = child.stdin.take();
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let run = async {
+        let write_input = async {
+            if let Some(mut stdin) = stdin {
+                // A command that exits without reading its input is not an
+                // error here; its exit status decides.
+                let _ = stdin.write_all(&input).await;
+            }
+        };
+        let read_stdout = async {
+            let mut buffer = Vec::new();
+            if let Some(stdout) = stdout {
+                stdout
+                    .take(MAX_PREVIEW_OUTPUT_BYTES as u64 + 1)
+                    .read_to_end(&mut buffer)
+                    .await?;
+            }
+            Ok::<_, std::io::Error>(buffer)
+        };
+        let read_stderr = async {
+            let mut buffer = Vec::new();
+            if let Some(stderr) = stderr {
+                stderr
+                    .take(STDERR_EXCERPT_BYTES as u64)
+                    .read_to_end(&mut buffer)
+                    .await?;
+            }
+            Ok::<_, std::io::Error>(buffer)
+        };
+        let ((), stdout, stderr) = tokio::join!(write_input, read_stdout, read_stderr);
+        Ok::<_, std::io::Error>((stdout?, stderr?))
+    };
+
+    let (stdout, stderr) = match tokio::time::timeout(timeout, run).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(err)) => return Err(failed(format!("Failed to read command output: {err}"), &[])),
+        Err(_) => {
+            return Err(failed(
+                format!(
+                    "Preview command timed out after {} seconds",
+                    timeout.as_secs_f32()
+                ),
+                &[],
+            ));
+        }
+    };
+    if stdout.len() > MAX_PREVIEW_OUTPUT_BYTES {
+        return Err(failed(
+            format!("Preview output is larger than {MAX_PREVIEW_OUTPUT_BYTES} bytes"),
+            &stderr,
+        ));
+    }
+    let status = match tokio::time::timeout(timeout, child.wait()).await {
+        Ok(Ok(status)) => status,
+        Ok(Err(err)) => {
+            return Err(failed(
+                format!("Failed to wait for command: {err}"),
+                &stderr,
+            ));
+        }
+        Err(_) => {
+            return Err(failed(
+                format!(
+                    "Preview command timed out after {} seconds",
+                    timeout.as_secs_f32()
+                ),
+                &stderr,
+            ));
+        }
+    };
+    drop(temp_input);
+    if !status.success() {
+        return Err(failed(
+            format!("Preview command failed ({status})"),
+            &stderr,
+        ));
+    }
+    Ok(stdout)
+}
+
+/// Checks command output and wraps it in a document for a sandboxed iframe.
+/// SVG must be well-formed XML with an `<svg>` root (no DTDs); HTML is
+/// passed through, relying on the iframe sandbox and CSP to neutralize it.
+fn embed_output(stdout: &[u8]) -> PreviewOutput {
+    let Ok(text) = std::str::from_utf8(stdout) else {
+        return failed("Preview output is not valid UTF-8", &[]);
+    };
+    let trimmed = text.trim_start();
+    if trimmed.is_empty() {
+        return failed("Preview command produced no output", &[]);
+    }
+    let looks_like_svg = trimmed.starts_with("<?xml") || trimmed.starts_with("<svg");
+    if looks_like_svg {
+        match roxmltree::Document::parse(trimmed) {
+            Ok(document) if document.root_element().tag_name().name() == "svg" => {}
+            Ok(_) => return failed("Preview output has no <svg> root element", &[]),
+            Err(err) => return failed(format!("Preview output is not valid SVG: {err}"), &[]),
+        }
+    }
+    PreviewOutput::Rendered(format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"Content-Security-Policy\" content=\"{PREVIEW_CSP}\">\
+         </head><body>{trimmed}</body></html>"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embed_output_checks_svg() {
+        let PreviewOutput::Rendered(document) =
+            embed_output(b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>\n")
+        else {
+            panic!("valid SVG should render");
+        };
+        assert!(document.contains("Content-Security-Policy"));
+        assert!(document.contains("<svg"));
+
+        assert!(matches!(
+            embed_output(b"<svg><g></svg>"),
+            PreviewOutput::Failed { .. }
+        ));
+        assert!(matches!(
+            embed_output(b"<?xml version=\"1.0\"?><html/>"),
+            PreviewOutput::Failed { .. }
+        ));
+        assert!(matches!(embed_output(b"  "), PreviewOutput::Failed { .. }));
+        assert!(matches!(
+            embed_output(b"<p>diagram</p>"),
+            PreviewOutput::Rendered(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_passes_input_file() {
+        let temp_dir = tempfile::TempDir::new().expect("failed to create temporary directory");
+        let source = temp_dir.path().join("graph.dot");
+        fs::write(&source, "digraph {}")
+            .await
+            .expect("failed to write source file");
+
+        let command = vec!["cat".to_string(), INPUT_PLACEHOLDER.to_string()];
+        let stdout = run_command(&command, &source, PREVIEW_COMMAND_TIMEOUT)
+            .await
+            .expect("cat should succeed");
+        assert_eq!(stdout, b"digraph {}");
+
+        let stdout = run_command(&["cat".to_string()], &source, PREVIEW_COMMAND_TIMEOUT)
+            .await
+            .expect("cat should read stdin");
+        assert_eq!(stdout, b"digraph {}");
+    }
+}
@@ -0,0 +1,247 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Fuzzy file matching for the quick switcher.
+//!
+//! Matching follows fzf's simple (v1) algorithm: a forward scan finds the
+//! first place the query completes as a subsequence, and a backward scan from
+//! there finds the tightest start. Queries only look at the in-memory
+//! [`ContentIndex`], never the filesystem.
+
+use super::checks::ContentIndex;
+
+/// Maximum number of results returned for one query.
+pub(crate) const MAX_RESULTS: usize = 20;
+
+const SCORE_MATCH: i64 = 16;
+const SCORE_GAP_START: i64 = -3;
+const SCORE_GAP_EXTENSION: i64 = -1;
+/// Consecutive matches keep at least this bonus, or the bonus of the
+/// boundary their run started on if that is larger.
+const BONUS_CONSECUTIVE: i64 = 4;
+const BONUS_BOUNDARY: i64 = 8;
+/// Matches right after `/` start a new path segment, the strongest signal.
+const BONUS_SEGMENT_START: i64 = 10;
+/// Added to title matches so they outrank equally good path matches.
+const BONUS_TITLE: i64 = 24;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct FuzzyMatch {
+    pub(crate) score: i64,
+    /// Character (not byte) offsets of the matched characters.
+    pub(crate) positions: Vec<usize>,
+}
+
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn boundary_bonus(previous: Option<char>, current: char) -> i64 {
+    match previous {
+        None | Some('/') => BONUS_SEGMENT_START,
+        Some('-' | '_' | ' ' | '.') => BONUS_BOUNDARY,
+        Some(previous) if previous.is_lowercase() && current.is_uppercase() => BONUS_BOUNDARY,
+        _ => 0,
+    }
+}
+
+/// Scores `candidate` against `query` (case-insensitive, whitespace in the
+/// query ignored). Returns `None` when the query is not a subsequence.
+pub(crate) fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(fold)
+        .collect();
+    if pattern.is_empty() {
+        return None;
+    }
+    let text: Vec<char> = candidate.chars().collect();
+
+    // Forward: where does the first complete occurrence end?
+    let mut matched = 0;
+    let mut end = None;
+    for (index, c) in text.iter().enumerate() {
+        if pattern.get(matched) == Some(&fold(*c)) {
+            matched += 1;
+            if matched == pattern.len() {
+                end = Some(index);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    // Backward: the tightest occurrence ending there.
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut remaining = pattern.iter().rev().peekable();
+    for index in (0..=end).rev() {
+        let Some(wanted) = remaining.peek() else {
+            break;
+        };
+        if text.get(index).map(|c| fold(*c)) == Some(**wanted) {
+            positions.push(index);
+            remaining.next();
+        }
+    }
+    positions.reverse();
+
+    let mut score = 0;
+    let mut previous_position: Option<usize> = None;
+    let mut run_bonus = 0;
+    for &position in &positions {
+        let current = text.get(position).copied().unwrap_or_default();
+        let previous_char = position
+            .checked_sub(1)
+            .and_then(|index| text.get(index).copied());
+        let mut bonus = boundary_bonus(previous_char, current);
+        match previous_position {
+            Some(previous) if position == previous + 1 => {
+                bonus = bonus.max(run_bonus).max(BONUS_CONSECUTIVE);
+            }
+            Some(previous) => {
+                let gap = (position - previous - 1) as i64;
+                score += SCORE_GAP_START + SCORE_GAP_EXTENSION * (gap - 1);
+                run_bonus = bonus;
+            }
+            None => run_bonus = bonus,
+        }
+        score += SCORE_MATCH + bonus;
+        previous_position = Some(position);
+    }
+
+    Some(FuzzyMatch { score, positions })
+}
+
+/// One quick-switcher hit.
+#[derive(Debug)]
+pub(crate) struct QuickSwitchHit {
+    pub(crate) path: String,
+    pub(crate) title: Option<String>,
+    pub(crate) score: i64,
+    pub(crate) path_positions: Vec<usize>,
+    pub(crate) title_positions: Vec<usize>,
+}
+
+/// Ranks every markdown document in `index` against `query`, best first.
+pub(crate) fn search(index: &ContentIndex, query: &str) -> Vec<QuickSwitchHit> {
+    let mut hits: Vec<QuickSwitchHit> = index
+        .documents
+        .iter()
+        .filter_map(|document| {
+            let path_match = fuzzy_match(query, &document.path);
+            let title_match = document
+                .title
+                .as_deref()
+                .and_then(|title| fuzzy_match(query, title));
+            let score = path_match
+                .as_ref()
+                .map(|m| m.score)
+                .max(title_match.as_ref().map(|m| m.score + BONUS_TITLE))?;
+            Some(QuickSwitchHit {
+                path: document.path.clone(),
+                title: document.title.clone(),
+                score,
+                path_positions: path_match.map(|m| m.positions).unwrap_or_default(),
+                title_positions: title_match.map(|m| m.positions).unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    hits.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.path.len().cmp(&b.path.len()))
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    hits.truncate(MAX_RESULTS);
+    hits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::web::checks::IndexedDocument;
+
+    fn document(path: &str, title: Option<&str>) -> IndexedDocument {
+        IndexedDocument {
+            path: path.to_string(),
+            content: String::new(),
+            title: title.map(ToString::to_string),
+        }
+    }
+
+    fn ranked_paths(index: &ContentIndex, query: &str) -> Vec<String> {
+        search(index, query)
+            .into_iter()
+            .map(|hit| hit.path)
+            .collect()
+    }
+
+    #[test]
+    fn test_fuzzy_match_requires_subsequence_and_reports_positions() {
+        assert_eq!(fuzzy_match("xyz", "posts/hello.md"), None);
+        assert_eq!(fuzzy_match("   ", "posts/hello.md"), None);
+
+        let found = fuzzy_match("PH", "posts/hello.md").expect("subsequence should match");
+        assert_eq!(found.positions, vec![0, 6]);
+    }
+
+    #[test]
+    fn test_fuzzy_match_prefers_tight_and_boundary_matches() {
+        let tight = fuzzy_match("hello", "posts/hello.md").expect("should match");
+        let scattered = fuzzy_match("hello", "posts/h-e-l-l-o.md").expect("should match");
+        assert!(tight.score > scattered.score);
+
+        let segment = fuzzy_match("abo", "posts/about.md").expect("should match");
+        let middle = fuzzy_match("abo", "posts/sabotage.md").expect("should match");
+        assert!(segment.score > middle.score);
+
+        // The backward pass shrinks to the tightest occurrence.
+        let shrunk = fuzzy_match("ab", "a/xxab.md").expect("should match");
+        assert_eq!(shrunk.positions, vec![4, 5]);
+    }
+
+    #[test]
+    fn test_search_ranks_titles_and_segment_starts_first() {
+        let index = ContentIndex {
+            documents: vec![
+                document("posts/2024-roadmap.md", None),
+                document("posts/misc.md", Some("Roadmap for next year")),
+                document("drafts/broadmapper.md", None),
+                document("unrelated.md", Some("Nothing here")),
+            ],
+            files: Vec::new(),
+            max_frontmatter_bytes: 65_536,
+            truncated: false,
+        };
+
+        assert_eq!(
+            ranked_paths(&index, "roadmap"),
+            vec![
+                "posts/misc.md",
+                "posts/2024-roadmap.md",
+                "drafts/broadmapper.md"
+            ]
+        );
+
+        let hits = search(&index, "roadmap");
+        let title_hit = hits.first().expect("title hit should rank first");
+        assert_eq!(title_hit.title_positions, vec![0, 1, 2, 3, 4, 5, 6]);
+        assert!(title_hit.path_positions.is_empty());
+    }
+
+    #[test]
+    fn test_search_caps_results() {
+        let index = ContentIndex {
+            documents: (0..50)
+                .map(|number| document(&format!("notes/note-{number}.md"), None))
+                .collect(),
+            files: Vec::new(),
+            max_frontmatter_bytes: 65_536,
+            truncated: false,
+        };
+        assert_eq!(search(&index, "note").len(), MAX_RESULTS);
+    }
+}
@@ -0,0 +1,176 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Remembers where moved files went and which files were deleted, so stale
+//! links can be redirected or explained instead of failing with a bare
+//! "Path does not exist".
+//!
+//! The map is persisted as JSON under the hidden `.wrangler` directory and
+//! loaded on first use. It holds at most [`MAX_REDIRECTS`] entries; the
+//! oldest are dropped first.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// Redirect map location, relative to the content root. Its dot-prefixed
+/// folder keeps it out of reach of the file routes.
+pub const REDIRECTS_FILE: &str = ".wrangler/redirects.json";
+
+/// Oldest entries beyond this many are dropped.
+pub const MAX_REDIRECTS: usize = 1000;
+
+/// What happened to a path that no longer exists.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PathFate {
+    Moved { to: String },
+    Deleted,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct RedirectEntry {
+    from: String,
+    #[serde(flatten)]
+    fate: PathFate,
+}
+
+#[derive(Clone)]
+pub struct PathRedirects {
+    path: PathBuf,
+    /// Oldest first; `None` until loaded from disk.
+    entries: Arc<Mutex<Option<Vec<RedirectEntry>>>>,
+}
+
+impl PathRedirects {
+    pub fn new(path: PathBuf) -> Self {
+        PathRedirects {
+            path,
+            entries: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn load(&self) -> Vec<RedirectEntry> {
+        match fs::read(&self.path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                warn!(
+                    "Ignoring unreadable redirect map {}: {}",
+                    self.path.display(),
+                    err
+                );
+                Vec::new()
+            }),
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    warn!(
+                        "Failed to read redirect map {}: {}",
+                        self.path.display(),
+                        err
+                    );
+                }
+                Vec::new()
+            }
+        }
+    }
+
+    async fn save(&self, entries: &[RedirectEntry]) -> Result<(), std::io::Error> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(entries)?).await?;
+        fs::rename(&temp_path, &self.path).await
+    }
+
+    /// Records that `from` is gone. Earlier entries pointing at `from` are
+    /// updated so chains of moves resolve in one hop, and any entry for a
+    /// path that exists again is dropped.
+    pub async fn record(&self, from: &str, fate: PathFate) {
+        let mut guard = self.entries.lock().await;
+        let entries = match guard.as_mut() {
+            Some(entries) => entries,
+            None => guard.insert(self.load().await),
+        };
+
+        let revived = match &fate {
+            PathFate::Moved { to } => Some(to.as_str()),
+            PathFate::Deleted => None,
+        };
+        entries.retain(|entry| entry.from != from && Some(entry.from.as_str()) != revived);
+        for entry in entries.iter_mut() {
+            if matches!(&entry.fate, PathFate::Moved { to } if to == from) {
+                entry.fate = fate.clone();
+            }
+        }
+        entries.push(RedirectEntry {
+            from: from.to_string(),
+            fate,
+        });
+        let overflow = entries.len().saturating_sub(MAX_REDIRECTS);
+        entries.drain(..overflow);
+
+        if let Err(err) = self.save(entries).await {
+            warn!(
+                "Failed to save redirect map {}: {}",
+                self.path.display(),
+                err
+            );
+        }
+    }
+
+    pub async fn lookup(&self, from: &str) -> Option<PathFate> {
+        let mut guard = self.entries.lock().await;
+        let entries = match guard.as_mut() {
+            Some(entries) => entries,
+            None => guard.insert(self.load().await),
+        };
+        entries
+            .iter()
+            .find(|entry| entry.from == from)
+            .map(|entry| entry.fate.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_redirects_collapse_chains_and_persist() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let path = temp_dir.path().join(REDIRECTS_FILE);
+        let redirects = PathRedirects::new(path.clone());
+
+        redirects
+            .record("a.md", PathFate::Moved { to: "b.md".into() })
+            .await;
+        redirects
+            .record("b.md", PathFate::Moved { to: "c.md".into() })
+            .await;
+        assert_eq!(
+            redirects.lookup("a.md").await,
+            Some(PathFate::Moved { to: "c.md".into() })
+        );
+
+        redirects.record("c.md", PathFate::Deleted).await;
+        // Moving something back onto an old path forgets its redirect.
+        redirects
+            .record("x.md", PathFate::Moved { to: "a.md".into() })
+            .await;
+
+        let reloaded = PathRedirects::new(path);
+        assert_eq!(reloaded.lookup("a.md").await, None);
+        assert_eq!(reloaded.lookup("b.md").await, Some(PathFate::Deleted));
+        assert_eq!(reloaded.lookup("c.md").await, Some(PathFate::Deleted));
+        assert_eq!(
+            reloaded.lookup("x.md").await,
+            Some(PathFate::Moved { to: "a.md".into() })
+        );
+    }
+}
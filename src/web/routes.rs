@@ -0,0 +1,328 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! The route table shared by `create_router` and `GET /api/routes`.
+//!
+//! Every route is listed once in [`ROUTES`]. The router registers each entry
+//! through an exhaustive match on [`Route`], and the manifest handler
+//! serializes the same table, so front-end code can look paths up by name
+//! instead of hardcoding them.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// Bumped when a route is renamed or removed, or its parameters change.
+pub(crate) const ROUTES_MANIFEST_VERSION: u32 = 1;
+
+/// Form field (and JSON key) carrying the CSRF token on cookie-authenticated
+/// writes. There is no CSRF header.
+pub(crate) const CSRF_FIELD: &str = "csrf_token";
+
+/// Header API clients authenticate with (`Bearer <token>`).
+pub(crate) const API_AUTH_HEADER: &str = "Authorization";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Route {
+    Index,
+    Preferences,
+    NewFile,
+    UploadImage,
+    Upload,
+    ImportUrl,
+    PasteImage,
+    CopyDirectory,
+    Edit,
+    Save,
+    SaveAs,
+    SetDraft,
+    Delete,
+    Rename,
+    Duplicate,
+    DeleteDirectory,
+    DeleteEmptyDirectory,
+    ImagePreview,
+    Image,
+    FilePreview,
+    File,
+    Raw,
+    FileInfo,
+    List,
+    FileStats,
+    Format,
+    Links,
+    Frontmatter,
+    FileContent,
+    Render,
+    Problems,
+    Snapshot,
+    Snapshots,
+    RestoreSnapshot,
+    QuickSwitch,
+    QuickSwitchApi,
+    Breadcrumbs,
+    Neighbors,
+    CsrfRefresh,
+    MoveMany,
+    Routes,
+    FrontmatterPatch,
+    PublishByTag,
+    Duplicates,
+    Config,
+    Status,
+    Ping,
+    Maintenance,
+    Sitemap,
+    RobotsTxt,
+    Trash,
+    RestoreTrash,
+    EmptyTrash,
+    ExportFrontmatter,
+    Mount,
+    Static,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct RouteSpec {
+    pub(crate) route: Route,
+    /// Stable name used as the manifest key.
+    pub(crate) name: &'static str,
+    /// Path template in axum syntax (`{param}`, `{*rest}`).
+    pub(crate) path: &'static str,
+    pub(crate) methods: &'static [&'static str],
+    /// Its POST/PUT handlers change no content, so it stays open during
+    /// maintenance.
+    pub(crate) read_only: bool,
+}
+
+const GET: &[&str] = &["GET"];
+const POST: &[&str] = &["POST"];
+const GET_POST: &[&str] = &["GET", "POST"];
+
+const fn spec(
+    route: Route,
+    name: &'static str,
+    path: &'static str,
+    methods: &'static [&'static str],
+) -> RouteSpec {
+    RouteSpec {
+        route,
+        name,
+        path,
+        methods,
+        read_only: false,
+    }
+}
+
+/// Marks `spec` as [`RouteSpec::read_only`].
+const fn read_only(spec: RouteSpec) -> RouteSpec {
+    RouteSpec {
+        read_only: true,
+        ..spec
+    }
+}
+
+pub(crate) const ROUTES: &[RouteSpec] = &[
+    spec(Route::Index, "index", "/", GET),
+    read_only(spec(
+        Route::Preferences,
+        "preferences",
+        "/preferences",
+        POST,
+    )),
+    spec(Route::NewFile, "new_file", "/new-file", GET_POST),
+    spec(
+        Route::UploadImage,
+        "upload_image",
+        "/upload-image",
+        GET_POST,
+    ),
+    spec(Route::Upload, "upload", "/upload", POST),
+    spec(Route::ImportUrl, "import_url", "/import-url", POST),
+    spec(Route::PasteImage, "paste_image", "/api/upload-image", POST),
+    spec(
+        Route::CopyDirectory,
+        "copy_directory",
+        "/copy-directory",
+        POST,
+    ),
+    spec(Route::Edit, "edit", "/edit", GET),
+    spec(Route::Save, "save", "/save", POST),
+    spec(Route::SaveAs, "save_as", "/save-as", POST),
+    spec(Route::SetDraft, "set_draft", "/set-draft", POST),
+    spec(Route::Delete, "delete", "/delete", POST),
+    spec(Route::Rename, "rename", "/rename", POST),
+    spec(Route::Duplicate, "duplicate", "/duplicate", POST),
+    spec(
+        Route::DeleteDirectory,
+        "delete_directory",
+        "/delete-dir",
+        GET_POST,
+    ),
+    spec(
+        Route::DeleteEmptyDirectory,
+        "delete_empty_directory",
+        "/delete-directory",
+        POST,
+    ),
+    spec(Route::ImagePreview, "image_preview", "/preview", GET),
+    spec(Route::Image, "image", "/image", GET),
+    spec(Route::FilePreview, "file_preview", "/file-preview", GET),
+    spec(Route::File, "file", "/file", GET),
+    spec(Route::Raw, "raw", "/raw", &["GET", "PUT"]),
+    spec(Route::FileInfo, "file_info", "/file-info", GET),
+    spec(Route::List, "list", "/api/list", GET),
+    spec(Route::FileStats, "file_stats", "/api/file-stats", GET),
+    read_only(spec(Route::Format, "format", "/api/format", POST)),
+    spec(Route::Links, "links", "/api/links", GET),
+    spec(Route::Frontmatter, "frontmatter", "/frontmatter", GET),
+    spec(Route::FileContent, "file_content", "/file-content", GET),
+    read_only(spec(Route::Render, "render", "/render", GET_POST)),
+    spec(Route::Problems, "problems", "/problems", GET),
+    spec(Route::Snapshot, "snapshot", "/snapshot", POST),
+    spec(Route::Snapshots, "snapshots", "/snapshots", GET),
+    spec(
+        Route::RestoreSnapshot,
+        "restore_snapshot",
+        "/snapshots/restore",
+        GET_POST,
+    ),
+    spec(Route::QuickSwitch, "quickswitch", "/quickswitch", GET),
+    spec(
+        Route::QuickSwitchApi,
+        "quickswitch_api",
+        "/api/quickswitch",
+        GET,
+    ),
+    spec(Route::Breadcrumbs, "breadcrumbs", "/api/breadcrumbs", GET),
+    spec(Route::Neighbors, "neighbors", "/api/neighbors", GET),
+    spec(Route::CsrfRefresh, "csrf_refresh", "/api/csrf/refresh", GET),
+    spec(Route::MoveMany, "move_many", "/api/move-many", POST),
+    spec(Route::Routes, "routes", "/api/routes", GET),
+    spec(
+        Route::FrontmatterPatch,
+        "frontmatter_patch",
+        "/api/frontmatter-patch",
+        POST,
+    ),
+    spec(
+        Route::PublishByTag,
+        "publish_by_tag",
+        "/api/publish-by-tag",
+        POST,
+    ),
+    spec(Route::Duplicates, "duplicates", "/api/duplicates", GET),
+    spec(Route::Config, "config", "/api/config", GET),
+    spec(Route::Status, "status", "/api/status", GET),
+    spec(Route::Ping, "ping", "/api/ping", GET),
+    read_only(spec(
+        Route::Maintenance,
+        "maintenance",
+        "/admin/maintenance",
+        POST,
+    )),
+    spec(Route::Sitemap, "sitemap", "/sitemap.xml", GET),
+    spec(Route::RobotsTxt, "robots_txt", "/robots.txt", GET),
+    spec(Route::Trash, "trash", "/trash", GET),
+    spec(Route::RestoreTrash, "restore_trash", "/restore", POST),
+    spec(Route::EmptyTrash, "empty_trash", "/trash/empty", POST),
+    spec(
+        Route::ExportFrontmatter,
+        "export_frontmatter",
+        "/export/frontmatter",
+        GET,
+    ),
+    spec(Route::Mount, "mount", "/mounts/{name}/{*path}", GET),
+    spec(Route::Static, "static", "/static/{*path}", GET),
+];
+
+impl Route {
+    /// The route registered at `path`, which must match the table exactly,
+    /// so templated paths never match.
+    pub(crate) fn at(path: &str) -> Option<Route> {
+        ROUTES
+            .iter()
+            .find(|spec| spec.path == path)
+            .map(|spec| spec.route)
+    }
+}
+
+/// Whether `path` is a [`RouteSpec::read_only`] route.
+pub(crate) fn is_read_only(path: &str) -> bool {
+    ROUTES
+        .iter()
+        .any(|spec| spec.read_only && spec.path == path)
+}
+
+#[derive(Serialize)]
+pub(crate) struct RouteManifest {
+    version: u32,
+    csrf_field: &'static str,
+    api_auth_header: &'static str,
+    routes: BTreeMap<&'static str, ManifestEntry>,
+}
+
+#[derive(Serialize)]
+struct ManifestEntry {
+    path: &'static str,
+    methods: &'static [&'static str],
+}
+
+pub(crate) fn manifest() -> RouteManifest {
+    RouteManifest {
+        version: ROUTES_MANIFEST_VERSION,
+        csrf_field: CSRF_FIELD,
+        api_auth_header: API_AUTH_HEADER,
+        routes: ROUTES
+            .iter()
+            .map(|spec| {
+                (
+                    spec.name,
+                    ManifestEntry {
+                        path: spec.path,
+                        methods: spec.methods,
+                    },
+                )
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_route_names_and_paths_are_unique() {
+        for (index, spec) in ROUTES.iter().enumerate() {
+            for other in ROUTES.iter().skip(index + 1) {
+                assert_ne!(spec.name, other.name);
+                assert_ne!(spec.path, other.path);
+                assert_ne!(spec.route, other.route);
+            }
+        }
+        assert_eq!(manifest().routes.len(), ROUTES.len());
+    }
+
+    #[test]
+    fn test_read_only_routes() {
+        assert!(is_read_only("/render"));
+        assert!(is_read_only("/admin/maintenance"));
+        assert!(!is_read_only("/save"));
+        assert!(!is_read_only("/nowhere"));
+        // Marking a GET-only route would be meaningless.
+        for spec in ROUTES.iter().filter(|spec| spec.read_only) {
+            assert!(spec.methods.iter().any(|method| *method != "GET"));
+        }
+    }
+
+    #[test]
+    fn test_route_at_matches_exact_paths() {
+        assert_eq!(Route::at("/edit"), Some(Route::Edit));
+        assert_eq!(Route::at("/api/ping"), Some(Route::Ping));
+        assert_eq!(Route::at("/edit/"), None);
+        assert_eq!(Route::at("/static/{*path}"), Some(Route::Static));
+        assert_eq!(Route::at("/static/app.js"), None);
+    }
+}
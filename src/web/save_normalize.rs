@@ -0,0 +1,149 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! `--save-normalize` and `--save-comparison` for `/save`.
+//!
+//! Browsers submit textarea content with CRLF line endings and editors
+//! disagree on the final newline, so a save can differ from the file only in
+//! bytes nobody sees. With normalizations on, what is written is always the
+//! normalized content. The comparison mode decides whether a save that only
+//! differs by those bytes writes anyway (`exact`) or counts as unchanged
+//! (`normalized`).
+
+use std::borrow::Cow;
+
+use crate::cli::{SaveComparison, SaveNormalizations};
+
+/// `content` with `normalizations` applied.
+pub(crate) fn normalize(content: &str, normalizations: SaveNormalizations) -> Cow<'_, str> {
+    let mut content = Cow::Borrowed(content);
+    if normalizations.line_endings && content.contains('\r') {
+        content = Cow::Owned(content.replace("\r\n", "\n").replace('\r', "\n"));
+    }
+    if normalizations.trailing_newline && !content.is_empty() && !content.ends_with('\n') {
+        content.to_mut().push('\n');
+    }
+    content
+}
+
+/// The content to write for a save of `submitted` over `existing`, or `None`
+/// when the save is a no-op under `comparison`.
+pub(crate) fn content_to_write<'a>(
+    existing: &str,
+    submitted: &'a str,
+    normalizations: SaveNormalizations,
+    comparison: SaveComparison,
+) -> Option<Cow<'a, str>> {
+    let unchanged = match comparison {
+        SaveComparison::Exact => existing == submitted,
+        SaveComparison::Normalized => {
+            normalize(existing, normalizations) == normalize(submitted, normalizations)
+        }
+    };
+    (!unchanged).then(|| normalize(submitted, normalizations))
+}
+
+/// How the outcome was decided, for the save status page, e.g.
+/// `exact comparison; normalized: line endings`.
+pub(crate) fn describe(normalizations: SaveNormalizations, comparison: SaveComparison) -> String {
+    let mode = match comparison {
+        SaveComparison::Exact => "exact comparison",
+        SaveComparison::Normalized => "normalized comparison",
+    };
+    let applied: Vec<&str> = [
+        (normalizations.line_endings, "line endings"),
+        (normalizations.trailing_newline, "trailing newline"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, name)| enabled.then_some(name))
+    .collect();
+    if applied.is_empty() {
+        format!("{mode}; no normalizations")
+    } else {
+        format!("{mode}; normalized: {}", applied.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: SaveNormalizations = SaveNormalizations {
+        line_endings: true,
+        trailing_newline: true,
+    };
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("a\r\nb\rc", ALL), "a\nb\nc\n");
+        assert_eq!(normalize("", ALL), "");
+        assert_eq!(normalize("a\n\n", ALL), "a\n\n");
+        assert_eq!(normalize("a\r\n", SaveNormalizations::default()), "a\r\n");
+    }
+
+    #[test]
+    fn test_write_decisions_by_edit_kind_and_mode() {
+        let existing = "# Post\n\nBody\n";
+        // (edit, submitted, written under exact, written under normalized)
+        let cases = [
+            ("none", existing, None, None),
+            (
+                "line endings only",
+                "# Post\r\n\r\nBody\r\n",
+                Some(existing),
+                None,
+            ),
+            (
+                "trailing newline only",
+                "# Post\n\nBody",
+                Some(existing),
+                None,
+            ),
+            (
+                "text",
+                "# Post\r\n\r\nNew body",
+                Some("# Post\n\nNew body\n"),
+                Some("# Post\n\nNew body\n"),
+            ),
+        ];
+        for (edit, submitted, exact, normalized) in cases {
+            assert_eq!(
+                content_to_write(existing, submitted, ALL, SaveComparison::Exact).as_deref(),
+                exact,
+                "{edit}, exact"
+            );
+            assert_eq!(
+                content_to_write(existing, submitted, ALL, SaveComparison::Normalized).as_deref(),
+                normalized,
+                "{edit}, normalized"
+            );
+        }
+
+        // Without normalizations both modes write the submission as is.
+        for comparison in [SaveComparison::Exact, SaveComparison::Normalized] {
+            assert_eq!(
+                content_to_write(
+                    existing,
+                    "# Post\r\n\r\nBody\r\n",
+                    SaveNormalizations::default(),
+                    comparison
+                )
+                .as_deref(),
+                Some("# Post\r\n\r\nBody\r\n")
+            );
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe(SaveNormalizations::default(), SaveComparison::Exact),
+            "exact comparison; no normalizations"
+        );
+        assert_eq!(
+            describe(ALL, SaveComparison::Normalized),
+            "normalized comparison; normalized: line endings, trailing newline"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! `sitemap.xml` generation for the published site.
+//!
+//! Markdown paths map to page URLs the way Hugo builds them: the extension
+//! is dropped, `index.md`/`_index.md` stand for their directory, and every
+//! page URL ends with `/`.
+
+use std::time::SystemTime;
+
+/// A page listed in the sitemap.
+pub(crate) struct SitemapEntry {
+    /// Markdown path relative to the content root.
+    pub(crate) path: String,
+    pub(crate) modified: Option<SystemTime>,
+}
+
+/// Absolute URL of the page built from the markdown file at `path`.
+pub(crate) fn page_url(base_url: &str, path: &str) -> String {
+    let lower = path.to_ascii_lowercase();
+    let stem_len = if lower.ends_with(".markdown") {
+        path.len() - ".markdown".len()
+    } else if lower.ends_with(".md") {
+        path.len() - ".md".len()
+    } else {
+        path.len()
+    };
+    let stem = path.get(..stem_len).unwrap_or(path);
+    let mut segments: Vec<&str> = stem.split('/').filter(|part| !part.is_empty()).collect();
+    if segments
+        .last()
+        .is_some_and(|last| *last == "index" || *last == "_index")
+    {
+        segments.pop();
+    }
+
+    let mut url = base_url.trim_end_matches('/').to_string();
+    url.push('/');
+    for segment in segments {
+        url.push_str(&urlencoding::encode(segment));
+        url.push('/');
+    }
+    url
+}
+
+/// Renders a sitemap listing `entries` in order.
+pub(crate) fn render(base_url: &str, entries: &[SitemapEntry]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for entry in entries {
+        xml.push_str("  <url>\n    <loc>");
+        xml.push_str(&html_escape::encode_text(&page_url(base_url, &entry.path)));
+        xml.push_str("</loc>\n");
+        if let Some(modified) = entry.modified {
+            let lastmod = chrono::DateTime::<chrono::Utc>::from(modified)
+                .format("%Y-%m-%dT%H:%M:%SZ")
+                .to_string();
+            xml.push_str("    <lastmod>");
+            xml.push_str(&lastmod);
+            xml.push_str("</lastmod>\n");
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_url_follows_hugo_layout() {
+        let base = "https://example.com/";
+        assert_eq!(page_url(base, "about.md"), "https://example.com/about/");
+        assert_eq!(page_url(base, "_index.md"), "https://example.com/");
+        assert_eq!(
+            page_url(base, "posts/index.markdown"),
+            "https://example.com/posts/"
+        );
+        assert_eq!(
+            page_url("https://example.com/blog", "posts/a b&c.md"),
+            "https://example.com/blog/posts/a%20b%26c/"
+        );
+    }
+}
@@ -0,0 +1,278 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Whole-tree snapshots stored as zip archives in a hidden directory.
+//!
+//! Archives are streamed entry by entry, so a snapshot never holds more than
+//! one file's buffer in memory. Restoring checks every entry name before
+//! anything is written: names must be plain relative paths inside the content
+//! root, and existing symlinks along the way are refused.
+
+use std::path::{Path, PathBuf};
+
+use async_zip::tokio::read::fs::ZipFileReader;
+use async_zip::tokio::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use tokio::fs;
+use tokio_util::compat::FuturesAsyncWriteCompatExt;
+use tracing::{info, warn};
+
+use crate::cli::Durability;
+use crate::paths::sanitize_relative_path;
+use crate::web::durable;
+use crate::web::error::WebError;
+
+/// Snapshot archives live here, relative to the content root. Being hidden,
+/// the directory is never listed, indexed, served by the file routes or itself
+/// snapshotted.
+pub(crate) const SNAPSHOT_DIR: &str = ".wrangler/snapshots";
+
+/// Older snapshots beyond this many are deleted after a new one is taken.
+pub(crate) const MAX_SNAPSHOTS: usize = 10;
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const SNAPSHOT_SUFFIX: &str = ".zip";
+
+#[derive(Debug)]
+pub(crate) struct SnapshotInfo {
+    pub(crate) name: String,
+    pub(crate) size: u64,
+    /// Unix seconds, from the name.
+    pub(crate) created: u64,
+}
+
+/// What a restore did to the tree.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct RestoreReport {
+    /// Files overwritten with the snapshot's content.
+    pub(crate) restored: Vec<String>,
+    /// Files recreated because they no longer existed.
+    pub(crate) added: Vec<String>,
+    /// Files already matching the snapshot.
+    pub(crate) unchanged: usize,
+}
+
+/// Snapshot names are generated by [`snapshot_name`]; anything else is
+/// rejected so a request can never point at an arbitrary file.
+pub(crate) fn is_snapshot_name(name: &str) -> bool {
+    name.strip_prefix(SNAPSHOT_PREFIX)
+        .and_then(|rest| rest.strip_suffix(SNAPSHOT_SUFFIX))
+        .is_some_and(|stamp| {
+            !stamp.is_empty() && stamp.chars().all(|c| c.is_ascii_digit() || c == '-')
+        })
+}
+
+/// Name for a snapshot taken at `timestamp` (unix seconds). `attempt`
+/// disambiguates snapshots taken within the same second.
+pub(crate) fn snapshot_name(timestamp: u64, attempt: usize) -> String {
+    if attempt == 0 {
+        format!("{SNAPSHOT_PREFIX}{timestamp}{SNAPSHOT_SUFFIX}")
+    } else {
+        format!("{SNAPSHOT_PREFIX}{timestamp}-{attempt}{SNAPSHOT_SUFFIX}")
+    }
+}
+
+/// Writes `files` (relative to `root`) into a new archive at `archive_path`.
+/// The archive is written under a temporary name and renamed into place, so
+/// an interrupted snapshot never shows up in the list.
+pub(crate) async fn write_snapshot(
+    archive_path: &Path,
+    root: &Path,
+    files: &[PathBuf],
+) -> Result<(), WebError> {
+    let partial_path = archive_path.with_extension("zip.partial");
+    let result = write_archive(&partial_path, root, files).await;
+    if let Err(err) = result {
+        if let Err(cleanup_err) = fs::remove_file(&partial_path).await {
+            warn!(
+                "Failed to remove partial snapshot {}: {}",
+                partial_path.display(),
+                cleanup_err
+            );
+        }
+        return Err(err);
+    }
+    fs::rename(&partial_path, archive_path).await?;
+    Ok(())
+}
+
+async fn write_archive(path: &Path, root: &Path, files: &[PathBuf]) -> Result<(), WebError> {
+    let file = fs::File::create_new(path).await?;
+    let mut writer = ZipFileWriter::with_tokio(file);
+    for relative_path in files {
+        let name = relative_path.to_string_lossy().replace('\\', "/");
+        let mut source = fs::File::open(root.join(relative_path)).await?;
+        let entry = ZipEntryBuilder::new(name.into(), Compression::Deflate);
+        let mut entry_writer = writer.write_entry_stream(entry).await?.compat_write();
+        tokio::io::copy(&mut source, &mut entry_writer).await?;
+        entry_writer.into_inner().close().await?;
+    }
+    writer.close().await?;
+    Ok(())
+}
+
+/// Lists snapshots in `dir`, newest first.
+pub(crate) async fn list_snapshots(dir: &Path) -> Result<Vec<SnapshotInfo>, WebError> {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut snapshots = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_snapshot_name(&name) || !entry.file_type().await?.is_file() {
+            continue;
+        }
+        snapshots.push(SnapshotInfo {
+            size: entry.metadata().await?.len(),
+            created: snapshot_sort_key(&name).0,
+            name,
+        });
+    }
+    // Names embed the creation time; compare numerically so that e.g. a
+    // `-1` suffix sorts after its base name.
+    snapshots.sort_by_key(|snapshot| std::cmp::Reverse(snapshot_sort_key(&snapshot.name)));
+    Ok(snapshots)
+}
+
+fn snapshot_sort_key(name: &str) -> (u64, u64) {
+    let stamp = name
+        .trim_start_matches(SNAPSHOT_PREFIX)
+        .trim_end_matches(SNAPSHOT_SUFFIX);
+    let (seconds, attempt) = stamp.split_once('-').unwrap_or((stamp, "0"));
+    (
+        seconds.parse().unwrap_or_default(),
+        attempt.parse().unwrap_or_default(),
+    )
+}
+
+/// Deletes all but the newest `keep` snapshots in `dir`.
+pub(crate) async fn prune_snapshots(dir: &Path, keep: usize) -> Result<(), WebError> {
+    for snapshot in list_snapshots(dir).await?.into_iter().skip(keep) {
+        fs::remove_file(dir.join(&snapshot.name)).await?;
+        info!("Removed old snapshot {}", snapshot.name);
+    }
+    Ok(())
+}
+
+/// Checks that an archive entry name is a plain, visible relative path.
+fn safe_entry_name(name: &str) -> Option<&str> {
+    if name.starts_with('/') {
+        return None;
+    }
+    let sanitized = sanitize_relative_path(name).ok()?;
+    if sanitized != name || name.is_empty() || name.split('/').any(|part| part.starts_with('.')) {
+        return None;
+    }
+    Some(name)
+}
+
+/// Refuses destinations that would pass through an existing symlink, which
+/// could otherwise point outside the content root.
+async fn ensure_no_symlinks(root: &Path, name: &str) -> Result<(), WebError> {
+    let mut current = root.to_path_buf();
+    for part in name.split('/') {
+        current.push(part);
+        match fs::symlink_metadata(&current).await {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(WebError::BadRequest(format!(
+                    "Refusing to restore {name}: {part} is a symlink"
+                )));
+            }
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Extracts `archive_path` over `root`. Files that are not in the snapshot
+/// are left alone. Every entry is validated before the first write.
+pub(crate) async fn restore_snapshot(
+    archive_path: &Path,
+    root: &Path,
+    max_bytes: u64,
+    durability: Durability,
+) -> Result<RestoreReport, WebError> {
+    let reader = ZipFileReader::new(archive_path).await?;
+
+    let mut files = Vec::new();
+    let mut total_bytes = 0u64;
+    for (index, entry) in reader.file().entries().iter().enumerate() {
+        let raw_name = entry.filename().as_str()?;
+        if entry.dir()? {
+            continue;
+        }
+        let name = safe_entry_name(raw_name).ok_or_else(|| {
+            WebError::BadRequest(format!(
+                "Snapshot contains an unsafe path ({raw_name}); nothing was restored"
+            ))
+        })?;
+        ensure_no_symlinks(root, name).await?;
+        total_bytes = total_bytes.saturating_add(entry.uncompressed_size());
+        if total_bytes > max_bytes {
+            return Err(WebError::BadRequest(
+                "Snapshot is larger than the restore limit; nothing was restored".to_string(),
+            ));
+        }
+        files.push((index, name.to_string()));
+    }
+
+    let mut report = RestoreReport::default();
+    for (index, name) in files {
+        let mut content = Vec::new();
+        reader
+            .reader_with_entry(index)
+            .await?
+            .read_to_end_checked(&mut content)
+            .await?;
+
+        let destination = root.join(&name);
+        match fs::read(&destination).await {
+            Ok(existing) if existing == content => {
+                report.unchanged += 1;
+                continue;
+            }
+            Ok(_) => report.restored.push(name),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                if let Some(parent) = destination.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                report.added.push(name);
+            }
+            Err(err) => return Err(err.into()),
+        }
+        durable::write_atomic(&destination, &content, durability).await?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_entry_names() {
+        assert!(is_snapshot_name(&snapshot_name(1_700_000_000, 0)));
+        assert!(is_snapshot_name(&snapshot_name(1_700_000_000, 2)));
+        assert!(!is_snapshot_name("snapshot-.zip"));
+        assert!(!is_snapshot_name("../snapshot-1.zip"));
+        assert!(!is_snapshot_name("notes.zip"));
+
+        assert_eq!(safe_entry_name("posts/a.md"), Some("posts/a.md"));
+        for unsafe_name in [
+            "../a.md",
+            "/etc/passwd",
+            "posts/../../a.md",
+            "posts\\a.md",
+            ".wrangler/x",
+            "posts//a.md",
+        ] {
+            assert_eq!(safe_entry_name(unsafe_name), None, "{unsafe_name}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Per-directory sort preferences.
+//!
+//! A directory may contain a `.wranglersort` file with `key = value` lines:
+//!
+//! ```text
+//! # newest first
+//! field = modified
+//! order = desc
+//! ```
+//!
+//! `field` is one of `name`, `modified` or `size`; `order` is `asc` or
+//! `desc`. Missing keys keep the default (name, ascending). Directories are
+//! always listed before files.
+
+use std::cmp::Ordering;
+use std::time::SystemTime;
+
+/// File name of the per-directory sort preference.
+pub(crate) const SORT_PREFERENCE_FILE: &str = ".wranglersort";
+
+/// Preference files larger than this are ignored.
+pub(crate) const MAX_SORT_PREFERENCE_BYTES: u64 = 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum SortField {
+    #[default]
+    Name,
+    Modified,
+    Size,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SortPreference {
+    pub(crate) field: SortField,
+    pub(crate) order: SortOrder,
+}
+
+/// The values an entry is sorted by.
+#[derive(Debug)]
+pub(crate) struct SortKey {
+    pub(crate) is_directory: bool,
+    pub(crate) name: String,
+    pub(crate) modified: Option<SystemTime>,
+    pub(crate) size: u64,
+}
+
+impl SortPreference {
+    /// Parses a `.wranglersort` file. Unknown keys or values are reported so
+    /// the caller can log them; the rest of the file still applies.
+    pub(crate) fn parse(content: &str) -> (Self, Vec<String>) {
+        let mut preference = SortPreference::default();
+        let mut problems = Vec::new();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                problems.push(format!("expected key = value, got '{line}'"));
+                continue;
+            };
+            let value = value.trim().to_lowercase();
+            match (key.trim().to_lowercase().as_str(), value.as_str()) {
+                ("field", "name") => preference.field = SortField::Name,
+                ("field", "modified") => preference.field = SortField::Modified,
+                ("field", "size") => preference.field = SortField::Size,
+                ("order", "asc") => preference.order = SortOrder::Ascending,
+                ("order", "desc") => preference.order = SortOrder::Descending,
+                (key, value) => problems.push(format!("unsupported setting {key} = {value}")),
+            }
+        }
+        (preference, problems)
+    }
+
+    pub(crate) fn compare(self, a: &SortKey, b: &SortKey) -> Ordering {
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        let by_field = match self.field {
+            SortField::Name => by_name(),
+            SortField::Modified => a.modified.cmp(&b.modified).then_with(by_name),
+            SortField::Size => a.size.cmp(&b.size).then_with(by_name),
+        };
+        let by_field = match self.order {
+            SortOrder::Ascending => by_field,
+            SortOrder::Descending => by_field.reverse(),
+        };
+        b.is_directory.cmp(&a.is_directory).then(by_field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sort_preference() {
+        let (preference, problems) =
+            SortPreference::parse("# newest first\nfield = Modified\n\norder=desc\n");
+        assert_eq!(
+            preference,
+            SortPreference {
+                field: SortField::Modified,
+                order: SortOrder::Descending,
+            }
+        );
+        assert!(problems.is_empty());
+
+        let (preference, problems) = SortPreference::parse("field = colour\norder = desc\nnope");
+        assert_eq!(preference.field, SortField::Name);
+        assert_eq!(preference.order, SortOrder::Descending);
+        assert_eq!(problems.len(), 2);
+    }
+}
@@ -0,0 +1,174 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Timestamp formatting shared by the JSON API and the HTML pages.
+//!
+//! API responses carry RFC 3339 in UTC plus the legacy epoch seconds. Pages
+//! show a relative string ("4 hours ago") with the absolute time, in the
+//! `--utc-offset` display zone, as a tooltip.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+
+/// Modification time fields flattened into API responses.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct ModifiedTimes {
+    /// RFC 3339 in UTC, e.g. `2026-02-03T04:05:06Z`.
+    pub(crate) modified_time: String,
+    /// Seconds since the Unix epoch, as the API returned before.
+    pub(crate) modified_epoch: String,
+}
+
+impl ModifiedTimes {
+    pub(crate) fn new(time: SystemTime) -> Self {
+        ModifiedTimes {
+            modified_time: rfc3339(time),
+            modified_epoch: epoch_seconds(time).to_string(),
+        }
+    }
+}
+
+/// A timestamp prepared for templates.
+#[derive(Clone, Debug)]
+pub(crate) struct HumanTime {
+    /// Machine-readable value for `<time datetime="...">`.
+    pub(crate) rfc3339: String,
+    /// Absolute time in the display zone, e.g. `2026-02-03 14:05 +10:00`.
+    pub(crate) absolute: String,
+    /// Relative to now, e.g. `4 hours ago`.
+    pub(crate) relative: String,
+}
+
+impl HumanTime {
+    pub(crate) fn new(time: SystemTime, offset: FixedOffset) -> Self {
+        Self::at(time, SystemTime::now(), offset)
+    }
+
+    fn at(time: SystemTime, now: SystemTime, offset: FixedOffset) -> Self {
+        HumanTime {
+            rfc3339: rfc3339(time),
+            absolute: absolute(time, offset),
+            relative: relative(time, now, offset),
+        }
+    }
+}
+
+pub(crate) fn epoch_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y-%m-%dT%H:%M:%SZ")
+        .to_string()
+}
+
+pub(crate) fn absolute(time: SystemTime, offset: FixedOffset) -> String {
+    DateTime::<Utc>::from(time)
+        .with_timezone(&offset)
+        .format("%Y-%m-%d %H:%M %:z")
+        .to_string()
+}
+
+/// Short date in the display zone, e.g. `Feb 3`.
+pub(crate) fn short_date(time: SystemTime, offset: FixedOffset) -> String {
+    DateTime::<Utc>::from(time)
+        .with_timezone(&offset)
+        .format("%b %-d")
+        .to_string()
+}
+
+fn plural(count: u64, unit: &str) -> String {
+    if count == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{count} {unit}s ago")
+    }
+}
+
+/// "just now", "5 minutes ago", "3 hours ago", "yesterday", "4 days ago",
+/// then a date. Times in the future (clock skew) read as "just now".
+pub(crate) fn relative(time: SystemTime, now: SystemTime, offset: FixedOffset) -> String {
+    let seconds = now
+        .duration_since(time)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => plural(seconds / 60, "minute"),
+        3600..86_400 => plural(seconds / 3600, "hour"),
+        86_400..172_800 => "yesterday".to_string(),
+        172_800..604_800 => format!("{} days ago", seconds / 86_400),
+        _ => DateTime::<Utc>::from(time)
+            .with_timezone(&offset)
+            .format("%b %-d, %Y")
+            .to_string(),
+    }
+}
+
+/// Parses a `--utc-offset` value such as `+10:00` or `-0530`.
+pub(crate) fn parse_utc_offset(value: &str) -> Result<FixedOffset, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("UTC offset '{value}' must look like +10:00 or -05:30"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // 2026-02-03T04:05:06Z
+    const INSTANT: u64 = 1_770_091_506;
+
+    fn at(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    #[test]
+    fn test_api_fields_for_fixed_instant() {
+        let times = ModifiedTimes::new(at(INSTANT));
+        assert_eq!(times.modified_time, "2026-02-03T04:05:06Z");
+        assert_eq!(times.modified_epoch, INSTANT.to_string());
+    }
+
+    #[test]
+    fn test_absolute_respects_offset() {
+        let utc = parse_utc_offset("+00:00").expect("UTC offset should parse");
+        let brisbane = parse_utc_offset("+10:00").expect("offset should parse");
+        assert_eq!(absolute(at(INSTANT), utc), "2026-02-03 04:05 +00:00");
+        assert_eq!(absolute(at(INSTANT), brisbane), "2026-02-03 14:05 +10:00");
+        assert_eq!(
+            short_date(
+                at(INSTANT),
+                parse_utc_offset("-05:00").expect("offset should parse")
+            ),
+            "Feb 2"
+        );
+        assert!(parse_utc_offset("Australia/Brisbane").is_err());
+    }
+
+    #[test]
+    fn test_relative_buckets() {
+        let utc = parse_utc_offset("+00:00").expect("UTC offset should parse");
+        let time = at(INSTANT);
+        let after = |seconds| at(INSTANT + seconds);
+        assert_eq!(relative(time, after(5), utc), "just now");
+        assert_eq!(relative(after(5), time, utc), "just now");
+        assert_eq!(relative(time, after(60), utc), "1 minute ago");
+        assert_eq!(relative(time, after(4 * 3600 + 59), utc), "4 hours ago");
+        assert_eq!(relative(time, after(30 * 3600), utc), "yesterday");
+        assert_eq!(relative(time, after(3 * 86_400), utc), "3 days ago");
+        assert_eq!(relative(time, after(30 * 86_400), utc), "Feb 3, 2026");
+
+        let human = HumanTime::at(time, after(7200), utc);
+        assert_eq!(human.relative, "2 hours ago");
+        assert_eq!(human.rfc3339, "2026-02-03T04:05:06Z");
+    }
+}
@@ -0,0 +1,268 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Deleted files and folders are moved into a hidden trash directory rather
+//! than removed, unless `--hard-delete` is set.
+//!
+//! Each deletion gets its own folder under [`TRASH_DIR`], named
+//! `<unix seconds>-<random hex>` so two deletions of the same name never
+//! collide. The deleted file or folder keeps its name inside that folder, and
+//! a sidecar `<folder>.json` next to it records where it came from, so
+//! restoring is a matter of moving it back. `/trash` lists the folders,
+//! `/restore` moves an entry back and `/trash/empty` removes them all.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rand::RngExt;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::warn;
+
+use super::{durable, unix_seconds};
+use crate::cli::Durability;
+
+/// Trash location, relative to the content root. Being hidden, it is never
+/// listed or indexed.
+pub(crate) const TRASH_DIR: &str = ".wrangler-trash";
+
+/// Sidecar describing one trashed file or folder.
+#[derive(Debug, Deserialize, Serialize)]
+pub(crate) struct TrashRecord {
+    /// Path the file was deleted from, relative to the content root.
+    pub(crate) original_path: String,
+    /// Unix seconds.
+    pub(crate) deleted_at: u64,
+}
+
+/// A trashed file or folder, as listed on `/trash`.
+#[derive(Debug)]
+pub(crate) struct TrashItem {
+    /// Name of its folder under [`TRASH_DIR`].
+    pub(crate) id: String,
+    /// The file or folder inside the trash folder.
+    pub(crate) path: PathBuf,
+    /// Whether a whole folder was deleted.
+    pub(crate) is_dir: bool,
+    /// `None` when the sidecar is missing or unreadable.
+    pub(crate) original_path: Option<String>,
+    /// Unix seconds, from the folder name.
+    pub(crate) deleted_at: u64,
+}
+
+/// Folder names are generated by [`move_to_trash`]; anything else is
+/// rejected so a request can never point at an arbitrary path.
+pub(crate) fn is_trash_id(id: &str) -> bool {
+    id.split_once('-').is_some_and(|(seconds, random)| {
+        !seconds.is_empty()
+            && seconds.chars().all(|c| c.is_ascii_digit())
+            && random.len() == 8
+            && random.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+/// The trashed entry with folder `id`, or `None` when there is none.
+pub(crate) async fn find(root: &Path, id: &str) -> std::io::Result<Option<TrashItem>> {
+    if !is_trash_id(id) {
+        return Ok(None);
+    }
+    let trash_dir = root.join(TRASH_DIR);
+    let mut entries = match fs::read_dir(trash_dir.join(id)).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    let Some(entry) = entries.next_entry().await? else {
+        return Ok(None);
+    };
+    let original_path = fs::read(trash_dir.join(format!("{id}.json")))
+        .await
+        .ok()
+        .and_then(|sidecar| serde_json::from_slice::<TrashRecord>(&sidecar).ok())
+        .map(|record| record.original_path);
+    let deleted_at = id
+        .split_once('-')
+        .and_then(|(seconds, _)| seconds.parse().ok())
+        .unwrap_or_default();
+    Ok(Some(TrashItem {
+        id: id.to_string(),
+        is_dir: entry.file_type().await?.is_dir(),
+        path: entry.path(),
+        original_path,
+        deleted_at,
+    }))
+}
+
+/// Everything in the trash, most recently deleted first.
+pub(crate) async fn list(root: &Path) -> std::io::Result<Vec<TrashItem>> {
+    let mut entries = match fs::read_dir(root.join(TRASH_DIR)).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut items = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let id = entry.file_name().to_string_lossy().to_string();
+        if is_trash_id(&id)
+            && let Some(item) = find(root, &id).await?
+        {
+            items.push(item);
+        }
+    }
+    items.sort_by(|a, b| {
+        b.deleted_at
+            .cmp(&a.deleted_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    Ok(items)
+}
+
+/// Moves `item` to `destination`, which the caller has checked is free, and
+/// removes its folder and sidecar.
+pub(crate) async fn restore(
+    root: &Path,
+    item: &TrashItem,
+    destination: &Path,
+    durability: Durability,
+) -> std::io::Result<()> {
+    durable::rename(&item.path, destination, durability).await?;
+    let trash_dir = root.join(TRASH_DIR);
+    if let Err(err) = fs::remove_dir(trash_dir.join(&item.id)).await {
+        warn!("Failed to remove trash folder {}: {}", item.id, err);
+    }
+    if let Err(err) = fs::remove_file(trash_dir.join(format!("{}.json", item.id))).await
+        && err.kind() != std::io::ErrorKind::NotFound
+    {
+        warn!("Failed to remove trash sidecar for {}: {}", item.id, err);
+    }
+    Ok(())
+}
+
+/// Permanently deletes everything in the trash, returning how many entries
+/// were removed.
+pub(crate) async fn empty(root: &Path) -> std::io::Result<usize> {
+    let removed = list(root).await?.len();
+    match fs::remove_dir_all(root.join(TRASH_DIR)).await {
+        Ok(()) => Ok(removed),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(err) => Err(err),
+    }
+}
+
+/// Moves `full_path`, a file or folder at `relative_path` under `root`, into
+/// a new trash folder and returns that folder relative to `root`.
+pub(crate) async fn move_to_trash(
+    root: &Path,
+    full_path: &Path,
+    relative_path: &str,
+    durability: Durability,
+) -> std::io::Result<String> {
+    let file_name = full_path.file_name().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "path has no file name")
+    })?;
+    let deleted_at = unix_seconds(SystemTime::now());
+    let folder_name = format!(
+        "{deleted_at}-{}",
+        hex::encode(rand::rng().random::<[u8; 4]>())
+    );
+    let trash_dir = root.join(TRASH_DIR);
+    fs::create_dir_all(&trash_dir).await?;
+    let folder = trash_dir.join(&folder_name);
+    fs::create_dir(&folder).await?;
+
+    if let Err(err) = durable::rename(full_path, &folder.join(file_name), durability).await {
+        if let Err(cleanup_err) = fs::remove_dir(&folder).await {
+            warn!(
+                "Failed to remove empty trash folder {}: {}",
+                folder.display(),
+                cleanup_err
+            );
+        }
+        return Err(err);
+    }
+
+    let record = TrashRecord {
+        original_path: relative_path.to_string(),
+        deleted_at,
+    };
+    let sidecar = serde_json::to_vec_pretty(&record).map_err(std::io::Error::other)?;
+    let sidecar_path = trash_dir.join(format!("{folder_name}.json"));
+    // The file itself is already safe in the trash; a missing sidecar only
+    // loses the note of where it came from.
+    if let Err(err) = durable::write_new(&sidecar_path, &sidecar, durability).await {
+        warn!(
+            "Failed to record original path of {} in {}: {}",
+            relative_path,
+            sidecar_path.display(),
+            err
+        );
+    }
+    Ok(format!("{TRASH_DIR}/{folder_name}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_move_to_trash_records_original_path() {
+        let temp_dir = TempDir::new().expect("failed to create temporary directory");
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("posts"))
+            .await
+            .expect("Failed to create directory");
+        let mut folders = Vec::new();
+        for content in ["# One\n", "# Two\n"] {
+            let full_path = root.join("posts/post.md");
+            fs::write(&full_path, content)
+                .await
+                .expect("Failed to write file");
+            let folder = move_to_trash(root, &full_path, "posts/post.md", Durability::Fsync)
+                .await
+                .expect("move_to_trash failed");
+            assert!(!full_path.exists());
+            assert_eq!(
+                fs::read_to_string(root.join(&folder).join("post.md"))
+                    .await
+                    .expect("Failed to read trashed file"),
+                content
+            );
+            let sidecar = fs::read(root.join(format!("{folder}.json")))
+                .await
+                .expect("Failed to read sidecar");
+            let record: TrashRecord =
+                serde_json::from_slice(&sidecar).expect("Sidecar is not JSON");
+            assert_eq!(record.original_path, "posts/post.md");
+            folders.push(folder);
+        }
+        // Deleting the same name twice keeps both copies.
+        assert_ne!(folders[0], folders[1]);
+
+        let items = list(root).await.expect("list failed");
+        assert_eq!(items.len(), 2);
+        assert!(
+            items
+                .iter()
+                .all(|item| item.original_path.as_deref() == Some("posts/post.md"))
+        );
+        assert!(find(root, "../posts").await.expect("find failed").is_none());
+        assert_eq!(empty(root).await.expect("empty failed"), 2);
+        assert!(list(root).await.expect("list failed").is_empty());
+    }
+
+    #[test]
+    fn test_is_trash_id() {
+        assert!(is_trash_id("1700000000-0a1b2c3d"));
+        for id in [
+            "",
+            "1700000000",
+            "x-0a1b2c3d",
+            "1700000000-0a1b2c3",
+            "../1-0a1b2c3d",
+        ] {
+            assert!(!is_trash_id(id), "{id}");
+        }
+    }
+}
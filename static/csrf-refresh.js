@@ -0,0 +1,46 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+// Refresh a little before expiry so a long edit session can still save.
+const CSRF_REFRESH_MARGIN_SECONDS = 60;
+const CSRF_RETRY_SECONDS = 30;
+
+function scheduleCsrfRefresh(expiresInSeconds) {
+	const delaySeconds = Math.max(
+		expiresInSeconds - CSRF_REFRESH_MARGIN_SECONDS,
+		CSRF_RETRY_SECONDS,
+	);
+	window.setTimeout(refreshCsrfToken, delaySeconds * 1000);
+}
+
+async function refreshCsrfToken() {
+	try {
+		// Set by the page when the app is mounted under a path prefix.
+		const basePath = document.documentElement.dataset.basePath ?? "";
+		const response = await fetch(`${basePath}/api/csrf/refresh`, {
+			headers: { Accept: "application/json" },
+		});
+		if (!response.ok) {
+			throw new Error(`HTTP ${response.status}`);
+		}
+		const data = await response.json();
+		document
+			.querySelectorAll('input[name="csrf_token"]')
+			.forEach((input) => {
+				input.value = data.csrf_token;
+			});
+		scheduleCsrfRefresh(data.expires_in);
+	} catch (error) {
+		console.warn("Failed to refresh CSRF token", error);
+		scheduleCsrfRefresh(0);
+	}
+}
+
+const initialExpiresIn = Number.parseInt(
+	document.body.dataset.csrfExpiresIn ?? "",
+	10,
+);
+if (Number.isFinite(initialExpiresIn)) {
+	scheduleCsrfRefresh(initialExpiresIn);
+}
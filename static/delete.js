@@ -8,6 +8,24 @@ function bindDeleteForms() {
 		form.addEventListener("submit", (event) => {
 			const pathInput = form.querySelector('input[name="path"]');
 			const filename = pathInput ? pathInput.value : "this file";
+			if ("confirmName" in form.dataset) {
+				// The server refuses the delete unless confirm_name matches.
+				const name = filename.split("/").pop();
+				const typed = prompt(`Type "${name}" to confirm deleting it.`);
+				if (typed === null || typed.trim() !== name) {
+					event.preventDefault();
+					return;
+				}
+				let confirmInput = form.querySelector('input[name="confirm_name"]');
+				if (!confirmInput) {
+					confirmInput = document.createElement("input");
+					confirmInput.type = "hidden";
+					confirmInput.name = "confirm_name";
+					form.appendChild(confirmInput);
+				}
+				confirmInput.value = typed.trim();
+				return;
+			}
 			const confirmed = confirm(
 				`Are you sure you want to delete "${filename}"?\n\nThis action cannot be undone.`,
 			);
@@ -2,13 +2,28 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+// Server-provided settings embedded by the page (see GET /api/config).
+function clientConfig() {
+	try {
+		return JSON.parse(document.documentElement.dataset.clientConfig || "{}");
+	} catch (error) {
+		console.error("Failed to parse client config:", error);
+		return {};
+	}
+}
+
+// Path prefix the app is mounted under, empty at the root.
+function basePath() {
+	return document.documentElement.dataset.basePath ?? "";
+}
+
 class EditorStorage {
 	constructor(filePath) {
 		this.filePath = filePath;
 		this.storageKey = `markdown-wrangler-${filePath}`;
 		this.timestampKey = `${this.storageKey}-timestamp`;
 		this.originalContentKey = `${this.storageKey}-original`;
-		this.checkInterval = 5000; // Check every 5 seconds
+		this.checkInterval = clientConfig().draft_check_interval_ms ?? 5000;
 		this.intervalId = null;
 
 		this.initializeStorage();
@@ -33,7 +48,7 @@ class EditorStorage {
 	async updateServerTimestamp() {
 		try {
 			const response = await fetch(
-				`/file-info?path=${encodeURIComponent(this.filePath)}`,
+				`${basePath()}/file-info?path=${encodeURIComponent(this.filePath)}`,
 			);
 			if (response.ok) {
 				const data = await response.json();
@@ -139,7 +154,7 @@ class EditorStorage {
 	async reloadFromServer() {
 		try {
 			const response = await fetch(
-				`/file-content?path=${encodeURIComponent(this.filePath)}`,
+				`${basePath()}/file-content?path=${encodeURIComponent(this.filePath)}`,
 			);
 			if (response.ok) {
 				const data = await response.json();
@@ -252,7 +267,7 @@ function initializeEditorStorage() {
 		}
 
 		// Handle form submission
-		const form = document.querySelector('form[action="/save"]');
+		const form = document.querySelector('form[action$="/save"]');
 		if (form) {
 			form.addEventListener("submit", () => {
 				// Clear draft when successfully saving
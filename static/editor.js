@@ -139,6 +139,10 @@ function processLists(content) {
 
 // Simple markdown preview (basic implementation)
 function updatePreview() {
+	// Text documents (org, AsciiDoc) are edited without a preview panel.
+	if (!preview) {
+		return;
+	}
 	let content = textarea.value;
 
 	// Strip frontmatter before processing
@@ -204,3 +208,45 @@ if (textarea && preview) {
 	textarea.addEventListener("input", updatePreview);
 	updatePreview(); // Initial preview
 }
+
+// Pasting an image uploads it next to the post and inserts the markdown
+// that embeds it at the cursor.
+async function uploadPastedImage(file) {
+	const form = textarea.form;
+	const body = new FormData();
+	body.append("path", form.elements.path.value);
+	body.append("csrf_token", form.elements.csrf_token.value);
+	body.append("image", file);
+	const basePath = document.documentElement.dataset.basePath ?? "";
+	const response = await fetch(`${basePath}/api/upload-image`, {
+		method: "POST",
+		headers: { Accept: "application/json" },
+		body,
+	});
+	if (!response.ok) {
+		throw new Error(`Image upload failed (${response.status})`);
+	}
+	const { markdown } = await response.json();
+	textarea.setRangeText(
+		markdown,
+		textarea.selectionStart,
+		textarea.selectionEnd,
+		"end",
+	);
+	textarea.dispatchEvent(new Event("input"));
+}
+
+if (textarea && preview) {
+	textarea.addEventListener("paste", (event) => {
+		const file = Array.from(event.clipboardData?.files ?? []).find((item) =>
+			item.type.startsWith("image/"),
+		);
+		if (!file) {
+			return;
+		}
+		event.preventDefault();
+		uploadPastedImage(file).catch((error) => {
+			console.error("Failed to upload pasted image:", error);
+		});
+	});
+}